@@ -0,0 +1,80 @@
+use sha2::Digest as _;
+use std::path::PathBuf;
+use std::{env, fs};
+
+fn main() {
+    println!("cargo::rerun-if-changed=build.rs");
+    println!("cargo::rerun-if-changed=data.minify.json");
+    println!("cargo::rerun-if-changed=data.minify.json.source");
+
+    let rules = embedded_rules();
+    let out = PathBuf::from(env::var_os("OUT_DIR").expect("OUT_DIR is set"));
+
+    let upstream = fs::read("data.minify.json").expect("read rules");
+    let sha256: [u8; 32] = sha2::Sha256::digest(&upstream).into();
+    fs::write(out.join("data.minify.json.sha256"), format!("{sha256:?}")).expect("write rules version");
+    let (commit, date) = upstream_source();
+    fs::write(out.join("data.minify.json.commit"), format!("{commit:?}")).expect("write rules commit");
+    fs::write(out.join("data.minify.json.date"), format!("{date:?}")).expect("write rules date");
+
+    #[cfg(feature = "compress-rules")]
+    {
+        let compressed = miniz_oxide::deflate::compress_to_vec(&rules, 10);
+        fs::write(out.join("data.minify.json.deflate"), compressed).expect("write compressed rules");
+    }
+    fs::write(out.join("data.minify.json"), rules).expect("write rules");
+}
+
+/// The upstream commit and date of the rules, as recorded in `data.minify.json.source`.
+fn upstream_source() -> (Option<String>, Option<String>) {
+    let source = fs::read_to_string("data.minify.json.source").unwrap_or_default();
+    let value = |key: &str| {
+        source
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .find(|(k, _)| k.trim() == key)
+            .map(|(_, v)| v.trim().to_owned())
+            .filter(|v| !v.is_empty())
+    };
+    (value("commit"), value("date"))
+}
+
+/// The rules as they are embedded, depending on the `embed-*` features.
+#[cfg(any(feature = "embed-full", not(any(feature = "embed-social", feature = "embed-search"))))]
+fn embedded_rules() -> Vec<u8> {
+    fs::read("data.minify.json").expect("read rules")
+}
+
+/// The rules as they are embedded, depending on the `embed-*` features.
+#[cfg(all(not(feature = "embed-full"), any(feature = "embed-social", feature = "embed-search")))]
+fn embedded_rules() -> Vec<u8> {
+    /// Providers that are always embedded, because they apply to all URLs.
+    const CORE: &[&str] = &["globalRules"];
+    /// Providers embedded with the `embed-social` feature.
+    #[cfg(feature = "embed-social")]
+    const SOCIAL: &[&str] = &[
+        "facebook", "twitter", "x", "reddit", "instagram", "LinkedIn", "LinkedIn Learning", "vk.com",
+        "messenger.com", "snapchat.com", "tiktok.com", "weibo", "t.umblr.com", "9gag.com", "giphy.com",
+        "medium.com", "youtube", "youtube_pagead", "youtube_apiads", "twitch", "bilibili.com",
+        "m.bilibili.com", "live.bilibili.com", "deviantart.com", "steamcommunity", "disq.us",
+        "pantip.com", "boredpanda.com", "meetup.com", "goodreads.com", "spotify.com",
+    ];
+    /// Providers embedded with the `embed-search` feature.
+    #[cfg(feature = "embed-search")]
+    const SEARCH: &[&str] = &["google", "googleSearch", "bing", "yandex", "duckduckgo"];
+
+    let mut names = CORE.to_vec();
+    #[cfg(feature = "embed-social")]
+    names.extend(SOCIAL);
+    #[cfg(feature = "embed-search")]
+    names.extend(SEARCH);
+
+    let data = fs::read("data.minify.json").expect("read rules");
+    let mut rules: serde_json::Value = serde_json::from_slice(&data).expect("parse rules");
+    rules["providers"]
+        .as_object_mut()
+        .expect("providers is a map")
+        .retain(|name, _| names.contains(&name.as_str()));
+    serde_json::to_vec(&rules).expect("serialize rules")
+}
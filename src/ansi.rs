@@ -0,0 +1,81 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+/// A part of terminal output, see [`UrlCleaner::ansi_escapes`][crate::UrlCleaner::ansi_escapes].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Segment {
+    /// Text between escape sequences, which may contain URLs
+    Text(Range<usize>),
+    /// The target of an OSC 8 hyperlink, which is a single URL
+    Hyperlink(Range<usize>),
+}
+
+/// Split terminal output into text and hyperlink targets, leaving out all escape sequences.
+///
+/// Sequences that aren't terminated extend to the end of the text.
+pub(crate) fn segments(s: &str) -> Vec<Segment> {
+    let bytes = s.as_bytes();
+    let mut segments = Vec::new();
+    let mut text_start = 0;
+    let mut i = 0;
+    while let Some(offset) = bytes[i..].iter().position(|&b| b == ESC) {
+        let start = i + offset;
+        if text_start < start {
+            segments.push(Segment::Text(text_start..start));
+        }
+        i = match bytes.get(start + 1) {
+            // CSI, like colors: parameter and intermediate bytes, then a final byte
+            Some(b'[') => {
+                let params = &bytes[start + 2..];
+                let end = start + 2 + params.iter().position(|b| !(0x20..=0x3f).contains(b)).unwrap_or(params.len());
+                if bytes.get(end).is_some_and(|b| (0x40..=0x7e).contains(b)) {
+                    end + 1
+                } else {
+                    end
+                }
+            }
+            // OSC, DCS, SOS, PM and APC: a string terminated by BEL or ST
+            Some(b']' | b'P' | b'X' | b'^' | b'_') => {
+                let body = start + 2;
+                let (end, next) = string_end(bytes, body);
+                if bytes[start + 1] == b']' {
+                    if let Some(target) = hyperlink_target(s, body..end) {
+                        segments.push(Segment::Hyperlink(target));
+                    }
+                }
+                next
+            }
+            // other escape sequences are a single character, which is ASCII if it's valid
+            Some(b) if b.is_ascii() => start + 2,
+            _ => start + 1,
+        };
+        text_start = i;
+    }
+    if text_start < bytes.len() {
+        segments.push(Segment::Text(text_start..bytes.len()));
+    }
+    segments
+}
+
+/// The end of a control string starting at `start`, and the index after its terminator.
+fn string_end(bytes: &[u8], start: usize) -> (usize, usize) {
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            BEL => return (i, i + 1),
+            ESC if bytes.get(i + 1) == Some(&b'\\') => return (i, i + 2),
+            _ => i += 1,
+        }
+    }
+    (bytes.len(), bytes.len())
+}
+
+/// The range of the URI in an OSC 8 sequence, like `8;id=1;https://example.com/`, unless it's empty.
+fn hyperlink_target(s: &str, body: Range<usize>) -> Option<Range<usize>> {
+    let params = s[body.clone()].strip_prefix("8;")?;
+    let uri = body.end - params.len() + params.find(';')? + 1;
+    (uri < body.end).then_some(uri..body.end)
+}
@@ -0,0 +1,125 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::{Debug, Formatter};
+
+use crate::Change;
+
+/// Something that records every URL that was modified, see [`UrlCleaner::audit_sink`][crate::UrlCleaner::audit_sink].
+///
+/// This is meant for compliance requirements to log all automated modifications of content.
+/// With the `std` and `serialize` features, `JsonlAuditLog` writes the records to a file.
+pub trait AuditSink {
+    /// Record a URL that was modified by cleaning.
+    ///
+    /// This is called from within the cleaning methods, so it should be quick and must not panic.
+    fn record(&self, original: &str, cleaned: &str, details: &CleanReport);
+}
+
+impl<T: AuditSink + ?Sized> AuditSink for Arc<T> {
+    fn record(&self, original: &str, cleaned: &str, details: &CleanReport) {
+        (**self).record(original, cleaned, details);
+    }
+}
+
+/// What was done to a URL, which is passed to an [`AuditSink`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct CleanReport {
+    /// The modifications, like those returned by
+    /// [`UrlCleaner::clear_single_url_explained`][crate::UrlCleaner::clear_single_url_explained].
+    ///
+    /// This is empty if the URL was only re-encoded, or changed by a
+    /// [`scheme_handler`][crate::UrlCleaner::scheme_handler].
+    pub changes: Vec<Change>,
+}
+
+pub(crate) struct Audit(pub(crate) Box<dyn AuditSink + Send + Sync>);
+
+impl Debug for Audit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str("AuditSink")
+    }
+}
+
+/// An [`AuditSink`] that writes a line of JSON per modified URL.
+///
+/// Each line is an object with the `timestamp` in seconds since the Unix epoch, the `original` and `cleaned` URL,
+/// and the `changes`, serialized like [`Change`].
+///
+/// # Example
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use clearurls::{JsonlAuditLog, UrlCleaner};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let log = Arc::new(JsonlAuditLog::open("audit.jsonl".as_ref())?);
+/// let cleaner = UrlCleaner::from_embedded_rules()?.audit_sink(Arc::clone(&log));
+/// cleaner.clear_single_url_str("https://example.com/?utm_source=abc")?;
+/// if let Some(e) = log.take_error() {
+///     return Err(e.into());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(all(feature = "std", feature = "serialize"))]
+#[derive(Debug)]
+pub struct JsonlAuditLog<W> {
+    writer: std::sync::Mutex<W>,
+    error: std::sync::Mutex<Option<std::io::Error>>,
+}
+
+#[cfg(all(feature = "std", feature = "serialize"))]
+impl JsonlAuditLog<std::fs::File> {
+    /// Open a file to append the records to, which is created if it doesn't exist.
+    ///
+    /// # Errors
+    /// If the file can't be opened.
+    pub fn open(path: &std::path::Path) -> std::io::Result<Self> {
+        std::fs::OpenOptions::new().create(true).append(true).open(path).map(Self::new)
+    }
+}
+
+#[cfg(all(feature = "std", feature = "serialize"))]
+impl<W: std::io::Write> JsonlAuditLog<W> {
+    /// Write the records to `writer`, which is flushed after every record.
+    #[must_use]
+    pub const fn new(writer: W) -> Self {
+        Self { writer: std::sync::Mutex::new(writer), error: std::sync::Mutex::new(None) }
+    }
+
+    /// The first error that occurred while writing, if any, which is cleared by this.
+    ///
+    /// [`AuditSink::record`] can't fail, so check this regularly if the records must not get lost.
+    pub fn take_error(&self) -> Option<std::io::Error> {
+        self.error.lock().unwrap_or_else(std::sync::PoisonError::into_inner).take()
+    }
+
+    /// Return the writer.
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(all(feature = "std", feature = "serialize"))]
+impl<W: std::io::Write> AuditSink for JsonlAuditLog<W> {
+    fn record(&self, original: &str, cleaned: &str, details: &CleanReport) {
+        #[derive(serde::Serialize)]
+        struct Record<'a> {
+            timestamp: u64,
+            original: &'a str,
+            cleaned: &'a str,
+            #[serde(flatten)]
+            details: &'a CleanReport,
+        }
+
+        let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let record = Record { timestamp, original, cleaned, details };
+        let mut line = serde_json::to_vec(&record).expect("records are serializable");
+        line.push(b'\n');
+        let mut writer = self.writer.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        if let Err(e) = writer.write_all(&line).and_then(|()| writer.flush()) {
+            self.error.lock().unwrap_or_else(std::sync::PoisonError::into_inner).get_or_insert(e);
+        }
+    }
+}
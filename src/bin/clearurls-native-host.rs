@@ -0,0 +1,106 @@
+//! A host for the native messaging protocol of browsers, so that companion extensions or Electron apps
+//! can clean URLs with this crate instead of bundling the rules engine of `ClearURLs`.
+//!
+//! Each message is JSON, prefixed with its length as a 32-bit integer in native byte order,
+//! both on stdin and stdout. The host answers every request in order and exits when stdin is closed.
+//!
+//! - `{"action": "clean", "url": "..."}` responds with `{"url": "..."}`
+//! - `{"action": "explain", "url": "..."}` responds with `{"rules": [...]}`, the rules that apply to the URL
+//!
+//! Errors are responded to with `{"error": {"code": "...", "message": "..."}}`.
+//! An `id` in the request is copied to the response.
+//! A message longer than 1 MiB is rejected without reading it with a `message_too_long` error,
+//! after which the host exits, because it can't find the start of the next message.
+
+use std::io::{self, Read, Write};
+
+use clearurls::UrlCleaner;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum Action {
+    Clean { url: String },
+    Explain { url: String },
+}
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    #[serde(flatten)]
+    action: Action,
+}
+
+/// The longest message that is read, so that a corrupted length prefix can't make the host allocate gigabytes.
+///
+/// This is a limit of this host. Browsers send messages of up to 4 GiB, but URLs are much shorter.
+const MAX_MESSAGE_LEN: u32 = 1024 * 1024;
+
+/// Read one message, or `None` at the end of the input.
+fn read_message(input: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match input.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_ne_bytes(len);
+    if len > MAX_MESSAGE_LEN {
+        let error = format!("message of {len} bytes is longer than the limit of {MAX_MESSAGE_LEN} bytes");
+        return Err(io::Error::new(io::ErrorKind::InvalidData, error));
+    }
+    let mut message = vec![0; len as usize];
+    input.read_exact(&mut message)?;
+    Ok(Some(message))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let message = serde_json::to_vec(message)?;
+    let len = u32::try_from(message.len()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    output.write_all(&len.to_ne_bytes())?;
+    output.write_all(&message)?;
+    output.flush()
+}
+
+fn respond(cleaner: &UrlCleaner, message: &[u8]) -> Value {
+    let request: Request = match serde_json::from_slice(message) {
+        Ok(request) => request,
+        Err(e) => return json!({ "error": { "code": "invalid_message", "message": e.to_string() } }),
+    };
+    let mut response = match request.action {
+        Action::Clean { url } => match cleaner.clear_single_url_str(&url) {
+            Ok(url) => json!({ "url": url }),
+            Err(e) => json!({ "error": e }),
+        },
+        Action::Explain { url } => match cleaner.rules_for(&url) {
+            Ok(rules) => json!({ "rules": rules }),
+            Err(e) => json!({ "error": e }),
+        },
+    };
+    if !request.id.is_null() {
+        response["id"] = request.id;
+    }
+    response
+}
+
+fn main() -> io::Result<()> {
+    let cleaner = UrlCleaner::shared();
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    loop {
+        let message = match read_message(&mut input) {
+            Ok(Some(message)) => message,
+            Ok(None) => return Ok(()),
+            // only the length check fails with `InvalidData`
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                let response = json!({ "error": { "code": "message_too_long", "message": e.to_string() } });
+                write_message(&mut output, &response)?;
+                return Err(e);
+            }
+            Err(e) => return Err(e),
+        };
+        write_message(&mut output, &respond(cleaner, &message))?;
+    }
+}
@@ -0,0 +1,164 @@
+//! A small HTTP service that cleans URLs with the embedded rules, for infrastructure that isn't written in Rust.
+//!
+//! Usage: `clearurls-server [ADDRESS] [POLICY]`, where the address defaults to `127.0.0.1:3000`.
+//! With a policy file, or the one named by `CLEARURLS_POLICY`, cleaning is configured by it, see `PolicyFile`.
+//! Environment variables like `CLEARURLS_STRIP_REFERRAL` override its settings, see `PolicyFile::env_overrides`.
+//!
+//! - `POST /clean` with `{"url": "..."}` responds with `{"url": "..."}`
+//! - `POST /clean-text` with `{"text": "..."}` responds with `{"text": "..."}`
+//! - `POST /clean-batch` with `{"urls": ["...", ...]}` responds with
+//!   `{"results": [{"url": "..."}, {"error": ...}, ...]}`
+//! - `GET /health` responds with `ok`
+//! - `GET /rules-version` responds with `{"version": "..."}`, the SHA-256 hash of the rules
+//! - `GET /metrics` responds with counters in the Prometheus text format, with the `metrics` feature
+//!
+//! Errors are responded to with status 422 and `{"error": {"code": "...", "message": "..."}}`,
+//! or `{"errors": [{"url": "...", "error": ...}, ...]}` for `/clean-text`.
+
+use std::path::Path;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use clearurls::{PolicyFile, UrlCleaner};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+type Response = Result<Json<Value>, (StatusCode, Json<Value>)>;
+
+#[derive(Deserialize)]
+struct CleanRequest {
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct CleanTextRequest {
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct CleanBatchRequest {
+    urls: Vec<String>,
+}
+
+/// Counters of the requests and cleaned URLs, in the Prometheus text format.
+#[cfg(feature = "metrics")]
+mod metrics {
+    use std::fmt::Write as _;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use clearurls::{CleanError, UrlCleaner};
+
+    const ENDPOINTS: [&str; 3] = ["clean", "clean-text", "clean-batch"];
+    static REQUESTS: [AtomicU64; 3] = [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)];
+    static UNCHANGED: AtomicU64 = AtomicU64::new(0);
+    static CHANGED: AtomicU64 = AtomicU64::new(0);
+    static ERRORS: AtomicU64 = AtomicU64::new(0);
+
+    pub fn request(endpoint: &str) {
+        if let Some(i) = ENDPOINTS.iter().position(|e| *e == endpoint) {
+            REQUESTS[i].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn url(original: &str, result: &Result<impl AsRef<str>, CleanError>) {
+        let counter = match result {
+            Ok(cleaned) if cleaned.as_ref() == original => &UNCHANGED,
+            Ok(_) => &CHANGED,
+            Err(_) => &ERRORS,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn render(cleaner: &UrlCleaner) -> String {
+        let mut out = String::new();
+        out.push_str("# HELP clearurls_requests_total Requests to the cleaning endpoints.\n");
+        out.push_str("# TYPE clearurls_requests_total counter\n");
+        for (endpoint, count) in ENDPOINTS.iter().zip(&REQUESTS) {
+            let count = count.load(Ordering::Relaxed);
+            let _ = writeln!(out, "clearurls_requests_total{{endpoint=\"{endpoint}\"}} {count}");
+        }
+        out.push_str("# HELP clearurls_urls_total URLs cleaned by /clean and /clean-batch, by result.\n");
+        out.push_str("# TYPE clearurls_urls_total counter\n");
+        for (result, count) in [("unchanged", &UNCHANGED), ("changed", &CHANGED), ("error", &ERRORS)] {
+            let count = count.load(Ordering::Relaxed);
+            let _ = writeln!(out, "clearurls_urls_total{{result=\"{result}\"}} {count}");
+        }
+        out.push_str("# HELP clearurls_rules_info The SHA-256 hash of the loaded rules.\n");
+        out.push_str("# TYPE clearurls_rules_info gauge\n");
+        let _ = writeln!(out, "clearurls_rules_info{{version=\"{}\"}} 1", cleaner.rules_version());
+        out
+    }
+}
+
+/// No-op counters without the `metrics` feature.
+#[cfg(not(feature = "metrics"))]
+mod metrics {
+    use clearurls::CleanError;
+
+    pub const fn request(_: &str) {}
+
+    pub const fn url<T>(_: &str, _: &Result<T, CleanError>) {}
+}
+
+async fn clean(State(cleaner): State<&'static UrlCleaner>, Json(req): Json<CleanRequest>) -> Response {
+    metrics::request("clean");
+    let result = cleaner.clear_single_url_str(&req.url);
+    metrics::url(&req.url, &result);
+    match result {
+        Ok(url) => Ok(Json(json!({ "url": url }))),
+        Err(e) => Err((StatusCode::UNPROCESSABLE_ENTITY, Json(json!({ "error": e })))),
+    }
+}
+
+async fn clean_text(State(cleaner): State<&'static UrlCleaner>, Json(req): Json<CleanTextRequest>) -> Response {
+    metrics::request("clean-text");
+    match cleaner.clear_text(&req.text) {
+        Ok(text) => Ok(Json(json!({ "text": text }))),
+        Err(e) => Err((StatusCode::UNPROCESSABLE_ENTITY, Json(json!({ "errors": e })))),
+    }
+}
+
+async fn clean_batch(State(cleaner): State<&'static UrlCleaner>, Json(req): Json<CleanBatchRequest>) -> Json<Value> {
+    metrics::request("clean-batch");
+    let results: Vec<Value> = req
+        .urls
+        .iter()
+        .map(|url| {
+            let result = cleaner.clear_single_url_str(url);
+            metrics::url(url, &result);
+            match result {
+                Ok(url) => json!({ "url": url }),
+                Err(e) => json!({ "error": e }),
+            }
+        })
+        .collect();
+    Json(json!({ "results": results }))
+}
+
+async fn rules_version(State(cleaner): State<&'static UrlCleaner>) -> Json<Value> {
+    Json(json!({ "version": cleaner.rules_version().to_string() }))
+}
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let address = std::env::args().nth(1).unwrap_or_else(|| "127.0.0.1:3000".into());
+    let cleaner = PolicyFile::load(std::env::args_os().nth(2).as_deref().map(Path::new))
+        .map_err(|e| std::io::Error::other(format!("error loading the policy: {e}")))?;
+    // the cleaner is shared by all requests until the server exits
+    let cleaner: &'static UrlCleaner = Box::leak(Box::new(cleaner));
+    let app = Router::new();
+    #[cfg(feature = "metrics")]
+    let app = app.route("/metrics", get(|State(cleaner)| async move { metrics::render(cleaner) }));
+    let app = app
+        .route("/clean", post(clean))
+        .route("/clean-text", post(clean_text))
+        .route("/clean-batch", post(clean_batch))
+        .route("/health", get(|| async { "ok" }))
+        .route("/rules-version", get(rules_version))
+        .with_state(cleaner);
+    let listener = tokio::net::TcpListener::bind(&address).await?;
+    println!("listening on http://{}", listener.local_addr()?);
+    axum::serve(listener, app).await
+}
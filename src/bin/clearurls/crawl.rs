@@ -0,0 +1,180 @@
+//! Auditing the links on web pages, see `--crawl`.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use clearurls::UrlCleaner;
+
+use crate::files::{self, Kind};
+
+/// How deeply sitemap indexes may refer to other sitemap indexes.
+const MAX_SITEMAP_DEPTH: usize = 3;
+
+pub struct Options {
+    /// How many pages are fetched at the same time
+    pub concurrency: usize,
+    /// Create a diff of the changes to each page
+    pub patch: bool,
+}
+
+/// The result of auditing a page.
+pub struct PageResult {
+    pub page: String,
+    /// The links that would change, with their cleaned version
+    pub replacements: Vec<(String, String)>,
+    /// A diff of the page source, with `--patch`, if something would change
+    pub patch: Option<String>,
+    pub errors: Vec<String>,
+}
+
+impl PageResult {
+    fn new(page: &str) -> Self {
+        Self { page: page.into(), replacements: Vec::new(), patch: None, errors: Vec::new() }
+    }
+}
+
+/// A page to audit, with its source if it was already fetched while looking for sitemaps.
+struct Page {
+    url: String,
+    source: Option<String>,
+}
+
+/// Audit the pages in the given sitemaps, the given pages, and the pages listed in the given files, one per line.
+///
+/// Each page is fetched once, even if it's listed several times. The results are in the order of the pages,
+/// preceded by the sitemaps and files that couldn't be read.
+pub fn run(cleaner: &UrlCleaner, inputs: &[String], options: &Options) -> Vec<PageResult> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(30))
+        .user_agent(concat!("clearurls/", env!("CARGO_PKG_VERSION")))
+        .build();
+    let mut pages = Vec::new();
+    let mut results = Vec::new();
+    let mut seen = HashSet::new();
+    for input in inputs {
+        collect(&agent, input, 0, &mut seen, &mut pages, &mut results);
+    }
+    let next = AtomicUsize::new(0);
+    let mut audited: Vec<(usize, PageResult)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..options.concurrency.min(pages.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut audited = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(page) = pages.get(i) else {
+                            break audited;
+                        };
+                        audited.push((i, audit(cleaner, &agent, page, options.patch)));
+                    }
+                })
+            })
+            .collect();
+        workers.into_iter().flat_map(|w| w.join().expect("auditing a page doesn't panic")).collect()
+    });
+    audited.sort_unstable_by_key(|(i, _)| *i);
+    results.extend(audited.into_iter().map(|(_, result)| result));
+    results
+}
+
+/// Add the pages of an input, which is a sitemap, a page, or a file that is either a sitemap or a list of pages.
+fn collect(
+    agent: &ureq::Agent,
+    input: &str,
+    depth: usize,
+    seen: &mut HashSet<String>,
+    pages: &mut Vec<Page>,
+    results: &mut Vec<PageResult>,
+) {
+    if !seen.insert(input.into()) {
+        return;
+    }
+    let is_url = input.starts_with("http://") || input.starts_with("https://");
+    let source =
+        if is_url { fetch(agent, input) } else { fs::read_to_string(input).map_err(|e| format!("error reading: {e}")) };
+    let source = match source {
+        Ok(source) => source,
+        Err(e) => {
+            results.push(PageResult { errors: vec![e], ..PageResult::new(input) });
+            return;
+        }
+    };
+    match sitemap(&source) {
+        Some((true, _)) if depth == MAX_SITEMAP_DEPTH => {
+            let errors = vec!["sitemaps are nested too deeply".into()];
+            results.push(PageResult { errors, ..PageResult::new(input) });
+        }
+        Some((true, sitemaps)) => {
+            for sitemap in sitemaps {
+                collect(agent, &sitemap, depth + 1, seen, pages, results);
+            }
+        }
+        Some((false, urls)) => {
+            pages.extend(urls.into_iter().filter(|url| seen.insert(url.clone())).map(|url| Page { url, source: None }));
+        }
+        None if is_url => pages.push(Page { url: input.into(), source: Some(source) }),
+        None => {
+            let urls = source.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#'));
+            let urls = urls.filter(|url| seen.insert((*url).into()));
+            pages.extend(urls.map(|url| Page { url: url.into(), source: None }));
+        }
+    }
+}
+
+/// Fetch a page and clean the URLs in it as HTML, without changing anything.
+fn audit(cleaner: &UrlCleaner, agent: &ureq::Agent, page: &Page, patch: bool) -> PageResult {
+    let mut result = PageResult::new(&page.url);
+    let source = match &page.source {
+        Some(source) => Cow::Borrowed(source),
+        None => match fetch(agent, &page.url) {
+            Ok(source) => Cow::Owned(source),
+            Err(e) => {
+                result.errors.push(e);
+                return result;
+            }
+        },
+    };
+    let cleaned = files::clean(cleaner, &source, Kind::Html);
+    if patch && cleaned.text != *source {
+        result.patch = Some(files::diff(&page.url, &source, &cleaned.text));
+    }
+    result.replacements = cleaned.replacements;
+    result.errors = cleaned.errors;
+    result
+}
+
+fn fetch(agent: &ureq::Agent, url: &str) -> Result<String, String> {
+    let response = agent.get(url).call().map_err(|e| format!("error fetching: {e}"))?;
+    response.into_string().map_err(|e| format!("error fetching: {e}"))
+}
+
+/// The locations in a sitemap and whether they are sitemaps themselves, or `None` if `source` isn't a sitemap.
+fn sitemap(source: &str) -> Option<(bool, Vec<String>)> {
+    let index = source.contains("<sitemapindex");
+    if !index && !source.contains("<urlset") {
+        return None;
+    }
+    let mut locations = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find("<loc>") {
+        rest = &rest[start + "<loc>".len()..];
+        let end = rest.find("</loc>").unwrap_or(rest.len());
+        let location = rest[..end].trim();
+        let location = location
+            .strip_prefix("<![CDATA[")
+            .and_then(|l| l.strip_suffix("]]>"))
+            .map_or_else(|| unescape_xml(location), String::from);
+        locations.push(location.trim().into());
+        rest = &rest[end..];
+    }
+    Some((index, locations))
+}
+
+/// Replace the predefined entities of XML, which are the only ones that may appear in sitemaps.
+fn unescape_xml(s: &str) -> String {
+    s.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
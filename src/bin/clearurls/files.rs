@@ -0,0 +1,321 @@
+//! Cleaning files in place, see `--files`.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use clearurls::{LinkError, UrlCleaner};
+
+/// How URLs are found in a file, chosen by its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// URLs in code spans and code blocks are left untouched, see `UrlCleaner::clear_markdown`.
+    Markdown,
+    /// URLs inside `<pre>` and `<code>` are left untouched, and `&amp;` in URLs is understood.
+    Html,
+    /// All URLs are cleaned.
+    Text,
+}
+
+impl Kind {
+    /// The kind of a file with a known extension.
+    pub fn of(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "md" | "markdown" => Some(Self::Markdown),
+            "html" | "htm" | "xhtml" => Some(Self::Html),
+            "txt" | "text" => Some(Self::Text),
+            _ => None,
+        }
+    }
+}
+
+pub struct Options {
+    /// Only clean files in directories that match one of these globs, instead of all files with a known extension.
+    pub globs: Vec<String>,
+    /// Print a diff of the changes instead of writing them.
+    pub dry_run: bool,
+}
+
+/// The result of cleaning a file.
+pub struct FileResult {
+    pub path: PathBuf,
+    /// Whether the file was changed, or would be changed with `--dry-run`
+    pub changed: bool,
+    /// The URLs that were replaced, with their cleaned version
+    pub replacements: Vec<(String, String)>,
+    /// The diff of the changes with `--dry-run`, instead of writing them
+    pub diff: Option<String>,
+    pub errors: Vec<String>,
+}
+
+/// Clean the given files, and the files in the given directories recursively.
+///
+/// Hidden files and directories, like `.git`, are skipped while walking a directory.
+pub fn run(cleaner: &UrlCleaner, paths: &[PathBuf], options: &Options) -> Vec<FileResult> {
+    let mut results = Vec::new();
+    for path in paths {
+        let mut files = Vec::new();
+        if path.is_dir() {
+            if let Err(e) = walk(path, path, options, &mut files) {
+                results.push(FileResult {
+                    path: path.clone(),
+                    changed: false,
+                    replacements: Vec::new(),
+                    diff: None,
+                    errors: vec![format!("error reading {}: {e}", path.display())],
+                });
+            }
+        } else {
+            files.push(path.clone());
+        }
+        results.extend(files.into_iter().map(|file| clean_file(cleaner, file, options.dry_run)));
+    }
+    results
+}
+
+fn walk(root: &Path, dir: &Path, options: &Options, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.map(|e| e.map(|e| e.path())).collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for path in entries {
+        if path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with('.')) {
+            continue;
+        }
+        if path.is_dir() {
+            walk(root, &path, options, files)?;
+        } else if is_included(root, &path, &options.globs) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_included(root: &Path, path: &Path, globs: &[String]) -> bool {
+    if globs.is_empty() {
+        return Kind::of(path).is_some();
+    }
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let relative: Vec<_> = relative.iter().filter_map(|c| c.to_str()).collect();
+    globs.iter().any(|glob| {
+        if glob.contains('/') {
+            matches_segments(&glob.split('/').collect::<Vec<_>>(), &relative)
+        } else {
+            // like in `.gitignore`, a glob without a slash matches the file name at any depth
+            relative.last().is_some_and(|name| matches_segment(glob.as_bytes(), name.as_bytes()))
+        }
+    })
+}
+
+/// Match path segments against glob segments, where `**` matches any number of segments.
+fn matches_segments(glob: &[&str], path: &[&str]) -> bool {
+    match glob.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|i| matches_segments(rest, &path[i..])),
+        Some((first, rest)) => path
+            .split_first()
+            .is_some_and(|(p, path)| matches_segment(first.as_bytes(), p.as_bytes()) && matches_segments(rest, path)),
+    }
+}
+
+/// Match a single segment against a glob with `*` and `?`.
+fn matches_segment(glob: &[u8], name: &[u8]) -> bool {
+    match glob.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|i| matches_segment(rest, &name[i..])),
+        Some((b'?', rest)) => !name.is_empty() && matches_segment(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && matches_segment(rest, &name[1..]),
+    }
+}
+
+fn clean_file(cleaner: &UrlCleaner, path: PathBuf, dry_run: bool) -> FileResult {
+    let mut result = FileResult { path, changed: false, replacements: Vec::new(), diff: None, errors: Vec::new() };
+    let text = match fs::read_to_string(&result.path) {
+        Ok(text) => text,
+        Err(e) => {
+            result.errors.push(e.to_string());
+            return result;
+        }
+    };
+    let cleaned = clean(cleaner, &text, Kind::of(&result.path).unwrap_or(Kind::Text));
+    result.replacements = cleaned.replacements;
+    result.errors = cleaned.errors;
+    result.changed = cleaned.text != text;
+    if result.changed {
+        if dry_run {
+            result.diff = Some(diff(&result.path.display().to_string(), &text, &cleaned.text));
+        } else if let Err(e) = write_atomically(&result.path, &cleaned.text) {
+            result.errors.push(e.to_string());
+        }
+    }
+    result
+}
+
+/// The result of cleaning the URLs in a text.
+pub struct Cleaned {
+    pub text: String,
+    /// The URLs that were replaced, with their cleaned version
+    pub replacements: Vec<(String, String)>,
+    /// The URLs that couldn't be cleaned, which are left as they are
+    pub errors: Vec<String>,
+}
+
+/// Clean the URLs in `text`.
+pub fn clean(cleaner: &UrlCleaner, text: &str, kind: Kind) -> Cleaned {
+    match kind {
+        Kind::Markdown => clean_markdown(cleaner, text),
+        Kind::Html => clean_html(cleaner, text),
+        Kind::Text => clean_text(cleaner, text, |_, _| true),
+    }
+}
+
+/// Clean the URLs in `text` that the link finder of the cleaner finds and for which `accept` returns `true`
+/// with their range and cleaned version.
+fn clean_text(cleaner: &UrlCleaner, text: &str, mut accept: impl FnMut(&Range<usize>, &str) -> bool) -> Cleaned {
+    let mut cleaned = Cleaned { text: String::new(), replacements: Vec::new(), errors: Vec::new() };
+    let mut last = 0;
+    let result = cleaner.clear_text_with(text, |range, new| {
+        if accept(&range, new) {
+            cleaned.text.push_str(&text[last..range.start]);
+            cleaned.text.push_str(new);
+            cleaned.replacements.push((text[range.clone()].into(), new.into()));
+            last = range.end;
+        }
+    });
+    cleaned.text.push_str(&text[last..]);
+    if let Err(errors) = result {
+        cleaned.errors = link_errors(&errors);
+    }
+    cleaned
+}
+
+/// Clean the URLs in Markdown with `UrlCleaner::clear_markdown`, which leaves code spans and code blocks untouched,
+/// and replace the URLs it changed in the source, so that the rest of the file is kept as it was written.
+fn clean_markdown(cleaner: &UrlCleaner, text: &str) -> Cleaned {
+    use markdown_it::plugins::cmark::inline::autolink::Autolink;
+    use markdown_it::plugins::cmark::inline::image::Image;
+    use markdown_it::plugins::cmark::inline::link::Link;
+    use markdown_it::plugins::extra::linkify::Linkified;
+
+    let mut parser = markdown_it::MarkdownIt::new();
+    markdown_it::plugins::cmark::add(&mut parser);
+    markdown_it::plugins::extra::linkify::add(&mut parser);
+    let mut doc = parser.parse(text);
+    let errors = cleaner.clear_markdown(&mut doc).err().unwrap_or_default();
+    // the parts of the source with a link, and its URL after cleaning
+    let mut links = Vec::new();
+    doc.walk(|node, _| {
+        let url = (node.cast::<Link>().map(|l| &l.url))
+            .or_else(|| node.cast::<Image>().map(|i| &i.url))
+            .or_else(|| node.cast::<Autolink>().map(|a| &a.url))
+            .or_else(|| node.cast::<Linkified>().map(|l| &l.url));
+        if let (Some(url), Some(map)) = (url, node.srcmap) {
+            let (start, end) = map.get_byte_offsets();
+            links.push((start..end, url.as_str()));
+        }
+    });
+    let mut cleaned = clean_text(cleaner, text, |range, new| {
+        links.iter().any(|(link, url)| link.start <= range.start && range.end <= link.end && *url == new)
+    });
+    cleaned.errors = link_errors(&errors);
+    cleaned
+}
+
+/// Clean the URLs in HTML, leaving the ones inside `<pre>` and `<code>` untouched, and understanding `&amp;` in them.
+fn clean_html(cleaner: &UrlCleaner, text: &str) -> Cleaned {
+    static FINDER: OnceLock<linkify::LinkFinder> = OnceLock::new();
+    let finder = FINDER.get_or_init(|| {
+        let mut finder = linkify::LinkFinder::new();
+        finder.kinds(&[linkify::LinkKind::Url]);
+        finder
+    });
+    let mut cleaned = Cleaned { text: String::new(), replacements: Vec::new(), errors: Vec::new() };
+    let mut last = 0;
+    for segment in segments(text) {
+        for link in finder.links(&text[segment.clone()]) {
+            let original = link.as_str();
+            // `&` is escaped in attributes and text
+            let url = original.replace("&amp;", "&");
+            match cleaner.clear_single_url_str(&url) {
+                Ok(new) if new != url => {
+                    let new = new.into_owned();
+                    cleaned.text.push_str(&text[last..segment.start + link.start()]);
+                    cleaned.text.push_str(&new.replace('&', "&amp;"));
+                    last = segment.start + link.end();
+                    cleaned.replacements.push((url, new));
+                }
+                Ok(_) => {}
+                Err(e) => cleaned.errors.push(format!("error cleaning {original}: {e}")),
+            }
+        }
+    }
+    cleaned.text.push_str(&text[last..]);
+    cleaned
+}
+
+fn link_errors(errors: &[LinkError]) -> Vec<String> {
+    errors.iter().map(|e| format!("error cleaning {}: {}", e.url(), e.error())).collect()
+}
+
+/// The parts of HTML outside of `<pre>` and `<code>` elements.
+fn segments(text: &str) -> Vec<Range<usize>> {
+    let mut skipped: Vec<_> = ["pre", "code"].iter().flat_map(|tag| html_elements(text, tag)).collect();
+    skipped.sort_by_key(|r| r.start);
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for r in skipped {
+        if r.start > start {
+            segments.push(start..r.start);
+        }
+        start = start.max(r.end);
+    }
+    segments.push(start..text.len());
+    segments
+}
+
+/// The elements with the given tag name in HTML, from the start of the opening tag to the end of the closing tag.
+fn html_elements(text: &str, tag: &str) -> Vec<Range<usize>> {
+    let lower = text.to_ascii_lowercase();
+    let open = format!("<{tag}");
+    let close = format!("</{tag}>");
+    let mut elements = Vec::new();
+    let mut from = 0;
+    while let Some(start) = lower[from..].find(&open).map(|i| from + i) {
+        let after = lower.as_bytes().get(start + open.len()).copied();
+        if !matches!(after, Some(b'>' | b' ' | b'\t' | b'\n' | b'\r' | b'/')) {
+            from = start + open.len();
+            continue;
+        }
+        let end = lower[start..].find(&close).map_or(text.len(), |i| start + i + close.len());
+        elements.push(start..end);
+        from = end;
+    }
+    elements
+}
+
+/// A diff of the lines that differ, which is printed for `--dry-run` and `--patch`.
+pub fn diff(name: &str, old: &str, new: &str) -> String {
+    let mut diff = format!("--- {name}\n+++ {name}\n");
+    // cleaned URLs never contain line breaks, so the lines correspond to each other
+    for (number, (old, new)) in old.lines().zip(new.lines()).enumerate() {
+        if old != new {
+            let _ = writeln!(diff, "@@ line {} @@\n-{old}\n+{new}", number + 1);
+        }
+    }
+    diff
+}
+
+/// Replace the file with a new one, so that it's never left half-written.
+fn write_atomically(path: &Path, contents: &str) -> std::io::Result<()> {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("file");
+    let tmp = path.with_file_name(format!(".{name}.clearurls-tmp"));
+    fs::write(&tmp, contents)?;
+    let result = fs::metadata(path)
+        .and_then(|m| fs::set_permissions(&tmp, m.permissions()))
+        .and_then(|()| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
@@ -0,0 +1,380 @@
+//! Remove tracking parameters from URLs on the command line.
+//!
+//! URLs given as arguments are cleaned and printed one per line.
+//! Without arguments, the text from stdin is printed with all URLs in it cleaned.
+//! With `--files`, the arguments are files and directories, which are cleaned in place.
+//! With `--crawl`, the arguments are sitemaps or pages,
+//! and the links on the pages that cleaning would change are listed.
+//! With `--lines`, stdin is cleaned line by line as it comes in, so that the tool can be used behind `tail -f`.
+//!
+//! The exit code is 0 if nothing was changed, 1 if something was changed and 2 if there were errors,
+//! so that CI pipelines can reject tracking parameters in published content.
+
+use std::io::{self, BufRead, Read, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, ValueEnum};
+use clearurls::{PolicyFile, UrlCleaner};
+use serde::Serialize;
+use url::Url;
+
+#[cfg(feature = "crawl")]
+mod crawl;
+mod files;
+
+#[derive(Parser)]
+#[command(version, about, after_help = EXIT_CODES)]
+struct Args {
+    /// The URLs to clean. If there are none, clean all URLs in the text from stdin.
+    urls: Vec<String>,
+    /// Treat the arguments as files and directories and clean the URLs in them in place.
+    ///
+    /// Directories are processed recursively. Markdown and HTML files are recognized by their extension,
+    /// so that URLs in code are left untouched. Other files are treated as plain text.
+    #[arg(long, requires = "urls")]
+    files: bool,
+    /// Only clean files in directories that match this glob, like `**/*.md`, instead of all Markdown, HTML
+    /// and text files. A glob without a slash matches the file name. Can be given multiple times.
+    #[arg(long, requires = "files")]
+    glob: Vec<String>,
+    /// Print a diff of the changes to files instead of writing them.
+    #[arg(long, requires = "files")]
+    dry_run: bool,
+    /// Treat the arguments as sitemaps, web pages, or files that list pages one per line, and list the links on the
+    /// pages that cleaning would change, without changing anything.
+    ///
+    /// Sitemap indexes are followed to the sitemaps they refer to. This is meant for auditing a site for leaking
+    /// trackers. The exit code is 1 if there is such a link.
+    #[cfg(feature = "crawl")]
+    #[arg(long, requires = "urls", conflicts_with = "files")]
+    crawl: bool,
+    /// How many pages are fetched at the same time with `--crawl`.
+    #[cfg(feature = "crawl")]
+    #[arg(long, requires = "crawl", default_value_t = 8,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    concurrency: usize,
+    /// With `--crawl`, print a diff of the changes to the source of each page instead of listing the links.
+    ///
+    /// With `--format json`, the diff is the `patch` of each record.
+    #[cfg(feature = "crawl")]
+    #[arg(long, requires = "crawl")]
+    patch: bool,
+    /// Clean each line from stdin as a single URL as soon as it is read, instead of reading all of stdin first.
+    ///
+    /// Lines that can't be cleaned are printed as they are, so that the output lines correspond to the input lines.
+    #[arg(long, conflicts_with = "urls")]
+    lines: bool,
+    /// With `--lines`, clean all URLs in each line instead of treating it as a single URL.
+    #[arg(long, requires = "lines")]
+    text: bool,
+    /// How to print the results.
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// Configure cleaning with a policy file instead of using the embedded rules with the default settings.
+    ///
+    /// Defaults to the file named by `CLEARURLS_POLICY`. Environment variables like `CLEARURLS_STRIP_REFERRAL`
+    /// override the settings in the file.
+    #[arg(long, value_name = "FILE")]
+    policy: Option<PathBuf>,
+    /// Keep running and replace URLs in the system clipboard with their cleaned version as they are copied.
+    #[cfg(feature = "desktop")]
+    #[arg(long, conflicts_with = "urls")]
+    watch_clipboard: bool,
+}
+
+const EXIT_CODES: &str = "Exit codes: 0 if nothing was changed, 1 if something was changed, 2 if there were errors.";
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// The cleaned URLs or text, or the changed files, with errors on stderr
+    Text,
+    /// A JSON object per input and line, with the fields `original`, `cleaned`, `changed`, `removed_params`
+    /// and `errors`. For files, `path` replaces `original` and `cleaned`. For pages with `--crawl`, `page` and
+    /// the `links` that would change, each with its `original` and `cleaned` URL, replace them.
+    Json,
+}
+
+/// The result of cleaning one input, which is printed with `--format json`.
+#[derive(Default, Serialize)]
+struct Record {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    page: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cleaned: Option<String>,
+    changed: bool,
+    removed_params: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    links: Vec<Link>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    patch: Option<String>,
+    errors: Vec<String>,
+}
+
+/// A link on a page that cleaning would change.
+#[derive(Serialize)]
+struct Link {
+    original: String,
+    cleaned: String,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let cleaner = match PolicyFile::load(args.policy.as_deref()) {
+        Ok(cleaner) => cleaner,
+        Err(e) => {
+            eprintln!("error loading the policy: {e}");
+            return ExitCode::from(2);
+        }
+    };
+    #[cfg(feature = "desktop")]
+    if args.watch_clipboard {
+        if let Err(e) = watch_clipboard(&cleaner) {
+            eprintln!("{e}");
+        }
+        return ExitCode::from(2);
+    }
+    if args.lines {
+        return clean_lines(&cleaner, args.text, args.format);
+    }
+    #[cfg(feature = "crawl")]
+    if args.crawl {
+        let options = crawl::Options { concurrency: args.concurrency, patch: args.patch };
+        let results = crawl::run(&cleaner, &args.urls, &options);
+        return finish(&results.into_iter().map(|r| page_record(r, args.format)).collect::<Vec<_>>(), args.format);
+    }
+    let records = if args.files {
+        let paths: Vec<PathBuf> = args.urls.iter().map(PathBuf::from).collect();
+        let options = files::Options { globs: args.glob, dry_run: args.dry_run };
+        let results = files::run(&cleaner, &paths, &options);
+        results.into_iter().map(|r| file_record(r, args.format, args.dry_run)).collect()
+    } else if args.urls.is_empty() {
+        vec![clean_stdin(&cleaner, args.format)]
+    } else {
+        args.urls.iter().map(|url| clean_url(&cleaner, url, args.format)).collect::<Vec<_>>()
+    };
+    finish(&records, args.format)
+}
+
+/// Print the records as JSON if requested, and return the exit code for them.
+fn finish(records: &[Record], format: Format) -> ExitCode {
+    if format == Format::Json {
+        records.iter().for_each(print_json);
+    }
+    exit_code(records.iter().any(|r| !r.errors.is_empty()), records.iter().any(|r| r.changed))
+}
+
+fn exit_code(errors: bool, changed: bool) -> ExitCode {
+    if errors {
+        ExitCode::from(2)
+    } else if changed {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn print_json(record: &Record) {
+    println!("{}", serde_json::to_string(record).expect("records can be serialized"));
+}
+
+fn clean_url(cleaner: &UrlCleaner, url: &str, format: Format) -> Record {
+    let mut record = Record { original: Some(url.into()), ..Record::default() };
+    match cleaner.clear_single_url_str(url) {
+        Ok(cleaned) => {
+            if format == Format::Text {
+                println!("{cleaned}");
+            }
+            record.changed = cleaned != url;
+            record.removed_params = removed_params(url, &cleaned);
+            record.cleaned = Some(cleaned.into_owned());
+        }
+        Err(e) => {
+            let e = format!("error cleaning {url}: {e}");
+            if format == Format::Text {
+                eprintln!("{e}");
+            }
+            record.errors.push(e);
+        }
+    }
+    record
+}
+
+fn clean_stdin(cleaner: &UrlCleaner, format: Format) -> Record {
+    let mut text = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut text) {
+        return print_errors(Record { errors: vec![format!("error reading stdin: {e}")], ..Record::default() }, format);
+    }
+    // URLs that can't be cleaned are left as they are, so the rest of the text is still printed
+    let cleaned = files::clean(cleaner, &text, files::Kind::Text);
+    let mut record = Record {
+        changed: cleaned.text != text,
+        removed_params: cleaned.replacements.iter().flat_map(|(old, new)| removed_params(old, new)).collect(),
+        errors: cleaned.errors,
+        ..Record::default()
+    };
+    if format == Format::Text {
+        if let Err(e) = io::stdout().write_all(cleaned.text.as_bytes()) {
+            record.errors.push(format!("error writing stdout: {e}"));
+        }
+    }
+    record.original = Some(text);
+    record.cleaned = Some(cleaned.text);
+    print_errors(record, format)
+}
+
+/// Clean stdin line by line, printing each result before reading the next line.
+///
+/// Only the current line is kept in memory, so this can run indefinitely.
+fn clean_lines(cleaner: &UrlCleaner, text: bool, format: Format) -> ExitCode {
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    let mut line = String::new();
+    let (mut errors, mut changed) = (false, false);
+    loop {
+        line.clear();
+        match input.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("error reading stdin: {e}");
+                errors = true;
+                break;
+            }
+        }
+        let content = line.trim_end_matches(['\n', '\r']);
+        let record = if text {
+            let cleaned = files::clean(cleaner, content, files::Kind::Text);
+            Record {
+                original: Some(content.into()),
+                changed: cleaned.text != content,
+                removed_params: cleaned.replacements.iter().flat_map(|(old, new)| removed_params(old, new)).collect(),
+                errors: cleaned.errors,
+                cleaned: Some(cleaned.text),
+                ..Record::default()
+            }
+        } else if content.trim().is_empty() {
+            Record { original: Some(content.into()), cleaned: Some(content.into()), ..Record::default() }
+        } else {
+            clean_url(cleaner, content.trim(), Format::Json)
+        };
+        let written = match format {
+            Format::Json => writeln!(output, "{}", serde_json::to_string(&record).expect("records can be serialized")),
+            Format::Text => {
+                for e in &record.errors {
+                    eprintln!("{e}");
+                }
+                writeln!(output, "{}", record.cleaned.as_deref().unwrap_or(content))
+            }
+        };
+        // stop quietly when the reader of the pipeline has gone away
+        if written.and_then(|()| output.flush()).is_err() {
+            break;
+        }
+        errors |= !record.errors.is_empty();
+        changed |= record.changed;
+    }
+    exit_code(errors, changed)
+}
+
+fn file_record(result: files::FileResult, format: Format, dry_run: bool) -> Record {
+    let path = result.path.display().to_string();
+    if format == Format::Text {
+        if let Some(diff) = &result.diff {
+            print!("{diff}");
+        } else if result.changed && !dry_run {
+            println!("{path}");
+        }
+    }
+    let record = Record {
+        path: Some(path.clone()),
+        changed: result.changed,
+        removed_params: result.replacements.iter().flat_map(|(old, new)| removed_params(old, new)).collect(),
+        errors: result.errors.into_iter().map(|e| format!("{path}: {e}")).collect(),
+        ..Record::default()
+    };
+    print_errors(record, format)
+}
+
+#[cfg(feature = "crawl")]
+fn page_record(result: crawl::PageResult, format: Format) -> Record {
+    if format == Format::Text {
+        if let Some(patch) = &result.patch {
+            print!("{patch}");
+        } else if !result.replacements.is_empty() {
+            println!("{}", result.page);
+            for (original, cleaned) in &result.replacements {
+                println!("  {original} -> {cleaned}");
+            }
+        }
+    }
+    let record = Record {
+        changed: !result.replacements.is_empty(),
+        removed_params: result.replacements.iter().flat_map(|(old, new)| removed_params(old, new)).collect(),
+        links: result.replacements.into_iter().map(|(original, cleaned)| Link { original, cleaned }).collect(),
+        errors: result.errors.into_iter().map(|e| format!("{}: {e}", result.page)).collect(),
+        patch: result.patch,
+        page: Some(result.page),
+        ..Record::default()
+    };
+    print_errors(record, format)
+}
+
+/// Print the errors of a record to stderr, unless they are part of the JSON output.
+fn print_errors(record: Record, format: Format) -> Record {
+    if format == Format::Text {
+        for e in &record.errors {
+            eprintln!("{e}");
+        }
+    }
+    record
+}
+
+/// The names of the query and fragment parameters of `original` that are missing from `cleaned`.
+fn removed_params(original: &str, cleaned: &str) -> Vec<String> {
+    let names = |url: &str| -> Vec<String> {
+        let Ok(url) = Url::parse(url) else { return Vec::new() };
+        let fragment = url::form_urlencoded::parse(url.fragment().unwrap_or("").as_bytes());
+        url.query_pairs().chain(fragment).map(|(name, _)| name.into_owned()).collect()
+    };
+    let mut remaining = names(cleaned);
+    let mut removed = Vec::new();
+    for name in names(original) {
+        match remaining.iter().position(|n| *n == name) {
+            Some(i) => {
+                remaining.swap_remove(i);
+            }
+            None => removed.push(name),
+        }
+    }
+    removed
+}
+
+/// Poll the clipboard and replace its content when it contains URLs that can be cleaned.
+#[cfg(feature = "desktop")]
+fn watch_clipboard(cleaner: &UrlCleaner) -> Result<(), String> {
+    use std::time::Duration;
+
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| format!("error opening the clipboard: {e}"))?;
+    let mut last = String::new();
+    loop {
+        // an error means that the clipboard is empty or contains something other than text
+        if let Ok(text) = clipboard.get_text() {
+            if text != last {
+                let (cleaned, _) = cleaner.clear_text_partial(&text);
+                if cleaned != text {
+                    clipboard
+                        .set_text(cleaned.as_ref())
+                        .map_err(|e| format!("error writing the clipboard: {e}"))?;
+                    eprintln!("cleaned {text}");
+                }
+                last = cleaned.into_owned();
+            }
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+}
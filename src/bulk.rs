@@ -0,0 +1,206 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::fmt::{Debug, Formatter};
+use core::num::NonZeroUsize;
+use core::ops::Deref;
+use core::time::Duration;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+use crate::{CleanError, UrlCleaner};
+
+type ProgressCallback = Box<dyn FnMut(&BulkProgress) + Send>;
+
+/// Cleans many stored URLs at once on a pool of threads, like all links in a database during a migration.
+///
+/// # Example
+/// ```
+/// # use std::sync::Arc;
+/// # use clearurls::{BulkCleaner, UrlCleaner};
+/// # fn main() -> Result<(), clearurls::Error> {
+/// let rows = vec![(1, "https://example.com/?utm_source=x".to_string()), (2, "https://example.com/".to_string())];
+/// let results = BulkCleaner::new(Arc::new(UrlCleaner::from_embedded_rules()?))
+///     .workers(4)
+///     .rate_limit(1000)
+///     .on_progress(|p| eprintln!("{} done, {} changed", p.done, p.changed))
+///     .clean(rows);
+/// for result in results.filter(|r| r.changed) {
+///     // UPDATE links SET url = ? WHERE id = ?
+///     assert_eq!((result.id, result.cleaned?), (1, "https://example.com/".to_string()));
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct BulkCleaner<C> {
+    cleaner: C,
+    workers: usize,
+    rate_limit: u32,
+    progress: Option<ProgressCallback>,
+}
+
+impl<C> Debug for BulkCleaner<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("BulkCleaner")
+            .field("workers", &self.workers)
+            .field("rate_limit", &self.rate_limit)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The outcome for one URL, see [`BulkCleaner::clean`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct BulkResult<K> {
+    /// The id that was passed in with the URL
+    pub id: K,
+    /// The cleaned URL
+    pub cleaned: Result<String, CleanError>,
+    /// Whether the cleaned URL differs from the original, so that it needs to be stored
+    pub changed: bool,
+}
+
+/// How far cleaning has progressed, which is passed to the callback of [`BulkCleaner::on_progress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BulkProgress {
+    /// The number of URLs that were cleaned so far, including those that caused an error
+    pub done: usize,
+    /// The number of URLs that were changed so far
+    pub changed: usize,
+    /// The number of URLs that caused an error so far
+    pub errors: usize,
+    /// The total number of URLs, if the iterator knows it
+    pub total: Option<usize>,
+    /// The time since cleaning started
+    pub elapsed: Duration,
+}
+
+impl<C: Deref<Target = UrlCleaner> + Send + Sync + 'static> BulkCleaner<C> {
+    /// Clean with the given cleaner, which is usually an [`Arc`], or the `&'static` one from
+    /// [`UrlCleaner::shared`], on as many threads as there are CPUs, without a rate limit.
+    #[must_use]
+    pub fn new(cleaner: C) -> Self {
+        let workers = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        Self { cleaner, workers, rate_limit: 0, progress: None }
+    }
+
+    /// The number of threads that clean URLs. At least one is used.
+    #[must_use]
+    pub const fn workers(mut self, workers: usize) -> Self {
+        self.workers = if workers == 0 { 1 } else { workers };
+        self
+    }
+
+    /// Take at most this many URLs per second from the iterator, to spread the load on the database that they are
+    /// read from and written to. `0` disables the limit, which is the default.
+    #[must_use]
+    pub const fn rate_limit(mut self, per_second: u32) -> Self {
+        self.rate_limit = per_second;
+        self
+    }
+
+    /// Call `callback` after every URL, on the thread that consumes the results.
+    #[must_use]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn on_progress<F: FnMut(&BulkProgress) + Send + 'static>(mut self, callback: F) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Start cleaning the URLs, each with an id, like the primary key of its row.
+    ///
+    /// The URLs are taken from the iterator on a thread of its own, so a database cursor can be passed in.
+    /// The results are returned as soon as they are ready, which isn't necessarily in the order of the URLs.
+    /// If the returned iterator is dropped early, no more URLs are taken and the threads stop.
+    pub fn clean<K, U, I>(self, urls: I) -> BulkResults<K>
+    where
+        K: Send + 'static,
+        U: AsRef<str> + Send + 'static,
+        I: IntoIterator<Item = (K, U)>,
+        I::IntoIter: Send + 'static,
+    {
+        let urls = urls.into_iter();
+        let total = match urls.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(lower),
+            _ => None,
+        };
+        let (input, queue) = mpsc::sync_channel::<(K, U)>(self.workers * 2);
+        let (output, results) = mpsc::channel();
+        let rate_limit = self.rate_limit;
+        thread::spawn(move || {
+            let interval = (rate_limit > 0).then(|| Duration::from_secs(1) / rate_limit);
+            let mut due = Instant::now();
+            for item in urls {
+                if let Some(interval) = interval {
+                    thread::sleep(due.saturating_duration_since(Instant::now()));
+                    due += interval;
+                }
+                if input.send(item).is_err() {
+                    break;
+                }
+            }
+        });
+        let queue = Arc::new(Mutex::new(queue));
+        let shared = Arc::new(self.cleaner);
+        for _ in 0..self.workers {
+            let (queue, output, cleaner) = (Arc::clone(&queue), output.clone(), Arc::clone(&shared));
+            thread::spawn(move || loop {
+                let item = queue.lock().unwrap_or_else(std::sync::PoisonError::into_inner).recv();
+                let Ok((id, url)) = item else {
+                    break;
+                };
+                let url = url.as_ref();
+                let result = match cleaner.clear_single_url_str(url) {
+                    Ok(new) => BulkResult { id, changed: new != url, cleaned: Ok(new.into_owned()) },
+                    Err(e) => BulkResult { id, changed: false, cleaned: Err(e) },
+                };
+                if output.send(result).is_err() {
+                    break;
+                }
+            });
+        }
+        let progress = BulkProgress { done: 0, changed: 0, errors: 0, total, elapsed: Duration::ZERO };
+        BulkResults { results, callback: self.progress, progress, start: Instant::now() }
+    }
+}
+
+/// The results of [`BulkCleaner::clean`], as they become ready.
+pub struct BulkResults<K> {
+    results: Receiver<BulkResult<K>>,
+    callback: Option<ProgressCallback>,
+    progress: BulkProgress,
+    start: Instant,
+}
+
+impl<K> BulkResults<K> {
+    /// The progress up to the last result that was returned.
+    #[must_use]
+    pub const fn progress(&self) -> &BulkProgress {
+        &self.progress
+    }
+}
+
+impl<K> Debug for BulkResults<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("BulkResults").field("progress", &self.progress).finish_non_exhaustive()
+    }
+}
+
+impl<K> Iterator for BulkResults<K> {
+    type Item = BulkResult<K>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.results.recv().ok()?;
+        self.progress.done += 1;
+        self.progress.changed += usize::from(result.changed);
+        self.progress.errors += usize::from(result.cleaned.is_err());
+        self.progress.elapsed = self.start.elapsed();
+        if let Some(callback) = &mut self.callback {
+            callback(&self.progress);
+        }
+        Some(result)
+    }
+}
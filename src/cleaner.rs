@@ -0,0 +1,203 @@
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{Debug, Formatter};
+
+use crate::{Error, UrlCleaner};
+
+/// Something that can clean a single URL.
+///
+/// This is implemented by [`UrlCleaner`], but you can implement it for your own types
+/// and combine them with a [`CleanerChain`].
+pub trait Cleaner {
+    /// Clean a single URL.
+    ///
+    /// The argument is a string that is *just* a URL, with no text around.
+    ///
+    /// # Errors
+    /// If an error occurred. See the [`Error`] enum for possible reasons.
+    fn clean<'a>(&self, url: &'a str) -> Result<Cow<'a, str>, Error>;
+}
+
+impl Cleaner for UrlCleaner {
+    fn clean<'a>(&self, url: &'a str) -> Result<Cow<'a, str>, Error> {
+        Ok(self.clear_single_url_str(url)?)
+    }
+}
+
+/// A sequence of [`Cleaner`]s that are applied one after the other.
+///
+/// # Example
+/// ```
+/// # use std::borrow::Cow;
+/// # use clearurls::{Cleaner, CleanerChain, Error, UrlCleaner};
+/// struct AmpUnwrapper;
+///
+/// impl Cleaner for AmpUnwrapper {
+///     fn clean<'a>(&self, url: &'a str) -> Result<Cow<'a, str>, Error> {
+///         Ok(match url.strip_suffix("/amp") {
+///             Some(stripped) => Cow::Borrowed(stripped),
+///             None => Cow::Borrowed(url),
+///         })
+///     }
+/// }
+///
+/// # fn main() -> Result<(), Error> {
+/// let chain = CleanerChain::new()
+///     .then(UrlCleaner::from_embedded_rules()?)
+///     .then(AmpUnwrapper);
+/// let res = chain.clean("https://example.com/article/amp?utm_source=abc")?;
+/// assert_eq!(res, "https://example.com/article");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct CleanerChain {
+    cleaners: Vec<Box<dyn Cleaner + Send + Sync>>,
+}
+
+impl CleanerChain {
+    /// Create an empty chain, which returns all URLs unchanged.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            cleaners: Vec::new(),
+        }
+    }
+
+    /// Append a cleaner to the end of the chain.
+    #[must_use]
+    pub fn then<C: Cleaner + Send + Sync + 'static>(mut self, cleaner: C) -> Self {
+        self.cleaners.push(Box::new(cleaner));
+        self
+    }
+}
+
+impl Cleaner for CleanerChain {
+    fn clean<'a>(&self, url: &'a str) -> Result<Cow<'a, str>, Error> {
+        let mut result = Cow::Borrowed(url);
+        for c in &self.cleaners {
+            result = match result {
+                Cow::Borrowed(url) => c.clean(url)?,
+                Cow::Owned(url) => {
+                    // a cleaner may return a borrowed substring, which still counts as a change
+                    let new = match c.clean(&url)? {
+                        Cow::Borrowed(b) if core::ptr::eq(b, url.as_str()) => None,
+                        new => Some(new.into_owned()),
+                    };
+                    Cow::Owned(new.unwrap_or(url))
+                }
+            };
+        }
+        Ok(result)
+    }
+}
+
+impl Debug for CleanerChain {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CleanerChain")
+            .field("len", &self.cleaners.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A [`Cleaner`] with scripted responses, for testing code that takes a [`Cleaner`] without loading any rules.
+///
+/// URLs without a response are returned unchanged. Every URL that was passed to it is recorded.
+///
+/// # Example
+/// ```
+/// # use clearurls::{Cleaner, CleanerChain, Error, MockCleaner};
+/// # fn main() -> Result<(), Error> {
+/// let mock = MockCleaner::new()
+///     .respond("https://example.com/?id=1", "https://example.com/")
+///     .fail("not a url", || Error::UrlSyntax(url::ParseError::RelativeUrlWithoutBase));
+/// assert_eq!(mock.clean("https://example.com/?id=1")?, "https://example.com/");
+/// assert_eq!(mock.clean("https://example.com/other")?, "https://example.com/other");
+/// assert!(mock.clean("not a url").is_err());
+/// assert_eq!(mock.calls(), ["https://example.com/?id=1", "https://example.com/other", "not a url"]);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "std")]
+#[derive(Default)]
+pub struct MockCleaner {
+    responses: BTreeMap<String, Response>,
+    calls: std::sync::Mutex<Vec<String>>,
+}
+
+/// What a [`MockCleaner`] does with a URL.
+#[cfg(feature = "std")]
+enum Response {
+    Clean(String),
+    // errors can't be cloned, so a new one is created for every call
+    Fail(Box<dyn Fn() -> Error + Send + Sync>),
+}
+
+#[cfg(feature = "std")]
+impl MockCleaner {
+    /// Create a mock that returns all URLs unchanged.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return `cleaned` for `url`.
+    #[must_use]
+    pub fn respond(mut self, url: &str, cleaned: &str) -> Self {
+        self.responses.insert(url.into(), Response::Clean(cleaned.into()));
+        self
+    }
+
+    /// Fail with the error returned by `error` for `url`.
+    #[must_use]
+    pub fn fail<F: Fn() -> Error + Send + Sync + 'static>(mut self, url: &str, error: F) -> Self {
+        self.responses.insert(url.into(), Response::Fail(Box::new(error)));
+        self
+    }
+
+    /// The URLs that were passed to [`clean`][Cleaner::clean] so far, in order.
+    #[must_use]
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap_or_else(std::sync::PoisonError::into_inner).clone()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Cleaner for MockCleaner {
+    fn clean<'a>(&self, url: &'a str) -> Result<Cow<'a, str>, Error> {
+        self.calls.lock().unwrap_or_else(std::sync::PoisonError::into_inner).push(url.into());
+        match self.responses.get(url) {
+            Some(Response::Clean(cleaned)) if cleaned != url => Ok(Cow::Owned(cleaned.clone())),
+            Some(Response::Fail(error)) => Err(error()),
+            _ => Ok(Cow::Borrowed(url)),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Debug for MockCleaner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("MockCleaner")
+            .field("responses", &self.responses.len())
+            .field("calls", &self.calls().len())
+            .finish()
+    }
+}
+
+/// A [`Cleaner`] for the URLs with a certain scheme, see [`UrlCleaner::scheme_handler`].
+pub(crate) struct SchemeHandler {
+    pub(crate) scheme: String,
+    pub(crate) cleaner: Box<dyn Cleaner + Send + Sync>,
+}
+
+impl Debug for SchemeHandler {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SchemeHandler")
+            .field("scheme", &self.scheme)
+            .finish_non_exhaustive()
+    }
+}
@@ -1,145 +1,153 @@
 use alloc::borrow::Cow;
 use alloc::fmt;
+use alloc::string::String;
 use alloc::vec::Vec;
 use core::marker::PhantomData;
 
-use regex::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
-use serde::de::{Error as _, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::de::{MapAccess, SeqAccess, Visitor};
 use serde::{Deserialize, Deserializer};
 
-/// Deserialize a [`Regex`]
-/// The result will have the `case_insensitive` flag set.
-pub(crate) fn deserialize_regex<'de, D>(d: D) -> Result<Regex, D::Error>
+/// Deserialize a [`Vec`] of key-value pairs from a map, keeping the order of the entries.
+pub(crate) fn deserialize_map_as_vec<'de, D, K, V>(d: D) -> Result<Vec<(K, V)>, D::Error>
 where
     D: Deserializer<'de>,
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
 {
-    let s = <Cow<'_, str>>::deserialize(d)?;
-    RegexBuilder::new(&s)
-        .case_insensitive(true)
-        .build()
-        .map_err(D::Error::custom)
-}
-
-/// Deserialize a [`Vec<Regex>`].
-/// All regexes will have the `case_insensitive` flag set.
-pub(crate) fn deserialize_regex_vec<'de, D>(d: D) -> Result<Vec<Regex>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    struct RegexVecVisitor;
-    impl<'a> Visitor<'a> for RegexVecVisitor {
-        type Value = Vec<Regex>;
+    struct MapAsVecVisitor<K, V>(PhantomData<(K, V)>);
+    impl<'de, K: Deserialize<'de>, V: Deserialize<'de>> Visitor<'de> for MapAsVecVisitor<K, V> {
+        type Value = Vec<(K, V)>;
 
         fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-            formatter.write_str("valid sequence")
+            formatter.write_str("valid map")
         }
 
-        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
         where
-            A: SeqAccess<'a>,
+            A: MapAccess<'de>,
         {
-            let cap = seq.size_hint().unwrap_or(0);
+            let cap = map.size_hint().unwrap_or(0);
             let mut vec = Vec::with_capacity(cap);
-            while let Some(el) = seq.next_element::<Cow<'_, str>>()? {
-                let regex = RegexBuilder::new(&el)
-                    .case_insensitive(true)
-                    .build()
-                    .map_err(A::Error::custom)?;
-                vec.push(regex);
+            while let Some(entry) = map.next_entry()? {
+                vec.push(entry);
             }
             Ok(vec)
         }
     }
 
-    d.deserialize_seq(RegexVecVisitor)
+    d.deserialize_map(MapAsVecVisitor(PhantomData))
 }
 
-/// Deserialize a [`RegexSet`].
-/// All regexes will have the `case_insensitive` flag set.
-pub(crate) fn deserialize_regex_set<'de, D>(d: D) -> Result<RegexSet, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let regexes = <Vec<Cow<'_, str>>>::deserialize(d)?;
-    RegexSetBuilder::new(regexes)
-        .case_insensitive(true)
-        .build()
-        .map_err(D::Error::custom)
+/// A string that borrows from the input if it contains no escape sequences.
+///
+/// Unlike [`Cow`] itself, this borrows even as an element of a [`Vec`].
+struct BorrowedStr<'a>(Cow<'a, str>);
+
+impl<'de: 'a, 'a> Deserialize<'de> for BorrowedStr<'a> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct StrVisitor<'a>(PhantomData<Cow<'a, str>>);
+        impl<'de: 'a, 'a> Visitor<'de> for StrVisitor<'a> {
+            type Value = BorrowedStr<'a>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a string")
+            }
+
+            fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E> {
+                Ok(BorrowedStr(Cow::Borrowed(v)))
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(BorrowedStr(Cow::Owned(v.into())))
+            }
+
+            fn visit_string<E>(self, v: String) -> Result<Self::Value, E> {
+                Ok(BorrowedStr(Cow::Owned(v)))
+            }
+        }
+
+        d.deserialize_str(StrVisitor(PhantomData))
+    }
 }
 
-/// Deserialize a [`Vec`] from a map by ignoring the keys.
-pub(crate) fn deserialize_map_as_vec<'de, D, T>(d: D) -> Result<Vec<T>, D::Error>
+/// Deserialize a [`Vec`] of strings, borrowing them from the input where possible.
+pub(crate) fn deserialize_strs<'de: 'a, 'a, D>(d: D) -> Result<Vec<Cow<'a, str>>, D::Error>
 where
     D: Deserializer<'de>,
-    T: Deserialize<'de>,
 {
-    struct MapAsVecVisitor<T>(PhantomData<T>);
-    impl<'de, T: Deserialize<'de>> Visitor<'de> for MapAsVecVisitor<T> {
-        type Value = Vec<T>;
+    struct StrsVisitor<'a>(PhantomData<Cow<'a, str>>);
+    impl<'de: 'a, 'a> Visitor<'de> for StrsVisitor<'a> {
+        type Value = Vec<Cow<'a, str>>;
 
         fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-            formatter.write_str("valid map")
+            formatter.write_str("a list of strings")
         }
 
-        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
         where
-            A: MapAccess<'de>,
+            A: SeqAccess<'de>,
         {
-            let cap = map.size_hint().unwrap_or(0);
-            let mut vec = Vec::with_capacity(cap);
-            while let Some((_, v)) = map.next_entry::<IgnoredAny, T>()? {
-                vec.push(v);
+            let mut vec = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(BorrowedStr(s)) = seq.next_element()? {
+                vec.push(s);
             }
             Ok(vec)
         }
     }
 
-    d.deserialize_map(MapAsVecVisitor(PhantomData))
+    d.deserialize_seq(StrsVisitor(PhantomData))
 }
 
 #[cfg(test)]
 mod tests {
     use crate::deserialize_utils::*;
+    use alloc::string::String;
     use serde_json::error::Category;
     use serde_json::json;
 
     #[test]
     fn test_deserialize_regex() {
-        let regex = deserialize_regex(json!("a")).unwrap();
+        use crate::{BuiltinEngine, CompileError, CompileOptions, RegexEngine as _};
+
+        let regex = BuiltinEngine.compile("a", &CompileOptions::default()).unwrap();
         assert!(regex.is_match("A"));
-        let error = deserialize_regex(json!("[")).unwrap_err();
+        let error = BuiltinEngine.compile("[", &CompileOptions::default()).unwrap_err();
+        assert!(matches!(error, CompileError::Invalid(_)));
+        let error = <Cow<'_, str>>::deserialize(json!(true)).unwrap_err();
         assert_eq!(error.classify(), Category::Data);
-        let error = deserialize_regex(json!(true)).unwrap_err();
-        assert_eq!(error.classify(), Category::Data);
-        let error = deserialize_regex(json!([])).unwrap_err();
+        let error = <Cow<'_, str>>::deserialize(json!([])).unwrap_err();
         assert_eq!(error.classify(), Category::Data);
     }
 
     #[test]
     fn test_deserialize_regex_set_error() {
-        let error = deserialize_regex_set(json!([".*", 1])).unwrap_err();
-        assert_eq!(error.classify(), Category::Data);
-        let error = deserialize_regex_set(json!("")).unwrap_err();
+        use crate::{BuiltinEngine, CompileError, CompileOptions, RegexEngine as _};
+
+        let error = deserialize_strs(json!([".*", 1])).unwrap_err();
         assert_eq!(error.classify(), Category::Data);
-        let error = deserialize_regex_set(json!(["["])).unwrap_err();
+        let error = deserialize_strs(json!("")).unwrap_err();
         assert_eq!(error.classify(), Category::Data);
+        let set = BuiltinEngine.compile_set(&["a", "b"], &CompileOptions::default()).unwrap();
+        assert!(set.is_match("B") && !set.is_match("c"));
+        let error = BuiltinEngine.compile_set(&[".*", "["], &CompileOptions::default()).unwrap_err();
+        assert!(matches!(error, CompileError::Invalid(_)));
     }
 
     #[test]
     fn test_deserialize_regex_vec_error() {
-        let error = deserialize_regex_vec(json!([".*", 1])).unwrap_err();
-        assert_eq!(error.classify(), Category::Data);
-        let error = deserialize_regex_vec(json!("")).unwrap_err();
+        let error = deserialize_strs(json!([".*", 1])).unwrap_err();
         assert_eq!(error.classify(), Category::Data);
-        let error = deserialize_regex_vec(json!(["["])).unwrap_err();
+        let error = deserialize_strs(json!("")).unwrap_err();
         assert_eq!(error.classify(), Category::Data);
+        let strs = deserialize_strs(&mut serde_json::Deserializer::from_str(r#"["a", "\\d"]"#)).unwrap();
+        assert!(matches!(strs[..], [Cow::Borrowed("a"), Cow::Owned(ref s)] if s == "\\d"));
     }
 
     #[test]
     fn test_deserialize_map_as_vec_error() {
-        let error = deserialize_map_as_vec::<_, bool>(json!(true)).unwrap_err();
+        let error = deserialize_map_as_vec::<_, String, bool>(json!(true)).unwrap_err();
         assert_eq!(error.classify(), Category::Data);
-        let error = deserialize_map_as_vec::<_, bool>(json!({"a": 5})).unwrap_err();
+        let error = deserialize_map_as_vec::<_, String, bool>(json!({"a": 5})).unwrap_err();
         assert_eq!(error.classify(), Category::Data);
     }
 }
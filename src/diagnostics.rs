@@ -0,0 +1,107 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::fmt::{Display, Formatter};
+
+use miette::{Diagnostic, LabeledSpan, SourceCode, SourceSpan};
+
+use crate::{LoadError, RulesLoader, UrlCleaner};
+
+/// A [`LoadError`], together with the rules, for reporting with [`miette`].
+///
+/// The offending part of the rules, like an invalid regex or a JSON syntax error, is labeled,
+/// so [`miette`] can highlight it in the source.
+///
+/// # Example
+/// ```
+/// # use clearurls::RulesLoader;
+/// let rules = r#"{"providers":{"example":{"urlPattern":"(unclosed"}}}"#;
+/// let diagnostic = RulesLoader::new().load_str_with_diagnostics(rules).unwrap_err();
+/// let label = miette::Diagnostic::labels(&diagnostic).unwrap().next().unwrap();
+/// assert_eq!(&rules[label.offset()..label.offset() + label.len()], r#""(unclosed""#);
+/// ```
+#[derive(Debug)]
+pub struct RulesDiagnostic {
+    error: LoadError,
+    rules: String,
+    span: Option<SourceSpan>,
+}
+
+impl RulesDiagnostic {
+    /// The underlying error.
+    #[must_use]
+    pub const fn error(&self) -> &LoadError {
+        &self.error
+    }
+
+    /// Discard the rules and return the underlying error.
+    #[must_use]
+    #[allow(clippy::missing_const_for_fn)] // false positive, destructors can't be const
+    pub fn into_error(self) -> LoadError {
+        self.error
+    }
+}
+
+impl Display for RulesDiagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(&self.error, f)
+    }
+}
+
+impl std::error::Error for RulesDiagnostic {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl Diagnostic for RulesDiagnostic {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(self.error.code()))
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        Some(&self.rules)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let span = self.span?;
+        Some(Box::new(core::iter::once(LabeledSpan::new_with_span(Some("here".into()), span))))
+    }
+}
+
+impl RulesLoader {
+    /// Load rules from a string like [`load_str`][Self::load_str], but on failure, return a [`RulesDiagnostic`]
+    /// that points to the offending part of the rules.
+    ///
+    /// This is meant for authoring custom rule files.
+    /// # Errors
+    /// See [`LoadError`]
+    pub fn load_str_with_diagnostics(&self, rules: &str) -> Result<UrlCleaner, RulesDiagnostic> {
+        self.load_str(rules).map_err(|error| {
+            let span = Self::locate(&error, rules);
+            RulesDiagnostic { error, rules: rules.into(), span }
+        })
+    }
+
+    /// Find the span in `rules` that caused `error`.
+    fn locate(error: &LoadError, rules: &str) -> Option<SourceSpan> {
+        match error {
+            // errors from the JSON parser have a position
+            LoadError::RuleSyntax(e) if e.line() > 0 => {
+                let line_start = rules.split_inclusive('\n').take(e.line() - 1).map(str::len).sum::<usize>();
+                let offset = (line_start + e.column().saturating_sub(1)).min(rules.len());
+                Some(SourceSpan::from((offset, usize::from(offset < rules.len()))))
+            }
+            LoadError::RegexSyntax { pattern, .. } | LoadError::RegexTooBig { pattern, .. } => {
+                find_pattern(rules, pattern)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Find the JSON string literal of `pattern` in `rules`.
+fn find_pattern(rules: &str, pattern: &str) -> Option<SourceSpan> {
+    let literal = serde_json::to_string(pattern).ok()?;
+    let offset = rules.find(&literal)?;
+    Some(SourceSpan::from((offset, literal.len())))
+}
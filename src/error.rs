@@ -0,0 +1,699 @@
+use core::fmt::{Display, Formatter};
+use core::str::Utf8Error;
+use url::ParseError;
+
+use crate::ParseRulesVersionError;
+
+/// The kind of an error, see [`Error::code`].
+///
+/// Every code has a name like `url_syntax` and a number, which both don't change between versions,
+/// so embedded and FFI consumers can branch on errors without formatting them.
+/// All codes exist regardless of the enabled features. Numbers of removed codes are not reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u16)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    /// An error reading a file, with the `std` feature
+    FileRead = 1,
+    /// See [`Error::RuleSyntax`]
+    RuleSyntax = 2,
+    /// See [`Error::UrlSyntax`]
+    UrlSyntax = 3,
+    /// See [`Error::RedirectionHasNoCapturingGroup`]
+    RedirectionHasNoCapturingGroup = 4,
+    /// See [`Error::RegexTooBig`]
+    RegexTooBig = 5,
+    /// See [`Error::PercentDecodeUtf8Error`]
+    PercentDecodeUtf8 = 6,
+    /// See [`Error::RulesVersionSyntax`]
+    RulesVersionSyntax = 7,
+    /// An invalid signature, with the `signature` feature
+    InvalidSignature = 8,
+    /// A network error, with the `network` feature
+    Network = 9,
+    /// See [`Error::UnexpectedRewrite`]
+    UnexpectedRewrite = 10,
+    /// See [`Error::UrlTooLong`]
+    UrlTooLong = 11,
+    /// Loading was aborted, with the `std` feature
+    Aborted = 12,
+    /// A malformed WARC file, with the `warc` feature
+    Warc = 13,
+    /// See [`Error::RegexSyntax`]
+    RegexSyntax = 14,
+    /// An invalid policy, with the `std` feature
+    InvalidPolicy = 15,
+}
+
+impl ErrorCode {
+    /// The name of the code in snake case, like `url_syntax`.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::FileRead => "file_read",
+            Self::RuleSyntax => "rule_syntax",
+            Self::UrlSyntax => "url_syntax",
+            Self::RedirectionHasNoCapturingGroup => "redirection_has_no_capturing_group",
+            Self::RegexTooBig => "regex_too_big",
+            Self::PercentDecodeUtf8 => "percent_decode_utf8",
+            Self::RulesVersionSyntax => "rules_version_syntax",
+            Self::InvalidSignature => "invalid_signature",
+            Self::Network => "network",
+            Self::UnexpectedRewrite => "unexpected_rewrite",
+            Self::UrlTooLong => "url_too_long",
+            Self::Aborted => "aborted",
+            Self::Warc => "warc",
+            Self::RegexSyntax => "regex_syntax",
+            Self::InvalidPolicy => "invalid_policy",
+        }
+    }
+
+    /// The number of the code, which is never 0.
+    #[must_use]
+    pub const fn as_u16(self) -> u16 {
+        self as u16
+    }
+
+    /// The code with a number from [`as_u16`][Self::as_u16], or `None` if there is none.
+    #[must_use]
+    pub const fn from_u16(n: u16) -> Option<Self> {
+        Some(match n {
+            1 => Self::FileRead,
+            2 => Self::RuleSyntax,
+            3 => Self::UrlSyntax,
+            4 => Self::RedirectionHasNoCapturingGroup,
+            5 => Self::RegexTooBig,
+            6 => Self::PercentDecodeUtf8,
+            7 => Self::RulesVersionSyntax,
+            8 => Self::InvalidSignature,
+            9 => Self::Network,
+            10 => Self::UnexpectedRewrite,
+            11 => Self::UrlTooLong,
+            12 => Self::Aborted,
+            13 => Self::Warc,
+            14 => Self::RegexSyntax,
+            15 => Self::InvalidPolicy,
+            _ => return None,
+        })
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Serialized as the [name][Self::as_str].
+#[cfg(feature = "serialize")]
+impl serde::Serialize for ErrorCode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// All errors of this crate, combined in one type
+///
+/// Loading rules fails with a [`LoadError`] and cleaning a URL fails with a [`CleanError`], which both convert into
+/// this type with `?`. It is returned where more kinds of errors can happen, like in a [`Cleaner`][crate::Cleaner].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// An Error occurred while opening or reading a file
+    #[cfg(feature = "std")]
+    FileRead(std::io::Error),
+    /// The provided rules is invalid json or doesn't have the expected format
+    RuleSyntax(serde_json::Error),
+    /// A URL could not be parsed from the input.
+    UrlSyntax(ParseError),
+    /// The rules contained a redirection regex that doesn't specify the target
+    RedirectionHasNoCapturingGroup {
+        /// The name of the provider in the rules
+        provider: alloc::string::String,
+        /// The redirection regex
+        pattern: alloc::string::String,
+    },
+    /// A regex in the rules is invalid or not supported by the [`RegexEngine`][crate::RegexEngine]
+    RegexSyntax {
+        /// The name of the provider in the rules
+        provider: alloc::string::String,
+        /// The regex
+        pattern: alloc::string::String,
+        /// Why it failed to compile
+        message: alloc::string::String,
+    },
+    /// A regex in the rules exceeds the limits configured in the [`RulesLoader`][crate::RulesLoader]
+    RegexTooBig {
+        /// The name of the provider in the rules
+        provider: alloc::string::String,
+        /// The regex
+        pattern: alloc::string::String,
+    },
+    /// Bytes that are invalid UTF-8
+    PercentDecodeUtf8Error(Utf8Error),
+    /// A [`RulesVersion`][crate::RulesVersion] could not be parsed
+    RulesVersionSyntax(ParseRulesVersionError),
+    /// The signature of the rules is malformed or doesn't match, see
+    /// [`RulesLoader::load_signed_str`][crate::RulesLoader::load_signed_str]
+    #[cfg(feature = "signature")]
+    InvalidSignature,
+    /// An error occurred while downloading from the network
+    #[cfg(feature = "network")]
+    Network(alloc::boxed::Box<ureq::Error>),
+    /// A raw rule changed the scheme, host or path of a URL, see [`UrlCleaner::strict`][crate::UrlCleaner::strict]
+    UnexpectedRewrite {
+        /// The name of the provider in the rules
+        provider: alloc::string::String,
+        /// The raw rule
+        pattern: alloc::string::String,
+    },
+    /// The URL is longer than allowed, see [`UrlCleaner::max_url_len`][crate::UrlCleaner::max_url_len].
+    /// Contains the length in bytes.
+    UrlTooLong(usize),
+    /// Loading was aborted by the callback of
+    /// [`RulesLoader::load_file_with_progress`][crate::RulesLoader::load_file_with_progress]
+    #[cfg(feature = "std")]
+    Aborted,
+    /// An error occurred while reading or writing a WARC file, or it is malformed,
+    /// see [`UrlCleaner::clean_warc`][crate::UrlCleaner::clean_warc]
+    #[cfg(feature = "warc")]
+    Warc(std::io::Error),
+    /// A [`PolicyFile`][crate::PolicyFile] or one of the environment variables that override it is invalid
+    #[cfg(feature = "std")]
+    InvalidPolicy(alloc::string::String),
+}
+
+impl Error {
+    /// A stable, machine-readable code for the kind of error, like [`ErrorCode::UrlSyntax`].
+    ///
+    /// Unlike the [`Display`] output, codes don't change between versions, so they are suitable for API responses
+    /// and for branching on errors without formatting them.
+    #[must_use]
+    pub const fn code(&self) -> ErrorCode {
+        match self {
+            #[cfg(feature = "std")]
+            Self::FileRead(_) => ErrorCode::FileRead,
+            Self::RuleSyntax(_) => ErrorCode::RuleSyntax,
+            Self::UrlSyntax(_) => ErrorCode::UrlSyntax,
+            Self::RedirectionHasNoCapturingGroup { .. } => ErrorCode::RedirectionHasNoCapturingGroup,
+            Self::RegexSyntax { .. } => ErrorCode::RegexSyntax,
+            Self::RegexTooBig { .. } => ErrorCode::RegexTooBig,
+            Self::PercentDecodeUtf8Error(_) => ErrorCode::PercentDecodeUtf8,
+            Self::RulesVersionSyntax(_) => ErrorCode::RulesVersionSyntax,
+            #[cfg(feature = "signature")]
+            Self::InvalidSignature => ErrorCode::InvalidSignature,
+            #[cfg(feature = "network")]
+            Self::Network(_) => ErrorCode::Network,
+            Self::UnexpectedRewrite { .. } => ErrorCode::UnexpectedRewrite,
+            Self::UrlTooLong(_) => ErrorCode::UrlTooLong,
+            #[cfg(feature = "std")]
+            Self::Aborted => ErrorCode::Aborted,
+            #[cfg(feature = "warc")]
+            Self::Warc(_) => ErrorCode::Warc,
+            #[cfg(feature = "std")]
+            Self::InvalidPolicy(_) => ErrorCode::InvalidPolicy,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            Self::FileRead(x) => write!(f, "error reading rules: {x}"),
+            Self::RuleSyntax(x) => write!(f, "error parsing rules: {x}"),
+            Self::UrlSyntax(x) => write!(f, "error parsing url: {x}"),
+            Self::RedirectionHasNoCapturingGroup { provider, pattern } => {
+                write!(f, "redirection regex {pattern} of provider {provider} has no capture group")
+            }
+            Self::PercentDecodeUtf8Error(x) => {
+                write!(f, "percent decoding resulted in non-UTF-8 bytes: {x}")
+            }
+            Self::RegexSyntax { provider, pattern, message } => {
+                write!(f, "regex {pattern} of provider {provider} is invalid: {message}")
+            }
+            Self::RegexTooBig { provider, pattern } => {
+                write!(f, "regex {pattern} of provider {provider} exceeds the configured limits")
+            }
+            Self::RulesVersionSyntax(x) => write!(f, "error parsing rules version: {x}"),
+            #[cfg(feature = "signature")]
+            Self::InvalidSignature => f.write_str("the signature of the rules is invalid"),
+            #[cfg(feature = "network")]
+            Self::Network(x) => write!(f, "network error: {x}"),
+            Self::UnexpectedRewrite { provider, pattern } => {
+                write!(f, "raw rule {pattern} of provider {provider} changed the scheme, host or path of the URL")
+            }
+            Self::UrlTooLong(x) => write!(f, "the URL is {x} bytes long, which is more than allowed"),
+            #[cfg(feature = "std")]
+            Self::Aborted => f.write_str("loading the rules was aborted"),
+            #[cfg(feature = "warc")]
+            Self::Warc(x) => write!(f, "error processing WARC: {x}"),
+            #[cfg(feature = "std")]
+            Self::InvalidPolicy(x) => write!(f, "invalid policy: {x}"),
+        }
+    }
+}
+
+/// Serialized as a struct with the [`code`][Error::code] as a string and the [`Display`] output as `message`.
+#[cfg(feature = "serialize")]
+impl serde::Serialize for Error {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct as _;
+
+        let mut s = serializer.serialize_struct("Error", 2)?;
+        s.serialize_field("code", &self.code())?;
+        s.serialize_field("message", &format_args!("{self}"))?;
+        s.end()
+    }
+}
+
+/// Logged as the [code][Error::code] and, where there is one, the regex and its provider or the length of the URL.
+#[cfg(feature = "defmt")]
+impl defmt::Format for Error {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        let code = self.code();
+        match self {
+            Self::RedirectionHasNoCapturingGroup { provider, pattern }
+            | Self::RegexSyntax { provider, pattern, .. }
+            | Self::RegexTooBig { provider, pattern }
+            | Self::UnexpectedRewrite { provider, pattern } => {
+                defmt::write!(f, "{}: regex {=str} of provider {=str}", code, pattern.as_str(), provider.as_str());
+            }
+            Self::UrlTooLong(len) => defmt::write!(f, "{}: {=usize} bytes", code, len),
+            _ => defmt::write!(f, "{}", code),
+        }
+    }
+}
+
+/// Errors that can happen while loading rules, see [`RulesLoader`][crate::RulesLoader].
+///
+/// Every variant has a counterpart with the same name in [`Error`], which it converts into with `?`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum LoadError {
+    /// An Error occurred while opening or reading a file
+    #[cfg(feature = "std")]
+    FileRead(std::io::Error),
+    /// The provided rules is invalid json or doesn't have the expected format
+    RuleSyntax(serde_json::Error),
+    /// A regex in the rules is invalid or not supported by the [`RegexEngine`][crate::RegexEngine]
+    RegexSyntax {
+        /// The name of the provider in the rules
+        provider: alloc::string::String,
+        /// The regex
+        pattern: alloc::string::String,
+        /// Why it failed to compile
+        message: alloc::string::String,
+    },
+    /// A regex in the rules exceeds the limits configured in the [`RulesLoader`][crate::RulesLoader]
+    RegexTooBig {
+        /// The name of the provider in the rules
+        provider: alloc::string::String,
+        /// The regex
+        pattern: alloc::string::String,
+    },
+    /// The signature of the rules is malformed or doesn't match, see
+    /// [`RulesLoader::load_signed_str`][crate::RulesLoader::load_signed_str]
+    #[cfg(feature = "signature")]
+    InvalidSignature,
+    /// Loading was aborted by the callback of
+    /// [`RulesLoader::load_file_with_progress`][crate::RulesLoader::load_file_with_progress]
+    #[cfg(feature = "std")]
+    Aborted,
+    /// The rules could not be downloaded, see [`PolicyFile`][crate::PolicyFile]
+    #[cfg(feature = "network")]
+    Network(alloc::boxed::Box<ureq::Error>),
+    /// A [`PolicyFile`][crate::PolicyFile] or one of the environment variables that override it is invalid
+    #[cfg(feature = "std")]
+    InvalidPolicy(alloc::string::String),
+}
+
+/// Errors that can happen while cleaning a URL with a [`UrlCleaner`][crate::UrlCleaner].
+///
+/// Every variant converts into the one with the same name in [`Error`] with `?`,
+/// except [`SchemeHandler`][Self::SchemeHandler], which converts into the error of the handler.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CleanError {
+    /// A URL could not be parsed from the input.
+    UrlSyntax(ParseError),
+    /// The rules contained a redirection regex that doesn't specify the target
+    RedirectionHasNoCapturingGroup {
+        /// The name of the provider in the rules
+        provider: alloc::string::String,
+        /// The redirection regex
+        pattern: alloc::string::String,
+    },
+    /// Bytes that are invalid UTF-8
+    PercentDecodeUtf8Error(Utf8Error),
+    /// A raw rule changed the scheme, host or path of a URL, see [`UrlCleaner::strict`][crate::UrlCleaner::strict]
+    UnexpectedRewrite {
+        /// The name of the provider in the rules
+        provider: alloc::string::String,
+        /// The raw rule
+        pattern: alloc::string::String,
+    },
+    /// The URL is longer than allowed, see [`UrlCleaner::max_url_len`][crate::UrlCleaner::max_url_len].
+    /// Contains the length in bytes.
+    UrlTooLong(usize),
+    /// A [`Cleaner`][crate::Cleaner] registered with
+    /// [`UrlCleaner::scheme_handler`][crate::UrlCleaner::scheme_handler] failed with another error
+    SchemeHandler(alloc::boxed::Box<Error>),
+}
+
+impl LoadError {
+    /// A stable, machine-readable code for the kind of error, the same as [`Error::code`].
+    #[must_use]
+    pub const fn code(&self) -> ErrorCode {
+        match self {
+            #[cfg(feature = "std")]
+            Self::FileRead(_) => ErrorCode::FileRead,
+            Self::RuleSyntax(_) => ErrorCode::RuleSyntax,
+            Self::RegexSyntax { .. } => ErrorCode::RegexSyntax,
+            Self::RegexTooBig { .. } => ErrorCode::RegexTooBig,
+            #[cfg(feature = "signature")]
+            Self::InvalidSignature => ErrorCode::InvalidSignature,
+            #[cfg(feature = "std")]
+            Self::Aborted => ErrorCode::Aborted,
+            #[cfg(feature = "network")]
+            Self::Network(_) => ErrorCode::Network,
+            #[cfg(feature = "std")]
+            Self::InvalidPolicy(_) => ErrorCode::InvalidPolicy,
+        }
+    }
+}
+
+impl CleanError {
+    /// A stable, machine-readable code for the kind of error, the same as [`Error::code`].
+    ///
+    /// For [`SchemeHandler`][Self::SchemeHandler], this is the code of the error of the handler.
+    #[must_use]
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::UrlSyntax(_) => ErrorCode::UrlSyntax,
+            Self::RedirectionHasNoCapturingGroup { .. } => ErrorCode::RedirectionHasNoCapturingGroup,
+            Self::PercentDecodeUtf8Error(_) => ErrorCode::PercentDecodeUtf8,
+            Self::UnexpectedRewrite { .. } => ErrorCode::UnexpectedRewrite,
+            Self::UrlTooLong(_) => ErrorCode::UrlTooLong,
+            Self::SchemeHandler(e) => e.code(),
+        }
+    }
+
+    /// Wrap the error of a scheme handler, keeping the variants that have a counterpart in this type.
+    pub(crate) fn from_handler(error: Error) -> Self {
+        Self::try_from(error).unwrap_or_else(|e| Self::SchemeHandler(alloc::boxed::Box::new(e)))
+    }
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            Self::FileRead(x) => write!(f, "error reading rules: {x}"),
+            Self::RuleSyntax(x) => write!(f, "error parsing rules: {x}"),
+            Self::RegexSyntax { provider, pattern, message } => {
+                write!(f, "regex {pattern} of provider {provider} is invalid: {message}")
+            }
+            Self::RegexTooBig { provider, pattern } => {
+                write!(f, "regex {pattern} of provider {provider} exceeds the configured limits")
+            }
+            #[cfg(feature = "signature")]
+            Self::InvalidSignature => f.write_str("the signature of the rules is invalid"),
+            #[cfg(feature = "std")]
+            Self::Aborted => f.write_str("loading the rules was aborted"),
+            #[cfg(feature = "network")]
+            Self::Network(x) => write!(f, "network error: {x}"),
+            #[cfg(feature = "std")]
+            Self::InvalidPolicy(x) => write!(f, "invalid policy: {x}"),
+        }
+    }
+}
+
+impl Display for CleanError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::UrlSyntax(x) => write!(f, "error parsing url: {x}"),
+            Self::RedirectionHasNoCapturingGroup { provider, pattern } => {
+                write!(f, "redirection regex {pattern} of provider {provider} has no capture group")
+            }
+            Self::PercentDecodeUtf8Error(x) => {
+                write!(f, "percent decoding resulted in non-UTF-8 bytes: {x}")
+            }
+            Self::UnexpectedRewrite { provider, pattern } => {
+                write!(f, "raw rule {pattern} of provider {provider} changed the scheme, host or path of the URL")
+            }
+            Self::UrlTooLong(x) => write!(f, "the URL is {x} bytes long, which is more than allowed"),
+            Self::SchemeHandler(e) => Display::fmt(e, f),
+        }
+    }
+}
+
+/// Serialized like [`Error`], as a struct with the `code` and the [`Display`] output as `message`.
+#[cfg(feature = "serialize")]
+impl serde::Serialize for LoadError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct as _;
+
+        let mut s = serializer.serialize_struct("Error", 2)?;
+        s.serialize_field("code", &self.code())?;
+        s.serialize_field("message", &format_args!("{self}"))?;
+        s.end()
+    }
+}
+
+/// Serialized like [`Error`], as a struct with the `code` and the [`Display`] output as `message`.
+#[cfg(feature = "serialize")]
+impl serde::Serialize for CleanError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct as _;
+
+        let mut s = serializer.serialize_struct("Error", 2)?;
+        s.serialize_field("code", &self.code())?;
+        s.serialize_field("message", &format_args!("{self}"))?;
+        s.end()
+    }
+}
+
+/// Logged as the [code][Self::code] and, where there is one, the regex and its provider.
+#[cfg(feature = "defmt")]
+impl defmt::Format for LoadError {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        let code = self.code();
+        if let Self::RegexSyntax { provider, pattern, .. } | Self::RegexTooBig { provider, pattern } = self {
+            defmt::write!(f, "{}: regex {=str} of provider {=str}", code, pattern.as_str(), provider.as_str());
+        } else {
+            defmt::write!(f, "{}", code);
+        }
+    }
+}
+
+/// Logged as the [code][Self::code] and, where there is one, the regex and its provider or the length of the URL.
+#[cfg(feature = "defmt")]
+impl defmt::Format for CleanError {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        let code = self.code();
+        match self {
+            Self::RedirectionHasNoCapturingGroup { provider, pattern }
+            | Self::UnexpectedRewrite { provider, pattern } => {
+                defmt::write!(f, "{}: regex {=str} of provider {=str}", code, pattern.as_str(), provider.as_str());
+            }
+            Self::UrlTooLong(len) => defmt::write!(f, "{}: {=usize} bytes", code, len),
+            Self::SchemeHandler(e) => defmt::write!(f, "{}", **e),
+            _ => defmt::write!(f, "{}", code),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::FileRead(e) => Some(e),
+            Self::RuleSyntax(e) => Some(e),
+            Self::RegexSyntax { .. } | Self::RegexTooBig { .. } | Self::Aborted | Self::InvalidPolicy(_) => None,
+            #[cfg(feature = "signature")]
+            Self::InvalidSignature => None,
+            #[cfg(feature = "network")]
+            Self::Network(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CleanError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::UrlSyntax(e) => Some(e),
+            Self::PercentDecodeUtf8Error(e) => Some(e),
+            Self::RedirectionHasNoCapturingGroup { .. } | Self::UnexpectedRewrite { .. } | Self::UrlTooLong(_) => None,
+            Self::SchemeHandler(e) => e.source(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::FileRead(e) => Some(e),
+            Self::RuleSyntax(e) => Some(e),
+            Self::UrlSyntax(e) => Some(e),
+            Self::RedirectionHasNoCapturingGroup { .. }
+            | Self::RegexSyntax { .. }
+            | Self::RegexTooBig { .. }
+            | Self::UnexpectedRewrite { .. }
+            | Self::UrlTooLong(_)
+            | Self::Aborted
+            | Self::InvalidPolicy(_) => None,
+            Self::PercentDecodeUtf8Error(e) => Some(e),
+            Self::RulesVersionSyntax(e) => Some(e),
+            #[cfg(feature = "signature")]
+            Self::InvalidSignature => None,
+            #[cfg(feature = "network")]
+            Self::Network(e) => Some(e),
+            #[cfg(feature = "warc")]
+            Self::Warc(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::FileRead(value)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(value: serde_json::Error) -> Self {
+        Self::RuleSyntax(value)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(value: ParseError) -> Self {
+        Self::UrlSyntax(value)
+    }
+}
+
+impl From<Utf8Error> for Error {
+    fn from(value: Utf8Error) -> Self {
+        Self::PercentDecodeUtf8Error(value)
+    }
+}
+
+impl From<ParseRulesVersionError> for Error {
+    fn from(value: ParseRulesVersionError) -> Self {
+        Self::RulesVersionSyntax(value)
+    }
+}
+
+#[cfg(feature = "network")]
+impl From<alloc::boxed::Box<ureq::Error>> for Error {
+    fn from(value: alloc::boxed::Box<ureq::Error>) -> Self {
+        Self::Network(value)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for LoadError {
+    fn from(value: std::io::Error) -> Self {
+        Self::FileRead(value)
+    }
+}
+
+impl From<serde_json::Error> for LoadError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::RuleSyntax(value)
+    }
+}
+
+impl From<ParseError> for CleanError {
+    fn from(value: ParseError) -> Self {
+        Self::UrlSyntax(value)
+    }
+}
+
+impl From<Utf8Error> for CleanError {
+    fn from(value: Utf8Error) -> Self {
+        Self::PercentDecodeUtf8Error(value)
+    }
+}
+
+impl From<LoadError> for Error {
+    fn from(value: LoadError) -> Self {
+        match value {
+            #[cfg(feature = "std")]
+            LoadError::FileRead(e) => Self::FileRead(e),
+            LoadError::RuleSyntax(e) => Self::RuleSyntax(e),
+            LoadError::RegexSyntax { provider, pattern, message } => Self::RegexSyntax { provider, pattern, message },
+            LoadError::RegexTooBig { provider, pattern } => Self::RegexTooBig { provider, pattern },
+            #[cfg(feature = "signature")]
+            LoadError::InvalidSignature => Self::InvalidSignature,
+            #[cfg(feature = "std")]
+            LoadError::Aborted => Self::Aborted,
+            #[cfg(feature = "network")]
+            LoadError::Network(e) => Self::Network(e),
+            #[cfg(feature = "std")]
+            LoadError::InvalidPolicy(e) => Self::InvalidPolicy(e),
+        }
+    }
+}
+
+impl From<CleanError> for Error {
+    fn from(value: CleanError) -> Self {
+        match value {
+            CleanError::UrlSyntax(e) => Self::UrlSyntax(e),
+            CleanError::RedirectionHasNoCapturingGroup { provider, pattern } => {
+                Self::RedirectionHasNoCapturingGroup { provider, pattern }
+            }
+            CleanError::PercentDecodeUtf8Error(e) => Self::PercentDecodeUtf8Error(e),
+            CleanError::UnexpectedRewrite { provider, pattern } => Self::UnexpectedRewrite { provider, pattern },
+            CleanError::UrlTooLong(len) => Self::UrlTooLong(len),
+            CleanError::SchemeHandler(e) => *e,
+        }
+    }
+}
+
+/// Fails with the original error if it can't happen while loading rules.
+impl TryFrom<Error> for LoadError {
+    type Error = Error;
+
+    fn try_from(value: Error) -> Result<Self, Error> {
+        match value {
+            #[cfg(feature = "std")]
+            Error::FileRead(e) => Ok(Self::FileRead(e)),
+            Error::RuleSyntax(e) => Ok(Self::RuleSyntax(e)),
+            Error::RegexSyntax { provider, pattern, message } => Ok(Self::RegexSyntax { provider, pattern, message }),
+            Error::RegexTooBig { provider, pattern } => Ok(Self::RegexTooBig { provider, pattern }),
+            #[cfg(feature = "signature")]
+            Error::InvalidSignature => Ok(Self::InvalidSignature),
+            #[cfg(feature = "std")]
+            Error::Aborted => Ok(Self::Aborted),
+            #[cfg(feature = "network")]
+            Error::Network(e) => Ok(Self::Network(e)),
+            #[cfg(feature = "std")]
+            Error::InvalidPolicy(e) => Ok(Self::InvalidPolicy(e)),
+            e => Err(e),
+        }
+    }
+}
+
+/// Fails with the original error if it can't happen while cleaning a URL.
+impl TryFrom<Error> for CleanError {
+    type Error = Error;
+
+    fn try_from(value: Error) -> Result<Self, Error> {
+        match value {
+            Error::UrlSyntax(e) => Ok(Self::UrlSyntax(e)),
+            Error::RedirectionHasNoCapturingGroup { provider, pattern } => {
+                Ok(Self::RedirectionHasNoCapturingGroup { provider, pattern })
+            }
+            Error::PercentDecodeUtf8Error(e) => Ok(Self::PercentDecodeUtf8Error(e)),
+            Error::UnexpectedRewrite { provider, pattern } => Ok(Self::UnexpectedRewrite { provider, pattern }),
+            Error::UrlTooLong(len) => Ok(Self::UrlTooLong(len)),
+            e => Err(e),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use alloc::string::String;
+use core::fmt::Write as _;
+
+use crate::UrlCleaner;
+
+impl UrlCleaner {
+    /// Translate the loaded rules into
+    /// [`AdGuard`](https://adguard.com/kb/general/ad-filtering/create-own-filters/#removeparam-modifier)
+    /// `$removeparam` filter rules, one per line.
+    ///
+    /// Only the expressible subset is translated:
+    /// - the rules, and the referral marketing rules if [`strip_referral_marketing`][Self::strip_referral_marketing]
+    ///   is enabled, become `$removeparam` rules for the URLs of their provider
+    /// - exceptions become `@@` rules that disable these rules again. Unlike in `ClearURLs`, they also disable
+    ///   identical rules of other providers.
+    ///
+    /// Raw rules, redirections and value rules can't be expressed and are listed as `!` comments instead.
+    /// `AdGuard` removes only query parameters, not those in the fragment.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let provider = r#"{"urlPattern":"^https?:\\/\\/example\\.com","rules":["ref_[a-z]{1,3}"]}"#;
+    /// let rules = format!(r#"{{"providers":{{"example":{provider}}}}}"#);
+    /// let cleaner = UrlCleaner::from_rules_str(&rules)?;
+    /// assert_eq!(
+    ///     cleaner.to_adguard_filter(),
+    ///     "/^https?:\\/\\/example\\.com/$removeparam=/^(?:ref_[a-z]{1\\,3})=/i\n",
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn to_adguard_filter(&self) -> String {
+        let mut filter = String::new();
+        for p in &self.rules.providers {
+            let params: alloc::vec::Vec<String> = p
+                .rules
+                .iter()
+                .chain(self.strip_referral_marketing.then_some(&p.referral_marketing).into_iter().flatten())
+                .map(|r| alloc::format!("$removeparam=/^(?:{})=/i", escape(r.as_str(), ',')))
+                .collect();
+            let url_pattern = adguard_pattern(p.url_pattern.as_str());
+            let scope = if url_pattern.is_empty() { "all URLs" } else { &url_pattern };
+            for param in &params {
+                writeln!(filter, "{url_pattern}{param}").expect("writing to a String doesn't fail");
+            }
+            for exception in &p.exceptions.patterns {
+                for param in &params {
+                    writeln!(filter, "@@{}{param}", adguard_pattern(exception))
+                        .expect("writing to a String doesn't fail");
+                }
+            }
+            for r in &p.value_rules {
+                writeln!(filter, "! value rule for {scope}: {} = {}", r.rule.as_str(), r.value.as_str())
+                    .expect("writing to a String doesn't fail");
+            }
+            for raw_rule in &p.raw_rules {
+                writeln!(filter, "! raw rule for {scope}: {}", raw_rule.as_str())
+                    .expect("writing to a String doesn't fail");
+            }
+            for redirection in &p.redirections {
+                writeln!(filter, "! redirection: {}", redirection.as_str()).expect("writing to a String doesn't fail");
+            }
+        }
+        filter
+    }
+}
+
+/// A URL pattern as an `AdGuard` regex, or nothing if it matches all URLs.
+fn adguard_pattern(pattern: &str) -> String {
+    match pattern {
+        ".*" | "" => String::new(),
+        p => alloc::format!("/{}/", escape(p, '/')),
+    }
+}
+
+/// Escape the unescaped occurrences of `c` and `/` in a regex.
+fn escape(pattern: &str, c: char) -> String {
+    let mut escaped = String::with_capacity(pattern.len());
+    let mut backslash = false;
+    for ch in pattern.chars() {
+        if !backslash && (ch == c || ch == '/') {
+            escaped.push('\\');
+        }
+        backslash = !backslash && ch == '\\';
+        escaped.push(ch);
+    }
+    escaped
+}
@@ -0,0 +1,83 @@
+use alloc::borrow::Cow;
+use alloc::string::String;
+
+use url::Url;
+
+use crate::{CleanError, UrlCleaner};
+
+/// Clean URLs with method syntax, for call sites that clean a single link inline.
+///
+/// # Example
+/// ```
+/// # use clearurls::{UrlCleanExt, UrlCleaner};
+/// # use url::Url;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let cleaner = UrlCleaner::from_embedded_rules()?;
+/// let res = "https://example.com/test?utm_source=abc".cleaned_with(&cleaner)?;
+/// assert_eq!(res, "https://example.com/test");
+///
+/// let mut url = Url::parse("https://example.com/test?utm_source=abc")?;
+/// url.clean_with(&cleaner)?;
+/// assert_eq!(url.as_str(), "https://example.com/test");
+/// # Ok(())
+/// # }
+/// ```
+pub trait UrlCleanExt {
+    /// The type of the cleaned URL, which may borrow from `self`
+    type Cleaned<'a>
+    where
+        Self: 'a;
+
+    /// Clean this URL with `cleaner`.
+    ///
+    /// # Errors
+    /// If an error occurred. See the [`CleanError`] enum for possible reasons.
+    fn cleaned_with<'a>(&'a self, cleaner: &UrlCleaner) -> Result<Self::Cleaned<'a>, CleanError>;
+
+    /// Clean this URL in place with `cleaner`.
+    ///
+    /// # Errors
+    /// If an error occurred. See the [`CleanError`] enum for possible reasons.
+    /// `self` is unchanged in that case.
+    fn clean_with(&mut self, cleaner: &UrlCleaner) -> Result<(), CleanError>
+    where
+        Self: Sized;
+}
+
+impl UrlCleanExt for str {
+    type Cleaned<'a> = Cow<'a, Self>;
+
+    fn cleaned_with<'a>(&'a self, cleaner: &UrlCleaner) -> Result<Self::Cleaned<'a>, CleanError> {
+        cleaner.clear_single_url_str(self)
+    }
+}
+
+impl UrlCleanExt for String {
+    type Cleaned<'a> = Cow<'a, str>;
+
+    fn cleaned_with<'a>(&'a self, cleaner: &UrlCleaner) -> Result<Self::Cleaned<'a>, CleanError> {
+        cleaner.clear_single_url_str(self)
+    }
+
+    fn clean_with(&mut self, cleaner: &UrlCleaner) -> Result<(), CleanError> {
+        if let Cow::Owned(cleaned) = cleaner.clear_single_url_str(self)? {
+            *self = cleaned;
+        }
+        Ok(())
+    }
+}
+
+impl UrlCleanExt for Url {
+    type Cleaned<'a> = Cow<'a, Self>;
+
+    fn cleaned_with<'a>(&'a self, cleaner: &UrlCleaner) -> Result<Self::Cleaned<'a>, CleanError> {
+        cleaner.clear_single_url(self)
+    }
+
+    fn clean_with(&mut self, cleaner: &UrlCleaner) -> Result<(), CleanError> {
+        if let Cow::Owned(cleaned) = cleaner.clear_single_url(self)? {
+            *self = cleaned;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::fmt::{Debug, Formatter};
+use url::Url;
+
+use crate::inspect::Change;
+
+/// Information passed to a hook registered with [`UrlCleaner::on_param_removal`][crate::UrlCleaner::on_param_removal].
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct HookContext<'a> {
+    /// The URL as it was passed to the matching provider.
+    pub url: &'a Url,
+    /// What the rules want to do with the URL.
+    pub action: Action<'a>,
+}
+
+/// A single modification that the rules want to apply to a URL.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub enum Action<'a> {
+    /// A query or fragment parameter is about to be removed.
+    RemoveParam {
+        /// The decoded parameter name
+        name: &'a str,
+        /// The decoded parameter value
+        value: &'a str,
+        /// The rule that matched the name
+        rule: &'a str,
+    },
+    /// A raw rule is about to be applied to the whole URL.
+    RawRule {
+        /// The raw rule that matched
+        rule: &'a str,
+    },
+    /// The URL is a redirection and is about to be replaced with its target.
+    Redirection {
+        /// The extracted target, still percent-encoded
+        target: &'a str,
+    },
+}
+
+/// The verdict of a hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// Apply the modification.
+    Allow,
+    /// Leave the URL as it is in this regard.
+    Veto,
+}
+
+pub(crate) struct Hook(Box<dyn Fn(&HookContext<'_>) -> Decision + Send + Sync>);
+
+impl Hook {
+    pub(crate) fn new<F>(f: F) -> Self
+    where
+        F: Fn(&HookContext<'_>) -> Decision + Send + Sync + 'static,
+    {
+        Self(Box::new(f))
+    }
+
+    pub(crate) fn allows(hook: Option<&Self>, url: &Url, action: Action<'_>) -> bool {
+        !hook.is_some_and(|h| (h.0)(&HookContext { url, action }) != Decision::Allow)
+    }
+}
+
+/// What is told about the modifications while cleaning a URL: the hook, and the changes that are recorded
+/// for [`UrlCleaner::clear_single_url_explained`][crate::UrlCleaner::clear_single_url_explained].
+#[derive(Default)]
+pub(crate) struct Observer<'a> {
+    pub(crate) hook: Option<&'a Hook>,
+    pub(crate) changes: Option<&'a RefCell<Vec<Change>>>,
+    /// If set, modifications are only flagged here instead of being made, for
+    /// [`UrlCleaner::is_clean`][crate::UrlCleaner::is_clean].
+    pub(crate) dry_run: Option<&'a Cell<bool>>,
+}
+
+impl Observer<'_> {
+    /// Whether the hook allows the modification, which is recorded if so.
+    ///
+    /// In a dry run, an allowed modification is flagged, but not made.
+    pub(crate) fn allows(&self, url: &Url, action: Action<'_>, change: impl FnOnce() -> Change) -> bool {
+        let allowed = Hook::allows(self.hook, url, action);
+        if let Some(changes) = self.changes.filter(|_| allowed) {
+            changes.borrow_mut().push(change());
+        }
+        if let Some(flag) = self.dry_run.filter(|_| allowed) {
+            flag.set(true);
+            return false;
+        }
+        allowed
+    }
+
+    /// Flag a modification that the hook isn't asked about, like normalization.
+    /// Returns whether this is a dry run, in which case the modification should not be made.
+    pub(crate) fn flag(&self) -> bool {
+        self.dry_run.inspect(|flag| flag.set(true)).is_some()
+    }
+
+    /// Whether a modification was flagged in a dry run, so that there is no need to look further.
+    pub(crate) fn is_flagged(&self) -> bool {
+        self.dry_run.is_some_and(Cell::get)
+    }
+}
+
+impl Debug for Hook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str("Hook")
+    }
+}
@@ -0,0 +1,219 @@
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::{LinkError, UrlCleaner};
+
+/// The iCalendar properties whose value is a single URI.
+const URI_PROPERTIES: [&str; 4] = ["URL", "ATTACH", "IMAGE", "CONFERENCE"];
+/// The iCalendar properties whose value is text that may contain links.
+const TEXT_PROPERTIES: [&str; 4] = ["DESCRIPTION", "COMMENT", "LOCATION", "X-ALT-DESC"];
+/// The length in bytes after which content lines are folded, without the line break.
+const MAX_LINE_LEN: usize = 75;
+
+impl UrlCleaner {
+    /// Clean the URLs in iCalendar data, like `.ics` files and calendar feeds, on a best-effort basis.
+    ///
+    /// This cleans the values of `URL`, `ATTACH`, `IMAGE` and `CONFERENCE`, and the links in the text of
+    /// `DESCRIPTION`, `COMMENT`, `LOCATION` and `X-ALT-DESC`, which are unescaped before and escaped after cleaning.
+    /// Folded lines are unfolded for cleaning, and changed lines are folded again after 75 bytes.
+    /// Other lines are left exactly as they are, as are attachments that are inline binary data.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?;
+    /// let ics = "BEGIN:VEVENT\r\nURL:https://example.com/event?utm_source=x\r\n\
+    ///     DESCRIPTION:Tickets\\, see https://example.com/tickets?utm_campaign=\r\n launch\\n\r\nEND:VEVENT\r\n";
+    /// let (cleaned, errors) = cleaner.clean_icalendar(ics);
+    /// assert_eq!(
+    ///     cleaned,
+    ///     "BEGIN:VEVENT\r\nURL:https://example.com/event\r\n\
+    ///     DESCRIPTION:Tickets\\, see https://example.com/tickets\\n\r\nEND:VEVENT\r\n"
+    /// );
+    /// assert!(errors.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Returns
+    /// The cleaned data, and the errors, each with the URL that caused it, which is left as it is.
+    #[must_use]
+    pub fn clean_icalendar<'a>(&self, ics: &'a str) -> (Cow<'a, str>, Vec<LinkError>) {
+        self.clean_content_lines(ics, &URI_PROPERTIES, &TEXT_PROPERTIES)
+    }
+
+    /// Clean the `URL` properties in vCard data, like `.vcf` files, on a best-effort basis.
+    ///
+    /// vCards are written in the same way as iCalendar data, so folding is handled like in
+    /// [`clean_icalendar`][Self::clean_icalendar]. Properties may be grouped, like `item1.URL`.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?;
+    /// let vcf = "BEGIN:VCARD\r\nVERSION:4.0\r\nURL;TYPE=work:https://example.com/jane?utm_source=x\r\nEND:VCARD\r\n";
+    /// let (cleaned, errors) = cleaner.clean_vcard(vcf);
+    /// assert_eq!(cleaned, "BEGIN:VCARD\r\nVERSION:4.0\r\nURL;TYPE=work:https://example.com/jane\r\nEND:VCARD\r\n");
+    /// assert!(errors.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Returns
+    /// The cleaned data, and the errors, each with the URL that caused it, which is left as it is.
+    #[must_use]
+    pub fn clean_vcard<'a>(&self, vcf: &'a str) -> (Cow<'a, str>, Vec<LinkError>) {
+        self.clean_content_lines(vcf, &["URL"], &[])
+    }
+
+    /// Clean the properties with the given names in data made of content lines, like iCalendar and vCard.
+    fn clean_content_lines<'a>(
+        &self,
+        data: &'a str,
+        uris: &[&str],
+        texts: &[&str],
+    ) -> (Cow<'a, str>, Vec<LinkError>) {
+        let mut result = String::new();
+        let mut errors = Vec::new();
+        let mut last = 0;
+        for line in content_lines(data) {
+            let folded = &data[line.clone()];
+            let ending = if folded.ends_with("\r\n") {
+                "\r\n"
+            } else if folded.ends_with('\n') {
+                "\n"
+            } else {
+                ""
+            };
+            if let Some(cleaned) = self.clean_content_line(&unfold(folded), uris, texts, &mut errors) {
+                result.push_str(&data[last..line.start]);
+                fold(&cleaned, ending, &mut result);
+                last = line.end;
+            }
+        }
+        if last == 0 {
+            return (Cow::Borrowed(data), errors);
+        }
+        result.push_str(&data[last..]);
+        (Cow::Owned(result), errors)
+    }
+
+    /// The cleaned version of an unfolded content line, if it changes.
+    fn clean_content_line(
+        &self,
+        line: &str,
+        uris: &[&str],
+        texts: &[&str],
+        errors: &mut Vec<LinkError>,
+    ) -> Option<String> {
+        let (head, value) = split_value(line)?;
+        let name = head.split(';').next().unwrap_or_default();
+        // the group of a vCard property comes before the name, like `item1.URL`
+        let name = name.rsplit('.').next().unwrap_or_default().to_ascii_uppercase();
+        if uris.contains(&name.as_str()) {
+            if head.to_ascii_uppercase().contains("VALUE=BINARY") {
+                return None;
+            }
+            match self.clear_single_url_str(value) {
+                Ok(cleaned) if cleaned != value => Some(format!("{head}:{cleaned}")),
+                Ok(_) => None,
+                Err(e) => {
+                    errors.push(LinkError::new(value, e));
+                    None
+                }
+            }
+        } else if texts.contains(&name.as_str()) {
+            let text = unescape(value);
+            let (cleaned, e) = self.clear_text_partial(&text);
+            errors.extend(e);
+            match cleaned {
+                Cow::Owned(cleaned) => Some(format!("{head}:{}", escape(&cleaned))),
+                Cow::Borrowed(_) => None,
+            }
+        } else {
+            None
+        }
+    }
+}
+
+/// The byte ranges of the content lines, each with its continuation lines and line break.
+fn content_lines(ics: &str) -> Vec<Range<usize>> {
+    let mut lines: Vec<Range<usize>> = Vec::new();
+    let mut start = 0;
+    for line in ics.split_inclusive('\n') {
+        let end = start + line.len();
+        match lines.last_mut() {
+            Some(last) if line.starts_with([' ', '\t']) => last.end = end,
+            _ => lines.push(start..end),
+        }
+        start = end;
+    }
+    lines
+}
+
+/// Join a folded content line, removing each line break and the whitespace character after it.
+fn unfold(folded: &str) -> String {
+    let mut line = String::with_capacity(folded.len());
+    for (i, part) in folded.split_inclusive('\n').enumerate() {
+        let part = part.trim_end_matches('\n').trim_end_matches('\r');
+        line.push_str(if i == 0 { part } else { &part[1..] });
+    }
+    line
+}
+
+/// Split a content line into the name with its parameters and the value, at the first colon outside of quotes.
+fn split_value(line: &str) -> Option<(&str, &str)> {
+    let mut quoted = false;
+    let colon = line.find(|c| {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        c == ':' && !quoted
+    })?;
+    Some((&line[..colon], &line[colon + 1..]))
+}
+
+/// Fold a content line after [`MAX_LINE_LEN`] bytes, without splitting characters, and end it with `ending`.
+fn fold(line: &str, ending: &str, out: &mut String) {
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > MAX_LINE_LEN {
+            out.push_str(if ending.is_empty() { "\r\n" } else { ending });
+            out.push(' ');
+            len = 1;
+        }
+        out.push(c);
+        len += c.len_utf8();
+    }
+    out.push_str(ending);
+}
+
+/// Resolve the backslash escapes of a text value.
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n' | 'N')) => {
+                chars.next();
+                text.push('\n');
+            }
+            ('\\', Some(escaped)) => {
+                chars.next();
+                text.push(escaped);
+            }
+            _ => text.push(c),
+        }
+    }
+    text
+}
+
+/// Escape backslashes, semicolons, commas and line breaks in a text value.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+}
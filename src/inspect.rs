@@ -0,0 +1,254 @@
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::str::FromStr;
+
+use url::{form_urlencoded, Url};
+
+use crate::hooks::Observer;
+use crate::matcher::is_full_match;
+use crate::{CleanError, UrlCleaner};
+
+/// A rule that applies to a URL, see [`UrlCleaner::rules_for`].
+///
+/// With the `serialize` feature, this is serialized with the variant name in snake case as `kind`,
+/// like `{"kind": "raw_rule", "rule": "..."}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize), serde(tag = "kind", rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum EffectiveRule {
+    /// A query or fragment parameter would be removed.
+    RemoveParam {
+        /// The decoded parameter name
+        name: String,
+        /// The rule that matches the name
+        rule: String,
+        /// Whether the rule is a referral marketing rule,
+        /// which only applies with [`UrlCleaner::strip_referral_marketing`]
+        referral_marketing: bool,
+    },
+    /// A raw rule matches the URL.
+    RawRule {
+        /// The raw rule
+        rule: String,
+    },
+    /// The URL is a redirection.
+    Redirection {
+        /// The redirection rule
+        rule: String,
+        /// The extracted target, still percent-encoded
+        target: String,
+    },
+}
+
+/// A modification that was made while cleaning a URL, see [`UrlCleaner::clear_single_url_explained`].
+///
+/// With the `serialize` feature, the [`Reason`] is flattened into this,
+/// like `{"provider": "...", "kind": "raw_rule", "rule": "..."}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct Change {
+    /// The name of the provider in the rules, or that of the built-in rules,
+    /// like `sessionIds` for [`UrlCleaner::strip_session_ids`]
+    pub provider: String,
+    /// What was changed, and by which rule
+    #[cfg_attr(feature = "serialize", serde(flatten))]
+    pub reason: Reason,
+}
+
+/// What was changed by which rule, see [`Change`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize), serde(tag = "kind", rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum Reason {
+    /// A parameter was removed by one of the `rules`.
+    Rule {
+        /// The decoded parameter name
+        name: String,
+        /// The rule that matched the name
+        rule: String,
+    },
+    /// A parameter was removed by one of the `referralMarketing` rules.
+    ReferralMarketing {
+        /// The decoded parameter name
+        name: String,
+        /// The rule that matched the name
+        rule: String,
+    },
+    /// A parameter was removed by one of the `x-valueRules`.
+    ValueRule {
+        /// The decoded parameter name
+        name: String,
+        /// The rule that matched the name
+        rule: String,
+        /// The rule that matched the value
+        value_rule: String,
+    },
+    /// A raw rule was applied to the whole URL.
+    RawRule {
+        /// The raw rule
+        rule: String,
+    },
+    /// The URL was replaced with the target of a redirection.
+    Redirection {
+        /// The redirection rule
+        rule: String,
+        /// The extracted target, still percent-encoded
+        target: String,
+    },
+}
+
+impl UrlCleaner {
+    /// Clean a single URL like [`clear_single_url_str`][Self::clear_single_url_str],
+    /// and report every modification with the provider and rule that caused it.
+    ///
+    /// Unlike [`rules_for`][Self::rules_for], this reports what was actually done, including the changes to
+    /// redirection targets and nested URLs, and respects the configuration and the hook.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::{Reason, UrlCleaner};
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?;
+    /// let (url, changes) = cleaner.clear_single_url_explained("https://example.com/?utm_source=abc&page=2")?;
+    /// assert_eq!(url, "https://example.com/?page=2");
+    /// assert_eq!(changes[0].provider, "globalRules");
+    /// assert!(matches!(&changes[0].reason, Reason::Rule { name, .. } if name == "utm_source"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// If an error occurred. See the [`CleanError`] enum for possible reasons.
+    pub fn clear_single_url_explained<'a>(&self, url: &'a str) -> Result<(Cow<'a, str>, Vec<Change>), CleanError> {
+        if self.is_too_long(url.len())? {
+            return Ok((Cow::Borrowed(url), Vec::new()));
+        }
+        if let Some(handler) = self.scheme_handler_for(url) {
+            let cleaned = handler.clean(url).map_err(CleanError::from_handler)?;
+            self.record(url, &cleaned, Vec::new());
+            return Ok((cleaned, Vec::new()));
+        }
+        if self.is_skipped_scheme(url) {
+            return Ok((Cow::Borrowed(url), Vec::new()));
+        }
+        let changes = RefCell::new(Vec::new());
+        let observer = Observer { hook: self.hook.as_ref(), changes: Some(&changes), dry_run: None };
+        let cleaned = self.clear_url_at_depth(Cow::Owned(Url::from_str(url)?), 0, &observer)?;
+        let cleaned = self.host_form.serialize(cleaned.into_owned());
+        let cleaned = if cleaned == url { Cow::Borrowed(url) } else { Cow::Owned(cleaned) };
+        let changes = changes.into_inner();
+        if self.audit.is_some() {
+            self.record(url, &cleaned, changes.clone());
+        }
+        Ok((cleaned, changes))
+    }
+
+    /// Report which rules apply to a URL, without cleaning it.
+    ///
+    /// This is meant for rule authors to check the coverage of a domain.
+    /// All rules are evaluated against the given URL, so if a redirection is reported,
+    /// the other rules wouldn't actually be applied, and the target isn't inspected.
+    /// Referral marketing rules are reported regardless of
+    /// [`strip_referral_marketing`][Self::strip_referral_marketing].
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::{EffectiveRule, UrlCleaner};
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?;
+    /// let rules = cleaner.rules_for("https://example.com/?utm_source=abc&page=2")?;
+    /// assert!(matches!(&rules[..], [EffectiveRule::RemoveParam { name, .. }] if name == "utm_source"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// If the URL can't be parsed or a redirection rule has no capturing group.
+    pub fn rules_for(&self, url: &str) -> Result<Vec<EffectiveRule>, CleanError> {
+        let url = Url::from_str(url)?;
+        let params: Vec<_> = url
+            .query_pairs()
+            .chain(form_urlencoded::parse(url.fragment().unwrap_or("").as_bytes()))
+            .collect();
+        let mut effective = Vec::new();
+        for p in self.rules.providers.iter().filter(|p| p.match_url(url.as_str())) {
+            for r in &p.redirections {
+                if let Some(group) = r.first_group(url.as_str()) {
+                    let group = group.ok_or_else(|| CleanError::RedirectionHasNoCapturingGroup {
+                        provider: p.name.clone(),
+                        pattern: r.as_str().into(),
+                    })?;
+                    effective.push(EffectiveRule::Redirection {
+                        rule: r.as_str().into(),
+                        target: url.as_str()[group].into(),
+                    });
+                }
+            }
+            for r in p.raw_rules.iter().filter(|r| r.is_match(url.as_str())) {
+                effective.push(EffectiveRule::RawRule { rule: r.as_str().into() });
+            }
+            let rules = p.rules.iter().map(|r| (r, false));
+            for (r, referral_marketing) in rules.chain(p.referral_marketing.iter().map(|r| (r, true))) {
+                for (name, _) in params.iter().filter(|(name, _)| is_full_match(&**r, name)) {
+                    effective.push(EffectiveRule::RemoveParam {
+                        name: name.clone().into_owned(),
+                        rule: r.as_str().into(),
+                        referral_marketing,
+                    });
+                }
+            }
+            for r in &p.value_rules {
+                let matches = params.iter().filter(|(name, value)| {
+                    is_full_match(&*r.rule, name) && is_full_match(&*r.value, value)
+                });
+                for (name, _) in matches {
+                    effective.push(EffectiveRule::RemoveParam {
+                        name: name.clone().into_owned(),
+                        rule: r.rule.as_str().into(),
+                        referral_marketing: false,
+                    });
+                }
+            }
+        }
+        Ok(effective)
+    }
+
+    /// Whether any provider of the rules matches a URL, which is cheaper than cleaning it.
+    ///
+    /// This is meant for pipelines that decide which records need cleaning at all.
+    /// Note that the global rules of `ClearURLs` match every URL.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let rules = r#"{"providers":{"example":{"urlPattern":"^https://example\\.com/"}}}"#;
+    /// let cleaner = UrlCleaner::from_rules_str(rules)?;
+    /// assert!(cleaner.matches_any("https://example.com/?a=1"));
+    /// assert!(!cleaner.matches_any("https://example.org/?a=1"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn matches_any(&self, url: &str) -> bool {
+        let url = parsed_or_raw(url);
+        self.rules.providers.iter().any(|p| p.match_url(&url))
+    }
+
+    /// The names of the providers of the rules that match a URL, in the order in which they are applied.
+    ///
+    /// See [`matches_any`][Self::matches_any].
+    #[must_use]
+    pub fn matching_providers(&self, url: &str) -> Vec<&str> {
+        let url = parsed_or_raw(url);
+        self.rules.providers.iter().filter(|p| p.match_url(&url)).map(|p| p.name.as_str()).collect()
+    }
+}
+
+/// The URL as the providers see it while cleaning, or as given if it can't be parsed.
+fn parsed_or_raw(url: &str) -> Cow<'_, str> {
+    Url::from_str(url).map_or(Cow::Borrowed(url), |url| Cow::Owned(url.into()))
+}
@@ -79,133 +79,1779 @@ extern crate alloc;
 extern crate std;
 
 use alloc::borrow::Cow;
+use core::cell::Cell;
 use core::fmt::{Display, Formatter};
-use core::str::{FromStr, Utf8Error};
-use regex::Regex;
+use core::str::FromStr;
 use url::{ParseError, Url};
 
+pub use audit::{AuditSink, CleanReport};
+#[cfg(feature = "std")]
+pub use bulk::{BulkCleaner, BulkProgress, BulkResult, BulkResults};
+#[cfg(all(feature = "std", feature = "serialize"))]
+pub use audit::JsonlAuditLog;
+pub use cleaner::{Cleaner, CleanerChain};
+#[cfg(feature = "std")]
+pub use cleaner::MockCleaner;
+use hooks::{Hook, Observer};
+#[cfg(feature = "diagnostics")]
+pub use diagnostics::RulesDiagnostic;
+pub use error::{CleanError, Error, ErrorCode, LoadError};
+pub use ext::UrlCleanExt;
+pub use hooks::{Action, Decision, HookContext};
+pub use inspect::{Change, EffectiveRule, Reason};
+pub use limits::{LengthOverflow, ParamOverflow};
+#[cfg(feature = "linkify")]
+pub use lint::Finding;
+#[cfg(feature = "std")]
+pub use loader::LoadProgress;
+#[cfg(feature = "axum")]
+pub use middleware::strip_tracking_params;
+pub use loader::RulesLoader;
+#[cfg(feature = "ecmascript")]
+pub use matcher::EcmaScriptEngine;
+pub use matcher::{BuiltinEngine, CompileError, CompileOptions, Matcher, MatcherSet, RegexEngine};
+pub use multi_url::MultiUrl;
+pub use normalize::{HostForm, Normalization, TrailingSlash};
+pub use policy::{Policy, PolicyMap};
+#[cfg(feature = "std")]
+pub use policy_file::PolicyFile;
+#[cfg(feature = "std")]
+pub use profile::ProviderProfile;
+pub use referrer::ReferrerPolicy;
+#[cfg(feature = "linkify")]
+pub use repair::TrailingPunctuation;
+#[cfg(feature = "testing")]
+pub use testing::{AdversarialUrl, ArbitraryProvider, ArbitraryRules};
+#[cfg(feature = "std")]
+pub use report::{BuildReport, PatternCost};
 use rules::Rules;
+pub use rules::RulesWarning;
+#[cfg(feature = "network")]
+pub use update::{UpdateStatus, UPSTREAM_RULES_HASH_URL};
+pub use version::{ParseRulesVersionError, RulesVersion};
+#[cfg(feature = "warc")]
+pub use warc::WarcSummary;
 
+#[cfg(feature = "linkify")]
+mod ansi;
+mod audit;
+#[cfg(feature = "std")]
+mod bulk;
+mod cleaner;
 mod deserialize_utils;
+#[cfg(feature = "diagnostics")]
+mod diagnostics;
+mod error;
+mod export;
+mod ext;
+mod hooks;
+#[cfg(feature = "linkify")]
+mod ical;
+mod inspect;
+mod limits;
+#[cfg(feature = "linkify")]
+mod lint;
+mod loader;
+mod matcher;
+#[cfg(feature = "axum")]
+mod middleware;
+mod multi_url;
+mod normalize;
+mod policy;
+#[cfg(feature = "std")]
+mod policy_file;
+#[cfg(feature = "std")]
+mod profile;
+mod referrer;
+mod regex_engine;
+#[cfg(feature = "linkify")]
+mod repair;
+#[cfg(feature = "std")]
+mod report;
 mod rules;
+#[cfg(feature = "ammonia")]
+mod sanitize;
 #[cfg(test)]
 #[allow(clippy::mod_module_files)]
 mod tests;
+#[cfg(feature = "testing")]
+mod testing;
+#[cfg(feature = "network")]
+mod update;
+mod version;
+#[cfg(feature = "warc")]
+mod warc;
+mod wrappers;
+
+/// How deep URLs inside query parameters are cleaned, see [`UrlCleaner::clean_nested_urls`].
+const MAX_NESTING_DEPTH: u8 = 4;
+
+/// The provider in the rules that applies to all URLs, see [`UrlCleaner::clean_mailto`].
+const GLOBAL_RULES: &str = "globalRules";
+
+fn default_skip_schemes() -> alloc::vec::Vec<alloc::string::String> {
+    alloc::vec!["data".into()]
+}
+
+#[cfg(feature = "linkify")]
+fn default_link_finder() -> linkify::LinkFinder {
+    let mut finder = linkify::LinkFinder::new();
+    finder.kinds(&[linkify::LinkKind::Url]);
+    finder
+}
+
+/// The finder for protocol-relative links, see [`UrlCleaner::protocol_relative`].
+#[cfg(feature = "linkify")]
+fn schemeless_link_finder() -> linkify::LinkFinder {
+    let mut finder = default_link_finder();
+    finder.url_must_have_scheme(false);
+    finder
+}
 
 /// A [`UrlCleaner`] can remove tracking parameters from URLs.
 ///
 /// This struct is relatively expensive to construct because it needs to parse the rules from JSON.
 /// It's recommended to create one per application and reuse it.
+///
+/// A [`UrlCleaner`] is `Send` and `Sync`, and it has no interior mutability: cleaning only reads it, so it can be
+/// shared between threads and async tasks with a reference or an [`Arc`][alloc::sync::Arc] without any locking.
+/// The only state that changes while cleaning is that of the hooks, cleaners and audit sinks that you add yourself.
 #[derive(Debug)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct UrlCleaner {
     rules: Rules,
+    rules_version: RulesVersion,
     strip_referral_marketing: bool,
+    decode_base64: bool,
+    base64_overrides: alloc::collections::BTreeMap<alloc::string::String, bool>,
+    follow_redirects: bool,
+    redirect_overrides: alloc::collections::BTreeMap<alloc::string::String, bool>,
+    clean_nested: bool,
+    multi_url: MultiUrl,
+    skip_schemes: alloc::vec::Vec<alloc::string::String>,
+    clean_mailto: bool,
+    scheme_handlers: alloc::vec::Vec<cleaner::SchemeHandler>,
+    hook: Option<Hook>,
+    audit: Option<audit::Audit>,
+    normalization: Option<Normalization>,
+    host_form: HostForm,
+    session_ids: Option<rules::Provider>,
+    x_links: Option<rules::Provider>,
+    unwrapping: wrappers::Unwrapping,
+    policies: PolicyMap,
+    strict: bool,
+    max_params: Option<(usize, ParamOverflow)>,
+    max_url_len: Option<(usize, LengthOverflow)>,
+    #[cfg(feature = "linkify")]
+    link_finder: linkify::LinkFinder,
+    #[cfg(feature = "linkify")]
+    schemeless_link_finder: linkify::LinkFinder,
+    #[cfg(feature = "linkify")]
+    ansi_escapes: bool,
+    #[cfg(feature = "linkify")]
+    repair_urls: bool,
+    #[cfg(feature = "linkify")]
+    trailing_punctuation: TrailingPunctuation,
+    #[cfg(feature = "markdown-it")]
+    skip_code: bool,
+    #[cfg(any(feature = "linkify", feature = "ammonia"))]
+    protocol_relative: bool,
 }
 
 impl UrlCleaner {
+    fn new(rules: Rules, rules_version: RulesVersion) -> Self {
+        Self {
+            rules,
+            rules_version,
+            strip_referral_marketing: false,
+            decode_base64: false,
+            base64_overrides: alloc::collections::BTreeMap::new(),
+            follow_redirects: true,
+            redirect_overrides: alloc::collections::BTreeMap::new(),
+            clean_nested: false,
+            multi_url: MultiUrl::Single,
+            skip_schemes: default_skip_schemes(),
+            clean_mailto: false,
+            scheme_handlers: alloc::vec::Vec::new(),
+            hook: None,
+            audit: None,
+            normalization: None,
+            host_form: HostForm::Ascii,
+            session_ids: None,
+            x_links: None,
+            unwrapping: wrappers::Unwrapping::default(),
+            policies: PolicyMap::new(),
+            strict: false,
+            max_params: None,
+            max_url_len: None,
+            #[cfg(feature = "linkify")]
+            link_finder: default_link_finder(),
+            #[cfg(feature = "linkify")]
+            schemeless_link_finder: schemeless_link_finder(),
+            #[cfg(feature = "linkify")]
+            ansi_escapes: false,
+            #[cfg(feature = "linkify")]
+            repair_urls: false,
+            #[cfg(feature = "linkify")]
+            trailing_punctuation: TrailingPunctuation::Finder,
+            #[cfg(feature = "markdown-it")]
+            skip_code: false,
+            #[cfg(any(feature = "linkify", feature = "ammonia"))]
+            protocol_relative: false,
+        }
+    }
+
     /// Construct a [`UrlCleaner`] with rules from a path, which will be opened and read.
     /// # Errors
-    /// See [`Error`]
-    #[cfg(feature = "std")]
-    pub fn from_rules_path(path: &std::path::Path) -> Result<Self, Error> {
-        Self::from_rules_file(std::fs::File::open(path)?)
+    /// See [`LoadError`]
+    #[cfg(feature = "std")]
+    pub fn from_rules_path(path: &std::path::Path) -> Result<Self, LoadError> {
+        RulesLoader::new().load_path(path)
+    }
+
+    /// Construct a [`UrlCleaner`] with rules from every `*.json` file in a directory.
+    ///
+    /// See [`RulesLoader::load_dir`] for how the files are merged.
+    /// # Errors
+    /// See [`LoadError`]
+    #[cfg(feature = "std")]
+    pub fn from_rules_dir(path: &std::path::Path) -> Result<Self, LoadError> {
+        RulesLoader::new().load_dir(path)
+    }
+
+    /// Construct a [`UrlCleaner`] with rules from a [reader][std::io::Read], most often a [`File`][std::fs::File]
+    ///
+    /// With the `gzip` feature, gzip-compressed input is detected and decompressed.
+    /// # Errors
+    /// See [`LoadError`]
+    #[cfg(feature = "std")]
+    pub fn from_rules_file<R: std::io::Read>(reader: R) -> Result<Self, LoadError> {
+        RulesLoader::new().load_file(reader)
+    }
+
+    /// # Errors
+    /// See [`LoadError`]
+    pub fn from_rules_str(rules: &str) -> Result<Self, LoadError> {
+        RulesLoader::new().load_str(rules)
+    }
+
+    /// Construct a [`UrlCleaner`] with rules from bytes, see [`RulesLoader::load_slice`].
+    /// # Errors
+    /// See [`LoadError`]
+    pub fn from_rules_slice(rules: &[u8]) -> Result<Self, LoadError> {
+        RulesLoader::new().load_slice(rules)
+    }
+
+    /// Construct using the JSON embedded in this library.
+    /// This may be outdated, but should provide a good baseline.
+    ///
+    /// # Errors
+    /// See [`LoadError`]
+    pub fn from_embedded_rules() -> Result<Self, LoadError> {
+        RulesLoader::new().load_embedded()
+    }
+
+    /// A cleaner without any providers, which returns URLs unchanged unless it is configured otherwise.
+    ///
+    /// This is useful in tests of code that takes a [`UrlCleaner`], so that they don't depend on the rules.
+    /// To script what happens to certain URLs, use a `MockCleaner` with the `std` feature instead.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let res = UrlCleaner::noop().clear_single_url_str("https://example.com/?utm_source=abc")?;
+    /// assert_eq!(res, "https://example.com/?utm_source=abc");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn noop() -> Self {
+        let rules = Rules {
+            providers: alloc::vec::Vec::new(),
+            warnings: alloc::vec::Vec::new(),
+            #[cfg(feature = "std")]
+            report: BuildReport::default(),
+        };
+        Self::new(rules, RulesVersion::of(br#"{"providers":{}}"#))
+    }
+
+    /// A cleaner with the embedded rules and default settings, shared by the whole process.
+    ///
+    /// It is constructed on first use. This is useful for small tools that don't want to pass
+    /// a [`UrlCleaner`] to every function that cleans URLs.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let res = UrlCleaner::shared().clear_single_url_str("https://example.com/test?utm_source=abc")?;
+    /// assert_eq!(res, "https://example.com/test");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    /// If the embedded rules are invalid, which is checked by the tests of this crate.
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn shared() -> &'static Self {
+        static SHARED: std::sync::OnceLock<UrlCleaner> = std::sync::OnceLock::new();
+        SHARED.get_or_init(|| Self::from_embedded_rules().expect("embedded rules are valid"))
+    }
+
+    /// The version of the rules this cleaner was constructed with.
+    ///
+    /// For the embedded rules, this is [`RulesVersion::EMBEDDED`].
+    #[must_use]
+    pub const fn rules_version(&self) -> RulesVersion {
+        self.rules_version
+    }
+
+    /// Things in the rules that were ignored while loading them, like fields that this crate doesn't understand.
+    ///
+    /// This helps to notice when the rules use a capability that this crate doesn't honor yet.
+    /// Loading doesn't fail because of them.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::{RulesWarning, UrlCleaner};
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_rules_str(r#"{"providers":{"a":{"urlPattern":".*","newFeature":true}}}"#)?;
+    /// assert!(matches!(&cleaner.warnings()[..], [RulesWarning::UnknownField { field, .. }] if field == "newFeature"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn warnings(&self) -> &[RulesWarning] {
+        &self.rules.warnings
+    }
+
+    /// How long loading the rules took, split into parsing the JSON and compiling the patterns,
+    /// with the patterns that were the most expensive to compile.
+    ///
+    /// This helps to decide how to reduce the startup time of short-lived programs.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?;
+    /// let report = cleaner.build_report();
+    /// println!("parsing took {:?}, compiling took {:?}", report.parse, report.compile);
+    /// for p in &report.slowest {
+    ///     println!("{:?} {} in {}", p.duration, p.pattern, p.provider);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub const fn build_report(&self) -> &BuildReport {
+        &self.rules.report
+    }
+
+    /// Configure whether you want to strip referral codes and similar parameters.
+    ///
+    /// While they can be considered to be tracking, they are useful on occasion.
+    /// The default is `false`, meaning these are kept.
+    #[must_use]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn strip_referral_marketing(mut self, value: bool) -> Self {
+        self.strip_referral_marketing = value;
+        self
+    }
+
+    /// Configure whether redirection targets may be base64 encoded.
+    ///
+    /// Some trackers encode the destination of a redirection with base64 instead of
+    /// percent-encoding it. If this is enabled, such targets are decoded, but only if the result
+    /// is an absolute `http` or `https` URL. Otherwise, the target is handled as usual.
+    /// This can be overridden per provider with
+    /// [`decode_base64_redirections_for`][Self::decode_base64_redirections_for].
+    /// The default is `false`.
+    #[must_use]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn decode_base64_redirections(mut self, value: bool) -> Self {
+        self.decode_base64 = value;
+        self
+    }
+
+    /// Configure whether the redirection targets of a single provider may be base64 encoded, regardless of
+    /// [`decode_base64_redirections`][Self::decode_base64_redirections] and of
+    /// [`Policy::CleanAggressive`].
+    ///
+    /// The provider is named as in the rules, like `google`.
+    /// Calling this again for the same provider replaces the previous value.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let rules = r#"{"providers":{"site":{"urlPattern":"^https://site\\.com","redirections":["\\?to=([^&]*)"]}}}"#;
+    /// let cleaner = UrlCleaner::from_rules_str(rules)?.decode_base64_redirections_for("site", true);
+    /// let res = cleaner.clear_single_url_str("https://site.com/r?to=aHR0cHM6Ly9leGFtcGxlLmNvbS8")?;
+    /// assert_eq!(res, "https://example.com/");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn decode_base64_redirections_for(mut self, provider: impl Into<alloc::string::String>, value: bool) -> Self {
+        self.base64_overrides.insert(provider.into(), value);
+        self
+    }
+
+    /// Configure whether the redirections in the rules are followed, which replaces a URL with its target.
+    ///
+    /// Following a redirection changes the host of a URL, which some deployments don't want to happen,
+    /// so if this is `false`, such URLs keep pointing to the redirecting site, but their parameters are still removed.
+    /// Raw rules that would move a URL to another host, like the one that unwraps links from the AMP cache
+    /// for [`clean_x_links`][Self::clean_x_links], are skipped as well.
+    /// This can be overridden per provider with [`follow_redirect_rules_for`][Self::follow_redirect_rules_for].
+    /// It doesn't affect [`unwrap_redirect_pages`][Self::unwrap_redirect_pages], which is opt-in.
+    /// The default is `true`.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?.follow_redirect_rules(false);
+    /// let url = "https://www.google.com/url?q=https%3A%2F%2Fexample.com%2F&usg=AOvVaw1";
+    /// let res = cleaner.clear_single_url_str(url)?;
+    /// assert_eq!(res, "https://www.google.com/url?q=https%3A%2F%2Fexample.com%2F");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub const fn follow_redirect_rules(mut self, value: bool) -> Self {
+        self.follow_redirects = value;
+        self
+    }
+
+    /// Configure whether the redirections of a single provider are followed, regardless of
+    /// [`follow_redirect_rules`][Self::follow_redirect_rules].
+    ///
+    /// The provider is named as in the rules, like `google`, or like the built-in rules, like `x`.
+    /// Calling this again for the same provider replaces the previous value.
+    #[must_use]
+    pub fn follow_redirect_rules_for(mut self, provider: impl Into<alloc::string::String>, value: bool) -> Self {
+        self.redirect_overrides.insert(provider.into(), value);
+        self
+    }
+
+    /// Remove the providers with these names from the rules, so that they don't apply to any URL.
+    ///
+    /// The providers are named as in the rules, like `google`. Unknown names are ignored.
+    /// The built-in rules are turned off with their own settings, like [`clean_x_links`][Self::clean_x_links].
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?.disable_providers(["globalRules"]);
+    /// let res = cleaner.clear_single_url_str("https://example.com/?utm_source=abc")?;
+    /// assert_eq!(res, "https://example.com/?utm_source=abc");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn disable_providers<I, S>(mut self, providers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let providers: alloc::vec::Vec<S> = providers.into_iter().collect();
+        self.rules.providers.retain(|p| !providers.iter().any(|name| name.as_ref() == p.name));
+        self
+    }
+
+    /// Configure whether URLs inside the values of query parameters are cleaned as well.
+    ///
+    /// Login and checkout flows often carry the next destination in a parameter like
+    /// `?next=https%3A%2F%2Fshop.example%2F%3Futm_source%3Dx`. If this is enabled, such values
+    /// are cleaned like any other URL and re-encoded in place.
+    /// The default is `false`.
+    #[must_use]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn clean_nested_urls(mut self, value: bool) -> Self {
+        self.clean_nested = value;
+        self
+    }
+
+    /// Configure how a redirection target or nested URL is handled if it contains several URLs,
+    /// like `dest=https://a.example/,https://b.example/`.
+    ///
+    /// The default is [`MultiUrl::Single`], which treats the value as one garbled URL.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::{MultiUrl, UrlCleaner};
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?.clean_nested_urls(true).multi_url(MultiUrl::Each);
+    /// let url = "https://example.com/?dest=https://a.example/?gclid=1,https://b.example/";
+    /// let res = cleaner.clear_single_url_str(url)?;
+    /// assert_eq!(res, "https://example.com/?dest=https%3A%2F%2Fa.example%2F%2Chttps%3A%2F%2Fb.example%2F");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub const fn multi_url(mut self, handling: MultiUrl) -> Self {
+        self.multi_url = handling;
+        self
+    }
+
+    /// Configure whether session IDs in the path are removed, like `;jsessionid=...` or `/sid/0123456789abcdef`.
+    ///
+    /// The upstream rules barely cover these, but they leak identifiers just like tracking parameters.
+    /// The built-in rules apply to all URLs, before the loaded rules, and are reported to the hook as raw rules.
+    /// The default is `false`.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?.strip_session_ids(true);
+    /// let res = cleaner.clear_single_url_str("https://example.com/shop;jsessionid=A1B2C3?item=5")?;
+    /// assert_eq!(res, "https://example.com/shop?item=5");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn strip_session_ids(mut self, value: bool) -> Self {
+        self.session_ids = value.then(rules::Provider::session_ids);
+        self
+    }
+
+    /// Configure whether links to X, formerly Twitter, are cleaned with built-in rules.
+    ///
+    /// These links are very common and carry several layers of tracking that the upstream rules only partly
+    /// cover, and not at all for `x.com`. The built-in rules
+    /// - remove the `s` and `t` parameters of shared links and `ref_src` and similar ones of embeds,
+    /// - also on mirrors like `fxtwitter.com` and `vxtwitter.com`,
+    /// - follow `/i/redirect?url=` links,
+    /// - unwrap links from the AMP cache, like `twitter-com.cdn.ampproject.org/c/s/twitter.com/...`,
+    /// - and remove `amp` from `t.co` links.
+    ///
+    /// `t.co` links themselves are left as they are, because resolving them needs a request.
+    /// The built-in rules apply before the loaded rules, under the provider name `x`.
+    /// The default is `false`.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?.clean_x_links(true);
+    /// let res = cleaner.clear_single_url_str("https://x.com/rustlang/status/1234?s=46&t=AbCdEf")?;
+    /// assert_eq!(res, "https://x.com/rustlang/status/1234");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn clean_x_links(mut self, value: bool) -> Self {
+        self.x_links = value.then(rules::Provider::x_links);
+        self
+    }
+
+    /// Configure whether the redirect pages of Google, Facebook and `YouTube` are unwrapped with built-in handlers.
+    ///
+    /// The upstream rules find the targets of these with regexes, which miss some of the edge cases, like
+    /// targets that are encoded more than once, an empty `q` next to the `url` of Google, or parameters like `usg`
+    /// whose value happens to look like the target parameter. The built-in handlers parse the query of
+    /// - `google.com/url?q=` and `google.com/url?url=`, on all Google domains like `google.co.uk`,
+    /// - `l.facebook.com/l.php?u=`, also on `lm.facebook.com` and `m.facebook.com`,
+    /// - and `youtube.com/redirect?q=`,
+    ///
+    /// and unwrap the link only if the target is an absolute `http` or `https` URL, which is cleaned as usual.
+    /// Unwrapping is reported to the hook as a redirection, under the provider name `googleRedirect`,
+    /// `facebookRedirect` or `youtubeRedirect`. The default is `false`.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?.unwrap_redirect_pages(true);
+    /// let res = cleaner.clear_single_url_str(
+    ///     "https://www.google.com/url?sa=t&q=&url=https%3A%2F%2Fexample.com%2Fa%252Fb%3Futm_source%3Dx&usg=AOvVaw1",
+    /// )?;
+    /// assert_eq!(res, "https://example.com/a%2Fb");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn unwrap_redirect_pages(mut self, value: bool) -> Self {
+        self.unwrapping.redirect_pages = value;
+        self
+    }
+
+    /// Configure whether links that email security gateways wrap around the original URL are unwrapped.
+    ///
+    /// Corporate mail often contains links that point to a gateway, which checks the target before redirecting.
+    /// Unlike usual redirections, their targets need some decoding beyond a capture group, which is built in for
+    /// - Outlook `SafeLinks`, like `https://nam12.safelinks.protection.outlook.com/?url=...`,
+    /// - and Proofpoint URL Defense version 3, like `https://urldefense.com/v3/__https://example.com/__;!!...$`.
+    ///
+    /// A link that is wrapped several times is unwrapped completely, and the target is cleaned as usual.
+    /// Unwrapping is reported to the hook as a redirection, under the provider name `safeLinks` or `urlDefense`.
+    /// The default is `false`.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?.unwrap_enterprise_wrappers(true);
+    /// let res = cleaner.clear_single_url_str(
+    ///     "https://nam12.safelinks.protection.outlook.com/?url=https%3A%2F%2Fexample.com%2F%3Futm_source%3Dmail\
+    ///     &data=05%7C01&sdata=abc&reserved=0",
+    /// )?;
+    /// assert_eq!(res, "https://example.com/");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "enterprise-wrappers")]
+    #[must_use]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn unwrap_enterprise_wrappers(mut self, value: bool) -> Self {
+        self.unwrapping.enterprise = value;
+        self
+    }
+
+    /// Configure per-domain policies that are consulted before the rules, see [`PolicyMap`].
+    ///
+    /// This replaces the previous policies. The default is an empty map.
+    /// URLs inside query parameters are looked up by their own domain.
+    #[must_use]
+    pub fn policies(mut self, policies: PolicyMap) -> Self {
+        self.policies = policies;
+        self
+    }
+
+    /// Configure whether a raw rule may change the scheme, host or path of a URL.
+    ///
+    /// If `true`, cleaning fails with [`CleanError::UnexpectedRewrite`] instead, which protects against overly broad
+    /// raw rules that mangle URLs. Redirections and [`strip_session_ids`][Self::strip_session_ids] are still allowed.
+    /// Note that some of the embedded raw rules remove path segments on purpose, like `/ref=...` on Amazon,
+    /// so this is mostly useful with custom rules.
+    /// The default is `false`.
+    #[must_use]
+    pub const fn strict(mut self, value: bool) -> Self {
+        self.strict = value;
+        self
+    }
+
+    /// Limit the number of query and fragment parameters that are processed per URL.
+    ///
+    /// Cleaning takes time proportional to the number of parameters, so services that clean untrusted URLs
+    /// should set a limit. URLs with more parameters are handled according to `overflow`.
+    /// The default is no limit.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::{ParamOverflow, UrlCleaner};
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?.max_params(2, ParamOverflow::Truncate);
+    /// let res = cleaner.clear_single_url_str("https://example.com/?a=1&utm_source=x&b=2")?;
+    /// assert_eq!(res, "https://example.com/?a=1");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub const fn max_params(mut self, limit: usize, overflow: ParamOverflow) -> Self {
+        self.max_params = Some((limit, overflow));
+        self
+    }
+
+    /// Limit the length in bytes of the URLs that are cleaned.
+    ///
+    /// Longer URLs are handled according to `overflow` before they are parsed, so that untrusted input
+    /// can't make the patterns scan megabytes of text. In text, this applies to each URL separately.
+    /// The default is no limit.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::{CleanError, LengthOverflow, UrlCleaner};
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?.max_url_len(30, LengthOverflow::Reject);
+    /// let res = cleaner.clear_single_url_str("https://example.com/?utm_source=abc");
+    /// assert!(matches!(res, Err(CleanError::UrlTooLong(35))));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub const fn max_url_len(mut self, limit: usize, overflow: LengthOverflow) -> Self {
+        self.max_url_len = Some((limit, overflow));
+        self
+    }
+
+    /// Configure how [`clear_text`][Self::clear_text] and its variants find URLs in text.
+    ///
+    /// The `*_with_linkfinder` variants take a finder per call instead.
+    /// The default finds URLs, but not email addresses.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), Vec<clearurls::LinkError>> {
+    /// let mut finder = linkify::LinkFinder::new();
+    /// finder.kinds(&[linkify::LinkKind::Url]).url_can_be_iri(false);
+    /// let cleaner = UrlCleaner::from_embedded_rules().unwrap().link_finder(finder);
+    /// let res = cleaner.clear_text("see https://example.com/?utm_source=abc")?;
+    /// assert_eq!(res, "see https://example.com/");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "linkify")]
+    #[must_use]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn link_finder(mut self, finder: linkify::LinkFinder) -> Self {
+        self.link_finder = finder;
+        self
+    }
+
+    /// Configure whether text is treated as terminal output that may contain ANSI escape sequences.
+    ///
+    /// If this is enabled, the methods that clean text, like [`clear_text`][Self::clear_text], leave escape sequences
+    /// like colors untouched, instead of possibly taking them for a part of a URL. The targets of OSC 8 hyperlinks,
+    /// like `\x1b]8;;https://example.com/?utm_source=x\x1b\\`, are cleaned in place, as well as the URLs in
+    /// the text between the escape sequences. This is meant for terminal multiplexers and log tools.
+    /// The default is `false`.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), Vec<clearurls::LinkError>> {
+    /// let cleaner = UrlCleaner::from_embedded_rules().unwrap().ansi_escapes(true);
+    /// let text = "\x1b]8;;https://example.com/?utm_source=x\x1b\\\x1b[1mlink\x1b[0m\x1b]8;;\x1b\\";
+    /// let res = cleaner.clear_text(text)?;
+    /// assert_eq!(res, "\x1b]8;;https://example.com/\x1b\\\x1b[1mlink\x1b[0m\x1b]8;;\x1b\\");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "linkify")]
+    #[must_use]
+    pub const fn ansi_escapes(mut self, value: bool) -> Self {
+        self.ansi_escapes = value;
+        self
+    }
+
+    /// Configure whether [`clear_text`][Self::clear_text] and its variants repair URLs that can't be parsed,
+    /// instead of reporting an error for them.
+    ///
+    /// Links that people paste are often messy. If a URL in text can't be parsed, its spaces are percent-encoded,
+    /// and for a link found in the text rather than the target of a hyperlink, punctuation at its end that belongs
+    /// to the text, like a period or an unbalanced closing parenthesis, is left out. Then the rest is cleaned.
+    /// If that doesn't help either, like for a port that is too large, the URL is left unchanged without an error,
+    /// since things in free text that look like links aren't always meant to be URLs.
+    /// The default is `false`.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), Vec<clearurls::LinkError>> {
+    /// let cleaner = UrlCleaner::from_embedded_rules().unwrap().repair_urls(true);
+    /// let res = cleaner.clear_text("https://example.com:99999/?utm_source=x https://example.com/?utm_source=x")?;
+    /// assert_eq!(res, "https://example.com:99999/?utm_source=x https://example.com/");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "linkify")]
+    #[must_use]
+    pub const fn repair_urls(mut self, value: bool) -> Self {
+        self.repair_urls = value;
+        self
+    }
+
+    /// Configure how punctuation at the end of links is handled by [`clear_text`][Self::clear_text] and its variants,
+    /// so that cleaned links don't swallow the punctuation of the text around them.
+    /// The default is [`TrailingPunctuation::Finder`].
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::{TrailingPunctuation, UrlCleaner};
+    /// # fn main() -> Result<(), Vec<clearurls::LinkError>> {
+    /// let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    /// let res = cleaner.clear_text("~~https://example.com/?utm_source=x~~")?;
+    /// assert_eq!(res, "~~https://example.com/");
+    /// let cleaner = cleaner.trailing_punctuation(TrailingPunctuation::Trim("_~".into()));
+    /// let res = cleaner.clear_text("~~https://example.com/?utm_source=x~~")?;
+    /// assert_eq!(res, "~~https://example.com/~~");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "linkify")]
+    #[must_use]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn trailing_punctuation(mut self, handling: TrailingPunctuation) -> Self {
+        self.trailing_punctuation = handling;
+        self
+    }
+
+    /// Configure whether [`clear_markdown`][Self::clear_markdown] leaves URLs inside inline `<code>` and `<pre>`
+    /// HTML tags untouched, since rewriting example URLs in technical posts changes their meaning.
+    ///
+    /// URLs in Markdown code spans and code blocks are never cleaned, because they are not links.
+    /// This only matters if the parser accepts inline HTML, which would otherwise let bare links between
+    /// the tags be detected and cleaned.
+    /// The default is `false`.
+    #[cfg(feature = "markdown-it")]
+    #[must_use]
+    pub const fn skip_code(mut self, value: bool) -> Self {
+        self.skip_code = value;
+        self
+    }
+
+    /// Configure whether protocol-relative links in documents, like `//cdn.example.com/x?utm_source=a`, are cleaned.
+    ///
+    /// Such a link is cleaned as if it used `https:`, and written back protocol-relative, unless a redirection
+    /// leads to a URL with another scheme. This applies to URLs found in text, Markdown and HTML attributes,
+    /// while [`clear_single_url_str`][Self::clear_single_url_str] still rejects them with [`CleanError::UrlSyntax`].
+    /// The default is `false`.
+    #[cfg(any(feature = "linkify", feature = "ammonia"))]
+    #[must_use]
+    pub const fn protocol_relative(mut self, value: bool) -> Self {
+        self.protocol_relative = value;
+        self
+    }
+
+    /// Add URL schemes that are passed through untouched, like `mailto` or `magnet`.
+    ///
+    /// Schemes are given without the colon and compared case-insensitively. They are added to the previous list,
+    /// which is `data` by default.
+    /// Use [`clear_skip_schemes`][Self::clear_skip_schemes] to start from an empty list.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?.skip_schemes(["myapp"]);
+    /// let res = cleaner.clear_single_url_str("myapp://open?utm_source=abc")?;
+    /// assert_eq!(res, "myapp://open?utm_source=abc");
+    /// assert_eq!(cleaner.clear_single_url_str("data:,utm_source=abc")?, "data:,utm_source=abc");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn skip_schemes<I, S>(mut self, schemes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<alloc::string::String>,
+    {
+        self.skip_schemes.extend(schemes.into_iter().map(Into::into));
+        self
+    }
+
+    /// Remove all URL schemes that are passed through untouched, including the default `data`,
+    /// so that URLs with these schemes are cleaned like any other.
+    #[must_use]
+    pub fn clear_skip_schemes(mut self) -> Self {
+        self.skip_schemes.clear();
+        self
+    }
+
+    /// Configure whether the query parameters of `mailto:` links are cleaned,
+    /// like in `mailto:a@example.com?subject=Hi&utm_source=newsletter`.
+    ///
+    /// Only the global rules that apply to all URLs, like `utm_source`, are used, because the parameters
+    /// of `mailto:` links are usually functional, like `subject` and `body`.
+    /// The default is `false`, which leaves `mailto:` links untouched.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?.clean_mailto(true);
+    /// let res = cleaner.clear_single_url_str("mailto:a@example.com?subject=Hi&utm_source=newsletter")?;
+    /// assert_eq!(res, "mailto:a@example.com?subject=Hi");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub const fn clean_mailto(mut self, value: bool) -> Self {
+        self.clean_mailto = value;
+        self
+    }
+
+    /// Register a [`Cleaner`] for the URLs with a certain scheme, like `magnet` or `intent`.
+    ///
+    /// URLs with that scheme are passed to the handler instead of the rules, also when cleaning text or documents.
+    /// The handler takes precedence over [`skip_schemes`][Self::skip_schemes] and [`clean_mailto`][Self::clean_mailto],
+    /// but [`max_url_len`][Self::max_url_len] still applies.
+    /// The scheme is given without the colon and compared case-insensitively.
+    /// A new handler for the same scheme replaces the previous.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::borrow::Cow;
+    /// # use clearurls::{Cleaner, Error, UrlCleaner};
+    /// /// Removes the trackers from magnet links.
+    /// struct MagnetTrackers;
+    ///
+    /// impl Cleaner for MagnetTrackers {
+    ///     fn clean<'a>(&self, url: &'a str) -> Result<Cow<'a, str>, Error> {
+    ///         let Some((base, query)) = url.split_once('?') else { return Ok(Cow::Borrowed(url)) };
+    ///         let params: Vec<_> = query.split('&').filter(|p| !p.starts_with("tr=")).collect();
+    ///         Ok(Cow::Owned(format!("{base}?{}", params.join("&"))))
+    ///     }
+    /// }
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?.scheme_handler("magnet", MagnetTrackers);
+    /// let res = cleaner.clear_single_url_str("magnet:?xt=urn:btih:c12fe1&tr=udp%3A%2F%2Ftracker.example")?;
+    /// assert_eq!(res, "magnet:?xt=urn:btih:c12fe1");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn scheme_handler<C>(mut self, scheme: impl Into<alloc::string::String>, cleaner: C) -> Self
+    where
+        C: Cleaner + Send + Sync + 'static,
+    {
+        let scheme = scheme.into();
+        self.scheme_handlers.retain(|h| !h.scheme.eq_ignore_ascii_case(&scheme));
+        self.scheme_handlers.push(cleaner::SchemeHandler { scheme, cleaner: alloc::boxed::Box::new(cleaner) });
+        self
+    }
+
+    /// Register a hook that is consulted before every parameter removal, raw rule and redirection.
+    ///
+    /// The hook can observe the modification and return [`Decision::Veto`] to prevent it,
+    /// for example to never touch parameters on a certain domain.
+    /// Only one hook can be registered; a new one replaces the previous.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::{Decision, UrlCleaner};
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?.on_param_removal(|ctx| {
+    ///     if ctx.url.host_str().is_some_and(|h| h.ends_with(".bank.com")) {
+    ///         Decision::Veto
+    ///     } else {
+    ///         Decision::Allow
+    ///     }
+    /// });
+    /// let res = cleaner.clear_single_url_str("https://www.bank.com/?utm_source=abc")?;
+    /// assert_eq!(res, "https://www.bank.com/?utm_source=abc");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn on_param_removal<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&HookContext<'_>) -> Decision + Send + Sync + 'static,
+    {
+        self.hook = Some(Hook::new(hook));
+        self
+    }
+
+    /// Register a sink that records every URL that is modified, like the `JsonlAuditLog` of the `serialize` feature.
+    ///
+    /// The sink is called by the methods that return cleaned URLs, like
+    /// [`clear_single_url_str`][Self::clear_single_url_str] or
+    /// [`clear_single_url_explained`][Self::clear_single_url_explained], and those that clean text,
+    /// but not by those that only check URLs, like [`is_clean`][Self::is_clean] or [`clean_key`][Self::clean_key].
+    /// A query cleaned with [`clean_query`][Self::clean_query] or [`clean_query_pairs`][Self::clean_query_pairs],
+    /// like by the `axum` middleware, is recorded as the query of `https://{host}/`.
+    /// URLs that are left as they are aren't recorded.
+    /// Only one sink can be registered; a new one replaces the previous.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::sync::{Arc, Mutex};
+    /// # use clearurls::{AuditSink, CleanReport, UrlCleaner};
+    /// #[derive(Default)]
+    /// struct Log(Mutex<Vec<String>>);
+    ///
+    /// impl AuditSink for Log {
+    ///     fn record(&self, original: &str, _cleaned: &str, _details: &CleanReport) {
+    ///         self.0.lock().unwrap().push(original.into());
+    ///     }
+    /// }
+    ///
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let log = Arc::new(Log::default());
+    /// let cleaner = UrlCleaner::from_embedded_rules()?.audit_sink(Arc::clone(&log));
+    /// cleaner.clear_single_url_str("https://example.com/?utm_source=abc")?;
+    /// cleaner.clear_single_url_str("https://example.com/")?;
+    /// assert_eq!(*log.0.lock().unwrap(), ["https://example.com/?utm_source=abc"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn audit_sink<S: AuditSink + Send + Sync + 'static>(mut self, sink: S) -> Self {
+        self.audit = Some(audit::Audit(alloc::boxed::Box::new(sink)));
+        self
+    }
+
+    /// Check the providers that changed the most URLs in `profile` first.
+    ///
+    /// Once a URL has no query and fragment left, the providers that only remove parameters are skipped.
+    /// With skewed traffic, checking the providers that usually remove all parameters first saves matching
+    /// the URL against the patterns of the others.
+    ///
+    /// Providers with raw rules or redirections keep their position, and the others are only moved between them,
+    /// so that the target of a redirection is still cleaned by the providers after it.
+    /// Providers with the same number of hits, like those that aren't in `profile`, keep their order.
+    /// The result is the same unless the pattern of a provider depends on parameters that another one removes.
+    /// The default is the order of the rules.
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn provider_order(mut self, profile: &ProviderProfile) -> Self {
+        for providers in self.rules.providers.split_mut(|p| !p.only_removes_params()) {
+            providers.sort_by_key(|p| core::cmp::Reverse(profile.get(&p.name)));
+        }
+        self
+    }
+
+    /// Normalize URLs after cleaning, to get stable canonical URLs for deduplication or as cache keys.
+    ///
+    /// Only the cleaned URL itself is normalized, not URLs nested in its parameters.
+    /// The default is no normalization beyond that of the [`url`] crate.
+    #[must_use]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn normalize(mut self, normalization: Normalization) -> Self {
+        self.normalization = Some(normalization);
+        self
+    }
+
+    /// Configure whether internationalized hosts are written as punycode or as Unicode.
+    ///
+    /// This applies to all functions that return strings, but not to [`clear_single_url`][Self::clear_single_url],
+    /// because a [`Url`] always holds the punycode form.
+    /// The default is [`HostForm::Ascii`].
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::{HostForm, UrlCleaner};
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?.host_form(HostForm::Unicode);
+    /// let res = cleaner.clear_single_url_str("https://xn--bcher-kva.example/?utm_source=abc")?;
+    /// assert_eq!(res, "https://bücher.example/");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub const fn host_form(mut self, form: HostForm) -> Self {
+        self.host_form = form;
+        self
+    }
+
+    /// Clean a single URL.
+    ///
+    /// The argument is a string that is *just* a URL, with no text around.
+    ///
+    /// The Cleaning may involve
+    /// - 1. removing tracking parameters
+    ///      and/or,
+    /// - 2. detecting redirections with the target url in a query parameters
+    ///
+    /// # Returns
+    /// a cleaned URL
+    ///
+    /// # Errors
+    /// If an error occurred. See the [`CleanError`] enum for possible reasons.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(self), err(Display)))]
+    pub fn clear_single_url_str<'a>(&self, url: &'a str) -> Result<Cow<'a, str>, CleanError> {
+        self.audited(url, |observer| self.clean_str(url, observer), |c| Cow::Borrowed(c))
+    }
+
+    /// Clean a list of URLs with one URL per line, like the exports of browsers and crawlers.
+    ///
+    /// The iterator yields every line with its line ending, if it has one, so that joining the lines gives the
+    /// cleaned file. Blank lines and comments starting with `#` are passed through, and so is whitespace around
+    /// the URLs.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::LinkError> {
+    /// let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    /// let list = "# links\r\nhttps://example.com/?utm_source=abc\n\nhttps://example.org/";
+    /// let cleaned = cleaner.clear_url_list(list.as_bytes()).collect::<Result<String, _>>()?;
+    /// assert_eq!(cleaned, "# links\r\nhttps://example.com/\n\nhttps://example.org/");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// An item is an error if reading the line fails, or if cleaning the URL in it fails,
+    /// see the [`Error`] enum for possible reasons. The [`url`][LinkError::url] of the error is the whole line
+    /// as it was read, with its line ending, so that it can be written back unchanged. The lines after it are
+    /// still read.
+    #[cfg(feature = "std")]
+    pub fn clear_url_list<'a, R: std::io::BufRead + 'a>(
+        &'a self,
+        mut reader: R,
+    ) -> impl Iterator<Item = Result<alloc::string::String, LinkError>> + 'a {
+        core::iter::from_fn(move || {
+            let mut line = alloc::string::String::new();
+            match reader.read_line(&mut line) {
+                Ok(0) => None,
+                Ok(_) => Some(self.clear_list_line(line)),
+                Err(e) => Some(Err(LinkError::new(&line, e))),
+            }
+        })
+    }
+
+    /// Clean the URL in a line of [`clear_url_list`][Self::clear_url_list], keeping everything around it.
+    #[cfg(feature = "std")]
+    fn clear_list_line(&self, line: alloc::string::String) -> Result<alloc::string::String, LinkError> {
+        let url = line.trim();
+        if url.is_empty() || url.starts_with('#') {
+            return Ok(line);
+        }
+        match self.clear_single_url_str(url) {
+            Ok(Cow::Owned(cleaned)) if cleaned != url => {
+                let start = line.len() - line.trim_start().len();
+                Ok([&line[..start], &cleaned, &line[start + url.len()..]].concat())
+            }
+            Ok(_) => Ok(line),
+            Err(e) => Err(LinkError::new(&line, e)),
+        }
+    }
+
+    /// [`clear_single_url_str`][Self::clear_single_url_str] without auditing, for checks that don't modify anything.
+    pub(crate) fn clean_str<'a>(&self, url: &'a str, observer: &Observer<'_>) -> Result<Cow<'a, str>, CleanError> {
+        if self.is_too_long(url.len())? {
+            return Ok(Cow::Borrowed(url));
+        }
+        if let Some(handler) = self.scheme_handler_for(url) {
+            return handler.clean(url).map_err(CleanError::from_handler);
+        }
+        if self.is_skipped_scheme(url) {
+            return Ok(Cow::Borrowed(url));
+        }
+        let parsed = Url::from_str(url)?;
+        let result = self.clear_url_at_depth(Cow::Borrowed(&parsed), 0, observer)?;
+        // most URLs in text are already clean, so avoid serializing them again
+        if matches!(result, Cow::Borrowed(_)) && parsed.as_str() == url && self.host_form == HostForm::Ascii {
+            return Ok(Cow::Borrowed(url));
+        }
+
+        Ok(Cow::Owned(self.host_form.serialize(result.into_owned())))
+    }
+
+    /// Clean a single URL.
+    ///
+    /// The Cleaning may involve
+    /// - 1. removing tracking parameters
+    ///      and/or,
+    /// - 2. detecting redirections with the target url in a query parameters
+    ///
+    /// # Returns
+    /// a cleaned URL
+    ///
+    /// # Errors
+    /// If an error occurred. See the [`CleanError`] enum for possible reasons.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(self), fields(url = %url), err(Display)))]
+    pub fn clear_single_url<'a>(&self, url: &'a Url) -> Result<Cow<'a, Url>, CleanError> {
+        self.audited(url.as_str(), |observer| self.clean_url(url, observer), |c| Cow::Borrowed(c.as_str()))
+    }
+
+    /// [`clear_single_url`][Self::clear_single_url] without auditing.
+    fn clean_url<'a>(&self, url: &'a Url, observer: &Observer<'_>) -> Result<Cow<'a, Url>, CleanError> {
+        if self.is_too_long(url.as_str().len())? {
+            return Ok(Cow::Borrowed(url));
+        }
+        if let Some(handler) = self.scheme_handler_for(url.as_str()) {
+            return match handler.clean(url.as_str()).map_err(CleanError::from_handler)? {
+                Cow::Borrowed(new) if new == url.as_str() => Ok(Cow::Borrowed(url)),
+                new => Ok(Cow::Owned(Url::from_str(&new)?)),
+            };
+        }
+        if self.is_skipped_scheme(url.as_str()) {
+            return Ok(Cow::Borrowed(url));
+        }
+        self.clear_url_at_depth(Cow::Borrowed(url), 0, observer)
+    }
+
+    /// Whether [`clear_single_url_str`][Self::clear_single_url_str] would leave `url` as it is,
+    /// without building the cleaned URL.
+    ///
+    /// This is meant for validators and linters that only need a yes or no, like a CI check for links in documentation.
+    /// Only modifications by the rules and by [`normalize`][Self::normalize] count, not the re-encoding of the URL
+    /// and its query, so `https://EXAMPLE.com` is clean, even though it's cleaned to `https://example.com/`.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?;
+    /// assert!(cleaner.is_clean("https://example.com/?q=rust")?);
+    /// assert!(!cleaner.is_clean("https://example.com/?q=rust&utm_source=abc")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// If an error occurred. See the [`CleanError`] enum for possible reasons.
+    pub fn is_clean(&self, url: &str) -> Result<bool, CleanError> {
+        if self.is_too_long(url.len())? {
+            return Ok(true);
+        }
+        if let Some(handler) = self.scheme_handler_for(url) {
+            return Ok(handler.clean(url).map_err(CleanError::from_handler)? == url);
+        }
+        if self.is_skipped_scheme(url) {
+            return Ok(true);
+        }
+        let parsed = Url::from_str(url)?;
+        let flag = Cell::new(false);
+        let observer = Observer { dry_run: Some(&flag), ..self.observer() };
+        self.clear_url_at_depth(Cow::Borrowed(&parsed), 0, &observer)?;
+        Ok(!flag.get())
+    }
+
+    /// Clean a single URL given as raw bytes, for proxies and log processors that don't work with [`str`].
+    ///
+    /// Bytes that are not valid UTF-8 are percent-encoded before cleaning, like browsers do.
+    /// The result is always valid UTF-8, but is returned as bytes for convenience.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?;
+    /// let res = cleaner.clear_url_bytes(b"https://example.com/caf\xe9?utm_source=abc")?;
+    /// assert_eq!(&*res, b"https://example.com/caf%E9");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// If an error occurred. See the [`CleanError`] enum for possible reasons.
+    pub fn clear_url_bytes<'a>(&self, url: &'a [u8]) -> Result<Cow<'a, [u8]>, CleanError> {
+        use alloc::string::ToString;
+        use percent_encoding::{percent_encode, CONTROLS};
+
+        if self.is_too_long(url.len())? {
+            return Ok(Cow::Borrowed(url));
+        }
+        let Ok(url) = core::str::from_utf8(url) else {
+            // non-ASCII bytes are always encoded, ASCII ones keep their meaning in the URL
+            let encoded = percent_encode(url, CONTROLS).to_string();
+            return Ok(Cow::Owned(self.clear_single_url_str(&encoded)?.into_owned().into_bytes()));
+        };
+        Ok(match self.clear_single_url_str(url)? {
+            Cow::Borrowed(b) => Cow::Borrowed(b.as_bytes()),
+            Cow::Owned(o) => Cow::Owned(o.into_bytes()),
+        })
+    }
+
+    /// Clean a single URL that may be relative to `base`.
+    ///
+    /// Relative URLs, like `/path?utm_source=abc` or `//example.com/path`, are resolved against
+    /// `base` before the rules are applied. If the cleaned URL still has the same origin as `base`,
+    /// it is returned in the same relative form as the input, otherwise as an absolute URL.
+    /// Absolute URLs are handled just like [`Self::clear_single_url_str`].
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # use url::Url;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?;
+    /// let base = Url::parse("https://example.com/blog/post.html")?;
+    /// let res = cleaner.clear_url_with_base("/test?utm_source=abc", &base)?;
+    /// assert_eq!(res, "/test");
+    /// let res = cleaner.clear_url_with_base("//example.org/test?utm_source=abc", &base)?;
+    /// assert_eq!(res, "//example.org/test");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// If an error occurred. See the [`CleanError`] enum for possible reasons.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), fields(base = %base), err(Display))
+    )]
+    pub fn clear_url_with_base<'a>(&self, url: &'a str, base: &Url) -> Result<Cow<'a, str>, CleanError> {
+        self.audited(url, |observer| self.clean_with_base(url, base, observer), |c| Cow::Borrowed(c))
     }
 
-    /// Construct a [`UrlCleaner`] with rules from a [reader][std::io::Read], most often a [`File`][std::fs::File]
-    /// # Errors
-    /// See [`Error`]
-    #[cfg(feature = "std")]
-    pub fn from_rules_file<R: std::io::Read>(reader: R) -> Result<Self, Error> {
-        let buf = std::io::BufReader::new(reader);
-        Ok(Self {
-            rules: serde_json::from_reader(buf)?,
-            strip_referral_marketing: false,
-        })
+    /// [`clear_url_with_base`][Self::clear_url_with_base] without auditing.
+    fn clean_with_base<'a>(
+        &self,
+        url: &'a str,
+        base: &Url,
+        observer: &Observer<'_>,
+    ) -> Result<Cow<'a, str>, CleanError> {
+        use alloc::string::ToString;
+        use url::Position;
+
+        if self.is_too_long(url.len())? {
+            return Ok(Cow::Borrowed(url));
+        }
+        match Url::from_str(url) {
+            Err(ParseError::RelativeUrlWithoutBase) => {}
+            _ => return self.clean_str(url, observer),
+        }
+        let absolute = base.join(url)?;
+        let cleaned = match self.clear_url_at_depth(Cow::Borrowed(&absolute), 0, observer)? {
+            Cow::Owned(cleaned) if cleaned != absolute => cleaned,
+            _ => return Ok(Cow::Borrowed(url)),
+        };
+        let relative = if url.starts_with("//") {
+            // strip the scheme and the colon
+            (cleaned.scheme() == base.scheme()).then(|| {
+                let scheme_len = cleaned.scheme().len();
+                self.host_form.serialize(cleaned.clone())[scheme_len + 1..].to_string()
+            })
+        } else if cleaned.origin() == base.origin() {
+            if url.starts_with('/') {
+                Some(cleaned[Position::BeforePath..].to_string())
+            } else {
+                base.make_relative(&cleaned)
+            }
+        } else {
+            None
+        };
+        Ok(Cow::Owned(relative.unwrap_or_else(|| self.host_form.serialize(cleaned))))
     }
 
+    /// A key for comparing URLs modulo tracking parameters.
+    ///
+    /// URLs that point to the same destination after cleaning have the same key.
+    /// The key is the cleaned URL in the normalized form of the [`url`] crate, which lowercases the host
+    /// and scheme, removes default ports and resolves dot segments, among other things.
+    /// Configure a [`Normalization`] with [`normalize`][Self::normalize] to also ignore trailing slashes
+    /// or the order of query parameters.
+    ///
     /// # Errors
-    /// See [`Error`]
-    pub fn from_rules_str(rules: &str) -> Result<Self, Error> {
-        Ok(Self {
-            rules: serde_json::from_str(rules)?,
-            strip_referral_marketing: false,
-        })
+    /// If an error occurred. See the [`CleanError`] enum for possible reasons.
+    pub fn clean_key(&self, url: &str) -> Result<alloc::string::String, CleanError> {
+        let url = Url::from_str(url)?;
+        Ok(self.clean_url(&url, &self.observer())?.into_owned().into())
     }
 
-    /// Construct using the JSON embedded in this library.
-    /// This may be outdated, but should provide a good baseline.
+    /// A key for grouping URLs in analytics, so that the variants of a link with different tracking parameters
+    /// are counted together.
+    ///
+    /// Unlike [`clean_key`][Self::clean_key], this doesn't depend on the configured [`Normalization`]:
+    /// on top of it, the host is lowercased, the remaining query parameters are sorted by name and the fragment
+    /// is dropped. The host is always in punycode.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?;
+    /// let key = cleaner.canonical_key("HTTPS://Example.com/post?page=2&utm_source=x&id=1#comments")?;
+    /// assert_eq!(key, "https://example.com/post?id=1&page=2");
+    /// assert_eq!(cleaner.canonical_key("https://example.com/post?id=1&page=2&fbclid=abc")?, key);
+    /// # Ok(())
+    /// # }
+    /// ```
     ///
     /// # Errors
-    /// See [`Error`]
-    pub fn from_embedded_rules() -> Result<Self, Error> {
-        Self::from_rules_str(include_str!("../data.minify.json"))
+    /// If an error occurred. See the [`CleanError`] enum for possible reasons.
+    pub fn canonical_key(&self, url: &str) -> Result<alloc::string::String, CleanError> {
+        let url = Url::from_str(url)?;
+        let mut key = self.clean_url(&url, &self.observer())?.into_owned();
+        Normalization::new().sort_query(true).apply(&mut key);
+        key.set_fragment(None);
+        if key.query() == Some("") {
+            key.set_query(None);
+        }
+        Ok(key.into())
     }
 
-    /// Configure whether you want to strip referral codes and similar parameters.
+    /// Whether two URLs point to the same destination after cleaning, see [`clean_key`][Self::clean_key].
     ///
-    /// While they can be considered to be tracking, they are useful on occasion.
-    /// The default is `false`, meaning these are kept.
-    #[must_use]
-    #[allow(clippy::missing_const_for_fn)]
-    pub fn strip_referral_marketing(mut self, value: bool) -> Self {
-        self.strip_referral_marketing = value;
-        self
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?;
+    /// assert!(cleaner.same_after_cleaning("https://example.com/?utm_source=a", "HTTPS://EXAMPLE.COM:443/")?);
+    /// assert!(!cleaner.same_after_cleaning("https://example.com/?page=1", "https://example.com/?page=2")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// If an error occurred with either URL. See the [`CleanError`] enum for possible reasons.
+    pub fn same_after_cleaning(&self, a: &str, b: &str) -> Result<bool, CleanError> {
+        Ok(self.clean_key(a)? == self.clean_key(b)?)
     }
 
-    /// Clean a single URL.
+    /// Clean many URLs and drop those that are duplicates after cleaning, keeping the first occurrence.
     ///
-    /// The argument is a string that is *just* a URL, with no text around.
+    /// This is useful for crawlers and bookmark managers, where the same page is often collected
+    /// with different tracking parameters.
     ///
-    /// The Cleaning may involve
-    /// - 1. removing tracking parameters
-    ///      and/or,
-    /// - 2. detecting redirections with the target url in a query parameters
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), Vec<clearurls::LinkError>> {
+    /// let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    /// let urls = cleaner.clean_and_dedup([
+    ///     "https://example.com/a?utm_source=mail",
+    ///     "https://example.com/b",
+    ///     "https://example.com/a?utm_source=feed",
+    /// ])?;
+    /// assert_eq!(urls, ["https://example.com/a", "https://example.com/b"]);
+    /// # Ok(())
+    /// # }
+    /// ```
     ///
-    /// # Returns
-    /// a cleaned URL
+    /// # Errors
+    /// Alls errors encountered are returned in a [`Vec`][alloc::vec::Vec],
+    /// together with the URL that caused them.
+    pub fn clean_and_dedup<'a, I>(&self, urls: I) -> Result<alloc::vec::Vec<Cow<'a, str>>, alloc::vec::Vec<LinkError>>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        use alloc::collections::BTreeSet;
+        use alloc::vec::Vec;
+
+        let mut seen = BTreeSet::new();
+        let mut cleaned = Vec::new();
+        let mut errors = Vec::new();
+        for url in urls {
+            match self.clear_single_url_str(url) {
+                Ok(c) => {
+                    if seen.insert(c.clone().into_owned()) {
+                        cleaned.push(c);
+                    }
+                }
+                Err(e) => errors.push(LinkError::new(url, e)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(cleaned)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Remove the tracking parameters from a query that is already decomposed into decoded name-value pairs.
+    ///
+    /// This is meant for frameworks that parse the query themselves, like routers that clean the parameters of
+    /// incoming requests before logging them. The rules apply as if the pairs were the query of `https://{host}/`,
+    /// so providers and [`policies`][Self::policies] are chosen by the host alone, and redirections and raw rules,
+    /// which need the whole URL, don't apply. The remaining pairs are returned in their original order.
+    /// [`max_url_len`][Self::max_url_len] applies to the length of that URL, and [`max_params`][Self::max_params]
+    /// to the number of pairs.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::borrow::Cow;
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?;
+    /// let pairs = [("q", "shoes"), ("utm_source", "newsletter"), ("page", "2")];
+    /// let pairs = pairs.into_iter().map(|(k, v)| (Cow::Borrowed(k), Cow::Borrowed(v)));
+    /// let cleaned = cleaner.clean_query_pairs("example.com", pairs)?;
+    /// let cleaned: Vec<_> = cleaned.iter().map(|(k, v)| (k.as_ref(), v.as_ref())).collect();
+    /// assert_eq!(cleaned, [("q", "shoes"), ("page", "2")]);
+    /// # Ok(())
+    /// # }
+    /// ```
     ///
     /// # Errors
-    /// If an error occurred. See the [`Error`] enum for possible reasons.
-    pub fn clear_single_url_str<'a>(&self, url: &'a str) -> Result<Cow<'a, str>, Error> {
-        if url.starts_with("data:") {
-            return Ok(Cow::Borrowed(url));
+    /// If `host` isn't a valid host, or if the URL is too long and [`LengthOverflow::Reject`] is configured.
+    #[allow(clippy::type_complexity)]
+    pub fn clean_query_pairs<'a, I>(
+        &self,
+        host: &str,
+        pairs: I,
+    ) -> Result<alloc::vec::Vec<(Cow<'a, str>, Cow<'a, str>)>, CleanError>
+    where
+        I: IntoIterator<Item = (Cow<'a, str>, Cow<'a, str>)>,
+    {
+        let pairs: alloc::vec::Vec<_> = pairs.into_iter().collect();
+        if self.max_url_len.is_some() && self.is_too_long(query_url(host, &pairs).len())? {
+            return Ok(pairs);
+        }
+        let original = if self.audit.is_some() { query_url(host, &pairs) } else { alloc::string::String::new() };
+        let clean = |observer: &Observer<'_>| self.remove_query_params(host, pairs, observer);
+        self.audited(&original, clean, |kept| query_url(host, kept).into())
+    }
+
+    /// [`clean_query_pairs`][Self::clean_query_pairs] without auditing.
+    #[allow(clippy::type_complexity)]
+    fn remove_query_params<'a>(
+        &self,
+        host: &str,
+        mut pairs: alloc::vec::Vec<(Cow<'a, str>, Cow<'a, str>)>,
+        observer: &Observer<'_>,
+    ) -> Result<alloc::vec::Vec<(Cow<'a, str>, Cow<'a, str>)>, CleanError> {
+        let url = Url::from_str(&alloc::format!("https://{host}/"))?;
+        if url.path() != "/" || url.query().is_some() || url.fragment().is_some() {
+            return Err(CleanError::UrlSyntax(ParseError::InvalidDomainCharacter));
         }
-        let mut result = Url::from_str(url)?;
-        for p in &self.rules.providers {
-            if p.match_url(result.as_str()) {
-                result = p.remove_fields_from_url(&result, self.strip_referral_marketing)?;
+        let policy = self.policies.get(&url);
+        let keep = match policy {
+            Some(Policy::Skip) => return Ok(pairs),
+            Some(Policy::AllowlistParams(names)) => names.as_slice(),
+            _ => &[],
+        };
+        if let Some((limit, overflow)) = self.max_params {
+            if pairs.len() > limit {
+                match overflow {
+                    ParamOverflow::PassThrough => return Ok(pairs),
+                    ParamOverflow::Truncate if observer.flag() => return Ok(pairs),
+                    ParamOverflow::Truncate => pairs.truncate(limit),
+                }
             }
         }
-
-        Ok(Cow::Owned(result.into()))
+        let strip_referral_marketing = self.strip_referral_marketing || matches!(policy, Some(Policy::CleanAggressive));
+        let providers = self.session_ids.iter().chain(&self.x_links).chain(&self.rules.providers);
+        for p in providers.filter(|p| p.match_url(url.as_str())) {
+            p.remove_params(&mut [&mut pairs], strip_referral_marketing, &url, observer, keep);
+        }
+        Ok(pairs)
     }
 
-    /// Clean a single URL.
+    /// Remove the tracking parameters from a raw query string, like `a=1&utm_source=x`, for a request to `host`.
     ///
-    /// The Cleaning may involve
-    /// - 1. removing tracking parameters
-    ///      and/or,
-    /// - 2. detecting redirections with the target url in a query parameters
+    /// This works like [`clean_query_pairs`][Self::clean_query_pairs], but the parameters that are kept are
+    /// returned exactly as they were written,
+    /// so that a request looks the same to the application if nothing is removed.
     ///
-    /// # Returns
-    /// a cleaned URL
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?;
+    /// assert_eq!(cleaner.clean_query("example.com", "q=a+b&fbclid=123&page=%32")?, "q=a+b&page=%32");
+    /// # Ok(())
+    /// # }
+    /// ```
     ///
     /// # Errors
-    /// If an error occurred. See the [`Error`] enum for possible reasons.
-    pub fn clear_single_url<'a>(&self, url: &'a Url) -> Result<Cow<'a, Url>, Error> {
-        if url.scheme().starts_with("data") {
-            return Ok(Cow::Borrowed(url));
+    /// If `host` isn't a valid host, or if the URL is too long and [`LengthOverflow::Reject`] is configured.
+    pub fn clean_query<'a>(&self, host: &str, query: &'a str) -> Result<Cow<'a, str>, CleanError> {
+        use alloc::string::String;
+        use alloc::vec::Vec;
+
+        fn parse(pair: &str) -> (Cow<'_, str>, Cow<'_, str>) {
+            url::form_urlencoded::parse(pair.as_bytes()).next().unwrap_or_default()
+        }
+
+        // the length of `https://{host}/?{query}`, which the rules are applied to
+        if self.is_too_long("https:///?".len() + host.len() + query.len())? {
+            return Ok(Cow::Borrowed(query));
+        }
+        let original = if self.audit.is_some() { alloc::format!("https://{host}/?{query}") } else { String::new() };
+        let clean = |observer: &Observer<'_>| {
+            let pairs: Vec<&str> = query.split('&').filter(|p| !p.is_empty()).collect();
+            let kept = self.remove_query_params(host, pairs.iter().map(|p| parse(p)).collect(), observer)?;
+            if kept.len() == pairs.len() {
+                return Ok(Cow::Borrowed(query));
+            }
+            // the kept pairs are in their original order, so they are found by going through the pairs once
+            let mut kept = kept.into_iter().peekable();
+            let pairs = pairs.into_iter().filter(|p| kept.next_if(|k| *k == parse(p)).is_some());
+            Ok(Cow::Owned(pairs.collect::<Vec<_>>().join("&")))
+        };
+        self.audited(&original, clean, |q| alloc::format!("https://{host}/?{q}").into())
+    }
+
+    fn is_skipped_scheme(&self, url: &str) -> bool {
+        url.split_once(':').is_some_and(|(scheme, _)| {
+            self.skip_schemes.iter().any(|s| s.eq_ignore_ascii_case(scheme))
+                || !self.clean_mailto && scheme.eq_ignore_ascii_case("mailto")
+        })
+    }
+
+    /// The handler registered with [`scheme_handler`][Self::scheme_handler] for the scheme of `url`.
+    fn scheme_handler_for(&self, url: &str) -> Option<&(dyn Cleaner + Send + Sync)> {
+        let (scheme, _) = url.split_once(':')?;
+        let handler = self.scheme_handlers.iter().find(|h| h.scheme.eq_ignore_ascii_case(scheme))?;
+        Some(&*handler.cleaner)
+    }
+
+    /// The observer for cleaning without recording the changes.
+    const fn observer(&self) -> Observer<'_> {
+        Observer { hook: self.hook.as_ref(), changes: None, dry_run: None }
+    }
+
+    /// Clean a URL with `clean`, recording the changes for the [`audit_sink`][Self::audit_sink] if there is one.
+    fn audited<T>(
+        &self,
+        original: &str,
+        clean: impl FnOnce(&Observer<'_>) -> Result<T, CleanError>,
+        as_str: impl for<'t> Fn(&'t T) -> Cow<'t, str>,
+    ) -> Result<T, CleanError> {
+        if self.audit.is_none() {
+            return clean(&self.observer());
+        }
+        let changes = core::cell::RefCell::new(alloc::vec::Vec::new());
+        let cleaned = clean(&Observer { hook: self.hook.as_ref(), changes: Some(&changes), dry_run: None })?;
+        self.record(original, &as_str(&cleaned), changes.into_inner());
+        Ok(cleaned)
+    }
+
+    /// Pass a modified URL to the [`audit_sink`][Self::audit_sink], if there is one.
+    pub(crate) fn record(&self, original: &str, cleaned: &str, changes: alloc::vec::Vec<Change>) {
+        if let Some(audit) = self.audit.as_ref().filter(|_| original != cleaned) {
+            audit.0.record(original, cleaned, &CleanReport { changes });
+        }
+    }
+
+    fn clear_url_at_depth<'a>(
+        &self,
+        mut url: Cow<'a, Url>,
+        depth: u8,
+        observer: &Observer<'_>,
+    ) -> Result<Cow<'a, Url>, CleanError> {
+        let policy = self.policies.get(&url);
+        let aggressive = match policy {
+            Some(Policy::Skip) => return Ok(url),
+            Some(Policy::CleanAggressive) => true,
+            _ => false,
+        };
+        let keep = match policy {
+            Some(Policy::AllowlistParams(names)) => names.as_slice(),
+            _ => &[],
+        };
+        if let Some((limit, overflow)) = self.max_params {
+            if limits::count_params(&url) > limit {
+                match overflow {
+                    ParamOverflow::PassThrough => return Ok(url),
+                    ParamOverflow::Truncate if observer.flag() => return Ok(url),
+                    ParamOverflow::Truncate => limits::truncate_params(url.to_mut(), limit),
+                }
+            }
+        }
+        url = self.unwrapping.unwrap_all(url, observer);
+        if observer.is_flagged() {
+            return Ok(url);
+        }
+        if self.is_trivially_clean(&url) {
+            return Ok(url);
+        }
+        // only the global rules apply to `mailto:` links
+        let mailto = url.scheme() == "mailto";
+        let providers = self.session_ids.iter().chain(&self.x_links).map(|p| (p, false));
+        for (p, strict) in providers.chain(self.rules.providers.iter().map(|p| (p, self.strict))) {
+            if p.only_removes_params() && url.query().is_none() && url.fragment().is_none() {
+                continue;
+            }
+            if (!mailto || p.name == GLOBAL_RULES) && p.match_url(url.as_str()) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(url_pattern = p.url_pattern.as_str(), "provider matched");
+                url = Cow::Owned(p.remove_fields_from_url(
+                    &url,
+                    self.strip_referral_marketing || aggressive,
+                    self.redirects_for(&p.name, aggressive),
+                    self.multi_url,
+                    observer,
+                    keep,
+                    strict,
+                )?);
+                if observer.is_flagged() {
+                    return Ok(url);
+                }
+            }
+        }
+        if (self.clean_nested || aggressive) && depth < MAX_NESTING_DEPTH {
+            if let Some(new) = self.clear_nested_urls(&url, depth + 1, observer)? {
+                url = Cow::Owned(new);
+            }
         }
-        let mut url = Cow::Borrowed(url);
-        for p in &self.rules.providers {
-            if p.match_url(url.as_str()) {
-                url = Cow::Owned(p.remove_fields_from_url(&url, self.strip_referral_marketing)?);
+        if let Some(normalization) = self.normalization.as_ref().filter(|_| depth == 0) {
+            let mut normalized = url.clone().into_owned();
+            normalization.apply(&mut normalized);
+            if normalized != *url && !observer.flag() {
+                url = Cow::Owned(normalized);
             }
         }
 
         Ok(url)
     }
 
+    /// How the redirections of a provider are handled, see [`follow_redirect_rules`][Self::follow_redirect_rules]
+    /// and [`decode_base64_redirections`][Self::decode_base64_redirections].
+    fn redirects_for(&self, provider: &str, aggressive: bool) -> rules::Redirects {
+        if !self.redirect_overrides.get(provider).copied().unwrap_or(self.follow_redirects) {
+            rules::Redirects::Ignore
+        } else if self.base64_overrides.get(provider).copied().unwrap_or(self.decode_base64 || aggressive) {
+            rules::Redirects::FollowBase64
+        } else {
+            rules::Redirects::Follow
+        }
+    }
+
+    /// Whether a URL should be returned unchanged because it's too long, see [`max_url_len`][Self::max_url_len].
+    const fn is_too_long(&self, len: usize) -> Result<bool, CleanError> {
+        match self.max_url_len {
+            Some((limit, overflow)) if len > limit => match overflow {
+                LengthOverflow::PassThrough => Ok(true),
+                LengthOverflow::Reject => Err(CleanError::UrlTooLong(len)),
+            },
+            _ => Ok(false),
+        }
+    }
+
+    /// A cheap check for URLs that can't be changed by any provider, without running the rules.
+    ///
+    /// Only raw rules and redirections apply to URLs without a query and fragment.
+    fn is_trivially_clean(&self, url: &Url) -> bool {
+        url.query().is_none()
+            && url.fragment().is_none()
+            && self.normalization.is_none()
+            && self.unwrapping.unwrap(url).is_none()
+            && !self
+                .session_ids
+                .iter()
+                .chain(&self.x_links)
+                .chain(&self.rules.providers)
+                .any(|p| (!p.raw_rules.is_empty() || !p.redirections.is_empty()) && p.match_url(url.as_str()))
+    }
+
+    /// Clean query and fragment parameter values that are URLs themselves.
+    /// Returns `None` if nothing was changed.
+    fn clear_nested_urls(&self, url: &Url, depth: u8, observer: &Observer<'_>) -> Result<Option<Url>, CleanError> {
+        use alloc::vec::Vec;
+
+        // the cleaned URL, or `None` if it's unchanged or not a URL
+        let clean = |v: &str| -> Result<Option<alloc::string::String>, CleanError> {
+            let Ok(nested) = Url::from_str(v) else { return Ok(None) };
+            Ok(match self.clear_url_at_depth(Cow::Borrowed(&nested), depth, observer)? {
+                Cow::Owned(cleaned) if cleaned != nested => Some(cleaned.into()),
+                _ => None,
+            })
+        };
+        let clean_value = |v: &str| -> Result<Option<alloc::string::String>, CleanError> {
+            let urls = match self.multi_url {
+                _ if !multi_url::is_url(v) => Vec::new(),
+                MultiUrl::Single => alloc::vec![(v, None)],
+                MultiUrl::First | MultiUrl::Each => multi_url::split(v),
+            };
+            Ok(match &urls[..] {
+                [] => None,
+                [(nested, _)] => clean(nested)?,
+                [(first, _), ..] if self.multi_url == MultiUrl::First => {
+                    Some(clean(first)?.unwrap_or_else(|| (*first).into()))
+                }
+                _ => {
+                    let mut joined = alloc::string::String::new();
+                    for (nested, separator) in &urls {
+                        joined.push_str(&clean(nested)?.unwrap_or_else(|| (*nested).into()));
+                        joined.extend(*separator);
+                    }
+                    Some(joined).filter(|joined| joined != v)
+                }
+            })
+        };
+        let query = url.query().map(|q| replace_values(q, clean_value)).transpose()?.flatten();
+        let fragment = url.fragment().map(|f| replace_values(f, clean_value)).transpose()?.flatten();
+        if query.is_none() && fragment.is_none() {
+            return Ok(None);
+        }
+        let mut url = url.clone();
+        if let Some(query) = query {
+            url.set_query(Some(&query));
+        }
+        if let Some(fragment) = fragment {
+            url.set_fragment(Some(&fragment));
+        }
+        Ok(Some(url))
+    }
+
     /// Clean all URLs in a text.
     ///
     /// This may involve
@@ -218,10 +1864,11 @@ impl UrlCleaner {
     /// Text outside of URLs is left unchanged.
     ///
     /// # Errors
-    /// Alls errors encountered are returned in a [`Vec`][alloc::vec::Vec].
+    /// Alls errors encountered are returned in a [`Vec`][alloc::vec::Vec],
+    /// together with the URL that caused them.
     #[cfg(feature = "linkify")]
-    pub fn clear_text<'a>(&self, s: &'a str) -> Result<Cow<'a, str>, alloc::vec::Vec<Error>> {
-        self.clear_text_with_linkfinder(s, &linkify::LinkFinder::new())
+    pub fn clear_text<'a>(&self, s: &'a str) -> Result<Cow<'a, str>, alloc::vec::Vec<LinkError>> {
+        self.clear_text_with_linkfinder(s, &self.link_finder)
     }
 
 
@@ -237,54 +1884,225 @@ impl UrlCleaner {
     /// Text outside of URLs is left unchanged.
     ///
     /// # Errors
-    /// Alls errors encountered are returned in a [`Vec`][alloc::vec::Vec].
+    /// Alls errors encountered are returned in a [`Vec`][alloc::vec::Vec],
+    /// together with the URL that caused them.
     #[cfg(feature = "linkify")]
     pub fn clear_text_with_linkfinder<'a>(
         &self,
         s: &'a str,
         finder: &linkify::LinkFinder,
-    ) -> Result<Cow<'a, str>, alloc::vec::Vec<Error>> {
-        use alloc::vec::Vec;
-        use alloc::string::String;
+    ) -> Result<Cow<'a, str>, alloc::vec::Vec<LinkError>> {
+        let (text, errors) = self.clear_text_partial_with_linkfinder(s, finder);
+        if errors.is_empty() {
+            Ok(text)
+        } else {
+            Err(errors)
+        }
+    }
 
-        let mut spans = Vec::new();
-        let mut errors = Vec::new();
+    /// Clean all URLs in a text, on a best-effort basis.
+    ///
+    /// Unlike [`Self::clear_text`], an error doesn't discard the other replacements.
+    ///
+    /// # Returns
+    /// The string with all URLs inside cleaned, except the ones that caused an error,
+    /// which are left unchanged. Text outside of URLs is left unchanged.
+    /// Additionally, all errors encountered are returned together with the URL that caused them.
+    #[cfg(feature = "linkify")]
+    #[must_use]
+    pub fn clear_text_partial<'a>(&self, s: &'a str) -> (Cow<'a, str>, alloc::vec::Vec<LinkError>) {
+        self.clear_text_partial_with_linkfinder(s, &self.link_finder)
+    }
 
-        for res in finder.spans(s) {
-            match res.kind() {
-                Some(linkify::LinkKind::Url) => match self.clear_single_url_str(res.as_str()) {
-                    Ok(cow) => spans.push(cow),
-                    Err(e) => errors.push(e),
-                },
-                _ => spans.push(Cow::Borrowed(res.as_str())),
-            }
+    /// Clean all URLs in a text, on a best-effort basis.
+    ///
+    /// Unlike [`Self::clear_text_with_linkfinder`], an error doesn't discard the other replacements.
+    ///
+    /// # Returns
+    /// The string with all URLs inside cleaned, except the ones that caused an error,
+    /// which are left unchanged. Text outside of URLs is left unchanged.
+    /// Additionally, all errors encountered are returned together with the URL that caused them.
+    #[cfg(feature = "linkify")]
+    #[must_use]
+    pub fn clear_text_partial_with_linkfinder<'a>(
+        &self,
+        s: &'a str,
+        finder: &linkify::LinkFinder,
+    ) -> (Cow<'a, str>, alloc::vec::Vec<LinkError>) {
+        use alloc::string::String;
+
+        let mut result = String::new();
+        let mut last = 0;
+        let errors = self.clear_text_with_linkfinder_callback(s, finder, |range, new| {
+            result.push_str(&s[last..range.start]);
+            result.push_str(new);
+            last = range.end;
+        });
+        if last == 0 {
+            return (Cow::Borrowed(s), errors);
         }
+        result.push_str(&s[last..]);
+        (Cow::Owned(result), errors)
+    }
 
+    /// Clean all URLs in a text by reporting the replacements instead of building a new string.
+    ///
+    /// `replace` is called in order with the byte range of every URL that changes and its cleaned form.
+    /// This lets editors apply the edits to their own buffers, like ropes or CRDTs.
+    /// URLs that cause an error are left unchanged, and cleaning continues with the next.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), Vec<clearurls::LinkError>> {
+    /// let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    /// let text = "see https://example.com/?utm_source=x and https://example.com/";
+    /// let mut edits = Vec::new();
+    /// cleaner.clear_text_with(text, |range, new| edits.push((range, new.to_string())))?;
+    /// assert_eq!(edits, [(4..37, "https://example.com/".to_string())]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Alls errors encountered are returned in a [`Vec`][alloc::vec::Vec],
+    /// together with the URL that caused them.
+    #[cfg(feature = "linkify")]
+    pub fn clear_text_with<F>(&self, s: &str, replace: F) -> Result<(), alloc::vec::Vec<LinkError>>
+    where
+        F: FnMut(core::ops::Range<usize>, &str),
+    {
+        let errors = self.clear_text_with_linkfinder_callback(s, &self.link_finder, replace);
         if errors.is_empty() {
-            if spans.iter().all(|s| matches!(s, Cow::Borrowed(_))) {
-                Ok(Cow::Borrowed(s))
-            } else {
-                Ok(Cow::Owned(spans.into_iter().collect::<String>()))
-            }
+            Ok(())
         } else {
             Err(errors)
         }
     }
 
+    #[cfg(feature = "linkify")]
+    fn clear_text_with_linkfinder_callback<F>(
+        &self,
+        s: &str,
+        finder: &linkify::LinkFinder,
+        mut replace: F,
+    ) -> alloc::vec::Vec<LinkError>
+    where
+        F: FnMut(core::ops::Range<usize>, &str),
+    {
+        let mut errors = alloc::vec::Vec::new();
+        // only the links found in the text may end with punctuation that belongs to it, not hyperlink targets
+        let mut clean = |range: core::ops::Range<usize>, linkified: bool| {
+            let url = &s[range.clone()];
+            match self.clear_document_url(url) {
+                Ok(cleaned) if cleaned != url => replace(range, &cleaned),
+                Ok(_) => {}
+                Err(CleanError::UrlSyntax(_)) if self.repair_urls => {
+                    let end =
+                        if linkified { repair::trimmed_len(url, self.trailing_punctuation.extra()) } else { url.len() };
+                    let repaired = repair::encode_spaces(&url[..end]);
+                    match self.clear_document_url(&repaired) {
+                        Ok(cleaned) if cleaned != url[..end] => replace(range.start..range.start + end, &cleaned),
+                        _ => {}
+                    }
+                }
+                Err(e) => errors.push(LinkError::new(url, e)),
+            }
+        };
+        let segments = if self.ansi_escapes {
+            ansi::segments(s)
+        } else {
+            alloc::vec![ansi::Segment::Text(0..s.len())]
+        };
+        for segment in segments {
+            match segment {
+                ansi::Segment::Text(text) => {
+                    for link in self.document_links(&s[text.clone()], finder) {
+                        clean(text.start + link.start..text.start + link.end, true);
+                    }
+                }
+                ansi::Segment::Hyperlink(target) => clean(target, false),
+            }
+        }
+        errors
+    }
+
+    /// The byte ranges of the URLs in `text`, in order, including protocol-relative ones
+    /// if [`protocol_relative`][Self::protocol_relative] is set.
+    #[cfg(feature = "linkify")]
+    pub(crate) fn document_links(
+        &self,
+        text: &str,
+        finder: &linkify::LinkFinder,
+    ) -> alloc::vec::Vec<core::ops::Range<usize>> {
+        let mut links: alloc::vec::Vec<_> =
+            finder.links(text).filter(|l| *l.kind() == linkify::LinkKind::Url).map(|l| l.start()..l.end()).collect();
+        if self.protocol_relative {
+            // linkify needs a scheme, so look for a link without one right after a `//` which isn't part of a URL
+            let relative: alloc::vec::Vec<_> = text
+                .match_indices("//")
+                .filter(|(i, _)| !text[..*i].ends_with(|c: char| c == ':' || c == '/' || c.is_alphanumeric()))
+                .filter_map(|(i, _)| {
+                    let rest = &text[i + 2..];
+                    let rest = &rest[..rest.find(char::is_whitespace).unwrap_or(rest.len())];
+                    let link = self.schemeless_link_finder.links(rest).next().filter(|l| l.start() == 0)?;
+                    Some(i..i + 2 + link.end())
+                })
+                .filter(|r| !links.iter().any(|l| l.start < r.end && r.start < l.end))
+                .collect();
+            links.extend(relative);
+            links.sort_unstable_by_key(|r| r.start);
+        }
+        if let TrailingPunctuation::Trim(extra) = &self.trailing_punctuation {
+            for link in &mut links {
+                link.end = link.start + repair::trimmed_len(&text[link.clone()], extra);
+            }
+            links.retain(|link| !link.is_empty());
+        }
+        links
+    }
+
+    /// Clean a URL found in a document, which may be protocol-relative if
+    /// [`protocol_relative`][Self::protocol_relative] is set.
+    #[cfg(any(feature = "linkify", feature = "ammonia"))]
+    pub(crate) fn clear_document_url<'a>(&self, url: &'a str) -> Result<Cow<'a, str>, CleanError> {
+        let Some(rest) = url.strip_prefix("//").filter(|r| self.protocol_relative && !r.starts_with('/')) else {
+            return self.clear_single_url_str(url);
+        };
+        let absolute = alloc::format!("https://{rest}");
+        let cleaned = self.clear_single_url_str(&absolute)?;
+        Ok(match cleaned.strip_prefix("https:") {
+            Some(relative) if relative == url => Cow::Borrowed(url),
+            Some(relative) => Cow::Owned(relative.into()),
+            None => Cow::Owned(cleaned.into_owned()),
+        })
+    }
+
     /// Clean all URLs in a Markdown document. This affects all kinds of URLs, like
     /// - proper Markdown Links
     /// - auto links (links inside angle brackets)
     /// - links to images
     /// - bare links with no extra markup.
     ///
+    /// URLs in code spans and code blocks are left untouched.
+    /// See [`skip_code`][Self::skip_code] for `<code>` and `<pre>` HTML tags.
+    ///
     /// The document will be modified in-place.
     ///
+    /// # Returns
+    /// The list of URLs that were changed, each with its old and new form.
+    /// If it's empty, the document was not modified.
+    /// The text of an auto link or a bare link is only replaced if its URL changed.
+    ///
     /// # Errors
     /// The algorithm continues with the rest of the document if an error occurs.
-    /// The return value is `Ok(())` if there were no errors.
-    /// Otherwise, the list of errors is returned as the `Err` value.
+    /// The return value is `Ok` if there were no errors.
+    /// Otherwise, the list of errors, each with the URL that caused it, is returned as the `Err` value.
     #[cfg(feature = "markdown-it")]
-    pub fn clear_markdown(&self, doc: &mut markdown_it::Node) -> Result<(), alloc::vec::Vec<Error>> {
+    pub fn clear_markdown(
+        &self,
+        doc: &mut markdown_it::Node,
+    ) -> Result<alloc::vec::Vec<Replacement>, alloc::vec::Vec<LinkError>> {
         use markdown_it::parser::inline::Text;
         use markdown_it::plugins::cmark::inline::autolink::Autolink;
         use markdown_it::plugins::cmark::inline::image::Image;
@@ -292,122 +2110,267 @@ impl UrlCleaner {
         use markdown_it::plugins::extra::linkify::Linkified;
         use markdown_it::Node;
         use alloc::string::String;
+        use alloc::vec::Vec;
 
-        fn replace_url(cleaner: &UrlCleaner, url: &mut String) -> Result<(), Error> {
-            match cleaner.clear_single_url_str(url)? {
-                Cow::Borrowed(_) => {}
-                Cow::Owned(new_url) => {
-                    *url = new_url;
+        /// Replace `url` with its cleaned version and return the replacement, if it changed.
+        fn replace_url<'r>(
+            cleaner: &UrlCleaner,
+            url: &mut String,
+            replacements: &'r mut Vec<Replacement>,
+        ) -> Result<Option<&'r Replacement>, LinkError> {
+            match cleaner.clear_document_url(url) {
+                Ok(Cow::Owned(new_url)) if *url != new_url => {
+                    replacements.push(Replacement {
+                        old: core::mem::replace(url, new_url),
+                        new: url.clone(),
+                    });
+                    Ok(replacements.last())
                 }
+                Ok(_) => Ok(None),
+                Err(e) => Err(LinkError::new(url, e)),
             }
-            Ok(())
         }
 
-        fn callback(cleaner: &UrlCleaner, node: &mut Node) -> Result<(), Error> {
+        /// Show the cleaned URL as the text of an auto link if it changed. Otherwise, the text is left alone,
+        /// since it may be written differently than the URL, like with non-ASCII characters that are percent-encoded.
+        fn replace_text(node: &mut Node, replacement: Option<&Replacement>) {
+            if let Some(Replacement { new, .. }) = replacement {
+                node.children = alloc::vec![Node::new(Text { content: new.clone() })];
+            }
+        }
+
+        fn callback(
+            cleaner: &UrlCleaner,
+            node: &mut Node,
+            replacements: &mut Vec<Replacement>,
+        ) -> Result<(), LinkError> {
             if let Some(link) = node.cast_mut::<Autolink>() {
-                replace_url(cleaner, &mut link.url)?;
-                node.children = alloc::vec![Node::new(Text {
-                    content: link.url.clone()
-                })];
+                let replacement = replace_url(cleaner, &mut link.url, replacements)?;
+                replace_text(node, replacement);
             }
             if let Some(link) = node.cast_mut::<Linkified>() {
-                replace_url(cleaner, &mut link.url)?;
-                node.children = alloc::vec![Node::new(Text {
-                    content: link.url.clone()
-                })];
+                let replacement = replace_url(cleaner, &mut link.url, replacements)?;
+                replace_text(node, replacement);
             }
             if let Some(link) = node.cast_mut::<Link>() {
-                replace_url(cleaner, &mut link.url)?;
+                replace_url(cleaner, &mut link.url, replacements)?;
             }
             if let Some(link) = node.cast_mut::<Image>() {
-                replace_url(cleaner, &mut link.url)?;
+                replace_url(cleaner, &mut link.url, replacements)?;
             }
             Ok(())
         }
 
-        let mut result = alloc::vec![];
-        doc.walk_mut(|node, _| {
-            if let Err(e) = callback(self, node) {
-                result.push(e);
-            };
-        });
+        let mut replacements = alloc::vec![];
+        let result = self.walk_markdown(doc, |node| callback(self, node, &mut replacements));
 
         if result.is_empty() {
-            Ok(())
+            Ok(replacements)
         } else {
             Err(result)
         }
     }
-}
 
-/// Various errors that can happen while cleaning a URL
-#[derive(Debug)]
-#[non_exhaustive]
-pub enum Error {
-    /// An Error occurred while opening or reading a file
-    #[cfg(feature = "std")]
-    FileRead(std::io::Error),
-    /// The provided rules is invalid json or doesn't have the expected format
-    RuleSyntax(serde_json::Error),
-    /// A URL could not be parsed from the input.
-    UrlSyntax(ParseError),
-    /// The rules contained a redirection regex that doesn't specify the target
-    RedirectionHasNoCapturingGroup(Regex),
-    /// Bytes that are invalid UTF-8
-    PercentDecodeUtf8Error(Utf8Error),
-}
+    /// Clean all URLs in a Markdown string and render it to HTML.
+    ///
+    /// This is a convenience wrapper around [`Self::clear_markdown`] that sets up a parser
+    /// with the `CommonMark` syntax and bare link detection.
+    /// If you need other syntax extensions, use [`Self::clear_markdown`] with your own parser.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?;
+    /// let html = cleaner.clear_markdown_str("[link](https://example.com/?utm_source=abc)").unwrap();
+    /// assert_eq!(html, "<p><a href=\"https://example.com/\">link</a></p>\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// All errors encountered are returned, each with the URL that caused it.
+    #[cfg(feature = "markdown-it")]
+    pub fn clear_markdown_str(&self, markdown: &str) -> Result<alloc::string::String, alloc::vec::Vec<LinkError>> {
+        let mut doc = markdown_parser().parse(markdown);
+        self.clear_markdown(&mut doc)?;
+        Ok(doc.render())
+    }
 
-impl Display for Error {
-    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
-        match self {
-            #[cfg(feature = "std")]
-            Self::FileRead(x) => write!(f, "error reading rules: {x}"),
-            Self::RuleSyntax(x) => write!(f, "error parsing rules: {x}"),
-            Self::UrlSyntax(x) => write!(f, "error parsing url: {x}"),
-            Self::RedirectionHasNoCapturingGroup(x) => {
-                write!(f, "redirection regex {x} has no capture group")
+    /// Call `visit` with every node of `doc`, except those in code if [`skip_code`][Self::skip_code] is set,
+    /// and collect the errors.
+    #[cfg(feature = "markdown-it")]
+    pub(crate) fn walk_markdown<F>(&self, doc: &mut markdown_it::Node, mut visit: F) -> alloc::vec::Vec<LinkError>
+    where
+        F: FnMut(&mut markdown_it::Node) -> Result<(), LinkError>,
+    {
+        use markdown_it::plugins::html::html_inline::HtmlInline;
+
+        /// `Some(true)` for an opening `<code>` or `<pre>` tag, `Some(false)` for a closing one
+        fn code_tag(html: &str) -> Option<bool> {
+            let (open, name) = match html.strip_prefix("</") {
+                Some(rest) => (false, rest),
+                None => (true, html.strip_prefix('<')?),
+            };
+            let end = name.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(name.len());
+            let name = &name[..end];
+            (name.eq_ignore_ascii_case("code") || name.eq_ignore_ascii_case("pre")).then_some(open)
+        }
+
+        // the depth of the innermost open `<code>` or `<pre>` tag, and how many are open
+        let mut code: Option<(u32, usize)> = None;
+        let mut result = alloc::vec![];
+        doc.walk_mut(|node, depth| {
+            if self.skip_code {
+                // tags are siblings of the text between them, so the element is over when we leave their parent
+                if code.is_some_and(|(d, _)| depth < d) {
+                    code = None;
+                }
+                if let Some(html) = node.cast::<HtmlInline>() {
+                    match code_tag(&html.content) {
+                        Some(true) => code = Some((depth, code.map_or(1, |(_, n)| n + 1))),
+                        Some(false) => code = code.and_then(|(d, n)| (n > 1).then(|| (d, n - 1))),
+                        None => {}
+                    }
+                }
+                if code.is_some() {
+                    return;
+                }
             }
-            Self::PercentDecodeUtf8Error(x) => {
-                write!(f, "percent decoding resulted in non-UTF-8 bytes: {x}")
+            if let Err(e) = visit(node) {
+                result.push(e);
             }
+        });
+
+        result
+    }
+}
+
+/// A Markdown parser with the `CommonMark` syntax and bare link detection.
+#[cfg(feature = "markdown-it")]
+pub(crate) fn markdown_parser() -> markdown_it::MarkdownIt {
+    let mut parser = markdown_it::MarkdownIt::new();
+    markdown_it::plugins::cmark::add(&mut parser);
+    markdown_it::plugins::extra::linkify::add(&mut parser);
+    parser
+}
+
+/// The URL of a query on `host` that is cleaned with [`UrlCleaner::clean_query_pairs`], for the audit sink.
+fn query_url(host: &str, pairs: &[(Cow<'_, str>, Cow<'_, str>)]) -> alloc::string::String {
+    let query = url::form_urlencoded::Serializer::new(alloc::string::String::new()).extend_pairs(pairs).finish();
+    if query.is_empty() {
+        alloc::format!("https://{host}/")
+    } else {
+        alloc::format!("https://{host}/?{query}")
+    }
+}
+
+/// Replace the values of the `&`-separated parameters in `params`, like a query, for which `clean` returns a new value.
+///
+/// Only the changed values are encoded again, everything else is kept as it was written.
+/// Returns `None` if nothing was changed.
+fn replace_values(
+    params: &str,
+    mut clean: impl FnMut(&str) -> Result<Option<alloc::string::String>, CleanError>,
+) -> Result<Option<alloc::string::String>, CleanError> {
+    let mut result = alloc::string::String::new();
+    let mut last = 0;
+    let mut start = 0;
+    for pair in params.split('&') {
+        let value_start = start + pair.find('=').map_or(pair.len(), |i| i + 1);
+        let end = start + pair.len();
+        start = end + 1;
+        let Some((_, value)) = url::form_urlencoded::parse(pair.as_bytes()).next() else { continue };
+        if let Some(cleaned) = clean(&value)? {
+            result.push_str(&params[last..value_start]);
+            result.extend(url::form_urlencoded::byte_serialize(cleaned.as_bytes()));
+            last = end;
         }
     }
+    if last == 0 {
+        return Ok(None);
+    }
+    result.push_str(&params[last..]);
+    Ok(Some(result))
 }
 
-#[cfg(feature = "std")]
-impl From<std::io::Error> for Error {
-    fn from(value: std::io::Error) -> Self {
-        Self::FileRead(value)
+/// A URL in a document that was replaced by its cleaned version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Replacement {
+    /// The URL before cleaning
+    pub old: alloc::string::String,
+    /// The URL after cleaning
+    pub new: alloc::string::String,
+}
+
+/// An [`Error`] together with the URL that caused it.
+///
+/// This is returned by the functions that clean many URLs at once,
+/// like `UrlCleaner::clear_text` with the `linkify` feature.
+#[derive(Debug)]
+pub struct LinkError {
+    url: alloc::string::String,
+    error: Error,
+}
+
+impl LinkError {
+    fn new(url: &str, error: impl Into<Error>) -> Self {
+        Self {
+            url: url.into(),
+            error: error.into(),
+        }
+    }
+
+    /// The URL that could not be cleaned.
+    #[must_use]
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The underlying error.
+    #[must_use]
+    pub const fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// Discard the URL and return the underlying error.
+    #[must_use]
+    #[allow(clippy::missing_const_for_fn)] // false positive, destructors can't be const
+    pub fn into_error(self) -> Error {
+        self.error
     }
 }
 
-impl From<serde_json::Error> for Error {
-    fn from(value: serde_json::Error) -> Self {
-        Self::RuleSyntax(value)
+/// Serialized as a struct with the `url` and the `error`.
+#[cfg(feature = "serialize")]
+impl serde::Serialize for LinkError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct as _;
+
+        let mut s = serializer.serialize_struct("LinkError", 2)?;
+        s.serialize_field("url", &self.url)?;
+        s.serialize_field("error", &self.error)?;
+        s.end()
     }
 }
 
-impl From<ParseError> for Error {
-    fn from(value: ParseError) -> Self {
-        Self::UrlSyntax(value)
+impl Display for LinkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "error cleaning {}: {}", self.url, self.error)
     }
 }
 
-impl From<Utf8Error> for Error {
-    fn from(value: Utf8Error) -> Self {
-        Self::PercentDecodeUtf8Error(value)
+#[cfg(feature = "defmt")]
+impl defmt::Format for LinkError {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "error cleaning {=str}: {}", self.url.as_str(), self.error);
     }
 }
 
 #[cfg(feature = "std")]
-impl std::error::Error for Error {
+impl std::error::Error for LinkError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        match self {
-            Self::FileRead(e) => Some(e),
-            Self::RuleSyntax(e) => Some(e),
-            Self::UrlSyntax(e) => Some(e),
-            Self::RedirectionHasNoCapturingGroup(_) => None,
-            Self::PercentDecodeUtf8Error(e) => Some(e),
-        }
+        Some(&self.error)
     }
 }
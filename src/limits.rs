@@ -0,0 +1,49 @@
+use url::Url;
+
+/// What to do with a URL that has more query and fragment parameters than allowed,
+/// see [`UrlCleaner::max_params`][crate::UrlCleaner::max_params].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ParamOverflow {
+    /// Return the URL unchanged, without applying the rules.
+    PassThrough,
+    /// Drop the parameters beyond the limit and clean the rest.
+    ///
+    /// The parameters in the query count first, then those in the fragment.
+    Truncate,
+}
+
+/// What to do with a URL that is longer than allowed, see [`UrlCleaner::max_url_len`][crate::UrlCleaner::max_url_len].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum LengthOverflow {
+    /// Return the URL unchanged.
+    PassThrough,
+    /// Fail with [`CleanError::UrlTooLong`][crate::CleanError::UrlTooLong].
+    Reject,
+}
+
+/// The number of query and fragment parameters, counted the way they are split when cleaning.
+pub(crate) fn count_params(url: &Url) -> usize {
+    let count = |s: Option<&str>| s.map_or(0, |s| s.bytes().filter(|&b| b == b'&').count() + 1);
+    count(url.query()) + count(url.fragment())
+}
+
+/// Keep only the first `limit` query and fragment parameters.
+pub(crate) fn truncate_params(url: &mut Url, limit: usize) {
+    let mut left = limit;
+    let mut truncate = |s: &str| {
+        let cut = match left {
+            0 => return None,
+            n => s.match_indices('&').nth(n - 1).map_or(s.len(), |(i, _)| i),
+        };
+        left -= s[..cut].split('&').count();
+        Some(s[..cut].into())
+    };
+    let query: Option<alloc::string::String> = url.query().and_then(&mut truncate);
+    let fragment: Option<alloc::string::String> = url.fragment().and_then(&mut truncate);
+    url.set_query(query.as_deref());
+    url.set_fragment(fragment.as_deref());
+}
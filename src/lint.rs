@@ -0,0 +1,124 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::{CleanError, LinkError, UrlCleaner};
+
+/// A URL in a document that cleaning would change, see [`UrlCleaner::lint_text`].
+///
+/// Replacing the `span` of the document with the `suggestion` fixes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct Finding {
+    /// The byte range of the URL in the document
+    pub span: Range<usize>,
+    /// The URL as it appears in the document
+    pub url: String,
+    /// The cleaned URL
+    pub suggestion: String,
+}
+
+impl UrlCleaner {
+    /// Find the URLs in a text that cleaning would change, without changing anything.
+    ///
+    /// This is meant for documentation toolchains that report tracking links as warnings and offer to fix them.
+    /// A URL counts if [`is_clean`][Self::is_clean] says so, so URLs that would only be re-encoded are not reported.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?;
+    /// let (findings, errors) = cleaner.lint_text("see https://example.com/?utm_source=x and https://example.com/");
+    /// assert_eq!(findings[0].span, 4..37);
+    /// assert_eq!(findings[0].suggestion, "https://example.com/");
+    /// assert_eq!(findings.len(), 1);
+    /// assert!(errors.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Returns
+    /// The findings in the order in which they appear, and the errors, each with the URL that caused it.
+    #[must_use]
+    pub fn lint_text(&self, s: &str) -> (Vec<Finding>, Vec<LinkError>) {
+        let mut findings = Vec::new();
+        let mut errors = Vec::new();
+        for link in self.link_finder.links(s).filter(|l| *l.kind() == linkify::LinkKind::Url) {
+            match self.lint_url(link.as_str(), link.start()..link.end()) {
+                Ok(finding) => findings.extend(finding),
+                Err(e) => errors.push(e),
+            }
+        }
+        (findings, errors)
+    }
+
+    /// Find the URLs in a Markdown document that cleaning would change, without changing anything.
+    ///
+    /// Like [`clear_markdown_str`][Self::clear_markdown_str], this finds all kinds of links,
+    /// and respects [`skip_code`][Self::skip_code]. See [`lint_text`][Self::lint_text] for details.
+    ///
+    /// The span is that of the URL as it's written. If the destination of a link is written differently
+    /// than the URL it stands for, like with backslash escapes or entities, the span covers the whole link instead.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?;
+    /// let (findings, _) = cleaner.lint_markdown("[link](https://example.com/?utm_source=x)");
+    /// assert_eq!(findings[0].span, 7..40);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Returns
+    /// The findings in the order in which they appear, and the errors, each with the URL that caused it.
+    #[cfg(feature = "markdown-it")]
+    #[must_use]
+    pub fn lint_markdown(&self, markdown: &str) -> (Vec<Finding>, Vec<LinkError>) {
+        use markdown_it::plugins::cmark::inline::autolink::Autolink;
+        use markdown_it::plugins::cmark::inline::image::Image;
+        use markdown_it::plugins::cmark::inline::link::Link;
+        use markdown_it::plugins::extra::linkify::Linkified;
+
+        let mut doc = crate::markdown_parser().parse(markdown);
+        let mut findings = Vec::new();
+        let errors = self.walk_markdown(&mut doc, |node| {
+            // the destination of a link comes after its text, which may contain the URL as well
+            let (url, from_end) = if let Some(link) = node.cast::<Autolink>() {
+                (&link.url, false)
+            } else if let Some(link) = node.cast::<Linkified>() {
+                (&link.url, false)
+            } else if let Some(link) = node.cast::<Link>() {
+                (&link.url, true)
+            } else if let Some(link) = node.cast::<Image>() {
+                (&link.url, true)
+            } else {
+                return Ok(());
+            };
+            let Some((start, end)) = node.srcmap.map(|s| s.get_byte_offsets()) else {
+                return Ok(());
+            };
+            let source = &markdown[start..end];
+            let offset = if from_end { source.rfind(url.as_str()) } else { source.find(url.as_str()) };
+            let span = offset.map_or(start..end, |i| start + i..start + i + url.len());
+            findings.extend(self.lint_url(url, span)?);
+            Ok(())
+        });
+        (findings, errors)
+    }
+
+    /// The finding for a single URL, if cleaning changes it.
+    fn lint_url(&self, url: &str, span: Range<usize>) -> Result<Option<Finding>, LinkError> {
+        let lint = || {
+            if self.is_clean(url)? {
+                return Ok(None);
+            }
+            let suggestion = self.clean_str(url, &self.observer())?.into_owned();
+            Ok(Some(Finding { span, url: url.into(), suggestion }))
+        };
+        lint().map_err(|e: CleanError| LinkError::new(url, e))
+    }
+}
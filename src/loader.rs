@@ -0,0 +1,452 @@
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use core::ops::ControlFlow;
+
+use crate::matcher::{BuiltinEngine, CompileOptions, RegexEngine};
+#[cfg(feature = "std")]
+use crate::rules::RawRulesSeed;
+use crate::rules::RawRules;
+use crate::{LoadError, RulesVersion, UrlCleaner};
+
+/// Options for loading rules into a [`UrlCleaner`].
+///
+/// The `from_rules_*` constructors of [`UrlCleaner`] use the default options.
+///
+/// # Example
+/// ```
+/// # use clearurls::{BuiltinEngine, RulesLoader};
+/// # fn main() -> Result<(), clearurls::Error> {
+/// let cleaner = RulesLoader::new()
+///     .regex_engine(BuiltinEngine)
+///     .load_str(r#"{"providers":{"example":{"urlPattern":".*","rules":["foo"]}}}"#)?;
+/// let res = cleaner.clear_single_url_str("https://example.com/test?foo=abc")?;
+/// assert_eq!(res, "https://example.com/test");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct RulesLoader {
+    pub(crate) engine: Box<dyn RegexEngine>,
+    pub(crate) options: CompileOptions,
+    lenient: bool,
+    #[cfg(feature = "json5")]
+    json5: bool,
+}
+
+impl Default for RulesLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RulesLoader {
+    /// Create a loader with the default options.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            engine: Box::new(BuiltinEngine),
+            options: CompileOptions::default(),
+            lenient: false,
+            #[cfg(feature = "json5")]
+            json5: false,
+        }
+    }
+
+    /// Set the [`RegexEngine`] that compiles the patterns of the rules.
+    ///
+    /// The default is the [`BuiltinEngine`].
+    #[must_use]
+    pub fn regex_engine<E: RegexEngine + 'static>(mut self, engine: E) -> Self {
+        self.engine = Box::new(engine);
+        self
+    }
+
+    /// Configure whether the patterns of the rules match case-insensitively, like in the `ClearURLs` extension.
+    ///
+    /// Set this to `false` for rules whose parameters are case-sensitive. A single pattern can opt out
+    /// with the inline flag `(?-i)` instead, like `(?-i)ID`.
+    /// The default is `true`.
+    #[must_use]
+    pub const fn case_insensitive(mut self, value: bool) -> Self {
+        self.options.case_insensitive = value;
+        self
+    }
+
+    /// Limit the approximate size of each compiled pattern in bytes.
+    ///
+    /// Patterns that exceed it make loading fail with [`LoadError::RegexTooBig`].
+    /// This is useful to bound memory and compile time when loading untrusted rules.
+    /// The default is the limit of the regex engine.
+    #[must_use]
+    pub const fn size_limit(mut self, bytes: usize) -> Self {
+        self.options.size_limit = Some(bytes);
+        self
+    }
+
+    /// Limit the approximate size of the cache that each pattern may use while matching, in bytes.
+    ///
+    /// The default is the limit of the regex engine.
+    #[must_use]
+    pub const fn dfa_size_limit(mut self, bytes: usize) -> Self {
+        self.options.dfa_size_limit = Some(bytes);
+        self
+    }
+
+    /// Limit how deeply groups and repetitions may be nested in each pattern.
+    ///
+    /// Patterns that exceed it make loading fail with [`LoadError::RegexTooBig`].
+    /// The default is the limit of the regex engine.
+    #[must_use]
+    pub const fn nest_limit(mut self, depth: u32) -> Self {
+        self.options.nest_limit = Some(depth);
+        self
+    }
+
+    /// Configure whether patterns that fail to compile are skipped instead of failing the whole load.
+    ///
+    /// Each skipped pattern is reported as [`RulesWarning::InvalidPattern`][crate::RulesWarning::InvalidPattern].
+    /// If the `urlPattern` or an exception of a provider is skipped, the whole provider is skipped,
+    /// because it would otherwise apply to URLs that it shouldn't touch.
+    /// This keeps a deployment working when an update of the rules contains a pattern that the regex engine rejects.
+    /// JSON syntax errors still fail.
+    /// The default is `false`.
+    #[must_use]
+    pub const fn lenient(mut self, value: bool) -> Self {
+        self.lenient = value;
+        self
+    }
+
+    /// Configure whether the rules may be written in [JSON5](https://json5.org/),
+    /// which allows comments and trailing commas.
+    ///
+    /// This is meant for rule files that are maintained by hand, so that every rule can be annotated
+    /// with the reason why it exists. The embedded rules are always parsed as strict JSON.
+    /// The default is `false`.
+    #[cfg(feature = "json5")]
+    #[must_use]
+    pub const fn json5(mut self, value: bool) -> Self {
+        self.json5 = value;
+        self
+    }
+
+    /// Load rules from a path, which will be opened and read.
+    /// # Errors
+    /// See [`LoadError`]
+    #[cfg(feature = "std")]
+    pub fn load_path(&self, path: &std::path::Path) -> Result<UrlCleaner, LoadError> {
+        self.load_file(std::fs::File::open(path)?)
+    }
+
+    /// Load rules from every `*.json` file in a directory, like a `rules.d/` for local overrides.
+    ///
+    /// The files are read in the order of their names. A provider replaces one with the same name
+    /// from an earlier file. The [`RulesVersion`] is computed over the contents of all files in that order.
+    /// Subdirectories and files with other extensions are ignored.
+    /// # Errors
+    /// See [`LoadError`]
+    #[cfg(feature = "std")]
+    pub fn load_dir(&self, path: &std::path::Path) -> Result<UrlCleaner, LoadError> {
+        let mut paths = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<alloc::vec::Vec<_>, _>>()?;
+        paths.retain(|p| p.extension().is_some_and(|e| e == "json") && p.is_file());
+        paths.sort();
+
+        self.load_raw(|| {
+            let mut hasher = sha2::Sha256::default();
+            let mut rules = RawRules {
+                providers: alloc::vec::Vec::new(),
+            };
+            for path in paths {
+                let inner = std::io::BufReader::new(std::fs::File::open(path)?);
+                let mut reader = HashingReader { inner, hasher };
+                rules.merge(self.read_rules(&mut reader)?);
+                hasher = reader.hasher;
+            }
+            Ok((rules, RulesVersion::from_hasher(hasher)))
+        })
+    }
+
+    /// Parse the rules of one file of [`load_dir`][Self::load_dir], streaming them unless they are JSON5.
+    #[cfg(feature = "std")]
+    #[cfg_attr(not(feature = "json5"), allow(clippy::unused_self))]
+    fn read_rules<R: std::io::Read>(&self, mut reader: R) -> Result<RawRules<'static>, LoadError> {
+        #[cfg(feature = "json5")]
+        if self.json5 {
+            let mut rules = alloc::vec::Vec::new();
+            reader.read_to_end(&mut rules)?;
+            return Ok(self.parse(&rules)?.into_owned());
+        }
+        let mut deserializer = serde_json::Deserializer::from_reader(&mut reader);
+        let raw = serde::Deserialize::deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        // serde_json stops after the value, so make sure that trailing whitespace is hashed as well
+        std::io::copy(&mut reader, &mut std::io::sink())?;
+        Ok(raw)
+    }
+
+    /// Load rules from a [reader][std::io::Read], most often a [`File`][std::fs::File]
+    ///
+    /// With the `gzip` feature, gzip-compressed input is detected and decompressed.
+    /// The [`RulesVersion`] is always computed over the decompressed JSON.
+    /// # Errors
+    /// See [`LoadError`]
+    #[cfg(feature = "std")]
+    pub fn load_file<R: std::io::Read>(&self, reader: R) -> Result<UrlCleaner, LoadError> {
+        self.load_file_impl(reader, &mut |_| ControlFlow::Continue(()))
+    }
+
+    /// Load rules from a [reader][std::io::Read] like [`load_file`][Self::load_file], calling `progress`
+    /// after each provider was parsed, so that a GUI can show how far loading has come.
+    ///
+    /// Return [`ControlFlow::Break`] from `progress` to stop reading and fail with [`LoadError::Aborted`].
+    /// The patterns are compiled after all providers were parsed.
+    /// # Errors
+    /// See [`LoadError`]
+    #[cfg(feature = "std")]
+    pub fn load_file_with_progress<R: std::io::Read>(
+        &self,
+        reader: R,
+        mut progress: impl FnMut(&LoadProgress<'_>) -> ControlFlow<()>,
+    ) -> Result<UrlCleaner, LoadError> {
+        let bytes_read = core::cell::Cell::new(0);
+        let reader = CountingReader { inner: reader, count: &bytes_read };
+        let mut providers = 0;
+        self.load_file_impl(reader, &mut |provider| {
+            providers += 1;
+            progress(&LoadProgress { provider, providers, bytes_read: bytes_read.get() })
+        })
+    }
+
+    #[cfg(feature = "std")]
+    fn load_file_impl<R: std::io::Read>(
+        &self,
+        reader: R,
+        progress: &mut dyn FnMut(&str) -> ControlFlow<()>,
+    ) -> Result<UrlCleaner, LoadError> {
+        #[cfg(feature = "gzip")]
+        {
+            use std::io::BufRead as _;
+
+            let mut reader = std::io::BufReader::new(reader);
+            if reader.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+                return self.load_json_reader(flate2::bufread::MultiGzDecoder::new(reader), progress);
+            }
+            self.load_json_reader(reader, progress)
+        }
+        #[cfg(not(feature = "gzip"))]
+        self.load_json_reader(reader, progress)
+    }
+
+    #[cfg(feature = "std")]
+    fn load_json_reader<R: std::io::Read>(
+        &self,
+        reader: R,
+        progress: &mut dyn FnMut(&str) -> ControlFlow<()>,
+    ) -> Result<UrlCleaner, LoadError> {
+        #[cfg(feature = "json5")]
+        if self.json5 {
+            let mut reader = reader;
+            let mut rules = alloc::vec::Vec::new();
+            reader.read_to_end(&mut rules)?;
+            return self.load_raw(|| {
+                let raw = self.parse(&rules)?;
+                if raw.providers.iter().any(|(name, _)| progress(name).is_break()) {
+                    return Err(LoadError::Aborted);
+                }
+                Ok((raw, RulesVersion::of(&rules)))
+            });
+        }
+        self.load_raw(|| {
+            let mut reader = HashingReader {
+                inner: std::io::BufReader::new(reader),
+                hasher: sha2::Sha256::default(),
+            };
+            // strings can't be borrowed from a reader, so they are copied as they are read
+            let mut deserializer = serde_json::Deserializer::from_reader(&mut reader);
+            let mut aborted = false;
+            let seed = RawRulesSeed(|name: &str| {
+                let flow = progress(name);
+                aborted = flow.is_break();
+                flow
+            });
+            let raw = match serde::de::DeserializeSeed::deserialize(seed, &mut deserializer) {
+                Err(_) if aborted => return Err(LoadError::Aborted),
+                raw => raw?,
+            };
+            deserializer.end()?;
+            // serde_json stops after the value, so make sure that trailing whitespace is hashed as well
+            std::io::copy(&mut reader, &mut std::io::sink())?;
+            Ok((raw, RulesVersion::from_hasher(reader.hasher)))
+        })
+    }
+
+    /// Load rules from a string.
+    /// # Errors
+    /// See [`LoadError`]
+    pub fn load_str(&self, rules: &str) -> Result<UrlCleaner, LoadError> {
+        self.load_slice(rules.as_bytes())
+    }
+
+    /// Load rules from bytes, like a buffer that was read or memory-mapped.
+    ///
+    /// Patterns without escape sequences are borrowed from `rules` until they are compiled, so this
+    /// needs less memory than `load_file` with the `std` feature if the rules are already in memory.
+    /// # Errors
+    /// See [`LoadError`]
+    pub fn load_slice(&self, rules: &[u8]) -> Result<UrlCleaner, LoadError> {
+        self.load_raw(|| Ok((self.parse(rules)?, RulesVersion::of(rules))))
+    }
+
+    /// Load rules from a string after verifying their ed25519 `signature` with `public_key`.
+    ///
+    /// Use this for rules that were downloaded, so that a compromised mirror can't feed you malicious patterns.
+    /// The signature is the 64 raw bytes over the exact bytes of `rules`.
+    /// The `ClearURLs` project doesn't sign its rules, so the key must be that of whoever signed them.
+    ///
+    /// # Errors
+    /// [`LoadError::InvalidSignature`] if the key or signature is malformed or the signature doesn't match.
+    /// Otherwise, see [`LoadError`].
+    #[cfg(feature = "signature")]
+    pub fn load_signed_str(
+        &self,
+        rules: &str,
+        signature: &[u8],
+        public_key: &[u8; 32],
+    ) -> Result<UrlCleaner, LoadError> {
+        verify_signature(rules.as_bytes(), signature, public_key)?;
+        self.load_str(rules)
+    }
+
+    /// Load rules from a [reader][std::io::Read] after verifying their signature,
+    /// see [`load_signed_str`][Self::load_signed_str].
+    ///
+    /// The whole input is read before anything is parsed.
+    ///
+    /// # Errors
+    /// [`LoadError::InvalidSignature`] if the key or signature is malformed or the signature doesn't match.
+    /// Otherwise, see [`LoadError`].
+    #[cfg(all(feature = "signature", feature = "std"))]
+    pub fn load_signed_file<R: std::io::Read>(
+        &self,
+        mut reader: R,
+        signature: &[u8],
+        public_key: &[u8; 32],
+    ) -> Result<UrlCleaner, LoadError> {
+        let mut rules = alloc::vec::Vec::new();
+        reader.read_to_end(&mut rules)?;
+        verify_signature(&rules, signature, public_key)?;
+        self.load_raw(|| Ok((self.parse(&rules)?, RulesVersion::of(&rules))))
+    }
+
+    /// Load the rules embedded in this library.
+    /// # Errors
+    /// See [`LoadError`]
+    #[cfg(not(feature = "compress-rules"))]
+    pub fn load_embedded(&self) -> Result<UrlCleaner, LoadError> {
+        let rules = include_str!(concat!(env!("OUT_DIR"), "/data.minify.json"));
+        self.load_raw(|| Ok((serde_json::from_str(rules)?, RulesVersion::EMBEDDED)))
+    }
+
+    /// Load the rules embedded in this library.
+    /// # Errors
+    /// See [`LoadError`]
+    #[cfg(feature = "compress-rules")]
+    pub fn load_embedded(&self) -> Result<UrlCleaner, LoadError> {
+        use serde::de::Error as _;
+
+        let compressed = include_bytes!(concat!(env!("OUT_DIR"), "/data.minify.json.deflate"));
+        let rules = miniz_oxide::inflate::decompress_to_vec(compressed)
+            .map_err(|e| LoadError::RuleSyntax(serde_json::Error::custom(e)))?;
+        self.load_raw(|| Ok((serde_json::from_slice(&rules)?, RulesVersion::EMBEDDED)))
+    }
+
+    /// Parse rules that are not embedded, respecting [`json5`][Self::json5].
+    #[cfg_attr(not(feature = "json5"), allow(clippy::unused_self))]
+    pub(crate) fn parse<'a>(&self, rules: &'a [u8]) -> Result<RawRules<'a>, LoadError> {
+        #[cfg(feature = "json5")]
+        if self.json5 {
+            use serde::de::Error as _;
+
+            let rules = core::str::from_utf8(rules).map_err(serde_json::Error::custom)?;
+            return json5::from_str(rules).map_err(|e| LoadError::RuleSyntax(serde_json::Error::custom(e)));
+        }
+        Ok(serde_json::from_slice(rules)?)
+    }
+
+    /// Compile the rules returned by `parse`, measuring both steps for [`UrlCleaner::build_report`].
+    fn load_raw<'a>(
+        &self,
+        parse: impl FnOnce() -> Result<(RawRules<'a>, RulesVersion), LoadError>,
+    ) -> Result<UrlCleaner, LoadError> {
+        #[cfg(feature = "std")]
+        let start = std::time::Instant::now();
+        let (raw, version) = parse()?;
+        #[cfg(feature = "std")]
+        let parsed = start.elapsed();
+        #[cfg_attr(not(feature = "std"), allow(unused_mut))]
+        let mut rules = raw.compile(&*self.engine, &self.options, self.lenient)?;
+        #[cfg(feature = "std")]
+        {
+            rules.report.parse = parsed;
+        }
+        Ok(UrlCleaner::new(rules, version))
+    }
+}
+
+#[cfg(feature = "signature")]
+fn verify_signature(rules: &[u8], signature: &[u8], public_key: &[u8; 32]) -> Result<(), LoadError> {
+    let key = ed25519_dalek::VerifyingKey::from_bytes(public_key).map_err(|_| LoadError::InvalidSignature)?;
+    let signature = ed25519_dalek::Signature::from_slice(signature).map_err(|_| LoadError::InvalidSignature)?;
+    key.verify_strict(rules, &signature).map_err(|_| LoadError::InvalidSignature)
+}
+
+/// How far [`RulesLoader::load_file_with_progress`] has come.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct LoadProgress<'a> {
+    /// The name of the provider that was just parsed
+    pub provider: &'a str,
+    /// The number of providers parsed so far, including this one
+    pub providers: usize,
+    /// The number of bytes read from the reader so far.
+    ///
+    /// Compare it with the size of the file to show a percentage. Because of buffering, this is a bit ahead
+    /// of the parser. With gzip-compressed input, these are the compressed bytes.
+    pub bytes_read: u64,
+}
+
+/// Counts the bytes that are read, for [`LoadProgress::bytes_read`].
+#[cfg(feature = "std")]
+struct CountingReader<'c, R> {
+    inner: R,
+    count: &'c core::cell::Cell<u64>,
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> std::io::Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.set(self.count.get() + n as u64);
+        Ok(n)
+    }
+}
+
+/// Feeds everything that is read into a hasher, to compute the [`RulesVersion`] while parsing.
+#[cfg(feature = "std")]
+struct HashingReader<R> {
+    inner: R,
+    hasher: sha2::Sha256,
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> std::io::Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        use sha2::Digest as _;
+
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
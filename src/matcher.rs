@@ -0,0 +1,277 @@
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::ops::Range;
+
+#[cfg(feature = "ecmascript")]
+use crate::regex_engine::is_syntax_error;
+use crate::regex_engine::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+
+/// A compiled pattern from the rules.
+///
+/// Implement this together with [`RegexEngine`] to use a different regex engine than the built-in one.
+pub trait Matcher: Debug + Send + Sync {
+    /// The pattern this was compiled from.
+    fn as_str(&self) -> &str;
+
+    /// Find the leftmost match in `haystack`, starting the search at byte offset `start`.
+    fn find_at(&self, haystack: &str, start: usize) -> Option<Range<usize>>;
+
+    /// Find the leftmost match in `haystack` and return the range of its first capturing group.
+    ///
+    /// Returns `None` if there is no match, and `Some(None)` if there is a match,
+    /// but the first group does not exist or did not participate in it.
+    fn first_group(&self, haystack: &str) -> Option<Option<Range<usize>>>;
+
+    /// Whether there is a match anywhere in `haystack`.
+    fn is_match(&self, haystack: &str) -> bool {
+        self.find_at(haystack, 0).is_some()
+    }
+}
+
+/// Compiled patterns that are only ever matched together, like the exceptions of a provider.
+///
+/// Implement this together with [`RegexEngine::compile_set`] if your engine can match many patterns in one pass.
+pub trait MatcherSet: Debug + Send + Sync {
+    /// Whether any of the patterns matches anywhere in `haystack`.
+    fn is_match(&self, haystack: &str) -> bool;
+}
+
+/// Compiles the patterns from the rules into [`Matcher`]s.
+///
+/// The upstream rules are written for JavaScript regexes, which occasionally diverge from
+/// the built-in engine. Use [`RulesLoader::regex_engine`][crate::RulesLoader::regex_engine]
+/// to load rules with a different engine.
+pub trait RegexEngine: Debug + Send + Sync {
+    /// Compile a single pattern.
+    ///
+    /// Engines should respect the options as far as they support them.
+    ///
+    /// # Errors
+    /// If the pattern is invalid, not supported, or exceeds the limits in `options`.
+    fn compile(&self, pattern: &str, options: &CompileOptions) -> Result<Box<dyn Matcher>, CompileError>;
+
+    /// Compile patterns that are only ever matched together, like the exceptions of a provider.
+    ///
+    /// The default implementation compiles each pattern with [`compile`][Self::compile] and tries them in turn.
+    ///
+    /// # Errors
+    /// If any of the patterns can't be compiled, see [`compile`][Self::compile].
+    fn compile_set(&self, patterns: &[&str], options: &CompileOptions) -> Result<Box<dyn MatcherSet>, CompileError> {
+        compile_each(self, patterns, options)
+    }
+}
+
+/// Compile each pattern on its own, for engines without sets.
+fn compile_each<E: RegexEngine + ?Sized>(
+    engine: &E,
+    patterns: &[&str],
+    options: &CompileOptions,
+) -> Result<Box<dyn MatcherSet>, CompileError> {
+    let matchers = patterns.iter().map(|p| engine.compile(p, options)).collect::<Result<Vec<_>, _>>()?;
+    Ok(Box::new(matchers))
+}
+
+/// Options for [`RegexEngine::compile`], configured with a [`RulesLoader`][crate::RulesLoader].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CompileOptions {
+    /// Whether the pattern should match case-insensitively.
+    pub case_insensitive: bool,
+    /// The approximate maximum size of a compiled pattern in bytes
+    pub size_limit: Option<usize>,
+    /// The approximate maximum size of the cache for lazy DFAs in bytes
+    pub dfa_size_limit: Option<usize>,
+    /// The maximum nesting depth of a pattern
+    pub nest_limit: Option<u32>,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            case_insensitive: true,
+            size_limit: None,
+            dfa_size_limit: None,
+            nest_limit: None,
+        }
+    }
+}
+
+impl CompileOptions {
+    const fn has_limits(&self) -> bool {
+        self.size_limit.is_some() || self.dfa_size_limit.is_some() || self.nest_limit.is_some()
+    }
+}
+
+/// The reason why a [`RegexEngine`] couldn't compile a pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CompileError {
+    /// The pattern is invalid or not supported by the engine.
+    Invalid(String),
+    /// The pattern exceeds the limits from the [`CompileOptions`].
+    TooBig(String),
+}
+
+/// The default [`RegexEngine`], which uses the `regex` crate, or `regex-lite` if only that feature is enabled.
+///
+/// `regex-lite` doesn't support [`CompileOptions::dfa_size_limit`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BuiltinEngine;
+
+impl RegexEngine for BuiltinEngine {
+    fn compile(&self, pattern: &str, options: &CompileOptions) -> Result<Box<dyn Matcher>, CompileError> {
+        let mut builder = RegexBuilder::new(pattern);
+        builder.case_insensitive(options.case_insensitive);
+        if let Some(limit) = options.size_limit {
+            builder.size_limit(limit);
+        }
+        #[cfg(feature = "regex")]
+        if let Some(limit) = options.dfa_size_limit {
+            builder.dfa_size_limit(limit);
+        }
+        if let Some(limit) = options.nest_limit {
+            builder.nest_limit(limit);
+        }
+        let result = builder.build();
+        match result {
+            Ok(regex) => Ok(Box::new(regex)),
+            // if it works without the limits, it's the limits' fault
+            Err(e) if options.has_limits() && RegexBuilder::new(pattern).build().is_ok() => {
+                Err(CompileError::TooBig(e.to_string()))
+            }
+            // the rules are written for JavaScript, so try again with JavaScript semantics
+            #[cfg(feature = "ecmascript")]
+            Err(e) if is_syntax_error(&e) => {
+                EcmaScriptEngine.compile(pattern, options).map_err(|_| CompileError::Invalid(e.to_string()))
+            }
+            Err(e) => Err(CompileError::Invalid(e.to_string())),
+        }
+    }
+
+    fn compile_set(&self, patterns: &[&str], options: &CompileOptions) -> Result<Box<dyn MatcherSet>, CompileError> {
+        let mut builder = RegexSetBuilder::new(patterns);
+        builder.case_insensitive(options.case_insensitive);
+        if let Some(limit) = options.size_limit {
+            builder.size_limit(limit);
+        }
+        #[cfg(feature = "regex")]
+        if let Some(limit) = options.dfa_size_limit {
+            builder.dfa_size_limit(limit);
+        }
+        if let Some(limit) = options.nest_limit {
+            builder.nest_limit(limit);
+        }
+        // on failure, compile them one by one, so that the error names the right reason,
+        // and so that patterns with JavaScript syntax fall back to the ECMAScript engine
+        let result = builder.build();
+        result.map_or_else(|_| compile_each(self, patterns, options), |set| Ok(Box::new(set)))
+    }
+}
+
+/// A [`RegexEngine`] with ECMAScript semantics, which uses the `regress` crate.
+///
+/// With the `ecmascript` feature, [`BuiltinEngine`] falls back to this engine for patterns it rejects,
+/// like those with lookarounds or backreferences.
+/// It doesn't support any of the limits in [`CompileOptions`].
+#[cfg(feature = "ecmascript")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EcmaScriptEngine;
+
+#[cfg(feature = "ecmascript")]
+impl RegexEngine for EcmaScriptEngine {
+    fn compile(&self, pattern: &str, options: &CompileOptions) -> Result<Box<dyn Matcher>, CompileError> {
+        let flags = regress::Flags { icase: options.case_insensitive, unicode: true, ..regress::Flags::default() };
+        match regress::Regex::with_flags(pattern, flags) {
+            Ok(regex) => Ok(Box::new(EcmaScriptRegex { regex, pattern: pattern.into() })),
+            Err(e) => Err(CompileError::Invalid(e.to_string())),
+        }
+    }
+}
+
+/// A pattern compiled by [`EcmaScriptEngine`], which needs to keep the pattern for [`Matcher::as_str`].
+#[cfg(feature = "ecmascript")]
+#[derive(Debug)]
+struct EcmaScriptRegex {
+    regex: regress::Regex,
+    pattern: String,
+}
+
+#[cfg(feature = "ecmascript")]
+impl Matcher for EcmaScriptRegex {
+    fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    fn find_at(&self, haystack: &str, start: usize) -> Option<Range<usize>> {
+        self.regex.find_from(haystack, start).next().map(|m| m.range())
+    }
+
+    fn first_group(&self, haystack: &str) -> Option<Option<Range<usize>>> {
+        self.regex.find(haystack).map(|m| m.group(1))
+    }
+}
+
+impl Matcher for Regex {
+    fn as_str(&self) -> &str {
+        self.as_str()
+    }
+
+    fn find_at(&self, haystack: &str, start: usize) -> Option<Range<usize>> {
+        self.find_at(haystack, start).map(|m| m.range())
+    }
+
+    fn first_group(&self, haystack: &str) -> Option<Option<Range<usize>>> {
+        self.captures(haystack).map(|c| c.get(1).map(|m| m.range()))
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        self.is_match(haystack)
+    }
+}
+
+impl MatcherSet for RegexSet {
+    fn is_match(&self, haystack: &str) -> bool {
+        self.is_match(haystack)
+    }
+}
+
+impl MatcherSet for Vec<Box<dyn Matcher>> {
+    fn is_match(&self, haystack: &str) -> bool {
+        self.iter().any(|m| m.is_match(haystack))
+    }
+}
+
+/// Replace all matches of `matcher` in `haystack` with nothing.
+pub(crate) fn remove_all<'h>(matcher: &dyn Matcher, haystack: &'h str) -> Cow<'h, str> {
+    let mut result = String::new();
+    let mut last = 0;
+    let mut start = 0;
+    while let Some(m) = matcher.find_at(haystack, start) {
+        if m.is_empty() {
+            // nothing to remove, but skip a char to make progress
+            match haystack[m.end..].chars().next() {
+                Some(c) => start = m.end + c.len_utf8(),
+                None => break,
+            }
+            continue;
+        }
+        result.push_str(&haystack[last..m.start]);
+        last = m.end;
+        start = m.end;
+    }
+    if last == 0 {
+        return Cow::Borrowed(haystack);
+    }
+    result.push_str(&haystack[last..]);
+    Cow::Owned(result)
+}
+
+/// Whether `matcher`'s leftmost match spans all of `haystack`.
+pub(crate) fn is_full_match(matcher: &dyn Matcher, haystack: &str) -> bool {
+    matcher
+        .find_at(haystack, 0)
+        .is_some_and(|m| m.len() == haystack.len())
+}
@@ -0,0 +1,56 @@
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::header::HOST;
+use axum::http::uri::{PathAndQuery, Uri};
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::UrlCleaner;
+
+/// An [`axum`] middleware that removes tracking parameters from the URI of incoming requests.
+///
+/// This keeps them out of handlers, canonical URLs and access logs.
+/// Add it with [`from_fn_with_state`][axum::middleware::from_fn_with_state].
+/// The parameters are removed with [`UrlCleaner::clean_query`], for the host in the URI or the `Host` header.
+/// The path and the parameters that are kept stay exactly as they were, and requests without a query aren't touched.
+///
+/// # Example
+/// ```
+/// # use std::sync::Arc;
+/// # use axum::routing::get;
+/// # use axum::Router;
+/// # use clearurls::UrlCleaner;
+/// # fn main() -> Result<(), clearurls::Error> {
+/// let cleaner = Arc::new(UrlCleaner::from_embedded_rules()?);
+/// let app: Router = Router::new()
+///     .route("/", get(|uri: axum::http::Uri| async move { uri.to_string() }))
+///     .layer(axum::middleware::from_fn_with_state(cleaner, clearurls::strip_tracking_params));
+/// # Ok(())
+/// # }
+/// ```
+pub async fn strip_tracking_params(
+    State(cleaner): State<Arc<UrlCleaner>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if let Some(uri) = clean_uri(&cleaner, &request) {
+        *request.uri_mut() = uri;
+    }
+    next.run(request).await
+}
+
+/// The URI of the request without tracking parameters, if there are any.
+fn clean_uri(cleaner: &UrlCleaner, request: &Request) -> Option<Uri> {
+    let uri = request.uri();
+    let host = uri.host().or_else(|| request.headers().get(HOST)?.to_str().ok()).unwrap_or("localhost");
+    let Cow::Owned(query) = cleaner.clean_query(host, uri.query()?).ok()? else {
+        return None;
+    };
+    let path_and_query = if query.is_empty() { uri.path().into() } else { format!("{}?{query}", uri.path()) };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(PathAndQuery::try_from(path_and_query).ok()?);
+    Uri::from_parts(parts).ok()
+}
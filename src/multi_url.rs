@@ -0,0 +1,48 @@
+use alloc::vec::Vec;
+
+/// How a value that contains several URLs is handled, like `dest=https://a.example/,https://b.example/`,
+/// see [`UrlCleaner::multi_url`][crate::UrlCleaner::multi_url].
+///
+/// A value is split where a `,`, `|`, `;` or space is followed by `http://` or `https://`,
+/// so separators inside the URLs themselves are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum MultiUrl {
+    /// Treat the value as a single URL, even though that URL is garbled.
+    #[default]
+    Single,
+    /// Use only the first URL. A redirection goes there, and a nested URL is replaced by the first one, cleaned.
+    First,
+    /// Clean each nested URL and keep the separators between them. A redirection goes to the first URL.
+    Each,
+}
+
+/// The characters that separate URLs in a single value.
+const SEPARATORS: [char; 4] = [',', '|', ';', ' '];
+
+/// Whether `s` starts with `http://` or `https://`, case-insensitively.
+pub(crate) fn is_url(s: &str) -> bool {
+    ["http://", "https://"].iter().any(|scheme| s.get(..scheme.len()).is_some_and(|p| p.eq_ignore_ascii_case(scheme)))
+}
+
+/// Split a value into the URLs in it, each with the separator that follows it.
+///
+/// There is always at least one part, the whole value if it contains a single URL.
+pub(crate) fn split(value: &str) -> Vec<(&str, Option<char>)> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    // all separators are a single byte
+    for (i, separator) in value.match_indices(SEPARATORS) {
+        if is_url(&value[i + 1..]) {
+            parts.push((&value[start..i], separator.chars().next()));
+            start = i + 1;
+        }
+    }
+    parts.push((&value[start..], None));
+    parts
+}
+
+/// The first URL in a value.
+pub(crate) fn first(value: &str) -> &str {
+    value.match_indices(SEPARATORS).find(|(i, _)| is_url(&value[i + 1..])).map_or(value, |(i, _)| &value[..i])
+}
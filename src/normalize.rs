@@ -0,0 +1,140 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use url::Url;
+
+/// A normalization that is applied to URLs after cleaning, see [`UrlCleaner::normalize`][crate::UrlCleaner::normalize].
+///
+/// The [`url`] crate already lowercases the scheme and the host of `http`, `https` and other special URLs,
+/// removes default ports and resolves dot segments. This additionally lowercases the host of other URLs and
+/// can apply a trailing slash policy, sort the query parameters and remove the fragment,
+/// so that equivalent URLs become identical.
+///
+/// # Example
+/// ```
+/// # use clearurls::{Normalization, TrailingSlash, UrlCleaner};
+/// # fn main() -> Result<(), clearurls::Error> {
+/// let cleaner = UrlCleaner::from_embedded_rules()?
+///     .normalize(Normalization::new().trailing_slash(TrailingSlash::Remove).sort_query(true));
+/// let res = cleaner.clear_single_url_str("https://example.com/a/?b=2&a=1&utm_source=x")?;
+/// assert_eq!(res, "https://example.com/a?a=1&b=2");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Normalization {
+    trailing_slash: TrailingSlash,
+    sort_query: bool,
+    strip_fragment: bool,
+}
+
+/// What to do with a slash at the end of the path, see [`Normalization::trailing_slash`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum TrailingSlash {
+    /// Leave the path as it is.
+    #[default]
+    Keep,
+    /// Append a slash if there is none.
+    Add,
+    /// Remove a slash unless the path is just `/`.
+    Remove,
+}
+
+/// How the host of a cleaned URL is written, see [`UrlCleaner::host_form`][crate::UrlCleaner::host_form].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum HostForm {
+    /// Punycode, like `xn--bcher-kva.example`, which is how the [`url`] crate serializes hosts.
+    #[default]
+    Ascii,
+    /// Unicode, like `bücher.example`, for displaying URLs to people.
+    Unicode,
+}
+
+impl HostForm {
+    /// Serialize `url` with the host in this form.
+    pub(crate) fn serialize(self, url: Url) -> String {
+        use url::Position;
+
+        match (self, url.domain()) {
+            (Self::Unicode, Some(domain)) if domain.split('.').any(|label| label.starts_with("xn--")) => {
+                match idna::domain_to_unicode(domain) {
+                    (unicode, Ok(())) => [&url[..Position::BeforeHost], &unicode, &url[Position::AfterHost..]].concat(),
+                    (_, Err(_)) => url.into(),
+                }
+            }
+            _ => url.into(),
+        }
+    }
+}
+
+impl Normalization {
+    /// A normalization that only lowercases the host.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            trailing_slash: TrailingSlash::Keep,
+            sort_query: false,
+            strip_fragment: false,
+        }
+    }
+
+    /// Configure what to do with a slash at the end of the path.
+    /// The default is [`TrailingSlash::Keep`].
+    #[must_use]
+    pub const fn trailing_slash(mut self, policy: TrailingSlash) -> Self {
+        self.trailing_slash = policy;
+        self
+    }
+
+    /// Configure whether query parameters are sorted by name.
+    ///
+    /// The sort is stable, so repeated parameters keep their relative order.
+    /// The default is `false`.
+    #[must_use]
+    pub const fn sort_query(mut self, value: bool) -> Self {
+        self.sort_query = value;
+        self
+    }
+
+    /// Configure whether the fragment is removed, like `#section` or a text fragment like `#:~:text=...`.
+    ///
+    /// Fragments only matter to the page that is shown, so this is useful for URLs that are compared or stored.
+    /// The default is `false`.
+    #[must_use]
+    pub const fn strip_fragment(mut self, value: bool) -> Self {
+        self.strip_fragment = value;
+        self
+    }
+
+    pub(crate) fn apply(self, url: &mut Url) {
+        if let Some(host) = url.host_str().filter(|h| h.bytes().any(|b| b.is_ascii_uppercase())) {
+            let host = host.to_ascii_lowercase();
+            // can't fail, because only the case changes
+            let _ = url.set_host(Some(&host));
+        }
+        if !url.cannot_be_a_base() {
+            let path = url.path();
+            let new_path = match self.trailing_slash {
+                TrailingSlash::Add if !path.ends_with('/') => Some([path, "/"].concat()),
+                TrailingSlash::Remove if path.len() > 1 && path.ends_with('/') => Some(path[..path.len() - 1].into()),
+                _ => None,
+            };
+            if let Some(path) = new_path {
+                url.set_path(&path);
+            }
+        }
+        if self.sort_query {
+            if let Some(query) = url.query() {
+                let mut params: Vec<&str> = query.split('&').collect();
+                params.sort_by_key(|p| p.split_once('=').map_or(*p, |(name, _)| name));
+                let query = params.join("&");
+                url.set_query(Some(&query));
+            }
+        }
+        if self.strip_fragment {
+            url.set_fragment(None);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use url::Url;
+
+/// Per-domain policies that are consulted before the rules, see [`UrlCleaner::policies`][crate::UrlCleaner::policies].
+///
+/// A policy for a domain applies to its subdomains as well, and the policy of the most specific domain wins.
+/// URLs whose domain has no policy are cleaned as usual.
+///
+/// # Example
+/// ```
+/// # use clearurls::{Policy, PolicyMap, UrlCleaner};
+/// # fn main() -> Result<(), clearurls::Error> {
+/// let policies = PolicyMap::new()
+///     .insert("intranet.example", Policy::Skip)
+///     .insert("shop.example", Policy::AllowlistParams(vec!["ref".into()]));
+/// let cleaner = UrlCleaner::from_embedded_rules()?.policies(policies);
+/// let res = cleaner.clear_single_url_str("https://wiki.intranet.example/?utm_source=abc")?;
+/// assert_eq!(res, "https://wiki.intranet.example/?utm_source=abc");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PolicyMap {
+    entries: Vec<(String, Policy)>,
+}
+
+/// How URLs of a domain are cleaned, see [`PolicyMap`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Policy {
+    /// Leave the URLs untouched.
+    Skip,
+    /// Clean the URLs as configured on the [`UrlCleaner`][crate::UrlCleaner].
+    ///
+    /// This is useful to exempt a subdomain from the policy of its parent domain.
+    CleanDefault,
+    /// Additionally strip referral marketing parameters, decode base64-encoded redirections
+    /// and clean URLs in query parameters.
+    CleanAggressive,
+    /// Never remove query or fragment parameters with one of these names, even if a rule matches them.
+    AllowlistParams(Vec<String>),
+}
+
+impl PolicyMap {
+    /// Create an empty map.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { entries: Vec::new() }
+    }
+
+    /// Set the policy for `domain` and its subdomains, replacing a previous one for the same domain.
+    ///
+    /// Domains are compared case-insensitively. A leading dot is ignored.
+    #[must_use]
+    pub fn insert(mut self, domain: &str, policy: Policy) -> Self {
+        let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+        self.entries.retain(|(d, _)| *d != domain);
+        self.entries.push((domain, policy));
+        self
+    }
+
+    /// The policy for the host of `url`, if any.
+    pub(crate) fn get(&self, url: &Url) -> Option<&Policy> {
+        let host = url.host_str()?.to_ascii_lowercase();
+        self.entries
+            .iter()
+            .filter(|(domain, _)| {
+                host.strip_suffix(domain.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.ends_with('.'))
+            })
+            .max_by_key(|(domain, _)| domain.len())
+            .map(|(_, policy)| policy)
+    }
+}
@@ -0,0 +1,329 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::str::FromStr;
+use std::path::{Path, PathBuf};
+
+use serde::de::IntoDeserializer as _;
+use serde::Deserialize;
+
+use crate::{LengthOverflow, LoadError, Normalization, ParamOverflow, RulesLoader, UrlCleaner};
+
+type Setter = fn(UrlCleaner, bool) -> UrlCleaner;
+
+/// The configuration of a [`UrlCleaner`] for a deployment, read from a file,
+/// so that operators can change how URLs are cleaned without changing code.
+///
+/// The file is JSON, or TOML if its name ends in `.toml` and the `toml` feature is enabled.
+/// Every setting is optional and defaults to the default of the corresponding method of [`UrlCleaner`]:
+///
+/// ```toml
+/// # a rules file or a directory of them, relative to this file, instead of the embedded rules
+/// rules = "rules.d"
+/// # or rules to download when the cleaner is built, with the `network` feature
+/// # rules_url = "https://rules2.clearurls.xyz/data.minify.json"
+/// # skip rule patterns that fail to compile, see `RulesLoader::lenient`
+/// lenient_rules = true
+/// disabled_providers = ["amazon"]
+///
+/// strip_referral_marketing = true
+/// follow_redirects = true
+/// decode_base64_redirections = false
+/// clean_nested_urls = false
+/// clean_mailto = false
+/// strict = false
+/// # passed through in addition to `data`
+/// skip_schemes = ["myapp"]
+/// strip_fragment = false
+///
+/// # heuristics beyond the rules
+/// strip_session_ids = true
+/// clean_x_links = true
+/// unwrap_redirect_pages = false
+///
+/// [limits]
+/// max_params = 200
+/// param_overflow = "truncate" # or "pass_through"
+/// max_url_len = 8192
+/// length_overflow = "reject" # or "pass_through"
+/// ```
+///
+/// For container deployments, every setting can be overridden with an environment variable,
+/// see [`env_overrides`][Self::env_overrides].
+///
+/// # Example
+/// ```
+/// # use clearurls::PolicyFile;
+/// # fn main() -> Result<(), clearurls::Error> {
+/// let cleaner = PolicyFile::from_json(r#"{"disabled_providers": ["globalRules"], "strip_fragment": true}"#)?.build()?;
+/// let res = cleaner.clear_single_url_str("https://example.com/?utm_source=abc#top")?;
+/// assert_eq!(res, "https://example.com/?utm_source=abc");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicyFile {
+    rules: Option<PathBuf>,
+    rules_url: Option<String>,
+    lenient_rules: bool,
+    disabled_providers: Vec<String>,
+    strip_referral_marketing: Option<bool>,
+    follow_redirects: Option<bool>,
+    decode_base64_redirections: Option<bool>,
+    clean_nested_urls: Option<bool>,
+    clean_mailto: Option<bool>,
+    strict: Option<bool>,
+    skip_schemes: Option<Vec<String>>,
+    strip_fragment: bool,
+    strip_session_ids: Option<bool>,
+    clean_x_links: Option<bool>,
+    unwrap_redirect_pages: Option<bool>,
+    limits: Limits,
+}
+
+/// The `[limits]` table of a [`PolicyFile`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Limits {
+    max_params: Option<usize>,
+    param_overflow: Option<ParamOverflow>,
+    max_url_len: Option<usize>,
+    length_overflow: Option<LengthOverflow>,
+}
+
+impl PolicyFile {
+    /// Read a policy file from a path.
+    ///
+    /// A relative path to the rules is resolved against the directory of the policy file.
+    /// # Errors
+    /// [`LoadError::FileRead`] if the file can't be read, and [`LoadError::InvalidPolicy`] if it isn't a valid policy.
+    pub fn from_path(path: &Path) -> Result<Self, LoadError> {
+        let contents = std::fs::read_to_string(path)?;
+        let is_toml = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("toml"));
+        let mut policy = if is_toml { Self::from_toml(&contents)? } else { Self::from_json(&contents)? };
+        if let (Some(rules), Some(dir)) = (&policy.rules, path.parent()) {
+            policy.rules = Some(dir.join(rules));
+        }
+        Ok(policy)
+    }
+
+    /// Parse a policy from JSON.
+    /// # Errors
+    /// [`LoadError::InvalidPolicy`] if it isn't a valid policy.
+    pub fn from_json(policy: &str) -> Result<Self, LoadError> {
+        serde_json::from_str(policy).map_err(|e| LoadError::InvalidPolicy(e.to_string()))
+    }
+
+    /// Parse a policy from TOML.
+    /// # Errors
+    /// [`LoadError::InvalidPolicy`] if it isn't a valid policy.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(policy: &str) -> Result<Self, LoadError> {
+        toml::from_str(policy).map_err(|e| LoadError::InvalidPolicy(e.to_string()))
+    }
+
+    #[cfg(not(feature = "toml"))]
+    fn from_toml(_: &str) -> Result<Self, LoadError> {
+        Err(LoadError::InvalidPolicy("TOML policy files need the `toml` feature".into()))
+    }
+
+    /// Construct the cleaner for a service or a tool as configured by the policy file at `path`,
+    /// or by the one named by `CLEARURLS_POLICY` if there is none, and by the [overrides][Self::env_overrides]
+    /// from the environment.
+    ///
+    /// Without any settings, this is a cleaner with the embedded rules, like [`UrlCleaner::from_embedded_rules`].
+    /// # Errors
+    /// See [`from_path`][Self::from_path], [`from_env`][Self::from_env] and [`build`][Self::build].
+    pub fn load(path: Option<&Path>) -> Result<UrlCleaner, LoadError> {
+        let policy = match path {
+            Some(path) => Self::from_path(path)?.env_overrides()?,
+            None => Self::from_env()?,
+        };
+        policy.build()
+    }
+
+    /// Read the policy file named by the environment variable `CLEARURLS_POLICY`, or start from the defaults
+    /// if it isn't set, and apply the [overrides][Self::env_overrides] from the environment.
+    /// # Errors
+    /// See [`from_path`][Self::from_path] and [`env_overrides`][Self::env_overrides].
+    pub fn from_env() -> Result<Self, LoadError> {
+        let policy = match std::env::var_os("CLEARURLS_POLICY") {
+            Some(path) => Self::from_path(path.as_ref())?,
+            None => Self::default(),
+        };
+        policy.env_overrides()
+    }
+
+    /// Override the settings of this policy with environment variables, which take precedence over the file.
+    ///
+    /// | Variable | Setting |
+    /// |----------|---------|
+    /// | `CLEARURLS_RULES` | `rules`, relative to the working directory |
+    /// | `CLEARURLS_RULES_URL` | `rules_url` |
+    /// | `CLEARURLS_LENIENT_RULES` | `lenient_rules` |
+    /// | `CLEARURLS_DISABLED_PROVIDERS` | `disabled_providers`, separated by commas |
+    /// | `CLEARURLS_STRIP_REFERRAL` | `strip_referral_marketing` |
+    /// | `CLEARURLS_FOLLOW_REDIRECTS` | `follow_redirects` |
+    /// | `CLEARURLS_DECODE_BASE64_REDIRECTIONS` | `decode_base64_redirections` |
+    /// | `CLEARURLS_CLEAN_NESTED_URLS` | `clean_nested_urls` |
+    /// | `CLEARURLS_CLEAN_MAILTO` | `clean_mailto` |
+    /// | `CLEARURLS_STRICT` | `strict` |
+    /// | `CLEARURLS_SKIP_SCHEMES` | `skip_schemes`, separated by commas |
+    /// | `CLEARURLS_STRIP_FRAGMENT` | `strip_fragment` |
+    /// | `CLEARURLS_STRIP_SESSION_IDS` | `strip_session_ids` |
+    /// | `CLEARURLS_CLEAN_X_LINKS` | `clean_x_links` |
+    /// | `CLEARURLS_UNWRAP_REDIRECT_PAGES` | `unwrap_redirect_pages` |
+    /// | `CLEARURLS_MAX_PARAMS` | `max_params` in `[limits]` |
+    /// | `CLEARURLS_PARAM_OVERFLOW` | `param_overflow` in `[limits]` |
+    /// | `CLEARURLS_MAX_URL_LEN` | `max_url_len` in `[limits]` |
+    /// | `CLEARURLS_LENGTH_OVERFLOW` | `length_overflow` in `[limits]` |
+    ///
+    /// Switches accept `true`, `false`, `1`, `0`, `yes`, `no`, `on` and `off`. Variables that are set to the
+    /// empty string are ignored, except for the lists, which they clear.
+    /// # Errors
+    /// [`LoadError::InvalidPolicy`] if a variable has an invalid value.
+    pub fn env_overrides(self) -> Result<Self, LoadError> {
+        self.overrides(|name| std::env::var(name).ok())
+    }
+
+    /// [`env_overrides`][Self::env_overrides] with the variables looked up by `lookup`.
+    pub(crate) fn overrides(mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<Self, LoadError> {
+        fn invalid(name: &str, value: &str) -> LoadError {
+            LoadError::InvalidPolicy(alloc::format!("invalid value {value:?} for {name}"))
+        }
+        fn flag(name: &str, value: &str) -> Result<bool, LoadError> {
+            match value.to_ascii_lowercase().as_str() {
+                "true" | "1" | "yes" | "on" => Ok(true),
+                "false" | "0" | "no" | "off" => Ok(false),
+                _ => Err(invalid(name, value)),
+            }
+        }
+        fn list(value: &str) -> Vec<String> {
+            value.split(',').map(str::trim).filter(|s| !s.is_empty()).map(Into::into).collect()
+        }
+        fn number<T: FromStr>(name: &str, value: &str) -> Result<T, LoadError> {
+            value.parse().map_err(|_| invalid(name, value))
+        }
+        fn variant<'de, T: Deserialize<'de>>(name: &str, value: &'de str) -> Result<T, LoadError> {
+            T::deserialize(value.into_deserializer()).map_err(|_: serde::de::value::Error| invalid(name, value))
+        }
+
+        let var = |name: &str| {
+            let name = alloc::format!("CLEARURLS_{name}");
+            lookup(&name).map(|value| (name, value))
+        };
+        // the lists can be cleared, everything else must have a value
+        let set = |name: &str| var(name).filter(|(_, value)| !value.is_empty());
+        if let Some((_, path)) = set("RULES") {
+            self.rules = Some(path.into());
+        }
+        if let Some((_, url)) = set("RULES_URL") {
+            self.rules_url = Some(url);
+        }
+        if let Some((name, value)) = set("LENIENT_RULES") {
+            self.lenient_rules = flag(&name, &value)?;
+        }
+        if let Some((_, value)) = var("DISABLED_PROVIDERS") {
+            self.disabled_providers = list(&value);
+        }
+        let flags = [
+            ("STRIP_REFERRAL", &mut self.strip_referral_marketing),
+            ("FOLLOW_REDIRECTS", &mut self.follow_redirects),
+            ("DECODE_BASE64_REDIRECTIONS", &mut self.decode_base64_redirections),
+            ("CLEAN_NESTED_URLS", &mut self.clean_nested_urls),
+            ("CLEAN_MAILTO", &mut self.clean_mailto),
+            ("STRICT", &mut self.strict),
+            ("STRIP_SESSION_IDS", &mut self.strip_session_ids),
+            ("CLEAN_X_LINKS", &mut self.clean_x_links),
+            ("UNWRAP_REDIRECT_PAGES", &mut self.unwrap_redirect_pages),
+        ];
+        for (name, setting) in flags {
+            if let Some((name, value)) = set(name) {
+                *setting = Some(flag(&name, &value)?);
+            }
+        }
+        if let Some((_, value)) = var("SKIP_SCHEMES") {
+            self.skip_schemes = Some(list(&value));
+        }
+        if let Some((name, value)) = set("STRIP_FRAGMENT") {
+            self.strip_fragment = flag(&name, &value)?;
+        }
+        if let Some((name, value)) = set("MAX_PARAMS") {
+            self.limits.max_params = Some(number(&name, &value)?);
+        }
+        if let Some((name, value)) = set("PARAM_OVERFLOW") {
+            self.limits.param_overflow = Some(variant(&name, &value)?);
+        }
+        if let Some((name, value)) = set("MAX_URL_LEN") {
+            self.limits.max_url_len = Some(number(&name, &value)?);
+        }
+        if let Some((name, value)) = set("LENGTH_OVERFLOW") {
+            self.limits.length_overflow = Some(variant(&name, &value)?);
+        }
+        Ok(self)
+    }
+
+    /// Load the rules and construct a [`UrlCleaner`] configured by this policy.
+    /// # Errors
+    /// See [`LoadError`]
+    pub fn build(self) -> Result<UrlCleaner, LoadError> {
+        let loader = RulesLoader::new().lenient(self.lenient_rules);
+        let mut cleaner = match (&self.rules_url, &self.rules) {
+            (Some(url), _) => load_url(&loader, url)?,
+            (None, Some(path)) if path.is_dir() => loader.load_dir(path)?,
+            (None, Some(path)) => loader.load_path(path)?,
+            (None, None) => loader.load_embedded()?,
+        };
+        cleaner = cleaner.disable_providers(&self.disabled_providers);
+        let settings: [(Option<bool>, Setter); 9] = [
+            (self.strip_referral_marketing, UrlCleaner::strip_referral_marketing),
+            (self.follow_redirects, UrlCleaner::follow_redirect_rules),
+            (self.decode_base64_redirections, UrlCleaner::decode_base64_redirections),
+            (self.clean_nested_urls, UrlCleaner::clean_nested_urls),
+            (self.clean_mailto, UrlCleaner::clean_mailto),
+            (self.strict, UrlCleaner::strict),
+            (self.strip_session_ids, UrlCleaner::strip_session_ids),
+            (self.clean_x_links, UrlCleaner::clean_x_links),
+            (self.unwrap_redirect_pages, UrlCleaner::unwrap_redirect_pages),
+        ];
+        for (value, set) in settings {
+            if let Some(value) = value {
+                cleaner = set(cleaner, value);
+            }
+        }
+        if let Some(schemes) = self.skip_schemes {
+            cleaner = cleaner.skip_schemes(schemes);
+        }
+        if self.strip_fragment {
+            cleaner = cleaner.normalize(Normalization::new().strip_fragment(true));
+        }
+        if let Some(limit) = self.limits.max_params {
+            cleaner = cleaner.max_params(limit, self.limits.param_overflow.unwrap_or(ParamOverflow::PassThrough));
+        }
+        if let Some(limit) = self.limits.max_url_len {
+            cleaner = cleaner.max_url_len(limit, self.limits.length_overflow.unwrap_or(LengthOverflow::Reject));
+        }
+        Ok(cleaner)
+    }
+}
+
+/// Download rules and load them.
+#[cfg(feature = "network")]
+fn load_url(loader: &RulesLoader, url: &str) -> Result<UrlCleaner, LoadError> {
+    let response = ureq::get(url).call().map_err(|e| LoadError::Network(alloc::boxed::Box::new(e)))?;
+    loader.load_file(response.into_reader())
+}
+
+#[cfg(not(feature = "network"))]
+fn load_url(_: &RulesLoader, _: &str) -> Result<UrlCleaner, LoadError> {
+    Err(LoadError::InvalidPolicy("downloading rules needs the `network` feature".into()))
+}
+
+impl UrlCleaner {
+    /// Construct a [`UrlCleaner`] as configured by a policy file, see [`PolicyFile`].
+    /// # Errors
+    /// See [`LoadError`]
+    pub fn from_policy_file(path: &Path) -> Result<Self, LoadError> {
+        PolicyFile::from_path(path)?.build()
+    }
+}
@@ -0,0 +1,77 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{AuditSink, CleanReport};
+
+/// How many URLs each provider was applied to, to check the most frequent ones first,
+/// see [`UrlCleaner::provider_order`][crate::UrlCleaner::provider_order].
+///
+/// Collect it from real traffic by registering it as the [`audit_sink`][crate::UrlCleaner::audit_sink],
+/// which counts the providers that changed a URL, or count hits yourself with [`hit`][Self::hit].
+/// It can also be built from counts that were saved with [`hits`][Self::hits] earlier.
+///
+/// # Example
+/// ```
+/// # use std::sync::Arc;
+/// # use clearurls::{ProviderProfile, UrlCleaner};
+/// # fn main() -> Result<(), clearurls::Error> {
+/// let profile = Arc::new(ProviderProfile::new());
+/// let cleaner = UrlCleaner::from_embedded_rules()?.audit_sink(Arc::clone(&profile));
+/// cleaner.clear_single_url_str("https://example.com/?utm_source=abc")?;
+/// assert_eq!(profile.hits(), [("globalRules".into(), 1)]);
+///
+/// let cleaner = UrlCleaner::from_embedded_rules()?.provider_order(&profile);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct ProviderProfile {
+    hits: std::sync::Mutex<BTreeMap<String, u64>>,
+}
+
+impl ProviderProfile {
+    /// An empty profile.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a URL that `provider` was applied to.
+    pub fn hit(&self, provider: &str) {
+        *self.lock().entry(provider.into()).or_default() += 1;
+    }
+
+    /// The number of URLs that each provider was applied to, the most frequent first.
+    pub fn hits(&self) -> Vec<(String, u64)> {
+        let mut hits: Vec<_> = self.lock().iter().map(|(name, hits)| (name.clone(), *hits)).collect();
+        hits.sort_by_key(|(_, hits)| core::cmp::Reverse(*hits));
+        hits
+    }
+
+    /// The number of URLs that `provider` was applied to.
+    pub fn get(&self, provider: &str) -> u64 {
+        self.lock().get(provider).copied().unwrap_or(0)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, u64>> {
+        self.hits.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl FromIterator<(String, u64)> for ProviderProfile {
+    fn from_iter<I: IntoIterator<Item = (String, u64)>>(iter: I) -> Self {
+        Self { hits: std::sync::Mutex::new(iter.into_iter().collect()) }
+    }
+}
+
+impl AuditSink for ProviderProfile {
+    fn record(&self, _original: &str, _cleaned: &str, details: &CleanReport) {
+        let mut providers: Vec<_> = details.changes.iter().map(|c| c.provider.as_str()).collect();
+        providers.sort_unstable();
+        providers.dedup();
+        for provider in providers {
+            self.hit(provider);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use alloc::string::String;
+use core::str::FromStr;
+
+use url::Url;
+
+use crate::UrlCleaner;
+
+/// How much of a referrer is kept, see [`UrlCleaner::clean_referrer`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ReferrerPolicy {
+    /// Keep the whole URL without tracking parameters
+    #[default]
+    Clean,
+    /// Keep only the origin, like `https://example.com/`
+    Origin,
+    /// Drop the referrer
+    NoReferrer,
+}
+
+impl UrlCleaner {
+    /// Clean the value of a `Referer` header, or a referrer reported to analytics, before forwarding or storing it.
+    ///
+    /// Like browsers do, this always removes the fragment and the username and password, and only accepts `http`
+    /// and `https` URLs. With [`ReferrerPolicy::Clean`], the tracking parameters are removed, and if that fails,
+    /// only the origin is kept, so that nothing leaks because of an error. The origin is also kept if cleaning
+    /// would follow a redirection to another site, like `https://www.google.com/url?q=...`, because the referrer
+    /// is the redirecting site, not the target.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::{ReferrerPolicy, UrlCleaner};
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?;
+    /// let referrer = "https://user@example.com/post?id=1&utm_source=x#comments";
+    /// let res = cleaner.clean_referrer(referrer, ReferrerPolicy::Clean);
+    /// assert_eq!(res.as_deref(), Some("https://example.com/post?id=1"));
+    /// let res = cleaner.clean_referrer(referrer, ReferrerPolicy::Origin);
+    /// assert_eq!(res.as_deref(), Some("https://example.com/"));
+    /// assert_eq!(cleaner.clean_referrer("android-app://com.example", ReferrerPolicy::Clean), None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Returns
+    /// The referrer to send, or `None` if there shouldn't be one, because of the policy or because the referrer
+    /// isn't an absolute `http` or `https` URL.
+    #[must_use]
+    pub fn clean_referrer(&self, referrer: &str, policy: ReferrerPolicy) -> Option<String> {
+        let url = Url::from_str(referrer.trim()).ok().filter(|u| matches!(u.scheme(), "http" | "https"))?;
+        let origin = || Some(url.origin().ascii_serialization() + "/");
+        match policy {
+            ReferrerPolicy::NoReferrer => None,
+            ReferrerPolicy::Origin => origin(),
+            ReferrerPolicy::Clean => {
+                let Ok(cleaned) = self.clear_single_url(&url) else {
+                    return origin();
+                };
+                if cleaned.origin() != url.origin() {
+                    return origin();
+                }
+                let mut cleaned = cleaned.into_owned();
+                cleaned.set_fragment(None);
+                // URLs with a host can always have credentials
+                let _ = cleaned.set_username("");
+                let _ = cleaned.set_password(None);
+                Some(self.host_form.serialize(cleaned))
+            }
+        }
+    }
+}
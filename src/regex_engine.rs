@@ -0,0 +1,99 @@
+//! The regex engine used by [`BuiltinEngine`][crate::BuiltinEngine],
+//! selected with the `regex` and `regex-lite` features.
+//!
+//! If both are enabled, `regex` takes precedence.
+
+#[cfg(not(any(feature = "regex", feature = "regex-lite")))]
+compile_error!("either the `regex` or the `regex-lite` feature must be enabled");
+
+#[cfg(feature = "regex")]
+pub(crate) use regex::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+
+#[cfg(all(feature = "regex-lite", not(feature = "regex")))]
+pub(crate) use lite::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+
+#[cfg(all(feature = "regex-lite", not(feature = "regex")))]
+mod lite {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    pub(crate) use regex_lite::{Regex, RegexBuilder};
+
+    /// `regex-lite` has no sets, so this just tries each regex in turn.
+    #[derive(Debug, Clone, Default)]
+    pub(crate) struct RegexSet(Vec<Regex>);
+
+    impl RegexSet {
+        pub(crate) fn is_match(&self, haystack: &str) -> bool {
+            self.0.iter().any(|r| r.is_match(haystack))
+        }
+    }
+
+    pub(crate) struct RegexSetBuilder {
+        patterns: Vec<String>,
+        case_insensitive: bool,
+        size_limit: Option<usize>,
+        nest_limit: Option<u32>,
+    }
+
+    impl RegexSetBuilder {
+        pub(crate) fn new<I, S>(patterns: I) -> Self
+        where
+            I: IntoIterator<Item = S>,
+            S: AsRef<str>,
+        {
+            Self {
+                patterns: patterns.into_iter().map(|p| p.as_ref().into()).collect(),
+                case_insensitive: false,
+                size_limit: None,
+                nest_limit: None,
+            }
+        }
+
+        pub(crate) const fn case_insensitive(&mut self, yes: bool) -> &mut Self {
+            self.case_insensitive = yes;
+            self
+        }
+
+        pub(crate) const fn size_limit(&mut self, limit: usize) -> &mut Self {
+            self.size_limit = Some(limit);
+            self
+        }
+
+        pub(crate) const fn nest_limit(&mut self, limit: u32) -> &mut Self {
+            self.nest_limit = Some(limit);
+            self
+        }
+
+        pub(crate) fn build(&self) -> Result<RegexSet, regex_lite::Error> {
+            self.patterns
+                .iter()
+                .map(|p| {
+                    let mut builder = RegexBuilder::new(p);
+                    builder.case_insensitive(self.case_insensitive);
+                    if let Some(limit) = self.size_limit {
+                        builder.size_limit(limit);
+                    }
+                    if let Some(limit) = self.nest_limit {
+                        builder.nest_limit(limit);
+                    }
+                    builder.build()
+                })
+                .collect::<Result<_, _>>()
+                .map(RegexSet)
+        }
+    }
+}
+
+/// Whether compiling failed because of the syntax, rather than the size of the compiled regex.
+#[cfg(all(feature = "ecmascript", feature = "regex"))]
+pub(crate) const fn is_syntax_error(e: &regex::Error) -> bool {
+    matches!(e, regex::Error::Syntax(_))
+}
+
+/// Whether compiling failed because of the syntax, rather than the size of the compiled regex.
+#[cfg(all(feature = "ecmascript", feature = "regex-lite", not(feature = "regex")))]
+pub(crate) fn is_syntax_error(e: &regex_lite::Error) -> bool {
+    // the error is opaque, but the message tells
+    !alloc::string::ToString::to_string(e).contains("limit")
+}
@@ -0,0 +1,68 @@
+use alloc::borrow::Cow;
+use alloc::string::String;
+
+/// How punctuation at the end of links in text is handled,
+/// see [`UrlCleaner::trailing_punctuation`][crate::UrlCleaner::trailing_punctuation].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum TrailingPunctuation {
+    /// Take the links as the link finder reports them.
+    ///
+    /// The default finder already leaves out punctuation like `.`, `,` or `)` that belongs to the text,
+    /// but it keeps characters that may end a URL, like the `_` and `~` that mark emphasis in chat messages.
+    #[default]
+    Finder,
+    /// Leave out punctuation that ends a sentence, closing brackets without an opening one in the URL,
+    /// and any of these characters, like `"_~"`, at the end of every link.
+    Trim(String),
+}
+
+impl TrailingPunctuation {
+    /// The characters that are left out in addition to the punctuation that ends a sentence.
+    pub(crate) fn extra(&self) -> &str {
+        match self {
+            Self::Finder => "",
+            Self::Trim(extra) => extra,
+        }
+    }
+}
+
+/// Punctuation that ends a sentence or a clause, rather than a URL.
+const TRAILING_PUNCTUATION: [char; 8] = ['.', ',', ':', ';', '!', '?', '\'', '"'];
+
+/// The length of `url` without the punctuation at its end that belongs to the surrounding text,
+/// like the `).` in `(see https://example.com/?a=1).`, or any of the `extra` characters.
+///
+/// Closing brackets are only removed if they have no opening counterpart in the URL,
+/// so the parentheses in `https://en.wikipedia.org/wiki/Rust_(programming_language)` are kept.
+pub(crate) fn trimmed_len(url: &str, extra: &str) -> usize {
+    let mut end = url.len();
+    while let Some(c) = url[..end].chars().next_back() {
+        let opening = match c {
+            ')' => '(',
+            ']' => '[',
+            '}' => '{',
+            '>' => '<',
+            c if TRAILING_PUNCTUATION.contains(&c) || extra.contains(c) => {
+                end -= c.len_utf8();
+                continue;
+            }
+            _ => break,
+        };
+        let url = &url[..end];
+        if url.matches(opening).count() >= url.matches(c).count() {
+            break;
+        }
+        end -= c.len_utf8();
+    }
+    end
+}
+
+/// `url` with its spaces percent-encoded as `%20`.
+pub(crate) fn encode_spaces(url: &str) -> Cow<'_, str> {
+    if url.contains(' ') {
+        Cow::Owned(url.replace(' ', "%20"))
+    } else {
+        Cow::Borrowed(url)
+    }
+}
@@ -0,0 +1,44 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+/// Where the time went while loading the rules, see [`UrlCleaner::build_report`][crate::UrlCleaner::build_report].
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct BuildReport {
+    /// The time spent parsing the JSON, including reading it from a file, but not decompressing embedded rules
+    pub parse: Duration,
+    /// The time spent compiling the patterns
+    pub compile: Duration,
+    /// The number of distinct patterns that were compiled
+    pub patterns: usize,
+    /// The patterns that took the longest to compile, slowest first, at most [`BuildReport::SLOWEST`]
+    pub slowest: Vec<PatternCost>,
+}
+
+/// The time spent compiling a pattern, see [`BuildReport::slowest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PatternCost {
+    /// The name of the first provider with this pattern
+    pub provider: String,
+    /// The pattern as it appears in the rules
+    pub pattern: String,
+    /// The time spent compiling it
+    pub duration: Duration,
+}
+
+impl BuildReport {
+    /// How many of the slowest patterns are kept.
+    pub const SLOWEST: usize = 10;
+
+    pub(crate) fn record(&mut self, provider: &str, pattern: &str, duration: Duration) {
+        self.patterns += 1;
+        if self.slowest.len() == Self::SLOWEST && self.slowest.last().is_some_and(|p| p.duration >= duration) {
+            return;
+        }
+        let i = self.slowest.partition_point(|p| p.duration >= duration);
+        self.slowest.insert(i, PatternCost { provider: provider.into(), pattern: pattern.into(), duration });
+        self.slowest.truncate(Self::SLOWEST);
+    }
+}
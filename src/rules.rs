@@ -1,57 +1,562 @@
 use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::str::FromStr;
-use alloc::string::String;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine as _;
 use percent_encoding::percent_decode_str;
-use regex::{Regex, RegexSet};
+use core::fmt::{Display, Formatter};
+use serde::de::IgnoredAny;
 use serde::Deserialize;
 use url::{form_urlencoded, Url};
 
-use crate::deserialize_utils::{
-    deserialize_map_as_vec, deserialize_regex, deserialize_regex_set, deserialize_regex_vec,
-};
-use crate::Error;
+use crate::deserialize_utils::{deserialize_map_as_vec, deserialize_strs};
+use crate::hooks::{Action, Observer};
+use crate::inspect::{Change, Reason};
+use crate::matcher::{is_full_match, remove_all, CompileError, CompileOptions, Matcher, MatcherSet, RegexEngine};
+use crate::multi_url::{self, MultiUrl};
+use crate::{CleanError, LoadError};
 
+/// The rules as they appear in the JSON, before the patterns are compiled.
+///
+/// The strings are borrowed from the input where possible, so that they aren't copied before compiling.
 #[derive(Debug, Deserialize)]
-pub(crate) struct Rules {
-    #[serde(deserialize_with = "deserialize_map_as_vec")]
-    pub(crate) providers: Vec<Provider>,
+pub(crate) struct RawRules<'a> {
+    /// The providers with their names
+    #[serde(borrow, deserialize_with = "deserialize_map_as_vec")]
+    pub(crate) providers: Vec<(String, RawProvider<'a>)>,
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
+pub(crate) struct RawProvider<'a> {
+    #[serde(borrow)]
+    pub(crate) url_pattern: Cow<'a, str>,
+    #[serde(borrow, default, deserialize_with = "deserialize_strs")]
+    pub(crate) rules: Vec<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "deserialize_strs")]
+    pub(crate) raw_rules: Vec<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "deserialize_strs")]
+    pub(crate) referral_marketing: Vec<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "deserialize_strs")]
+    pub(crate) exceptions: Vec<Cow<'a, str>>,
+    #[serde(borrow, default, deserialize_with = "deserialize_strs")]
+    pub(crate) redirections: Vec<Cow<'a, str>>,
+    /// An extension of this crate, which the `ClearURLs` browser extension doesn't understand
+    #[serde(borrow, default, rename = "x-valueRules")]
+    pub(crate) value_rules: Vec<RawValueRule<'a>>,
+    /// Fields that this crate doesn't understand, which are reported as [`RulesWarning`]s
+    #[serde(flatten)]
+    #[allow(clippy::zero_sized_map_values)] // serde can only flatten the remaining fields into a map
+    pub(crate) unknown: BTreeMap<String, IgnoredAny>,
+}
+
+/// Removes a parameter only if its value matches as well.
+///
+/// This is an extension of this crate, for parameters like `ref`, which is
+/// a tracking token on some sites, but a git ref on others.
+#[derive(Deserialize, Debug)]
+pub(crate) struct RawValueRule<'a> {
+    #[serde(borrow)]
+    pub(crate) rule: Cow<'a, str>,
+    #[serde(borrow)]
+    pub(crate) value: Cow<'a, str>,
+}
+
+/// A compiled pattern, shared between all providers with the same pattern.
+pub(crate) type Pattern = Arc<dyn Matcher>;
+
+/// Compiles each distinct pattern only once, since many providers repeat patterns like `utm_source`.
+pub(crate) struct Compiler<'a> {
+    engine: &'a dyn RegexEngine,
+    options: &'a CompileOptions,
+    compiled: BTreeMap<String, Pattern>,
+    /// The provider whose patterns are being compiled
+    provider: String,
+    /// Whether invalid patterns are skipped with a warning
+    lenient: bool,
+    warnings: Vec<RulesWarning>,
+    #[cfg(feature = "std")]
+    report: crate::BuildReport,
+}
+
+impl<'a> Compiler<'a> {
+    pub(crate) fn new(engine: &'a dyn RegexEngine, options: &'a CompileOptions, lenient: bool) -> Self {
+        Self {
+            engine,
+            options,
+            compiled: BTreeMap::new(),
+            provider: String::new(),
+            lenient,
+            warnings: Vec::new(),
+            #[cfg(feature = "std")]
+            report: crate::BuildReport::default(),
+        }
+    }
+
+    fn compile(&mut self, pattern: &str) -> Result<Pattern, LoadError> {
+        if let Some(compiled) = self.compiled.get(pattern) {
+            return Ok(Arc::clone(compiled));
+        }
+        #[cfg(feature = "std")]
+        let start = std::time::Instant::now();
+        let compiled: Pattern = self
+            .engine
+            .compile(pattern, self.options)
+            .map_err(|e| self.error(pattern, e))?
+            .into();
+        #[cfg(feature = "std")]
+        self.report.record(&self.provider, pattern, start.elapsed());
+        self.compiled.insert(pattern.into(), Arc::clone(&compiled));
+        Ok(compiled)
+    }
+
+    /// Like [`compile`][Self::compile], but in lenient mode, an invalid pattern is skipped with a warning.
+    fn compile_or_skip(&mut self, pattern: &str) -> Result<Option<Pattern>, LoadError> {
+        match self.compile(pattern) {
+            Err(e) if self.lenient => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(provider = self.provider.as_str(), pattern, error = %e, "skipping invalid pattern");
+                self.warnings.push(RulesWarning::InvalidPattern {
+                    provider: self.provider.clone(),
+                    pattern: pattern.into(),
+                    error: e.to_string(),
+                });
+                Ok(None)
+            }
+            result => result.map(Some),
+        }
+    }
+
+    /// Compile all patterns, skipping the invalid ones in lenient mode.
+    fn compile_all(&mut self, patterns: &[Cow<'_, str>]) -> Result<Vec<Pattern>, LoadError> {
+        patterns.iter().filter_map(|p| self.compile_or_skip(p).transpose()).collect()
+    }
+
+    /// Compile the exceptions of a provider into one set, or return `None` if one of them is invalid in lenient mode.
+    fn compile_exceptions(&mut self, patterns: &[Cow<'_, str>]) -> Result<Option<Exceptions>, LoadError> {
+        if patterns.is_empty() {
+            return Ok(Some(Exceptions::default()));
+        }
+        let strs: Vec<&str> = patterns.iter().map(AsRef::as_ref).collect();
+        #[cfg(feature = "std")]
+        let start = std::time::Instant::now();
+        match self.engine.compile_set(&strs, self.options) {
+            Ok(set) => {
+                #[cfg(feature = "std")]
+                self.report.record(&self.provider, &strs.join("|"), start.elapsed());
+                Ok(Some(Exceptions { patterns: strs.into_iter().map(Into::into).collect(), set: Some(set) }))
+            }
+            Err(e) => {
+                // the set doesn't tell which pattern is at fault, so find it
+                for p in &strs {
+                    if self.compile_or_skip(p)?.is_none() {
+                        return Ok(None);
+                    }
+                }
+                Err(self.error(&strs.join("|"), e))
+            }
+        }
+    }
+
+    fn error(&self, pattern: &str, error: CompileError) -> LoadError {
+        let (provider, pattern) = (self.provider.clone(), pattern.into());
+        match error {
+            CompileError::Invalid(message) => LoadError::RegexSyntax { provider, pattern, message },
+            CompileError::TooBig(_) => LoadError::RegexTooBig { provider, pattern },
+        }
+    }
+}
+
+/// The exceptions of a provider, which are matched in one pass.
+#[derive(Debug, Default)]
+pub(crate) struct Exceptions {
+    /// The patterns as they appear in the rules
+    pub(crate) patterns: Vec<String>,
+    set: Option<Box<dyn MatcherSet>>,
+}
+
+impl Exceptions {
+    pub(crate) fn is_match(&self, url: &str) -> bool {
+        self.set.as_ref().is_some_and(|set| set.is_match(url))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ValueRule {
+    pub(crate) rule: Pattern,
+    pub(crate) value: Pattern,
+}
+
+#[derive(Debug)]
+pub(crate) struct Rules {
+    pub(crate) providers: Vec<Provider>,
+    pub(crate) warnings: Vec<RulesWarning>,
+    #[cfg(feature = "std")]
+    pub(crate) report: crate::BuildReport,
+}
+
+/// Something in the rules that was ignored, see [`UrlCleaner::warnings`][crate::UrlCleaner::warnings].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RulesWarning {
+    /// A provider has a field that this crate doesn't understand, like `completeProvider`.
+    UnknownField {
+        /// The name of the provider
+        provider: String,
+        /// The name of the field
+        field: String,
+    },
+    /// A pattern failed to compile and was skipped, see [`RulesLoader::lenient`][crate::RulesLoader::lenient].
+    ///
+    /// If it is the `urlPattern` or an exception, the whole provider was skipped.
+    InvalidPattern {
+        /// The name of the provider
+        provider: String,
+        /// The pattern as it appears in the rules
+        pattern: String,
+        /// Why it failed to compile
+        error: String,
+    },
+}
+
+impl Display for RulesWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::UnknownField { provider, field } => write!(f, "unknown field {field} in provider {provider}"),
+            Self::InvalidPattern { provider, pattern, error } => {
+                write!(f, "skipped invalid pattern {pattern} in provider {provider}: {error}")
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
 pub(crate) struct Provider {
-    #[serde(deserialize_with = "deserialize_regex")]
-    pub(crate) url_pattern: Regex,
-    #[serde(default, deserialize_with = "deserialize_regex_vec")]
-    pub(crate) rules: Vec<Regex>,
-    #[serde(default, deserialize_with = "deserialize_regex_vec")]
-    pub(crate) raw_rules: Vec<Regex>,
-    #[serde(default, deserialize_with = "deserialize_regex_vec")]
-    pub(crate) referral_marketing: Vec<Regex>,
-    #[serde(default, deserialize_with = "deserialize_regex_set")]
-    pub(crate) exceptions: RegexSet,
-    #[serde(default, deserialize_with = "deserialize_regex_vec")]
-    pub(crate) redirections: Vec<Regex>,
+    /// The key of the provider in the JSON
+    pub(crate) name: String,
+    pub(crate) url_pattern: Pattern,
+    pub(crate) rules: Vec<Pattern>,
+    pub(crate) raw_rules: Vec<Pattern>,
+    pub(crate) referral_marketing: Vec<Pattern>,
+    pub(crate) exceptions: Exceptions,
+    pub(crate) redirections: Vec<Pattern>,
+    pub(crate) value_rules: Vec<ValueRule>,
+}
+
+impl RawRules<'_> {
+    /// Copy the strings that are borrowed from the input, so that the input can be dropped.
+    #[cfg(all(feature = "std", feature = "json5"))]
+    pub(crate) fn into_owned(self) -> RawRules<'static> {
+        fn owned(s: Cow<'_, str>) -> Cow<'static, str> {
+            Cow::Owned(s.into_owned())
+        }
+        let providers = self.providers.into_iter().map(|(name, p)| {
+            let p = RawProvider {
+                url_pattern: owned(p.url_pattern),
+                rules: p.rules.into_iter().map(owned).collect(),
+                raw_rules: p.raw_rules.into_iter().map(owned).collect(),
+                referral_marketing: p.referral_marketing.into_iter().map(owned).collect(),
+                exceptions: p.exceptions.into_iter().map(owned).collect(),
+                redirections: p.redirections.into_iter().map(owned).collect(),
+                value_rules: (p.value_rules.into_iter())
+                    .map(|r| RawValueRule { rule: owned(r.rule), value: owned(r.value) })
+                    .collect(),
+                unknown: p.unknown,
+            };
+            (name, p)
+        });
+        RawRules { providers: providers.collect() }
+    }
+
+    /// Add the providers of `other`, replacing those with the same name.
+    #[cfg(feature = "std")]
+    pub(crate) fn merge(&mut self, other: Self) {
+        for (name, provider) in other.providers {
+            match self.providers.iter_mut().find(|(n, _)| *n == name) {
+                Some(existing) => existing.1 = provider,
+                None => self.providers.push((name, provider)),
+            }
+        }
+    }
+
+    pub(crate) fn compile(
+        self,
+        engine: &dyn RegexEngine,
+        options: &CompileOptions,
+        lenient: bool,
+    ) -> Result<Rules, LoadError> {
+        let mut compiler = Compiler::new(engine, options, lenient);
+        #[cfg(feature = "std")]
+        let start = std::time::Instant::now();
+        let mut providers = Vec::with_capacity(self.providers.len());
+        for (name, p) in self.providers {
+            for field in p.unknown.keys() {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(provider = name.as_str(), field = field.as_str(), "unknown field in rules");
+                compiler.warnings.push(RulesWarning::UnknownField { provider: name.clone(), field: field.clone() });
+            }
+            compiler.provider.clone_from(&name);
+            providers.extend(p.compile(name, &mut compiler)?);
+        }
+        Ok(Rules {
+            providers,
+            warnings: compiler.warnings,
+            #[cfg(feature = "std")]
+            report: crate::BuildReport { compile: start.elapsed(), ..compiler.report },
+        })
+    }
+}
+
+/// Deserializes [`RawRules`] like the derived implementation, but calls the closure with the name of each provider
+/// after it was parsed. If the closure returns [`ControlFlow::Break`][core::ops::ControlFlow::Break], parsing fails.
+#[cfg(feature = "std")]
+pub(crate) struct RawRulesSeed<F>(pub(crate) F);
+
+#[cfg(feature = "std")]
+impl<'de, F: FnMut(&str) -> core::ops::ControlFlow<()>> serde::de::DeserializeSeed<'de> for RawRulesSeed<F> {
+    type Value = RawRules<'de>;
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        d.deserialize_struct("RawRules", &["providers"], self)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de, F: FnMut(&str) -> core::ops::ControlFlow<()>> serde::de::Visitor<'de> for RawRulesSeed<F> {
+    type Value = RawRules<'de>;
+
+    fn expecting(&self, formatter: &mut Formatter<'_>) -> core::fmt::Result {
+        formatter.write_str("rules with providers")
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(mut self, mut seq: A) -> Result<Self::Value, A::Error> {
+        use serde::de::Error as _;
+
+        let providers = seq.next_element_seed(ProvidersSeed(&mut self.0))?;
+        let providers = providers.ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        Ok(RawRules { providers })
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(mut self, mut map: A) -> Result<Self::Value, A::Error> {
+        use serde::de::Error as _;
+
+        let mut providers = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key != "providers" {
+                map.next_value::<IgnoredAny>()?;
+            } else if providers.is_some() {
+                return Err(A::Error::duplicate_field("providers"));
+            } else {
+                providers = Some(map.next_value_seed(ProvidersSeed(&mut self.0))?);
+            }
+        }
+        let providers = providers.ok_or_else(|| A::Error::missing_field("providers"))?;
+        Ok(RawRules { providers })
+    }
+}
+
+/// The `providers` of [`RawRulesSeed`].
+#[cfg(feature = "std")]
+struct ProvidersSeed<'f, F>(&'f mut F);
+
+#[cfg(feature = "std")]
+impl<'de, F: FnMut(&str) -> core::ops::ControlFlow<()>> serde::de::DeserializeSeed<'de> for ProvidersSeed<'_, F> {
+    type Value = Vec<(String, RawProvider<'de>)>;
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        d.deserialize_map(self)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de, F: FnMut(&str) -> core::ops::ControlFlow<()>> serde::de::Visitor<'de> for ProvidersSeed<'_, F> {
+    type Value = Vec<(String, RawProvider<'de>)>;
+
+    fn expecting(&self, formatter: &mut Formatter<'_>) -> core::fmt::Result {
+        formatter.write_str("valid map")
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        use serde::de::Error as _;
+
+        let mut providers = Vec::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some((name, provider)) = map.next_entry::<String, RawProvider<'de>>()? {
+            if (self.0)(&name).is_break() {
+                return Err(A::Error::custom("aborted"));
+            }
+            providers.push((name, provider));
+        }
+        Ok(providers)
+    }
+}
+
+impl RawProvider<'_> {
+    /// Compile the provider, or return `None` if it was skipped in lenient mode.
+    fn compile(self, name: String, compiler: &mut Compiler<'_>) -> Result<Option<Provider>, LoadError> {
+        let url_pattern = compiler.compile_or_skip(&self.url_pattern)?;
+        let rules = compiler.compile_all(&self.rules)?;
+        let raw_rules = compiler.compile_all(&self.raw_rules)?;
+        let referral_marketing = compiler.compile_all(&self.referral_marketing)?;
+        let exceptions = compiler.compile_exceptions(&self.exceptions)?;
+        let redirections = compiler.compile_all(&self.redirections)?;
+        let mut value_rules = Vec::with_capacity(self.value_rules.len());
+        for r in &self.value_rules {
+            let rule = compiler.compile_or_skip(&r.rule)?;
+            if let (Some(rule), Some(value)) = (rule, compiler.compile_or_skip(&r.value)?) {
+                value_rules.push(ValueRule { rule, value });
+            }
+        }
+        // without these, the provider would apply to URLs that it shouldn't touch
+        let (Some(url_pattern), Some(exceptions)) = (url_pattern, exceptions) else {
+            return Ok(None);
+        };
+        Ok(Some(Provider {
+            name,
+            url_pattern,
+            rules,
+            raw_rules,
+            referral_marketing,
+            exceptions,
+            redirections,
+            value_rules,
+        }))
+    }
 }
 
 impl Provider {
+    /// Raw rules for session IDs in the path,
+    /// see [`UrlCleaner::strip_session_ids`][crate::UrlCleaner::strip_session_ids].
+    ///
+    /// They only apply to the path, so that `;sid=...` in a query or fragment is left alone.
+    #[allow(clippy::zero_sized_map_values)]
+    pub(crate) fn session_ids() -> Self {
+        let raw = RawProvider {
+            url_pattern: ".*".into(),
+            rules: Vec::new(),
+            raw_rules: [
+                // matrix parameters like `;jsessionid=...`
+                r";(?:jsessionid|phpsessid|sessionid|sid)=[^/?#;]*",
+                // path segments like `/sid/0123456789abcdef`
+                r"/sid/[0-9a-f]{16,}\b",
+            ]
+            .map(Cow::Borrowed)
+            .into(),
+            referral_marketing: Vec::new(),
+            exceptions: Vec::new(),
+            redirections: Vec::new(),
+            value_rules: Vec::new(),
+            unknown: BTreeMap::new(),
+        };
+        let mut provider = Self::builtin("sessionIds", raw);
+        provider.raw_rules = provider.raw_rules.into_iter().map(|r| Arc::new(InPath(r)) as Pattern).collect();
+        provider
+    }
+
+    /// Rules for links to X, formerly Twitter, see [`UrlCleaner::clean_x_links`][crate::UrlCleaner::clean_x_links].
+    #[allow(clippy::zero_sized_map_values)]
+    pub(crate) fn x_links() -> Self {
+        let raw = RawProvider {
+            // the sites, their mirrors with better embeds, their AMP cache, and the link shortener
+            url_pattern: concat!(
+                r"^https?://(?:[a-z0-9-]+\.)*?",
+                r"(?:(?:twitter|x|fxtwitter|vxtwitter|fixupx|fixvx)\.com",
+                r"|(?:twitter|x)-com\.cdn\.ampproject\.org|t\.co)",
+                r"(?:[:/?#]|$)",
+            )
+            .into(),
+            rules: [
+                // share sheet and copy link, like `?s=20&t=AbCdEf`
+                "s",
+                "t",
+                // embedded timelines and tweet buttons
+                "ref_src",
+                "ref_url",
+                "src",
+                "cn",
+                // `t.co` links in AMP pages
+                "amp",
+            ]
+            .map(Cow::Borrowed)
+            .into(),
+            // the AMP cache prefixes the original URL with its own host
+            raw_rules: [r"(?:twitter|x)-com\.cdn\.ampproject\.org/(?:[a-z]/)*s/"].map(Cow::Borrowed).into(),
+            referral_marketing: Vec::new(),
+            exceptions: Vec::new(),
+            redirections: [r"^https?://(?:[a-z0-9-]+\.)*?(?:twitter|x)\.com/i/redirect\?(?:[^#]*&)?url=([^&#]+)"]
+                .map(Cow::Borrowed)
+                .into(),
+            value_rules: Vec::new(),
+            unknown: BTreeMap::new(),
+        };
+        Self::builtin("x", raw)
+    }
+
+    /// Compile built-in rules, which are known to be valid.
+    fn builtin(name: &str, raw: RawProvider<'_>) -> Self {
+        raw.compile(name.into(), &mut Compiler::new(&crate::BuiltinEngine, &CompileOptions::default(), false))
+            .ok()
+            .flatten()
+            .expect("built-in rules are valid")
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn remove_fields_from_url(
         &self,
-        url: &Url,
+        input: &Url,
         strip_referral_marketing: bool,
-    ) -> Result<Url, Error> {
-        if let Some(redirect) = self.get_redirection(url.as_str())? {
-            let url = repeatedly_urldecode(redirect)?;
-            return Ok(Url::from_str(&url)?);
+        redirects: Redirects,
+        multi_url: MultiUrl,
+        observer: &Observer<'_>,
+        keep: &[String],
+        strict: bool,
+    ) -> Result<Url, CleanError> {
+        let redirection = match redirects {
+            Redirects::Ignore => None,
+            Redirects::Follow | Redirects::FollowBase64 => self.get_redirection(input.as_str())?,
         };
-        let mut url = Cow::Borrowed(url.as_str());
+        if let Some((rule, redirect)) = redirection {
+            let change = || self.change(Reason::Redirection { rule: rule.as_str().into(), target: redirect.into() });
+            if observer.allows(input, Action::Redirection { target: redirect }, change) {
+                let decode_base64 = redirects == Redirects::FollowBase64;
+                let url = match decode_base64.then(|| base64_decode_url(redirect)).flatten() {
+                    Some(url) => url,
+                    // only the first of several URLs in the target is followed
+                    None if multi_url != MultiUrl::Single => {
+                        Url::from_str(multi_url::first(&repeatedly_urldecode(redirect)?))?
+                    }
+                    None => Url::from_str(&repeatedly_urldecode(redirect)?)?,
+                };
+                #[cfg(feature = "tracing")]
+                tracing::debug!(target = %url, "following redirection");
+                return Ok(url);
+            }
+        }
+        let mut url = Cow::Borrowed(input.as_str());
         for r in &self.raw_rules {
-            match r.replace_all(&url, "") {
-                Cow::Borrowed(_) => {}
-                Cow::Owned(new) => url = Cow::Owned(new),
+            if !r.is_match(&url) {
+                continue;
             }
+            let Cow::Owned(new) = remove_all(&**r, &url) else { continue };
+            // a raw rule that moves the URL to another host, like out of the AMP cache, is a redirection too
+            if matches!(redirects, Redirects::Ignore) && !has_same_host(input, &new) {
+                continue;
+            }
+            let change = || self.change(Reason::RawRule { rule: r.as_str().into() });
+            if !observer.allows(input, Action::RawRule { rule: r.as_str() }, change) {
+                continue;
+            }
+            #[cfg(feature = "tracing")]
+            tracing::trace!(raw_rule = r.as_str(), "raw rule applied");
+            if strict && !has_same_location(input, &new) {
+                return Err(CleanError::UnexpectedRewrite { provider: self.name.clone(), pattern: r.as_str().into() });
+            }
+            url = Cow::Owned(new);
         }
         // clones the string
         let mut url = Url::from_str(&url)?;
@@ -59,11 +564,7 @@ impl Provider {
         let fragments = url.fragment().unwrap_or("");
         let mut fragments: Vec<(Cow<'_, str>, Cow<'_, str>)> =
             form_urlencoded::parse(fragments.as_bytes()).collect();
-
-        for r in self.get_rules(strip_referral_marketing) {
-            fields.retain(|(k, _)| !is_full_match(r, k));
-            fragments.retain(|(k, _)| !is_full_match(r, k));
-        }
+        self.remove_params(&mut [&mut fields, &mut fragments], strip_referral_marketing, input, observer, keep);
         let query = serialize_params(fields.iter());
         let fragment = serialize_params(fragments.iter());
         url.set_query(query.as_deref());
@@ -72,44 +573,132 @@ impl Provider {
         Ok(url)
     }
 
+    /// Remove the parameters that match the rules from each list of decoded parameters of `input`.
+    pub(crate) fn remove_params(
+        &self,
+        params: &mut [&mut Vec<(Cow<'_, str>, Cow<'_, str>)>],
+        strip_referral_marketing: bool,
+        input: &Url,
+        observer: &Observer<'_>,
+        keep: &[String],
+    ) {
+        let is_kept = |name: &str| keep.iter().any(|k| k == name);
+        for (r, referral_marketing) in self.get_rules(strip_referral_marketing) {
+            let is_removed = |k: &str, v: &str| {
+                let reason = |name, rule| {
+                    if referral_marketing {
+                        Reason::ReferralMarketing { name, rule }
+                    } else {
+                        Reason::Rule { name, rule }
+                    }
+                };
+                !is_kept(k) && is_removed(r, k, v, input, observer, || self.change(reason(k.into(), r.as_str().into())))
+            };
+            for p in params.iter_mut() {
+                p.retain(|(k, v)| !is_removed(k, v));
+            }
+        }
+        for r in &self.value_rules {
+            let is_removed = |k: &str, v: &str| {
+                let change = || {
+                    self.change(Reason::ValueRule {
+                        name: k.into(),
+                        rule: r.rule.as_str().into(),
+                        value_rule: r.value.as_str().into(),
+                    })
+                };
+                !is_kept(k) && is_full_match(&*r.value, v) && is_removed(&r.rule, k, v, input, observer, change)
+            };
+            for p in params.iter_mut() {
+                p.retain(|(k, v)| !is_removed(k, v));
+            }
+        }
+    }
+
     pub(crate) fn match_url(&self, url: &str) -> bool {
         self.url_pattern.is_match(url) && !self.match_exception(url)
     }
 
+    /// Whether the provider only removes parameters, so that it can't change a URL without a query and fragment.
+    pub(crate) fn only_removes_params(&self) -> bool {
+        self.raw_rules.is_empty() && self.redirections.is_empty()
+    }
+
     fn match_exception(&self, url: &str) -> bool {
         url == "javascript:void(0)" || self.exceptions.is_match(url)
     }
 
-    fn get_redirection<'a>(&self, url: &'a str) -> Result<Option<&'a str>, Error> {
+    fn change(&self, reason: Reason) -> Change {
+        Change { provider: self.name.clone(), reason }
+    }
+
+    fn get_redirection<'a>(&self, url: &'a str) -> Result<Option<(&Pattern, &'a str)>, CleanError> {
         for r in &self.redirections {
-            if let Some(c) = r.captures(url) {
-                let c = c
-                    .get(1)
-                    .ok_or_else(|| Error::RedirectionHasNoCapturingGroup(r.clone()))?;
-                let s = c.as_str();
-                return Ok(Some(s));
+            if let Some(group) = r.first_group(url) {
+                let group = group
+                    .ok_or_else(|| CleanError::RedirectionHasNoCapturingGroup {
+                        provider: self.name.clone(),
+                        pattern: r.as_str().into(),
+                    })?;
+                return Ok(Some((r, &url[group])));
             }
         }
         Ok(None)
     }
 
-    fn get_rules(&self, strip_referral_marketing: bool) -> impl Iterator<Item = &Regex> {
-        if strip_referral_marketing {
-            self.rules.iter().chain(self.referral_marketing.iter())
-        } else {
-            #[allow(clippy::iter_on_empty_collections)] // false positive, core::iter::empty() doesn't work because it has a different type
-            self.rules.iter().chain([].iter())
+    /// The rules for parameter names, and whether each is a referral marketing rule.
+    fn get_rules(&self, strip_referral_marketing: bool) -> impl Iterator<Item = (&Pattern, bool)> {
+        let referral_marketing = if strip_referral_marketing { &self.referral_marketing[..] } else { &[] };
+        self.rules.iter().map(|r| (r, false)).chain(referral_marketing.iter().map(|r| (r, true)))
+    }
+}
+
+/// Restricts a pattern to the part of a URL before the query and the fragment.
+#[derive(Debug)]
+struct InPath(Pattern);
+
+impl InPath {
+    fn path_end(url: &str) -> usize {
+        url.find(['?', '#']).unwrap_or(url.len())
+    }
+}
+
+impl Matcher for InPath {
+    fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    fn find_at(&self, haystack: &str, start: usize) -> Option<core::ops::Range<usize>> {
+        let haystack = &haystack[..Self::path_end(haystack)];
+        if start > haystack.len() {
+            return None;
         }
+        self.0.find_at(haystack, start)
     }
+
+    fn first_group(&self, haystack: &str) -> Option<Option<core::ops::Range<usize>>> {
+        self.0.first_group(&haystack[..Self::path_end(haystack)])
+    }
+}
+
+/// Whether and how a provider follows its redirections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Redirects {
+    /// Leave redirections as they are, but still remove the parameters
+    Ignore,
+    /// Replace a redirection with its percent-encoded target
+    Follow,
+    /// Like `Follow`, but the target may be base64 encoded as well
+    FollowBase64,
 }
 
-fn serialize_params<'a>(
+pub(crate) fn serialize_params<'a>(
     mut params: impl Iterator<Item = &'a (Cow<'a, str>, Cow<'a, str>)>,
 ) -> Option<String> {
     let first2: Vec<_> = params.by_ref().take(2).collect();
     let ret = match &first2[..] {
         [] => String::new(),
-        [anchor] if anchor.1 == "" => anchor.0.clone().into_owned(),
+        [anchor] if anchor.1.is_empty() => anchor.0.clone().into_owned(),
         _ => form_urlencoded::Serializer::new(String::new())
             .extend_pairs(first2)
             .extend_pairs(params)
@@ -118,7 +707,7 @@ fn serialize_params<'a>(
     Some(ret).filter(|r| !r.is_empty())
 }
 
-fn repeatedly_urldecode(s: &str) -> Result<Cow<'_, str>, Error> {
+fn repeatedly_urldecode(s: &str) -> Result<Cow<'_, str>, CleanError> {
     let mut before = Cow::Borrowed(s);
     loop {
         let after = percent_decode_str(&before).decode_utf8()?;
@@ -138,8 +727,54 @@ fn repeatedly_urldecode(s: &str) -> Result<Cow<'_, str>, Error> {
     }
 }
 
-fn is_full_match(regex: &Regex, haystack: &str) -> bool {
-    regex
-        .find(haystack)
-        .is_some_and(|m| m.len() == haystack.len())
+/// Decode a redirection target that is a base64 encoded URL.
+///
+/// Returns `None` if the target is not valid base64 or if it decodes to anything
+/// other than an absolute http(s) URL, so that the caller can fall back to the usual handling.
+fn base64_decode_url(s: &str) -> Option<Url> {
+    const CONFIG: GeneralPurposeConfig =
+        GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+    const ENGINES: [GeneralPurpose; 2] = [
+        GeneralPurpose::new(&alphabet::STANDARD, CONFIG),
+        GeneralPurpose::new(&alphabet::URL_SAFE, CONFIG),
+    ];
+
+    let s = percent_decode_str(s).decode_utf8().ok()?;
+    if s.starts_with("http") {
+        return None;
+    }
+    ENGINES.iter().find_map(|engine| {
+        let decoded = String::from_utf8(engine.decode(s.as_bytes()).ok()?).ok()?;
+        let url = Url::from_str(&decoded).ok()?;
+        (matches!(url.scheme(), "http" | "https") && url.has_host()).then_some(url)
+    })
+}
+
+/// Whether `url` parses to a URL with the same host as `input`.
+fn has_same_host(input: &Url, url: &str) -> bool {
+    Url::from_str(url).is_ok_and(|url| url.host() == input.host())
+}
+
+/// Whether `url` parses to the same scheme, host and path as `input`.
+fn has_same_location(input: &Url, url: &str) -> bool {
+    Url::from_str(url).is_ok_and(|url| {
+        url.scheme() == input.scheme() && url.host() == input.host() && url.path() == input.path()
+    })
+}
+
+fn is_removed(
+    rule: &Pattern,
+    name: &str,
+    value: &str,
+    url: &Url,
+    observer: &Observer<'_>,
+    change: impl FnOnce() -> Change,
+) -> bool {
+    let removed = is_full_match(&**rule, name)
+        && observer.allows(url, Action::RemoveParam { name, value, rule: rule.as_str() }, change);
+    #[cfg(feature = "tracing")]
+    if removed {
+        tracing::trace!(param = name, rule = rule.as_str(), "removing parameter");
+    }
+    removed
 }
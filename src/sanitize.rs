@@ -0,0 +1,216 @@
+use alloc::borrow::Cow;
+use alloc::string::String;
+use core::ops::Deref;
+
+use crate::UrlCleaner;
+
+/// The attributes whose value is a single URL.
+const URL_ATTRIBUTES: [&str; 7] = ["href", "src", "cite", "action", "formaction", "poster", "longdesc"];
+
+impl UrlCleaner {
+    /// A filter for [`ammonia::Builder::attribute_filter`] that cleans the URLs in attributes like `href`, `src`
+    /// or `srcset`, so that sanitizing HTML removes tracking parameters in the same pass.
+    ///
+    /// The cleaner is usually an [`Arc`][std::sync::Arc], or the `&'static` one from [`shared`][Self::shared].
+    /// Relative URLs and those that cause an error are kept as they are, and no attribute is removed.
+    /// To combine this with a filter of your own, use [`clean_html_attribute`][Self::clean_html_attribute] in it.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::sync::Arc;
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = Arc::new(UrlCleaner::from_embedded_rules()?);
+    /// let html = ammonia::Builder::default()
+    ///     .attribute_filter(UrlCleaner::ammonia_filter(cleaner))
+    ///     .clean(r#"<a href="https://example.com/?utm_source=x" onclick="track()">link</a>"#)
+    ///     .to_string();
+    /// assert_eq!(html, r#"<a href="https://example.com/" rel="noopener noreferrer">link</a>"#);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ammonia_filter<C>(
+        cleaner: C,
+    ) -> impl for<'u> Fn(&str, &str, &'u str) -> Option<Cow<'u, str>> + Send + Sync + 'static
+    where
+        C: Deref<Target = Self> + Send + Sync + 'static,
+    {
+        move |element, attribute, value| Some(cleaner.clean_html_attribute(element, attribute, value))
+    }
+
+    /// Clean the value of an HTML attribute if it contains URLs, see [`ammonia_filter`][Self::ammonia_filter].
+    ///
+    /// Besides attributes that are a single URL, like the `href` of `<link rel="canonical">`, this cleans
+    /// - each candidate of a `srcset`,
+    /// - the `url(...)` values in inline `style` attributes,
+    /// - the target of `<meta http-equiv="refresh" content="0;url=...">`,
+    /// - and the `content` of `<meta>` tags that is a URL, like that of `og:url`.
+    ///
+    /// Attributes that don't contain URLs are returned as they are.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?;
+    /// let srcset = "https://a.com/s.png?utm_source=x 1x, https://a.com/l.png 2x";
+    /// let res = cleaner.clean_html_attribute("img", "srcset", srcset);
+    /// assert_eq!(res, "https://a.com/s.png 1x, https://a.com/l.png 2x");
+    /// let res = cleaner.clean_html_attribute("div", "style", "background: url('https://a.com/bg.png?fbclid=1')");
+    /// assert_eq!(res, "background: url('https://a.com/bg.png')");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn clean_html_attribute<'u>(&self, element: &str, attribute: &str, value: &'u str) -> Cow<'u, str> {
+        let attribute = attribute.to_ascii_lowercase();
+        if attribute == "srcset" || attribute == "imagesrcset" {
+            return self.clean_srcset(value);
+        } else if attribute == "style" {
+            return self.clean_css_urls(value);
+        } else if attribute == "content" && element.eq_ignore_ascii_case("meta") {
+            return self.clean_meta_content(value);
+        }
+        let is_url = URL_ATTRIBUTES.contains(&attribute.as_str())
+            || (element.eq_ignore_ascii_case("object") && attribute == "data");
+        if !is_url {
+            return Cow::Borrowed(value);
+        }
+        self.clean_url_value(value.trim()).map_or(Cow::Borrowed(value), Cow::Owned)
+    }
+
+    /// Clean the URLs of the image candidates in a `srcset`, like `a.png 1x, b.png 2x`.
+    ///
+    /// A URL may contain commas, unless at its end, and descriptors end at the next comma outside of parentheses.
+    fn clean_srcset<'u>(&self, srcset: &'u str) -> Cow<'u, str> {
+        let mut result = String::new();
+        let mut last = 0;
+        let mut pos = 0;
+        while pos < srcset.len() {
+            pos += srcset[pos..].find(|c: char| !c.is_ascii_whitespace() && c != ',').unwrap_or(srcset.len() - pos);
+            let start = pos;
+            pos += srcset[pos..].find(|c: char| c.is_ascii_whitespace()).unwrap_or(srcset.len() - pos);
+            let url = srcset[start..pos].trim_end_matches(',');
+            // a candidate without descriptors ends with the commas after its URL
+            if url.len() == pos - start {
+                let mut depth = 0_usize;
+                pos += srcset[pos..]
+                    .find(|c| match c {
+                        '(' => {
+                            depth += 1;
+                            false
+                        }
+                        ')' => {
+                            depth = depth.saturating_sub(1);
+                            false
+                        }
+                        ',' => depth == 0,
+                        _ => false,
+                    })
+                    .unwrap_or(srcset.len() - pos);
+            }
+            if let Some(cleaned) = self.clean_url_value(url).filter(|c| !c.ends_with(',')) {
+                result.push_str(&srcset[last..start]);
+                result.push_str(&cleaned);
+                last = start + url.len();
+            }
+        }
+        replaced(srcset, result, last)
+    }
+
+    /// Clean the URLs in the `url(...)` values of CSS declarations, like `background: url("a.png")`.
+    ///
+    /// URLs with escapes are left as they are, as are those that can't be written in the same way after cleaning.
+    fn clean_css_urls<'u>(&self, css: &'u str) -> Cow<'u, str> {
+        let mut result = String::new();
+        let mut last = 0;
+        let mut pos = 0;
+        // lowercasing keeps the byte offsets
+        let lowercase = css.to_ascii_lowercase();
+        while let Some(i) = lowercase[pos..].find("url(") {
+            pos += i + "url(".len();
+            pos += css[pos..].find(|c: char| !c.is_ascii_whitespace()).unwrap_or(css.len() - pos);
+            let quote = css[pos..].chars().next().filter(|&c| c == '"' || c == '\'');
+            let start = pos + quote.map_or(0, char::len_utf8);
+            let end = quote.map_or_else(
+                || css[start..].find(|c: char| c == ')' || c.is_ascii_whitespace()),
+                |q| css[start..].find(q),
+            );
+            let Some(end) = end.map(|e| start + e) else {
+                break;
+            };
+            pos = end;
+            let url = &css[start..end];
+            let forbidden: &[char] = match quote {
+                Some(q) => &[q, '\\', '\n'],
+                None => &['(', ')', '"', '\'', '\\'],
+            };
+            if url.contains('\\') {
+                continue;
+            }
+            if let Some(cleaned) = self.clean_url_value(url).filter(|c| !c.contains(forbidden)) {
+                result.push_str(&css[last..start]);
+                result.push_str(&cleaned);
+                last = end;
+            }
+        }
+        replaced(css, result, last)
+    }
+
+    /// Clean the `content` of a `<meta>` tag, which is either a refresh like `5; url='...'` or possibly a URL.
+    fn clean_meta_content<'u>(&self, content: &'u str) -> Cow<'u, str> {
+        let range = refresh_target(content).or_else(|| {
+            let start = content.len() - content.trim_start().len();
+            content.trim().starts_with("http").then(|| (start, start + content.trim().len()))
+        });
+        let Some((start, end, cleaned)) =
+            range.and_then(|(start, end)| Some((start, end, self.clean_url_value(&content[start..end])?)))
+        else {
+            return Cow::Borrowed(content);
+        };
+        Cow::Owned([&content[..start], &cleaned, &content[end..]].concat())
+    }
+
+    /// The cleaned URL, if cleaning changes it, and `None` if not or if it causes an error.
+    fn clean_url_value(&self, url: &str) -> Option<String> {
+        match self.clear_document_url(url) {
+            Ok(Cow::Owned(cleaned)) if cleaned != url => Some(cleaned),
+            _ => None,
+        }
+    }
+}
+
+/// Finish a string that was built by replacing parts of `original`, up to `last`.
+fn replaced(original: &str, mut result: String, last: usize) -> Cow<'_, str> {
+    if last == 0 {
+        return Cow::Borrowed(original);
+    }
+    result.push_str(&original[last..]);
+    Cow::Owned(result)
+}
+
+/// The byte range of the URL in the `content` of a `<meta http-equiv="refresh">`, following the HTML standard,
+/// like `0;url=https://example.com/` or `5, URL='https://example.com/'`.
+fn refresh_target(content: &str) -> Option<(usize, usize)> {
+    let is_space = |c: char| c.is_ascii_whitespace();
+    let rest = content.trim_start_matches(is_space);
+    let rest = rest.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+    if rest.len() == content.trim_start_matches(is_space).len() {
+        return None;
+    }
+    let rest = rest.trim_start_matches(is_space);
+    let rest = rest.strip_prefix([';', ',']).unwrap_or(rest).trim_start_matches(is_space);
+    let rest = match rest.get(..3) {
+        Some(url) if url.eq_ignore_ascii_case("url") => {
+            rest[3..].trim_start_matches(is_space).strip_prefix('=').map_or(rest, |r| r.trim_start_matches(is_space))
+        }
+        _ => rest,
+    };
+    let start = content.len() - rest.len();
+    let end = match rest.chars().next() {
+        Some(quote @ ('"' | '\'')) => start + 1 + rest[1..].find(quote).unwrap_or(rest.len() - 1),
+        _ => start + rest.trim_end_matches(is_space).len(),
+    };
+    let start = start + usize::from(rest.starts_with(['"', '\'']));
+    (start < end).then_some((start, end))
+}
@@ -0,0 +1,222 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use serde_json::{json, Value};
+
+/// The hosts that generated providers apply to and generated URLs point to, each with the pattern for it.
+const HOSTS: [(&str, &str); 5] = [
+    ("example.com", r"example\.com"),
+    ("shop.example", r"shop\.example"),
+    ("news.example.org", r"news\.example\.org"),
+    ("t.co", r"t\.co"),
+    ("xn--bcher-kva.example", r"xn--bcher-kva\.example"),
+];
+/// Names of query parameters, both for tracking and for the function of a site.
+const PARAMS: [&str; 12] =
+    ["utm_source", "utm_medium", "utm_campaign", "fbclid", "gclid", "ref", "tag", "id", "q", "page", "url", "sid"];
+/// Patterns that are removed from the whole URL, like the upstream raw rules.
+const RAW_RULES: [&str; 3] = ["/ref=[^/?]*", r"\?utm_[a-z]+=[^&#]*$", "/tracking/"];
+/// Path segments, including dot segments and those that raw rules and exceptions match.
+const PATH_SEGMENTS: [&str; 9] = ["/", "/a", "/redirect", "/out", "/keep", "/..", "/%2e%2e", "/ref=abc", "/tracking/"];
+/// Values of query parameters, including encoded, invalid and nested ones.
+const VALUES: [&str; 14] = [
+    "",
+    "1",
+    "x",
+    "a+b",
+    "%20",
+    "%F0",
+    "%25",
+    "%00",
+    "==",
+    "é",
+    "https%3A%2F%2Fexample.com%2F%3Futm_source%3Dx",
+    "https://shop.example/?gclid=1&id=2",
+    "aHR0cHM6Ly9leGFtcGxlLmNvbS8_dXRtX3NvdXJjZT14",
+    "#",
+];
+
+/// A provider with a realistic shape, generated with [`arbitrary`] for property tests and fuzzing.
+///
+/// All patterns are valid, so rules made of these providers always load. The patterns are the strings that
+/// appear in the JSON, so they can be changed before converting the rules with [`ArbitraryRules::to_json`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ArbitraryProvider {
+    /// The pattern of the URLs that the provider applies to
+    pub url_pattern: String,
+    /// The parameters to remove
+    pub rules: Vec<String>,
+    /// Patterns to remove from the whole URL
+    pub raw_rules: Vec<String>,
+    /// The parameters to remove if [`strip_referral_marketing`][crate::UrlCleaner::strip_referral_marketing] is set
+    pub referral_marketing: Vec<String>,
+    /// The patterns of URLs that the provider doesn't apply to
+    pub exceptions: Vec<String>,
+    /// Patterns whose first group is the target of a redirection
+    pub redirections: Vec<String>,
+}
+
+impl ArbitraryProvider {
+    /// The provider as a JSON object, in the format of the `ClearURLs` rules.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        json!({
+            "urlPattern": self.url_pattern,
+            "rules": self.rules,
+            "rawRules": self.raw_rules,
+            "referralMarketing": self.referral_marketing,
+            "exceptions": self.exceptions,
+            "redirections": self.redirections,
+        })
+        .to_string()
+    }
+}
+
+impl<'a> Arbitrary<'a> for ArbitraryProvider {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let (_, host) = *u.choose(&HOSTS)?;
+        // some providers apply to all URLs, like the global rules
+        let url_pattern = if u.ratio(1, 8)? { ".*".into() } else { format!(r"^https?://(?:[a-z0-9-]+\.)*?{host}") };
+        let rules = list(u, 5, param_pattern)?;
+        let raw_rules = list(u, 1, |u| Ok(String::from(*u.choose(&RAW_RULES)?)))?;
+        let referral_marketing = list(u, 2, |u| Ok(String::from(*u.choose(&["tag", "ref", "affiliate_id"])?)))?;
+        let exceptions = list(u, 2, |u| {
+            let path = u.choose(&["keep", "account", "redirect/keep"])?;
+            Ok(format!(r"^https?://(?:[a-z0-9-]+\.)*?{host}/{path}"))
+        })?;
+        let redirections = list(u, 1, |u| {
+            let path = u.choose(&["redirect", "out", "l"])?;
+            Ok(format!(r"^https?://(?:[a-z0-9-]+\.)*?{host}/{path}\?.*?(?:url|to)=([^&]+)"))
+        })?;
+        Ok(Self { url_pattern, rules, raw_rules, referral_marketing, exceptions, redirections })
+    }
+}
+
+/// Rules made of [`ArbitraryProvider`]s, generated with [`arbitrary`] for property tests and fuzzing.
+///
+/// # Example
+/// ```
+/// # use arbitrary::{Arbitrary, Unstructured};
+/// # use clearurls::{AdversarialUrl, ArbitraryRules, UrlCleaner};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut u = Unstructured::new(b"some bytes from a fuzzer or a property test");
+/// let cleaner = UrlCleaner::from_rules_str(&ArbitraryRules::arbitrary(&mut u)?.to_json())?;
+/// let url = AdversarialUrl::arbitrary(&mut u)?;
+/// // cleaning may fail, but it must never panic
+/// let _ = cleaner.clear_single_url_str(url.as_ref());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ArbitraryRules {
+    /// The providers with their names, which are unique
+    pub providers: Vec<(String, ArbitraryProvider)>,
+}
+
+impl ArbitraryRules {
+    /// The rules as JSON, in the format of the `ClearURLs` rules, with the providers in order.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let providers: Vec<String> =
+            self.providers.iter().map(|(name, p)| format!("{}:{}", Value::from(name.as_str()), p.to_json())).collect();
+        format!(r#"{{"providers":{{{}}}}}"#, providers.join(","))
+    }
+}
+
+impl<'a> Arbitrary<'a> for ArbitraryRules {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let len = u.int_in_range(0..=8)?;
+        let providers = (0..len).map(|i| Ok((format!("provider{i}"), ArbitraryProvider::arbitrary(u)?)));
+        Ok(Self { providers: providers.collect::<Result<_>>()? })
+    }
+}
+
+/// A URL that is likely to trip up cleaning, generated with [`arbitrary`] for property tests and fuzzing.
+///
+/// It combines hosts in different cases and encodings, credentials, ports, dot segments, repeated and empty
+/// parameters, encoded, invalid and nested values, long values and fragments that look like queries.
+/// It isn't always a valid URL, so cleaning may fail, but it must never panic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdversarialUrl(pub String);
+
+impl Display for AdversarialUrl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for AdversarialUrl {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<'a> Arbitrary<'a> for AdversarialUrl {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut url = String::from(*u.choose(&["https://", "https://", "http://", "HTTPS://", "https:", "//"])?);
+        if u.ratio(1, 6)? {
+            url.push_str(u.choose(&["user@", "user:pass@", "a%40b@"])?);
+        }
+        if u.ratio(1, 3)? {
+            url.push_str(u.choose(&["www.", "WWW.", "m.", "a.b."])?);
+        }
+        let host = if u.ratio(1, 4)? {
+            u.choose(&["EXAMPLE.COM", "bücher.example", "127.0.0.1", "[::1]"])?
+        } else {
+            &u.choose(&HOSTS)?.0
+        };
+        url.push_str(host);
+        if u.ratio(1, 6)? {
+            url.push_str(u.choose(&[":443", ":80", ":8080"])?);
+        }
+        for _ in 0..u.int_in_range(0..=3)? {
+            url.push_str(u.choose(&PATH_SEGMENTS)?);
+        }
+        if u.ratio(4, 5)? {
+            url.push('?');
+            for i in 0..u.int_in_range(0..=6)? {
+                if i > 0 {
+                    url.push_str(u.choose(&["&", "&", "&&", ";"])?);
+                }
+                url.push_str(u.choose(&PARAMS)?);
+                if u.ratio(7, 8)? {
+                    url.push('=');
+                    if u.ratio(1, 20)? {
+                        url.push_str(&"a".repeat(u.int_in_range(256..=4096)?));
+                    } else {
+                        url.push_str(u.choose(&VALUES)?);
+                    }
+                }
+            }
+        }
+        if u.ratio(1, 4)? {
+            url.push_str(u.choose(&["#", "#top", "#!/path?utm_source=x", "#utm_medium=y&id=1"])?);
+        }
+        Ok(Self(url))
+    }
+}
+
+/// Generate up to `max` elements with `f`.
+fn list<'a>(
+    u: &mut Unstructured<'a>,
+    max: usize,
+    mut f: impl FnMut(&mut Unstructured<'a>) -> Result<String>,
+) -> Result<Vec<String>> {
+    (0..u.int_in_range(0..=max)?).map(|_| f(u)).collect()
+}
+
+/// A pattern for the name of a parameter, like the upstream rules contain them.
+fn param_pattern(u: &mut Unstructured<'_>) -> Result<String> {
+    let name = u.choose(&PARAMS)?;
+    Ok(match u.int_in_range(0..=3)? {
+        0 => String::from(*name),
+        1 => format!("(?:{name})"),
+        2 => format!("{name}[0-9]*"),
+        _ => format!("{name}|{}", u.choose(&PARAMS)?),
+    })
+}
@@ -1,11 +1,11 @@
 #![allow(clippy::trivial_regex)]
 
 use super::*;
-use crate::rules::Provider;
-use crate::Error::{PercentDecodeUtf8Error, RedirectionHasNoCapturingGroup};
+use crate::hooks::{Hook, Observer};
+use crate::rules::{Exceptions, Pattern, Provider, Redirects};
+use crate::CleanError::{PercentDecodeUtf8Error, RedirectionHasNoCapturingGroup};
 use alloc::string::ToString;
 use alloc::vec;
-use regex::RegexSet;
 use serde_json::error::Category;
 #[cfg(feature = "std")]
 use std::error::Error as _;
@@ -16,6 +16,41 @@ const _: () = {
     const fn assert_auto_traits<T: Send + Sync + 'static>() {}
     assert_auto_traits::<UrlCleaner>();
     assert_auto_traits::<Error>();
+    assert_auto_traits::<LinkError>();
+    assert_auto_traits::<CleanerChain>();
+    #[cfg(feature = "std")]
+    assert_auto_traits::<ProviderProfile>();
+    #[cfg(feature = "std")]
+    assert_auto_traits::<MockCleaner>();
+    assert_auto_traits::<RulesLoader>();
+    assert_auto_traits::<RulesWarning>();
+    #[cfg(feature = "std")]
+    assert_auto_traits::<BuildReport>();
+    #[cfg(feature = "warc")]
+    assert_auto_traits::<WarcSummary>();
+};
+
+// owned by the thread that consumes them, so they only need to move between threads
+#[cfg(feature = "std")]
+const _: () = {
+    const fn assert_send<T: Send + 'static>() {}
+    assert_send::<BulkCleaner<alloc::sync::Arc<UrlCleaner>>>();
+    assert_send::<BulkResults<u64>>();
+};
+
+// futures must be `Send` to be spawned on a multi-threaded tokio runtime
+#[cfg(feature = "axum")]
+const _: () = {
+    use axum::extract::{Request, State};
+    use axum::middleware::Next;
+
+    const fn assert_send_future<F, H>(_: &H)
+    where
+        F: core::future::Future + Send + 'static,
+        H: Fn(State<alloc::sync::Arc<UrlCleaner>>, Request, Next) -> F,
+    {
+    }
+    assert_send_future(&strip_tracking_params);
 };
 
 #[allow(edition_2024_expr_fragment_specifier)]
@@ -25,6 +60,10 @@ macro_rules! assert_matches {
     };
 }
 
+fn regex(pattern: &str) -> Pattern {
+    BuiltinEngine.compile(pattern, &CompileOptions::default()).unwrap().into()
+}
+
 #[test]
 fn test_referral_marketing_setter() {
     let cleaner = UrlCleaner::from_rules_str(r#"{"providers":{}}"#).unwrap();
@@ -36,15 +75,17 @@ fn test_referral_marketing_setter() {
 #[test]
 fn test_strip_referral_marketing() {
     let provider = Provider {
-        url_pattern: Regex::new("https://example.com").unwrap(),
+        name: "example".into(),
+        url_pattern: regex("https://example.com"),
         rules: vec![],
         raw_rules: vec![],
-        referral_marketing: vec![Regex::new("ref").unwrap()],
-        exceptions: RegexSet::default(),
+        referral_marketing: vec![regex("ref")],
+        exceptions: Exceptions::default(),
         redirections: vec![],
+        value_rules: vec![],
     };
     let res = provider
-        .remove_fields_from_url(&Url::from_str("https://example.com?ref=1").unwrap(), true)
+        .remove_fields_from_url(&Url::from_str("https://example.com?ref=1").unwrap(), true, Redirects::Follow, MultiUrl::Single, &Observer::default(), &[], false)
         .unwrap();
     assert_eq!(res.as_str(), "https://example.com/");
 }
@@ -53,22 +94,29 @@ fn test_strip_referral_marketing() {
 #[test]
 fn test_invalid_redirection() {
     let provider = Provider {
-        url_pattern: Regex::new("^https?://(?:[a-z0-9-]+\\.)*?google(?:\\.[a-z]{2,}){1,}").unwrap(),
+        name: "example".into(),
+        url_pattern: regex("^https?://(?:[a-z0-9-]+\\.)*?google(?:\\.[a-z]{2,}){1,}"),
         rules: vec![],
         raw_rules: vec![],
-        referral_marketing: vec![Regex::new("ref").unwrap()],
-        exceptions: RegexSet::default(),
+        referral_marketing: vec![regex("ref")],
+        exceptions: Exceptions::default(),
         // this regex is missing a capturing group around the last https...
-        redirections: vec![Regex::new("^https?://(?:[a-z0-9-]+\\.)*?google(?:\\.[a-z]{2,}){1,}/url\\?.*?(?:url|q)=https?[^&]+").unwrap()],
+        redirections: vec![regex("^https?://(?:[a-z0-9-]+\\.)*?google(?:\\.[a-z]{2,}){1,}/url\\?.*?(?:url|q)=https?[^&]+")],
+        value_rules: vec![],
     };
     let err = provider
         .remove_fields_from_url(
             &Url::from_str("https://google.co.uk/url?foo=bar&q=http%3A%2F%2Fexample.com%2Fimage.png&bar=foo").unwrap(),
             false,
+            Redirects::Follow,
+            MultiUrl::Single,
+            &Observer::default(),
+            &[],
+            false,
         )
         .unwrap_err();
-    assert_matches!(err, RedirectionHasNoCapturingGroup(_));
-    assert_eq!(err.to_string(), "redirection regex ^https?://(?:[a-z0-9-]+\\.)*?google(?:\\.[a-z]{2,}){1,}/url\\?.*?(?:url|q)=https?[^&]+ has no capture group");
+    assert_matches!(err, RedirectionHasNoCapturingGroup { ref provider, .. } if provider == "example");
+    assert_eq!(err.to_string(), "redirection regex ^https?://(?:[a-z0-9-]+\\.)*?google(?:\\.[a-z]{2,}){1,}/url\\?.*?(?:url|q)=https?[^&]+ of provider example has no capture group");
     #[cfg(feature = "std")]
     {
         assert!(err.source().is_none());
@@ -79,16 +127,18 @@ fn test_invalid_redirection() {
 #[test]
 fn test_invalid_urldecode() {
     let provider = Provider {
-        url_pattern: Regex::new("^https?://(?:[a-z0-9-]+\\.)*?google(?:\\.[a-z]{2,}){1,}").unwrap(),
+        name: "example".into(),
+        url_pattern: regex("^https?://(?:[a-z0-9-]+\\.)*?google(?:\\.[a-z]{2,}){1,}"),
         rules: vec![],
         raw_rules: vec![],
-        referral_marketing: vec![Regex::new("ref").unwrap()],
-        exceptions: RegexSet::default(),
-        redirections: vec![Regex::new("^https?://(?:[a-z0-9-]+\\.)*?google(?:\\.[a-z]{2,}){1,}/url\\?.*?(?:url|q)=(https?[^&]+)").unwrap()],
+        referral_marketing: vec![regex("ref")],
+        exceptions: Exceptions::default(),
+        redirections: vec![regex("^https?://(?:[a-z0-9-]+\\.)*?google(?:\\.[a-z]{2,}){1,}/url\\?.*?(?:url|q)=(https?[^&]+)")],
+        value_rules: vec![],
     };
     // a byte F0 is not valid utf 8
     let err = provider
-        .remove_fields_from_url(&Url::from_str("https://google.co.uk/url?foo=bar&q=http%F0").unwrap(), false)
+        .remove_fields_from_url(&Url::from_str("https://google.co.uk/url?foo=bar&q=http%F0").unwrap(), false, Redirects::Follow, MultiUrl::Single, &Observer::default(), &[], false)
         .unwrap_err();
     assert_matches!(err, PercentDecodeUtf8Error(_));
     #[cfg(feature = "std")]
@@ -104,35 +154,45 @@ fn test_invalid_urldecode() {
 #[test]
 fn test_raw_rules_unchanged() {
     let provider = Provider {
-        url_pattern: Regex::new("^https?://pantip.com").unwrap(),
+        name: "example".into(),
+        url_pattern: regex("^https?://pantip.com"),
         rules: vec![],
-        raw_rules: vec![Regex::new("#lead.*").unwrap()],
+        raw_rules: vec![regex("#lead.*")],
         referral_marketing: vec![],
-        exceptions: RegexSet::default(),
+        exceptions: Exceptions::default(),
         redirections: vec![],
+        value_rules: vec![],
     };
-    let res = provider.remove_fields_from_url(&Url::from_str("https://pantip.com/").unwrap(), false);
+    let res = provider.remove_fields_from_url(&Url::from_str("https://pantip.com/").unwrap(), false, Redirects::Follow, MultiUrl::Single, &Observer::default(), &[], false);
     assert_eq!(res.unwrap().as_str(), "https://pantip.com/");
 }
 
 #[test]
 fn test_raw_rules_produce_invalid_url() {
     let provider = Provider {
-        url_pattern: Regex::new("https://example.com").unwrap(),
+        name: "example".into(),
+        url_pattern: regex("https://example.com"),
         rules: vec![],
-        raw_rules: vec![Regex::new("https://").unwrap()],
+        raw_rules: vec![regex("https://")],
         referral_marketing: vec![],
-        exceptions: RegexSet::default(),
+        exceptions: Exceptions::default(),
         redirections: vec![],
+        value_rules: vec![],
     };
     let err = provider
-        .remove_fields_from_url(&Url::from_str("https://example.com").unwrap(), false)
+        .remove_fields_from_url(&Url::from_str("https://example.com").unwrap(), false, Redirects::Follow, MultiUrl::Single, &Observer::default(), &[], false)
         .unwrap_err();
-    assert_matches!(err, Error::UrlSyntax(_));
+    assert_matches!(err, CleanError::UrlSyntax(_));
     #[cfg(feature = "std")]
     {
-        assert_matches!(err, Error::UrlSyntax(ref inner) if error_eq(inner, err.source().unwrap()));
+        assert_matches!(err, CleanError::UrlSyntax(ref inner) if error_eq(inner, err.source().unwrap()));
     }
+
+    let err = provider
+        .remove_fields_from_url(&Url::from_str("https://example.com").unwrap(), false, Redirects::Follow, MultiUrl::Single, &Observer::default(), &[], true)
+        .unwrap_err();
+    assert_matches!(err, CleanError::UnexpectedRewrite { ref pattern, .. } if pattern == "https://");
+    assert_eq!(err.to_string(), "raw rule https:// of provider example changed the scheme, host or path of the URL");
 }
 
 #[test]
@@ -149,16 +209,16 @@ fn test_from_read_vec() {
 #[cfg(feature = "std")]
 fn test_from_file_invalid_json() {
     let err = UrlCleaner::from_rules_file(b"[".as_slice()).unwrap_err();
-    assert_matches!(err, Error::RuleSyntax(ref e) if e.classify() == Category::Eof);
+    assert_matches!(err, LoadError::RuleSyntax(ref e) if e.classify() == Category::Eof);
 }
 
 #[test]
 fn test_from_str_invalid_json() {
     let err = UrlCleaner::from_rules_str("[").unwrap_err();
-    assert_matches!(err, Error::RuleSyntax(ref e) if e.classify() == Category::Eof);
+    assert_matches!(err, LoadError::RuleSyntax(ref e) if e.classify() == Category::Eof);
     #[cfg(feature = "std")]
     {
-        assert_matches!(err, Error::RuleSyntax(ref inner) if error_ptr_eq(inner, err.source().unwrap()));
+        assert_matches!(err, LoadError::RuleSyntax(ref inner) if error_ptr_eq(inner, err.source().unwrap()));
     }
     assert_eq!(
         err.to_string(),
@@ -192,6 +252,78 @@ fn test_from_path() {
     assert_eq!(c.rules.providers[0].rules[0].as_str(), "foo");
 }
 
+#[test]
+#[cfg(feature = "gzip")]
+fn test_from_gzip_path() {
+    let json = br#"{"providers":{"example":{"urlPattern":"","rules":["foo"]}}}"#;
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    let mut encoder = flate2::write::GzEncoder::new(&mut file, flate2::Compression::default());
+    encoder.write_all(json).unwrap();
+    encoder.finish().unwrap();
+    let c = UrlCleaner::from_rules_path(file.path()).unwrap();
+    assert_eq!(c.rules.providers[0].rules[0].as_str(), "foo");
+    assert_eq!(c.rules_version(), RulesVersion::of(json));
+
+    let err = UrlCleaner::from_rules_file(&[0x1f, 0x8b, 0][..]).unwrap_err();
+    assert_matches!(err, LoadError::RuleSyntax(_) | LoadError::FileRead(_));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_from_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = br#"{"providers":{"a":{"urlPattern":"","rules":["foo"]},"b":{"urlPattern":"","rules":["bar"]}}}"#;
+    let local = br#"{"providers":{"b":{"urlPattern":"","rules":["baz"]},"c":{"urlPattern":"","rules":["qux"]}}}"#;
+    std::fs::write(dir.path().join("10-upstream.json"), base).unwrap();
+    std::fs::write(dir.path().join("20-local.json"), local).unwrap();
+    std::fs::write(dir.path().join("README.md"), "not rules").unwrap();
+    std::fs::create_dir(dir.path().join("disabled.json")).unwrap();
+
+    let c = UrlCleaner::from_rules_dir(dir.path()).unwrap();
+    let rules: vec::Vec<_> = c.rules.providers.iter().map(|p| p.rules[0].as_str()).collect();
+    assert_eq!(rules, ["foo", "baz", "qux"]);
+    assert_eq!(c.rules_version(), RulesVersion::of(&[&base[..], &local[..]].concat()));
+
+    std::fs::write(dir.path().join("30-broken.json"), "{").unwrap();
+    let err = UrlCleaner::from_rules_dir(dir.path()).unwrap_err();
+    assert_matches!(err, LoadError::RuleSyntax(_));
+    let err = UrlCleaner::from_rules_dir(&dir.path().join("missing")).unwrap_err();
+    assert_matches!(err, LoadError::FileRead(_));
+}
+
+#[test]
+#[cfg(feature = "json5")]
+fn test_json5() {
+    let json5 = r#"{
+        providers: {
+            example: {
+                urlPattern: "",
+                // tracks newsletter signups
+                rules: ["foo",],
+            },
+        },
+    }"#;
+    let err = RulesLoader::new().load_str(json5).unwrap_err();
+    assert_matches!(err, LoadError::RuleSyntax(_));
+
+    let loader = RulesLoader::new().json5(true);
+    let c = loader.load_str(json5).unwrap();
+    assert_eq!(c.rules.providers[0].rules[0].as_str(), "foo");
+    assert_eq!(c.rules_version(), RulesVersion::of(json5.as_bytes()));
+    let c = loader.load_file(json5.as_bytes()).unwrap();
+    assert_eq!(c.rules_version(), RulesVersion::of(json5.as_bytes()));
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("rules.json"), json5).unwrap();
+    let c = loader.load_dir(dir.path()).unwrap();
+    assert_eq!(c.rules.providers[0].rules[0].as_str(), "foo");
+    assert_eq!(c.rules_version(), RulesVersion::of(json5.as_bytes()));
+
+    let err = loader.load_str("{providers: ").unwrap_err();
+    assert_matches!(err, LoadError::RuleSyntax(ref e) if e.classify() == Category::Data);
+    let err = loader.load_file(&b"\xff"[..]).unwrap_err();
+    assert_matches!(err, LoadError::RuleSyntax(_));
+}
+
 #[test]
 #[cfg(feature = "std")]
 fn test_from_invalid_path() {
@@ -199,31 +331,94 @@ fn test_from_invalid_path() {
     let path = file.path().to_path_buf();
     file.close().unwrap();
     let err = UrlCleaner::from_rules_path(&path).unwrap_err();
-    assert_matches!(err, Error::FileRead(ref e) if e.kind() == std::io::ErrorKind::NotFound);
-    assert_matches!(err, Error::FileRead(ref inner) if error_ptr_eq(inner, err.source().unwrap()));
+    assert_matches!(err, LoadError::FileRead(ref e) if e.kind() == std::io::ErrorKind::NotFound);
+    assert_matches!(err, LoadError::FileRead(ref inner) if error_ptr_eq(inner, err.source().unwrap()));
     assert!(err.to_string().starts_with("error reading rules: "));
 }
 
+#[test]
+fn test_rules_version() {
+    let json = r#"{"providers":{"example":{"urlPattern":".*","rules":["foo"]}}}
+"#;
+    let version = UrlCleaner::from_rules_str(json).unwrap().rules_version();
+    assert_eq!(version, RulesVersion::of(json.as_bytes()));
+    #[cfg(feature = "std")]
+    assert_eq!(UrlCleaner::from_rules_file(json.as_bytes()).unwrap().rules_version(), version);
+    assert_ne!(version, RulesVersion::EMBEDDED);
+    assert_eq!(UrlCleaner::from_embedded_rules().unwrap().rules_version(), RulesVersion::EMBEDDED);
+
+    let empty = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+    assert_eq!(RulesVersion::of(b"").to_string(), empty);
+    assert_eq!(empty.parse(), Ok(RulesVersion::of(b"")));
+    assert_eq!(version.to_string().to_uppercase().parse(), Ok(version));
+    assert_eq!("abc".parse::<RulesVersion>(), Err(ParseRulesVersionError));
+    assert_eq!(empty.replace('e', "g").parse::<RulesVersion>(), Err(ParseRulesVersionError));
+    assert_eq!(alloc::format!("+{}", &empty[1..]).parse::<RulesVersion>(), Err(ParseRulesVersionError));
+    if let Some(commit) = RulesVersion::EMBEDDED_COMMIT {
+        assert!(commit.bytes().all(|b| b.is_ascii_hexdigit()), "{commit}");
+    }
+}
+
 #[test]
 fn test_remove_fields_from_url_errors() {
     let provider = UrlCleaner {
         rules: Rules {
             providers: vec![Provider {
-                url_pattern: Regex::new(".*").unwrap(),
+                name: "example".into(),
+                url_pattern: regex(".*"),
                 rules: vec![],
                 raw_rules: vec![],
                 referral_marketing: vec![],
-                exceptions: RegexSet::default(),
+                exceptions: Exceptions::default(),
                 redirections: vec![],
+                value_rules: vec![],
             }],
+            warnings: vec![],
+            #[cfg(feature = "std")]
+            report: BuildReport::default(),
         },
+        rules_version: RulesVersion::of(b""),
         strip_referral_marketing: false,
+        decode_base64: false,
+        base64_overrides: alloc::collections::BTreeMap::new(),
+        follow_redirects: true,
+        redirect_overrides: alloc::collections::BTreeMap::new(),
+        clean_nested: false,
+        multi_url: MultiUrl::Single,
+        skip_schemes: default_skip_schemes(),
+        clean_mailto: false,
+        scheme_handlers: vec![],
+        hook: None,
+        audit: None,
+        normalization: None,
+        host_form: HostForm::Ascii,
+        session_ids: None,
+        x_links: None,
+        unwrapping: wrappers::Unwrapping::default(),
+        policies: PolicyMap::new(),
+        strict: false,
+        max_params: None,
+        max_url_len: None,
+        #[cfg(feature = "linkify")]
+        link_finder: default_link_finder(),
+        #[cfg(feature = "linkify")]
+        schemeless_link_finder: schemeless_link_finder(),
+        #[cfg(feature = "linkify")]
+        ansi_escapes: false,
+        #[cfg(feature = "linkify")]
+        repair_urls: false,
+        #[cfg(feature = "linkify")]
+        trailing_punctuation: TrailingPunctuation::Finder,
+        #[cfg(feature = "markdown-it")]
+        skip_code: false,
+        #[cfg(any(feature = "linkify", feature = "ammonia"))]
+        protocol_relative: false,
     };
     let err = provider.clear_single_url_str("//example.com").unwrap_err();
-    assert_matches!(err, Error::UrlSyntax(_));
+    assert_matches!(err, CleanError::UrlSyntax(_));
     #[cfg(feature = "std")]
     {
-        assert_matches!(err, Error::UrlSyntax(ref inner) if error_eq(inner, err.source().unwrap()));
+        assert_matches!(err, CleanError::UrlSyntax(ref inner) if error_eq(inner, err.source().unwrap()));
     }
     assert_eq!(
         err.to_string(),
@@ -231,6 +426,795 @@ fn test_remove_fields_from_url_errors() {
     );
 }
 
+#[test]
+fn test_hook_veto() {
+    let provider = Provider {
+        name: "example".into(),
+        url_pattern: regex("^https?://(?:[a-z0-9-]+\\.)*?google(?:\\.[a-z]{2,}){1,}"),
+        rules: vec![regex("foo")],
+        raw_rules: vec![regex("/url")],
+        referral_marketing: vec![],
+        exceptions: Exceptions::default(),
+        redirections: vec![regex("^https?://(?:[a-z0-9-]+\\.)*?google(?:\\.[a-z]{2,}){1,}/url\\?.*?(?:url|q)=(https?[^&]+)")],
+        value_rules: vec![],
+    };
+    let url = Url::from_str("https://google.co.uk/url?foo=bar&q=http%3A%2F%2Fexample.com%2F").unwrap();
+
+    let allow = Hook::new(|_| Decision::Allow);
+    let res = provider.remove_fields_from_url(&url, false, Redirects::Follow, MultiUrl::Single, &Observer { hook: Some(&allow), changes: None, dry_run: None }, &[], false).unwrap();
+    assert_eq!(res.as_str(), "http://example.com/");
+
+    let veto_redirect = Hook::new(|ctx| match ctx.action {
+        Action::Redirection { target } => {
+            assert_eq!(target, "http%3A%2F%2Fexample.com%2F");
+            Decision::Veto
+        }
+        _ => Decision::Allow,
+    });
+    let res = provider.remove_fields_from_url(&url, false, Redirects::Follow, MultiUrl::Single, &Observer { hook: Some(&veto_redirect), changes: None, dry_run: None }, &[], false).unwrap();
+    assert_eq!(res.as_str(), "https://google.co.uk/?q=http%3A%2F%2Fexample.com%2F");
+
+    let veto_all = Hook::new(|ctx| {
+        assert_eq!(ctx.url.host_str(), Some("google.co.uk"));
+        if let Action::RemoveParam { name, value, rule } = ctx.action {
+            assert_eq!((name, value, rule), ("foo", "bar", "foo"));
+        }
+        Decision::Veto
+    });
+    let res = provider.remove_fields_from_url(&url, false, Redirects::Follow, MultiUrl::Single, &Observer { hook: Some(&veto_all), changes: None, dry_run: None }, &[], false).unwrap();
+    assert_eq!(res.as_str(), url.as_str());
+}
+
+#[test]
+fn test_cleaner_chain() {
+    struct Suffix(&'static str);
+    impl Cleaner for Suffix {
+        fn clean<'a>(&self, url: &'a str) -> Result<Cow<'a, str>, Error> {
+            Ok(url.strip_suffix(self.0).map_or(Cow::Borrowed(url), Cow::Borrowed))
+        }
+    }
+
+    let empty = CleanerChain::new();
+    assert_matches!(empty.clean("https://example.com/").unwrap(), Cow::Borrowed("https://example.com/"));
+
+    let chain = CleanerChain::new().then(Suffix("/b")).then(Suffix("/a"));
+    assert_matches!(chain.clean("https://example.com/c").unwrap(), Cow::Borrowed("https://example.com/c"));
+    assert_matches!(chain.clean("https://example.com/a/b").unwrap(), Cow::Borrowed("https://example.com"));
+
+    let chain = CleanerChain::new()
+        .then(UrlCleaner::from_rules_str(r#"{"providers":{"example":{"urlPattern":".*","rules":["foo"]}}}"#).unwrap())
+        .then(Suffix("/a"))
+        .then(Suffix("/x"));
+    let res = chain.clean("https://example.com/a?foo=1").unwrap();
+    assert_matches!(res, Cow::Owned(ref s) if s == "https://example.com");
+    assert_eq!(alloc::format!("{chain:?}"), "CleanerChain { len: 3, .. }");
+}
+
+#[test]
+fn test_noop() {
+    let cleaner = UrlCleaner::noop();
+    assert!(cleaner.rules.providers.is_empty());
+    assert_eq!(cleaner.rules_version(), RulesVersion::of(br#"{"providers":{}}"#));
+    assert_eq!(cleaner.clean("https://example.com/?utm_source=x").unwrap(), "https://example.com/?utm_source=x");
+    let cleaner = cleaner.strip_session_ids(true);
+    let res = cleaner.clean("https://example.com/a;jsessionid=1?utm_source=x").unwrap();
+    assert_eq!(res, "https://example.com/a?utm_source=x");
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_mock_cleaner() {
+    let mock = MockCleaner::new()
+        .respond("https://example.com/a?x=1", "https://example.com/a")
+        .respond("https://example.com/same", "https://example.com/same")
+        .fail("https://example.com/error", || Error::UrlSyntax(ParseError::EmptyHost));
+    let chain = CleanerChain::new().then(mock);
+    assert_matches!(chain.clean("https://example.com/a?x=1").unwrap(), Cow::Owned(ref s) if s == "https://example.com/a");
+    assert_matches!(chain.clean("https://example.com/same").unwrap(), Cow::Borrowed(_));
+    assert_matches!(chain.clean("https://example.com/other").unwrap(), Cow::Borrowed(_));
+    assert_matches!(chain.clean("https://example.com/error"), Err(Error::UrlSyntax(ParseError::EmptyHost)));
+    assert_matches!(chain.clean("https://example.com/error"), Err(Error::UrlSyntax(ParseError::EmptyHost)));
+
+    let mock = MockCleaner::new().respond("a", "b");
+    assert_eq!(mock.clean("a").unwrap(), "b");
+    assert_eq!(mock.calls(), ["a"]);
+    assert_eq!(alloc::format!("{mock:?}"), "MockCleaner { responses: 1, calls: 1 }");
+}
+
+#[test]
+fn test_base64_redirection() {
+    let provider = Provider {
+        name: "example".into(),
+        url_pattern: regex("^https?://site.com"),
+        rules: vec![],
+        raw_rules: vec![],
+        referral_marketing: vec![],
+        exceptions: Exceptions::default(),
+        redirections: vec![regex("^https?://site.com/.*?\\?to=([^&]*)")],
+        value_rules: vec![],
+    };
+    let test = |input: &str, decode_base64: bool, expected: &str| {
+        let url = Url::from_str(input).unwrap();
+        let redirects = if decode_base64 { Redirects::FollowBase64 } else { Redirects::Follow };
+        let res = provider.remove_fields_from_url(&url, false, redirects, MultiUrl::Single, &Observer::default(), &[], false).unwrap();
+        assert_eq!(res.as_str(), expected, "input {input}, decode_base64 {decode_base64}");
+    };
+
+    // https://example.com/?a=1
+    test("https://site.com/r?to=aHR0cHM6Ly9leGFtcGxlLmNvbS8/YT0x", true, "https://example.com/?a=1");
+    test("https://site.com/r?to=aHR0cHM6Ly9leGFtcGxlLmNvbS8_YT0x", true, "https://example.com/?a=1");
+    test("https://site.com/r?to=aHR0cHM6Ly9leGFtcGxlLmNvbS8%2FYT0x", true, "https://example.com/?a=1");
+    // https://example.com/ with and without padding
+    test("https://site.com/r?to=aHR0cHM6Ly9leGFtcGxlLmNvbS8%3D", true, "https://example.com/");
+    test("https://site.com/r?to=aHR0cHM6Ly9leGFtcGxlLmNvbS8", true, "https://example.com/");
+    test("https://site.com/r?to=aHR0cHM6Ly9leGFtcGxlLmNvbS8", false, "http://ahr0chm6ly9legftcgxllmnvbs8/");
+    // not base64 at all
+    test("https://site.com/r?to=example.com", true, "http://example.com/");
+    // valid base64, but not a http URL: javascript:alert(1)
+    test("https://site.com/r?to=amF2YXNjcmlwdDphbGVydCgxKQ", true, "http://amf2yxnjcmlwddphbgvydcgxkq/");
+    // plain URLs are never decoded
+    test("https://site.com/r?to=https%3A%2F%2Fexample.com%2F", true, "https://example.com/");
+}
+
+#[test]
+fn test_base64_setter() {
+    let cleaner = UrlCleaner::from_rules_str(r#"{"providers":{}}"#).unwrap();
+    assert!(!cleaner.decode_base64);
+    let cleaner = cleaner.decode_base64_redirections(true);
+    assert!(cleaner.decode_base64);
+
+    let rules = r#"{"providers":{
+        "a":{"urlPattern":"^https://a\\.com","redirections":["\\?to=([^&]*)"]},
+        "b":{"urlPattern":"^https://b\\.com","redirections":["\\?to=([^&]*)"]}
+    }}"#;
+    // https://example.com/
+    let test = |cleaner: &UrlCleaner, expected: [&str; 2]| {
+        let res = ["a", "b"].map(|host| {
+            let url = alloc::format!("https://{host}.com/r?to=aHR0cHM6Ly9leGFtcGxlLmNvbS8");
+            cleaner.clear_single_url_str(&url).unwrap().into_owned()
+        });
+        assert_eq!(res, expected);
+    };
+    let cleaner = UrlCleaner::from_rules_str(rules).unwrap().decode_base64_redirections_for("a", true);
+    test(&cleaner, ["https://example.com/", "http://ahr0chm6ly9legftcgxllmnvbs8/"]);
+    let cleaner = cleaner.decode_base64_redirections(true).decode_base64_redirections_for("a", false);
+    test(&cleaner, ["http://ahr0chm6ly9legftcgxllmnvbs8/", "https://example.com/"]);
+}
+
+#[test]
+fn test_skip_schemes() {
+    let cleaner = UrlCleaner::from_rules_str(r#"{"providers":{"all":{"urlPattern":".*","rules":["utm_source"]}}}"#).unwrap();
+    let test = |cleaner: &UrlCleaner, url: &str, expected: &str| {
+        assert_eq!(cleaner.clear_single_url_str(url).unwrap(), expected);
+        let parsed = Url::from_str(url).unwrap();
+        assert_eq!(cleaner.clear_single_url(&parsed).unwrap().as_str(), expected);
+    };
+    assert_eq!(cleaner.clear_single_url_str("DATA:,utm_source").unwrap(), "DATA:,utm_source");
+    test(&cleaner, "javascript:void(0)", "javascript:void(0)");
+    test(&cleaner, "javascript:f()?utm_source=1", "javascript:f()");
+    test(&cleaner, "mailto:a@example.com?utm_source=1", "mailto:a@example.com?utm_source=1");
+
+    let cleaner = cleaner.skip_schemes(["mailto"]).clean_mailto(true);
+    test(&cleaner, "mailto:a@example.com?utm_source=1", "mailto:a@example.com?utm_source=1");
+    test(&cleaner, "javascript:void(0)", "javascript:void(0)");
+    test(&cleaner, "data:text/plain?utm_source=1", "data:text/plain?utm_source=1");
+
+    let cleaner = cleaner.clear_skip_schemes();
+    test(&cleaner, "data:text/plain?utm_source=1", "data:text/plain");
+}
+
+#[test]
+fn test_clean_mailto() {
+    let rules = r#"{"providers":{
+        "globalRules":{"urlPattern":".*","rules":["utm_source"]},
+        "other":{"urlPattern":".*","rules":["subject"]}
+    }}"#;
+    let url = "mailto:a@example.com?subject=Hi&utm_source=1";
+    let cleaner = UrlCleaner::from_rules_str(rules).unwrap();
+    assert_eq!(cleaner.clear_single_url_str(url).unwrap(), url);
+    assert!(cleaner.is_clean(url).unwrap());
+    assert_eq!(cleaner.clear_single_url_str("https://example.com/?subject=Hi&utm_source=1").unwrap(), "https://example.com/");
+
+    let cleaner = cleaner.clean_mailto(true);
+    assert_eq!(cleaner.clear_single_url_str(url).unwrap(), "mailto:a@example.com?subject=Hi");
+    assert_eq!(cleaner.clear_single_url_str("MAILTO:a@example.com?utm_source=1").unwrap(), "mailto:a@example.com");
+    assert!(!cleaner.is_clean(url).unwrap());
+}
+
+#[test]
+fn test_scheme_handler() {
+    struct Upper;
+    impl Cleaner for Upper {
+        fn clean<'a>(&self, url: &'a str) -> Result<Cow<'a, str>, Error> {
+            Ok(if url.ends_with("?x") { Cow::Owned(url.to_uppercase()) } else { Cow::Borrowed(url) })
+        }
+    }
+
+    let cleaner = UrlCleaner::from_rules_str(r#"{"providers":{"all":{"urlPattern":".*","rules":["utm_source"]}}}"#)
+        .unwrap()
+        .scheme_handler("javascript", Upper)
+        .scheme_handler("Intent", Upper);
+    assert_eq!(cleaner.clear_single_url_str("javascript:a?x").unwrap(), "JAVASCRIPT:A?X");
+    assert_eq!(cleaner.clear_single_url_str("INTENT://a?x").unwrap(), "INTENT://A?X");
+    assert_eq!(cleaner.clear_single_url_str("intent://a?utm_source=1").unwrap(), "intent://a?utm_source=1");
+    assert_eq!(cleaner.clear_single_url_str("other://a?utm_source=1").unwrap(), "other://a");
+    assert!(!cleaner.is_clean("intent://a?x").unwrap());
+    assert!(cleaner.is_clean("intent://a?utm_source=1").unwrap());
+    let (url, changes) = cleaner.clear_single_url_explained("javascript:a?x").unwrap();
+    assert_eq!((url.as_ref(), changes.len()), ("JAVASCRIPT:A?X", 0));
+
+    let url = Url::from_str("intent://a?x").unwrap();
+    assert_eq!(cleaner.clear_single_url(&url).unwrap().as_str(), "intent://A?X");
+    let url = Url::from_str("intent://a?y").unwrap();
+    assert!(matches!(cleaner.clear_single_url(&url).unwrap(), Cow::Borrowed(_)));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_shared() {
+    let a = UrlCleaner::shared();
+    let b = std::thread::spawn(UrlCleaner::shared).join().unwrap();
+    assert!(core::ptr::eq(a, b));
+    assert!(!a.rules.providers.is_empty());
+}
+
+#[test]
+fn test_compile_rules() {
+    let c = UrlCleaner::from_rules_str(r#"{"providers":{"example":{"urlPattern":"a","exceptions":["b"]}}}"#).unwrap();
+    assert!(c.rules.providers[0].url_pattern.is_match("A"));
+    assert!(c.rules.providers[0].exceptions.is_match("B"));
+    assert!(!c.rules.providers[0].exceptions.is_match("A"));
+
+    let invalid = [
+        r#"{"providers":{"example":{"urlPattern":true}}}"#,
+        r#"{"providers":{"example":{"urlPattern":[]}}}"#,
+        r#"{"providers":{"example":{"urlPattern":"","rules":[".*", 1]}}}"#,
+        r#"{"providers":{"example":{"urlPattern":"","rules":""}}}"#,
+        r#"{"providers":{"example":{"urlPattern":"","exceptions":[".*", 1]}}}"#,
+        r#"{"providers":{"example":{"urlPattern":"","exceptions":""}}}"#,
+    ];
+    for rules in invalid {
+        let err = UrlCleaner::from_rules_str(rules).unwrap_err();
+        assert_matches!(err, LoadError::RuleSyntax(ref e) if e.classify() == Category::Data);
+    }
+
+    let invalid = [
+        r#"{"providers":{"example":{"urlPattern":"["}}}"#,
+        r#"{"providers":{"example":{"urlPattern":"","rules":["["]}}}"#,
+        r#"{"providers":{"example":{"urlPattern":"","exceptions":["a", "["]}}}"#,
+    ];
+    for rules in invalid {
+        let err = UrlCleaner::from_rules_str(rules).unwrap_err();
+        assert_matches!(err, LoadError::RegexSyntax { ref provider, ref pattern, .. } if provider == "example" && pattern == "[");
+        assert_eq!(err.code(), ErrorCode::RegexSyntax);
+        assert!(err.to_string().starts_with("regex [ of provider example is invalid: "));
+    }
+}
+
+#[test]
+fn test_to_adguard_filter() {
+    let rules = r#"{"providers":{
+        "global":{"urlPattern":".*","rules":["utm_source"],"exceptions":["^https?:\\/\\/matrix\\.org\\/"]},
+        "example":{"urlPattern":"^https?:\\/\\/example\\.com/","rules":["a/b"],"referralMarketing":["ref"],
+            "rawRules":["\\/ref=[^/?]*"],"redirections":["^https?:\\/\\/example\\.com\\/out\\?url=([^&]*)"]}
+    }}"#;
+    let cleaner = UrlCleaner::from_rules_str(rules).unwrap();
+    let expected = r"$removeparam=/^(?:utm_source)=/i
+@@/^https?:\/\/matrix\.org\//$removeparam=/^(?:utm_source)=/i
+/^https?:\/\/example\.com\//$removeparam=/^(?:a\/b)=/i
+! raw rule for /^https?:\/\/example\.com\//: \/ref=[^/?]*
+! redirection: ^https?:\/\/example\.com\/out\?url=([^&]*)
+";
+    assert_eq!(cleaner.to_adguard_filter(), expected);
+
+    let filter = cleaner.strip_referral_marketing(true).to_adguard_filter();
+    assert!(filter.contains("/^https?:\\/\\/example\\.com\\//$removeparam=/^(?:ref)=/i\n"));
+}
+
+#[test]
+fn test_rules_for() {
+    let rules = r#"{"providers":{
+        "example":{"urlPattern":"^https?:\\/\\/example\\.com","rules":["utm_[a-z]+"],"referralMarketing":["ref"],
+            "rawRules":["\\/amp"],"exceptions":["^https?:\\/\\/example\\.com\\/login"],
+            "redirections":["^https?:\\/\\/example\\.com\\/out\\?to=([^&]*)"]}
+    }}"#;
+    let cleaner = UrlCleaner::from_rules_str(rules).unwrap();
+
+    let effective = cleaner.rules_for("https://example.com/amp?utm_source=a&page=1#ref=b").unwrap();
+    assert_eq!(
+        effective,
+        [
+            EffectiveRule::RawRule { rule: "\\/amp".into() },
+            EffectiveRule::RemoveParam {
+                name: "utm_source".into(),
+                rule: "utm_[a-z]+".into(),
+                referral_marketing: false
+            },
+            EffectiveRule::RemoveParam {
+                name: "ref".into(),
+                rule: "ref".into(),
+                referral_marketing: true
+            },
+        ]
+    );
+
+    let effective = cleaner.rules_for("https://example.com/out?to=https%3A%2F%2Fexample.org").unwrap();
+    assert_matches!(&effective[..], [EffectiveRule::Redirection { target, .. }] if target == "https%3A%2F%2Fexample.org");
+
+    assert!(cleaner.rules_for("https://example.com/login?utm_source=a").unwrap().is_empty());
+    assert!(cleaner.rules_for("https://example.org/?utm_source=a").unwrap().is_empty());
+    assert_matches!(cleaner.rules_for("//example.com"), Err(CleanError::UrlSyntax(_)));
+}
+
+#[test]
+fn test_custom_engine() {
+    /// Matches the pattern literally
+    #[derive(Debug)]
+    struct Literal(alloc::string::String);
+    impl Matcher for Literal {
+        fn as_str(&self) -> &str {
+            &self.0
+        }
+        fn find_at(&self, haystack: &str, start: usize) -> Option<core::ops::Range<usize>> {
+            let pos = haystack[start..].find(&self.0)? + start;
+            Some(pos..pos + self.0.len())
+        }
+        fn first_group(&self, _haystack: &str) -> Option<Option<core::ops::Range<usize>>> {
+            None
+        }
+    }
+    #[derive(Debug)]
+    struct LiteralEngine;
+    impl RegexEngine for LiteralEngine {
+        fn compile(&self, pattern: &str, options: &CompileOptions) -> Result<alloc::boxed::Box<dyn Matcher>, CompileError> {
+            assert!(options.case_insensitive);
+            if pattern.is_empty() {
+                return Err(CompileError::Invalid("empty pattern".into()));
+            }
+            Ok(alloc::boxed::Box::new(Literal(pattern.into())))
+        }
+    }
+
+    let c = RulesLoader::new()
+        .regex_engine(LiteralEngine)
+        .load_str(r#"{"providers":{"example":{"urlPattern":"example.com","rules":["a.b"],"rawRules":["/x"]}}}"#)
+        .unwrap();
+    let res = c.clear_single_url_str("https://example.com/x/y/x?a.b=1&axb=2").unwrap();
+    assert_eq!(res, "https://example.com/y?axb=2");
+
+    let err = RulesLoader::new()
+        .regex_engine(LiteralEngine)
+        .load_str(r#"{"providers":{"example":{"urlPattern":""}}}"#)
+        .unwrap_err();
+    assert_eq!(err.to_string(), "regex  of provider example is invalid: empty pattern");
+}
+
+#[test]
+fn test_remove_all() {
+    use crate::matcher::remove_all;
+    assert_matches!(remove_all(&*regex("x"), "abc"), Cow::Borrowed("abc"));
+    assert_matches!(remove_all(&*regex("x*"), "abc"), Cow::Borrowed("abc"));
+    assert_eq!(remove_all(&*regex("x*"), "xaxxbäx"), "abä");
+    assert_eq!(remove_all(&*regex("a|"), "äab"), "äb");
+}
+
+#[test]
+fn test_case_insensitive() {
+    let rules = r#"{"providers":{"example":{"urlPattern":".*","rules":["id","(?-i)Ref"]}}}"#;
+    let url = "https://example.com/?ID=1&id=2&Ref=3&ref=4";
+    let c = RulesLoader::new().load_str(rules).unwrap();
+    assert_eq!(c.clear_single_url_str(url).unwrap(), "https://example.com/?ref=4");
+    let c = RulesLoader::new().case_insensitive(false).load_str(rules).unwrap();
+    assert_eq!(c.clear_single_url_str(url).unwrap(), "https://example.com/?ID=1&ref=4");
+}
+
+#[test]
+fn test_ecmascript_fallback() {
+    // lookahead isn't supported by the built-in engine
+    let rules = r#"{"providers":{"example":{"urlPattern":".*","rules":["(?!keep$)[a-z]+"]}}}"#;
+    let url = "https://example.com/?keep=1&drop=2&ID=3";
+    #[cfg(feature = "ecmascript")]
+    {
+        let c = RulesLoader::new().load_str(rules).unwrap();
+        assert_eq!(c.clear_single_url_str(url).unwrap(), "https://example.com/?keep=1");
+    }
+    #[cfg(not(feature = "ecmascript"))]
+    {
+        let _ = url;
+        assert_matches!(RulesLoader::new().load_str(rules), Err(LoadError::RegexSyntax { .. }));
+    }
+}
+
+#[test]
+fn test_lenient() {
+    let rules = r#"{"providers":{
+        "a":{"urlPattern":"^https://a\\.com","rules":["(bad","foo"],"x-valueRules":[{"rule":"ref","value":"(bad"}]},
+        "b":{"urlPattern":"^https://b\\.com","rules":["foo"],"exceptions":["(bad"]},
+        "c":{"urlPattern":"(bad","rules":["foo"]}
+    }}"#;
+    assert_matches!(RulesLoader::new().load_str(rules), Err(LoadError::RegexSyntax { .. }));
+
+    let c = RulesLoader::new().lenient(true).load_str(rules).unwrap();
+    assert_eq!(c.clear_single_url_str("https://a.com/?foo=1&ref=2").unwrap(), "https://a.com/?ref=2");
+    assert_eq!(c.clear_single_url_str("https://b.com/?foo=1").unwrap(), "https://b.com/?foo=1");
+    let warnings: vec::Vec<_> = c
+        .warnings()
+        .iter()
+        .map(|w| match w {
+            RulesWarning::InvalidPattern { provider, pattern, .. } => (provider.as_str(), pattern.as_str()),
+            w => panic!("unexpected warning {w}"),
+        })
+        .collect();
+    assert_eq!(warnings, [("a", "(bad"), ("a", "(bad"), ("b", "(bad"), ("c", "(bad")]);
+    assert!(c.warnings()[0].to_string().starts_with("skipped invalid pattern (bad in provider a: "));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_load_file_with_progress() {
+    use core::ops::ControlFlow;
+
+    let rules = r#"{"providers":{"a":{"urlPattern":"a","rules":["foo"]},"b":{"urlPattern":"b"},"c":{"urlPattern":"c"}}}"#;
+    let mut seen = vec![];
+    let c = RulesLoader::new()
+        .load_file_with_progress(rules.as_bytes(), |p| {
+            seen.push((p.provider.to_string(), p.providers, p.bytes_read));
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+    assert_eq!(c.clear_single_url_str("https://a.com/?foo=1").unwrap(), "https://a.com/");
+    assert_eq!(c.rules_version(), RulesVersion::of(rules.as_bytes()));
+    let len = rules.len() as u64;
+    assert_eq!(seen, [("a".to_string(), 1, len), ("b".to_string(), 2, len), ("c".to_string(), 3, len)]);
+
+    let mut count = 0;
+    let err = RulesLoader::new()
+        .load_file_with_progress(rules.as_bytes(), |p| {
+            count += 1;
+            if p.provider == "b" { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+        })
+        .unwrap_err();
+    assert_matches!(err, LoadError::Aborted);
+    assert_eq!(err.code(), ErrorCode::Aborted);
+    assert_eq!(count, 2);
+
+    let err = RulesLoader::new().load_file_with_progress(&b"{}"[..], |_| ControlFlow::Continue(())).unwrap_err();
+    assert_eq!(err.to_string(), "error parsing rules: missing field `providers` at line 1 column 2");
+}
+
+#[cfg(feature = "signature")]
+#[test]
+fn test_signed_rules() {
+    use ed25519_dalek::{Signer, SigningKey};
+
+    let json = r#"{"providers":{"example":{"urlPattern":".*","rules":["foo"]}}}"#;
+    let key = SigningKey::from_bytes(&[7; 32]);
+    let public_key = key.verifying_key().to_bytes();
+    let signature = key.sign(json.as_bytes()).to_bytes();
+    let loader = RulesLoader::new();
+
+    let cleaner = loader.load_signed_str(json, &signature, &public_key).unwrap();
+    assert_eq!(cleaner.clear_single_url_str("https://example.com/?foo=1").unwrap(), "https://example.com/");
+    #[cfg(feature = "std")]
+    loader.load_signed_file(json.as_bytes(), &signature, &public_key).unwrap();
+
+    let tampered = json.replace("foo", "bar");
+    assert_matches!(loader.load_signed_str(&tampered, &signature, &public_key), Err(LoadError::InvalidSignature));
+    assert_matches!(loader.load_signed_str(json, &signature[..63], &public_key), Err(LoadError::InvalidSignature));
+    let other_key = SigningKey::from_bytes(&[8; 32]).verifying_key().to_bytes();
+    assert_matches!(loader.load_signed_str(json, &signature, &other_key), Err(LoadError::InvalidSignature));
+    #[cfg(feature = "std")]
+    assert_matches!(
+        loader.load_signed_file(tampered.as_bytes(), &signature, &public_key),
+        Err(LoadError::InvalidSignature)
+    );
+}
+
+#[test]
+fn test_regex_limits() {
+    let rules = r#"{"providers":{"example":{"urlPattern":"((a))","rules":["\\w{100}"]}}}"#;
+    assert!(RulesLoader::new().size_limit(10_000_000).nest_limit(10).dfa_size_limit(10_000_000).load_str(rules).is_ok());
+
+    let err = RulesLoader::new().nest_limit(1).load_str(rules).unwrap_err();
+    assert_matches!(err, LoadError::RegexTooBig { ref pattern, .. } if pattern == "((a))");
+    assert_eq!(err.to_string(), "regex ((a)) of provider example exceeds the configured limits");
+    #[cfg(feature = "std")]
+    {
+        assert!(err.source().is_none());
+    }
+
+    let err = RulesLoader::new().size_limit(1000).load_str(rules).unwrap_err();
+    assert_matches!(err, LoadError::RegexTooBig { ref pattern, .. } if pattern == "\\w{100}");
+
+    // invalid patterns are still reported as such
+    let err = RulesLoader::new().size_limit(1000).load_str(r#"{"providers":{"example":{"urlPattern":"["}}}"#).unwrap_err();
+    assert_matches!(err, LoadError::RegexSyntax { .. });
+}
+
+#[test]
+#[cfg(all(feature = "embed-social", not(feature = "embed-search"), not(feature = "embed-full")))]
+fn test_embedded_subset() {
+    let c = UrlCleaner::from_embedded_rules().unwrap();
+    assert!(c.rules.providers.len() < 40);
+    // global rules are always included
+    assert_eq!(c.clear_single_url_str("https://example.com/?utm_source=1").unwrap(), "https://example.com/");
+    assert_eq!(c.clear_single_url_str("https://twitter.com/?ref_src=1").unwrap(), "https://twitter.com/");
+    let google = "https://www.google.com/url?q=https%3A%2F%2Fpypi.org%2Fproject%2FUnalix";
+    assert_eq!(c.clear_single_url_str(google).unwrap(), google);
+}
+
+#[test]
+fn test_value_rules() {
+    let rules = r#"{"providers":{"example":{"urlPattern":".*","rules":["utm_source"],
+        "x-valueRules":[{"rule":"ref","value":"[0-9a-f]{16,}"}]}}}"#;
+    let cleaner = UrlCleaner::from_rules_str(rules).unwrap();
+    let test = |url: &str, expected: &str| assert_eq!(cleaner.clear_single_url_str(url).unwrap(), expected);
+    test("https://example.com/?ref=0123456789abcdef01&a=1", "https://example.com/?a=1");
+    test("https://example.com/?ref=main&a=1", "https://example.com/?ref=main&a=1");
+    test("https://example.com/?ref=xx0123456789abcdef01", "https://example.com/?ref=xx0123456789abcdef01");
+    test("https://example.com/#ref=0123456789abcdef01", "https://example.com/");
+
+    let effective = cleaner.rules_for("https://example.com/?ref=0123456789abcdef01&ref=main").unwrap();
+    assert_matches!(&effective[..], [EffectiveRule::RemoveParam { rule, .. }] if rule == "ref");
+    assert!(cleaner.to_adguard_filter().contains("! value rule for all URLs: ref = [0-9a-f]{16,}\n"));
+
+    let err = UrlCleaner::from_rules_str(r#"{"providers":{"example":{"urlPattern":".*","x-valueRules":[{"rule":"ref","value":"("}]}}}"#)
+        .unwrap_err();
+    assert_matches!(err, LoadError::RegexSyntax { .. });
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_provider_order() {
+    let rules = r#"{"providers": {
+        "a": {"urlPattern": "^https?://a\\.com", "rules": ["x"]},
+        "redirect": {"urlPattern": "^https?://r\\.com", "redirections": ["^https?://r\\.com/\\?to=([^&]*)"]},
+        "b": {"urlPattern": "", "rules": ["utm_source"]},
+        "c": {"urlPattern": "^https?://c\\.com", "rules": ["y"]}
+    }}"#;
+    let urls = [
+        "https://r.com/?to=https%3A%2F%2Fc.com%2F%3Fy%3D1%26utm_source%3Dz",
+        "https://c.com/?y=1&utm_source=z",
+        "https://a.com/?x=1&utm_source=z#y=1",
+    ];
+    let names = |c: &UrlCleaner| c.rules.providers.iter().map(|p| p.name.clone()).collect::<vec::Vec<_>>();
+
+    let c = UrlCleaner::from_rules_str(rules).unwrap();
+    let cleaned: vec::Vec<_> = urls.iter().map(|url| c.clear_single_url_str(url).unwrap().into_owned()).collect();
+    assert_eq!(cleaned, ["https://c.com/", "https://c.com/", "https://a.com/#y=1"]);
+    let profile = ProviderProfile::new();
+    for url in urls {
+        c.matching_providers(url).into_iter().for_each(|p| profile.hit(p));
+    }
+    assert_eq!(profile.hits(), [("b".into(), 3), ("a".into(), 1), ("c".into(), 1), ("redirect".into(), 1)]);
+    let profile = std::sync::Arc::new(ProviderProfile::new());
+    let c = UrlCleaner::from_rules_str(rules).unwrap().audit_sink(std::sync::Arc::clone(&profile));
+    for url in urls {
+        c.clear_single_url_str(url).unwrap();
+    }
+    assert_eq!(profile.hits(), [("b".into(), 3), ("c".into(), 2), ("a".into(), 1), ("redirect".into(), 1)]);
+
+    // providers only move between those with redirections or raw rules
+    let profile = [("c".into(), 5), ("b".into(), 2), ("redirect".into(), 9)].into_iter().collect();
+    let c = UrlCleaner::from_rules_str(rules).unwrap().provider_order(&profile);
+    assert_eq!(names(&c), ["a", "redirect", "c", "b"]);
+    let reordered: vec::Vec<_> = urls.iter().map(|url| c.clear_single_url_str(url).unwrap().into_owned()).collect();
+    assert_eq!(reordered, cleaned);
+    let c = UrlCleaner::from_rules_str(rules).unwrap().provider_order(&ProviderProfile::new());
+    assert_eq!(names(&c), ["a", "redirect", "b", "c"]);
+}
+
+#[test]
+#[cfg(feature = "serialize")]
+fn test_serialize_error() {
+    let err = UrlCleaner::from_rules_str("{}").unwrap_err();
+    let json = serde_json::to_value(&err).unwrap();
+    assert_eq!(json["code"], "rule_syntax");
+    assert_eq!(json["message"], err.to_string());
+
+    let err = LinkError::new("http://a b", Url::from_str("http://a b").unwrap_err());
+    assert_eq!(
+        serde_json::to_string(&err).unwrap(),
+        r#"{"url":"http://a b","error":{"code":"url_syntax","message":"error parsing url: invalid domain character"}}"#
+    );
+}
+
+#[test]
+fn test_error_split() {
+    struct Failing;
+    impl Cleaner for Failing {
+        fn clean<'a>(&self, _: &'a str) -> Result<Cow<'a, str>, Error> {
+            Err(Error::RulesVersionSyntax(ParseRulesVersionError))
+        }
+    }
+
+    let err = UrlCleaner::from_rules_str("{}").unwrap_err();
+    let (message, code) = (err.to_string(), err.code());
+    let err = Error::from(err);
+    assert_eq!((err.to_string(), err.code()), (message, code));
+    assert!(matches!(LoadError::try_from(err), Ok(LoadError::RuleSyntax(_))));
+
+    let cleaner = UrlCleaner::noop().max_url_len(10, LengthOverflow::Reject).scheme_handler("fail", Failing);
+    let err = cleaner.clear_single_url_str("https://example.com/").unwrap_err();
+    let (message, code) = (err.to_string(), err.code());
+    let err = Error::from(err);
+    assert_eq!((err.to_string(), err.code()), (message, code));
+    assert!(matches!(LoadError::try_from(err), Err(Error::UrlTooLong(20))));
+
+    let err = cleaner.clear_single_url_str("fail:x").unwrap_err();
+    assert_matches!(err, CleanError::SchemeHandler(ref e) if matches!(**e, Error::RulesVersionSyntax(_)));
+    let expected = Error::RulesVersionSyntax(ParseRulesVersionError).to_string();
+    assert_eq!((err.to_string(), err.code()), (expected, ErrorCode::RulesVersionSyntax));
+    assert!(matches!(Error::from(err), Error::RulesVersionSyntax(_)));
+}
+
+#[test]
+fn test_error_code() {
+    let codes: vec::Vec<_> = (0..=u16::from(u8::MAX)).filter_map(ErrorCode::from_u16).collect();
+    assert_eq!(codes.len(), 15);
+    for (i, code) in codes.iter().enumerate() {
+        assert_eq!(usize::from(code.as_u16()), i + 1);
+        assert_eq!(code.to_string(), code.as_str());
+    }
+    assert_eq!(ErrorCode::from_u16(0), None);
+    assert_eq!(ErrorCode::UrlSyntax.as_str(), "url_syntax");
+    assert_eq!(ErrorCode::Warc.as_u16(), 13);
+
+    let err = UrlCleaner::noop().clear_single_url_str("no url").unwrap_err();
+    assert_eq!(err.code(), ErrorCode::UrlSyntax);
+    assert_eq!(Error::from(err).code(), ErrorCode::UrlSyntax);
+}
+
+#[test]
+#[cfg(feature = "diagnostics")]
+fn test_rules_diagnostic() {
+    use miette::Diagnostic;
+
+    fn label<'a>(rules: &'a str, loader: &RulesLoader) -> Option<&'a str> {
+        let diagnostic = loader.load_str_with_diagnostics(rules).unwrap_err();
+        assert_eq!(diagnostic.code().unwrap().to_string(), diagnostic.error().code().as_str());
+        diagnostic.labels().and_then(|mut l| l.next()).map(|l| &rules[l.offset()..l.offset() + l.len()])
+    }
+    let loader = RulesLoader::new();
+    let rules = "{\"providers\": {\n  \"a\": {\"urlPattern\": \".*\", \"rules\": [\"ok\", \"x{\"]}}}";
+    assert_eq!(label(rules, &loader), Some("\"x{\""));
+    let rules = "{\"providers\": {\n  \"a\": {\"urlPattern\": \".*\" \"rules\": []}}}";
+    assert_eq!(label(rules, &loader), Some("\""));
+    let rules = r#"{"providers":{"a":{"urlPattern":"a","x-valueRules":[{"rule":"a","value":"\\w{1000}"}]}}}"#;
+    assert_eq!(label(rules, &loader.size_limit(1000)), Some(r#""\\w{1000}""#));
+    assert_eq!(label("", &RulesLoader::new()), Some(""));
+
+    let err = RulesLoader::new().load_str_with_diagnostics("[]").unwrap_err().into_error();
+    assert_matches!(err, LoadError::RuleSyntax(_));
+}
+
+#[test]
+fn test_warnings() {
+    let c = UrlCleaner::from_rules_str(
+        r#"{"providers":{"a":{"urlPattern":".*","completeProvider":false,"rules":["x"]},"b":{"urlPattern":".*","z":{"y":[1]},"forceRedirection":true}}}"#,
+    )
+    .unwrap();
+    assert_eq!(c.rules.providers[0].rules[0].as_str(), "x");
+    let warnings: vec::Vec<_> = c.warnings().iter().map(ToString::to_string).collect();
+    assert_eq!(
+        warnings,
+        [
+            "unknown field completeProvider in provider a",
+            "unknown field forceRedirection in provider b",
+            "unknown field z in provider b",
+        ]
+    );
+
+    let c = UrlCleaner::from_embedded_rules().unwrap();
+    assert!(c.warnings().iter().all(|w| matches!(w, RulesWarning::UnknownField { field, .. } if field == "completeProvider" || field == "forceRedirection")));
+}
+
+#[test]
+fn test_shared_patterns() {
+    let c = UrlCleaner::from_rules_str(
+        r#"{"providers":{"a":{"urlPattern":".*","rules":["utm_source","x"]},"b":{"urlPattern":".*","referralMarketing":["utm_source"]}}}"#,
+    )
+    .unwrap();
+    let [a, b] = &c.rules.providers[..] else { panic!() };
+    assert!(alloc::sync::Arc::ptr_eq(&a.url_pattern, &b.url_pattern));
+    assert!(alloc::sync::Arc::ptr_eq(&a.rules[0], &b.referral_marketing[0]));
+    assert!(!alloc::sync::Arc::ptr_eq(&a.rules[0], &a.rules[1]));
+}
+
+#[test]
+fn test_raw_rules_borrow() {
+    let json = br#"{"providers":{"a":{"urlPattern":"^https?://a\\.com","rules":["utm_source","x\\d"],"x-valueRules":[{"rule":"ref","value":".*"}]}}}"#;
+    let raw = RulesLoader::new().parse(json).unwrap();
+    let (name, provider) = &raw.providers[0];
+    assert_eq!(name, "a");
+    // strings with escape sequences have to be copied
+    assert_matches!(&provider.url_pattern, Cow::Owned(p) if p == r"^https?://a\.com");
+    assert_matches!(&provider.rules[..], [Cow::Borrowed("utm_source"), Cow::Owned(_)]);
+    assert_matches!(provider.value_rules[0].rule, Cow::Borrowed("ref"));
+
+    let c = UrlCleaner::from_rules_slice(json).unwrap();
+    assert_eq!(c.clear_single_url_str("https://a.com/?utm_source=1&x1=2&y=3").unwrap(), "https://a.com/?y=3");
+    assert_eq!(c.rules_version(), RulesVersion::of(json));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_build_report() {
+    let c = UrlCleaner::from_rules_str(
+        r#"{"providers":{"a":{"urlPattern":".*","rules":["x","y"]},"b":{"urlPattern":".*","rules":["x","\\w{100}"]}}}"#,
+    )
+    .unwrap();
+    let report = c.build_report();
+    assert_eq!(report.patterns, 4);
+    assert_eq!(report.slowest.len(), 4);
+    assert!(report.slowest.windows(2).all(|w| w[0].duration >= w[1].duration));
+    assert!(report.slowest.iter().any(|p| p.provider == "b" && p.pattern == r"\w{100}"));
+    assert!(report.slowest.iter().all(|p| p.pattern != "x" || p.provider == "a"));
+
+    let c = UrlCleaner::from_embedded_rules().unwrap();
+    assert_eq!(c.build_report().slowest.len(), BuildReport::SLOWEST);
+    assert!(c.build_report().patterns > BuildReport::SLOWEST);
+}
+
+#[test]
+fn test_is_google() {
+    for host in ["google.com", "google.de", "google.co.uk", "google.com.au"] {
+        assert!(wrappers::is_google(host), "{host}");
+    }
+    for host in ["google.example.de", "google.co.evil.uk", "notgoogle.com", "google.c", "google.com.", "google."] {
+        assert!(!wrappers::is_google(host), "{host}");
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_policy_env_overrides() {
+    let env = |vars: &'static [(&'static str, &'static str)]| {
+        move |name: &str| vars.iter().find(|(n, _)| *n == name).map(|(_, v)| (*v).to_string())
+    };
+    let file = PolicyFile::from_json(r#"{"disabled_providers": ["a"], "strict": true, "skip_schemes": ["data"]}"#).unwrap();
+
+    let policy = file.clone().overrides(env(&[])).unwrap();
+    assert_eq!(policy, file);
+    let policy = file
+        .clone()
+        .overrides(env(&[
+            ("CLEARURLS_DISABLED_PROVIDERS", " b, c ,"),
+            ("CLEARURLS_STRICT", ""),
+            ("CLEARURLS_SKIP_SCHEMES", ""),
+            ("CLEARURLS_CLEAN_MAILTO", "ON"),
+            ("CLEARURLS_PARAM_OVERFLOW", "truncate"),
+        ]))
+        .unwrap();
+    let expected = r#"{"disabled_providers": ["b", "c"], "strict": true, "skip_schemes": [], "clean_mailto": true,
+        "limits": {"param_overflow": "truncate"}}"#;
+    assert_eq!(policy, PolicyFile::from_json(expected).unwrap());
+
+    let err = file.clone().overrides(env(&[("CLEARURLS_STRICT", "maybe")])).unwrap_err();
+    assert_eq!(err.to_string(), r#"invalid policy: invalid value "maybe" for CLEARURLS_STRICT"#);
+    let err = file.overrides(env(&[("CLEARURLS_LENGTH_OVERFLOW", "truncate")])).unwrap_err();
+    assert_matches!(err, LoadError::InvalidPolicy(_));
+}
+
+#[test]
+#[cfg(feature = "linkify")]
+fn test_trimmed_len() {
+    let trimmed = |url: &'static str| &url[..repair::trimmed_len(url, "~")];
+    assert_eq!(trimmed("https://example.com/a)."), "https://example.com/a");
+    assert_eq!(trimmed("https://example.com/a_(b))"), "https://example.com/a_(b)");
+    assert_eq!(trimmed("https://example.com/?a=[1]\","), "https://example.com/?a=[1]");
+    assert_eq!(trimmed("https://example.com/ä!?"), "https://example.com/ä");
+    assert_eq!(trimmed("..."), "");
+    assert_eq!(trimmed("https://example.com/?a=1~~"), "https://example.com/?a=1");
+}
+
+#[test]
+#[cfg(feature = "linkify")]
+fn test_encode_spaces() {
+    assert_eq!(repair::encode_spaces("https://example.com/a b?c=d e"), "https://example.com/a%20b?c=d%20e");
+    assert_matches!(repair::encode_spaces("https://example.com/"), alloc::borrow::Cow::Borrowed(_));
+}
+
 #[cfg(feature = "std")]
 fn error_eq<T: std::error::Error + PartialEq + 'static>(
     x: &T,
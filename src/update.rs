@@ -0,0 +1,59 @@
+use alloc::boxed::Box;
+
+use crate::{Error, RulesVersion, UrlCleaner};
+
+/// Where the `ClearURLs` project publishes the hash of its current rules.
+pub const UPSTREAM_RULES_HASH_URL: &str = "https://rules2.clearurls.xyz/rules.minify.hash";
+
+/// Whether newer rules than the loaded ones are available, see [`UrlCleaner::check_for_rules_update`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UpdateStatus {
+    /// The loaded rules are the published ones.
+    Current,
+    /// Different rules are published.
+    Available {
+        /// The version of the published rules
+        version: RulesVersion,
+    },
+}
+
+impl UpdateStatus {
+    /// Compare the version of the loaded rules with the published version.
+    #[must_use]
+    pub fn compare(current: RulesVersion, published: RulesVersion) -> Self {
+        if current == published {
+            Self::Current
+        } else {
+            Self::Available { version: published }
+        }
+    }
+}
+
+impl UrlCleaner {
+    /// Check whether the `ClearURLs` project publishes different rules than the loaded ones.
+    ///
+    /// This only downloads the hash from [`UPSTREAM_RULES_HASH_URL`], not the rules themselves.
+    ///
+    /// # Errors
+    /// If the hash cannot be downloaded or is malformed.
+    pub fn check_for_rules_update(&self) -> Result<UpdateStatus, Error> {
+        self.check_for_rules_update_at(UPSTREAM_RULES_HASH_URL)
+    }
+
+    /// Like [`check_for_rules_update`][Self::check_for_rules_update], but with the hash
+    /// downloaded from `hash_url`, for example a mirror.
+    ///
+    /// The response must be the hexadecimal SHA-256 of the rules, see [`RulesVersion`].
+    ///
+    /// # Errors
+    /// If the hash cannot be downloaded or is malformed.
+    pub fn check_for_rules_update_at(&self, hash_url: &str) -> Result<UpdateStatus, Error> {
+        let body = ureq::get(hash_url)
+            .call()
+            .map_err(Box::new)?
+            .into_string()
+            .map_err(|e| Box::new(e.into()))?;
+        Ok(UpdateStatus::compare(self.rules_version(), body.parse()?))
+    }
+}
@@ -0,0 +1,99 @@
+use core::fmt::{Display, Formatter};
+use core::str::FromStr;
+
+use sha2::{Digest, Sha256};
+
+/// Identifies a version of the rules by the SHA-256 hash of their JSON.
+///
+/// This is the same hash that the `ClearURLs` project publishes next to its rules,
+/// so it can be used to check whether a newer version is available.
+/// It is displayed and parsed as lowercase hexadecimal.
+///
+/// # Example
+/// ```
+/// # use clearurls::{RulesVersion, UrlCleaner};
+/// # fn main() -> Result<(), clearurls::Error> {
+/// let cleaner = UrlCleaner::from_embedded_rules()?;
+/// assert_eq!(cleaner.rules_version(), RulesVersion::EMBEDDED);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RulesVersion {
+    sha256: [u8; 32],
+}
+
+impl RulesVersion {
+    /// The version of the rules embedded in this library,
+    /// see [`UrlCleaner::from_embedded_rules`][crate::UrlCleaner::from_embedded_rules].
+    ///
+    /// This always refers to all upstream rules, even if only a subset is embedded.
+    pub const EMBEDDED: Self = Self {
+        sha256: include!(concat!(env!("OUT_DIR"), "/data.minify.json.sha256")),
+    };
+
+    /// The commit of the upstream repository <https://github.com/ClearURLs/Rules> that the embedded rules were
+    /// taken from, if it was recorded when they were updated.
+    pub const EMBEDDED_COMMIT: Option<&'static str> = include!(concat!(env!("OUT_DIR"), "/data.minify.json.commit"));
+
+    /// The date of [`EMBEDDED_COMMIT`][Self::EMBEDDED_COMMIT] as `YYYY-MM-DD`, if it was recorded when the rules
+    /// were updated.
+    pub const EMBEDDED_DATE: Option<&'static str> = include!(concat!(env!("OUT_DIR"), "/data.minify.json.date"));
+
+    /// Compute the version of the given JSON.
+    #[must_use]
+    pub fn of(rules: &[u8]) -> Self {
+        Self {
+            sha256: Sha256::digest(rules).into(),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn from_hasher(hasher: Sha256) -> Self {
+        Self {
+            sha256: hasher.finalize().into(),
+        }
+    }
+
+    /// The SHA-256 hash of the rules.
+    #[must_use]
+    pub const fn sha256(&self) -> &[u8; 32] {
+        &self.sha256
+    }
+}
+
+impl Display for RulesVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        self.sha256.iter().try_for_each(|b| write!(f, "{b:02x}"))
+    }
+}
+
+/// The error when parsing a [`RulesVersion`] from a string that isn't 64 hexadecimal digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseRulesVersionError;
+
+impl Display for ParseRulesVersionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str("rules version must be 64 hexadecimal digits")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseRulesVersionError {}
+
+impl FromStr for RulesVersion {
+    type Err = ParseRulesVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.len() != 64 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ParseRulesVersionError);
+        }
+        let mut sha256 = [0; 32];
+        for (byte, hex) in sha256.iter_mut().zip(s.as_bytes().chunks(2)) {
+            let hex = core::str::from_utf8(hex).map_err(|_| ParseRulesVersionError)?;
+            *byte = u8::from_str_radix(hex, 16).map_err(|_| ParseRulesVersionError)?;
+        }
+        Ok(Self { sha256 })
+    }
+}
@@ -0,0 +1,275 @@
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use std::io::{self, BufRead, Read, Write};
+
+use sha2::{Digest, Sha256};
+
+use crate::{Error, LinkError, UrlCleaner};
+
+/// The headers of a WARC record that contain a URL to clean.
+const URI_HEADERS: [&str; 2] = ["WARC-Target-URI", "WARC-Refers-To-Target-URI"];
+
+/// What was done to a WARC file, see [`UrlCleaner::clean_warc`].
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct WarcSummary {
+    /// The number of records
+    pub records: usize,
+    /// The number of records whose header or payload was changed
+    pub changed: usize,
+    /// The URLs that couldn't be cleaned, which are left as they are
+    pub errors: Vec<LinkError>,
+}
+
+/// A WARC record, whose header fields are kept in order.
+struct Record {
+    version: String,
+    headers: Vec<(String, String)>,
+    block: Vec<u8>,
+}
+
+impl Record {
+    /// Read the next record, or `None` at the end of the input.
+    fn read(input: &mut impl BufRead) -> io::Result<Option<Self>> {
+        let mut line = String::new();
+        // the line breaks after the previous block are skipped as well
+        while line.trim_end().is_empty() {
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+        }
+        let version = line.trim_end().to_string();
+        if !version.starts_with("WARC/") {
+            return Err(invalid(format!("expected a WARC record, found {version:?}")));
+        }
+        let mut headers: Vec<(String, String)> = Vec::new();
+        loop {
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                return Err(invalid("the header of a record is incomplete".into()));
+            }
+            let field = line.trim_end_matches(['\r', '\n']);
+            if field.is_empty() {
+                break;
+            } else if let (true, Some((_, value))) = (field.starts_with([' ', '\t']), headers.last_mut()) {
+                // a folded line continues the value of the previous field
+                value.push(' ');
+                value.push_str(field.trim());
+            } else {
+                let (name, value) = field.split_once(':').ok_or_else(|| invalid(format!("invalid header {field:?}")))?;
+                headers.push((name.trim().into(), value.trim().into()));
+            }
+        }
+        let mut record = Self { version, headers, block: Vec::new() };
+        let length = record
+            .header("Content-Length")
+            .and_then(|l| l.parse().ok())
+            .ok_or_else(|| invalid("a record has no valid Content-Length".into()))?;
+        input.take(length).read_to_end(&mut record.block)?;
+        if record.block.len() as u64 != length {
+            return Err(invalid("a record is shorter than its Content-Length".into()));
+        }
+        Ok(Some(record))
+    }
+
+    fn write(&self, output: &mut impl Write) -> io::Result<()> {
+        write!(output, "{}\r\n", self.version)?;
+        for (name, value) in &self.headers {
+            write!(output, "{name}: {value}\r\n")?;
+        }
+        output.write_all(b"\r\n")?;
+        output.write_all(&self.block)?;
+        output.write_all(b"\r\n\r\n")
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+
+    /// Replace the value of a header field, if the record has it.
+    fn replace_header(&mut self, name: &str, value: String) {
+        if let Some((_, v)) = self.headers.iter_mut().find(|(n, _)| n.eq_ignore_ascii_case(name)) {
+            *v = value;
+        }
+    }
+
+    /// The position of the payload in the block, if it's HTML that can be changed.
+    ///
+    /// For `response` records, that is the body of the HTTP response, unless it's compressed or chunked.
+    fn html_payload(&self) -> Option<usize> {
+        let content_type = self.header("Content-Type").unwrap_or_default();
+        match self.header("WARC-Type")? {
+            "resource" if is_html(content_type) => Some(0),
+            "response" if content_type.starts_with("application/http") => {
+                let end = self.block.windows(4).position(|w| w == b"\r\n\r\n")?;
+                let http = core::str::from_utf8(&self.block[..end]).ok()?;
+                let mut html = false;
+                for (name, value) in http.lines().skip(1).filter_map(|l| l.split_once(':')) {
+                    match name.trim().to_ascii_lowercase().as_str() {
+                        "content-type" => html = is_html(value),
+                        "transfer-encoding" => return None,
+                        "content-encoding" if !value.trim().eq_ignore_ascii_case("identity") => return None,
+                        _ => {}
+                    }
+                }
+                html.then_some(end + 4)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl UrlCleaner {
+    /// Clean the URLs in the records of a WARC file, as written by web archiving tools, to store captures without
+    /// tracking parameters.
+    ///
+    /// The `WARC-Target-URI` and `WARC-Refers-To-Target-URI` of every record are cleaned, as well as the URLs in the
+    /// HTML payloads of `response` and `resource` records, where `&amp;` in URLs is understood. Payloads that are
+    /// compressed or chunked are left as they are. If a block changes, its `Content-Length` is updated,
+    /// as is that of the HTTP response, and its `WARC-Block-Digest` and `WARC-Payload-Digest` are replaced
+    /// with SHA-256 digests. `revisit` records that refer to a changed payload by its digest aren't updated.
+    ///
+    /// The input must be uncompressed. Read `.warc.gz` files through a decoder for multiple gzip members,
+    /// like `flate2::read::MultiGzDecoder`.
+    ///
+    /// # Example
+    /// ```
+    /// # use clearurls::UrlCleaner;
+    /// # fn main() -> Result<(), clearurls::Error> {
+    /// let cleaner = UrlCleaner::from_embedded_rules()?;
+    /// let warc = "WARC/1.1\r\nWARC-Type: request\r\nWARC-Target-URI: https://example.com/?utm_source=x\r\n\
+    ///     Content-Length: 0\r\n\r\n\r\n\r\n";
+    /// let mut cleaned = Vec::new();
+    /// let summary = cleaner.clean_warc(warc.as_bytes(), &mut cleaned)?;
+    /// assert_eq!(summary.changed, 1);
+    /// assert!(String::from_utf8(cleaned).unwrap().contains("WARC-Target-URI: https://example.com/\r\n"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// If reading or writing fails, or if the input isn't a valid WARC file.
+    /// URLs that can't be cleaned are left as they are and returned in the [`WarcSummary`].
+    pub fn clean_warc<R: BufRead, W: Write>(&self, mut input: R, mut output: W) -> Result<WarcSummary, Error> {
+        let mut summary = WarcSummary::default();
+        while let Some(mut record) = Record::read(&mut input).map_err(Error::Warc)? {
+            summary.records += 1;
+            if self.clean_record(&mut record, &mut summary.errors) {
+                summary.changed += 1;
+            }
+            record.write(&mut output).map_err(Error::Warc)?;
+        }
+        output.flush().map_err(Error::Warc)?;
+        Ok(summary)
+    }
+
+    /// Clean the URIs and the HTML payload of a record, and tell whether anything changed.
+    fn clean_record(&self, record: &mut Record, errors: &mut Vec<LinkError>) -> bool {
+        let mut changed = false;
+        for (name, value) in &mut record.headers {
+            if !URI_HEADERS.iter().any(|h| h.eq_ignore_ascii_case(name)) {
+                continue;
+            }
+            // WARC 1.0 writes the URI in angle brackets
+            let bracketed = value.strip_prefix('<').and_then(|v| v.strip_suffix('>'));
+            let (uri, is_bracketed) = (bracketed.unwrap_or(value), bracketed.is_some());
+            let cleaned = match self.clear_single_url_str(uri) {
+                Ok(Cow::Owned(cleaned)) if cleaned != uri => cleaned,
+                Ok(_) => continue,
+                Err(e) => {
+                    errors.push(LinkError::new(uri, e));
+                    continue;
+                }
+            };
+            *value = if is_bracketed { format!("<{cleaned}>") } else { cleaned };
+            changed = true;
+        }
+        let Some(start) = record.html_payload() else {
+            return changed;
+        };
+        let html = core::str::from_utf8(&record.block[start..]).ok();
+        let Some(payload) = html.and_then(|html| self.clean_html(html, errors)) else {
+            return changed;
+        };
+        let mut block = Vec::with_capacity(start + payload.len());
+        if start > 0 {
+            // the HTTP header, without the empty line that ends it
+            let http = String::from_utf8_lossy(&record.block[..start - 2]);
+            for line in http.split_inclusive("\r\n") {
+                match line.split_once(':') {
+                    Some((name, _)) if name.trim().eq_ignore_ascii_case("Content-Length") => {
+                        block.extend_from_slice(format!("{name}: {}\r\n", payload.len()).as_bytes());
+                    }
+                    _ => block.extend_from_slice(line.as_bytes()),
+                }
+            }
+            block.extend_from_slice(b"\r\n");
+        }
+        block.extend_from_slice(payload.as_bytes());
+        record.replace_header("WARC-Payload-Digest", digest(payload.as_bytes()));
+        record.replace_header("WARC-Block-Digest", digest(&block));
+        record.replace_header("Content-Length", block.len().to_string());
+        record.block = block;
+        true
+    }
+
+    /// Clean the URLs in HTML, where `&` in URLs is written as `&amp;`, and return it if anything changed.
+    fn clean_html(&self, html: &str, errors: &mut Vec<LinkError>) -> Option<String> {
+        let mut result = String::new();
+        let mut last = 0;
+        for link in self.document_links(html, &self.link_finder) {
+            let link_str = &html[link.clone()];
+            let escaped = link_str.contains("&amp;");
+            let url = if escaped { Cow::Owned(link_str.replace("&amp;", "&")) } else { Cow::Borrowed(link_str) };
+            match self.clear_document_url(&url) {
+                Ok(cleaned) if cleaned != url => {
+                    result.push_str(&html[last..link.start]);
+                    result.push_str(&if escaped { Cow::Owned(cleaned.replace('&', "&amp;")) } else { cleaned });
+                    last = link.end;
+                }
+                Ok(_) => {}
+                Err(e) => errors.push(LinkError::new(&url, e)),
+            }
+        }
+        if last == 0 {
+            return None;
+        }
+        result.push_str(&html[last..]);
+        Some(result)
+    }
+}
+
+fn is_html(content_type: &str) -> bool {
+    let media_type = content_type.split(';').next().unwrap_or_default().trim();
+    media_type.eq_ignore_ascii_case("text/html") || media_type.eq_ignore_ascii_case("application/xhtml+xml")
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// A SHA-256 digest in the labelled base32 form of WARC headers, like `sha256:ABC...`.
+fn digest(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+    let mut digest = String::from("sha256:");
+    let (mut buffer, mut bits) = (0_u16, 0);
+    for &byte in Sha256::digest(bytes).as_slice() {
+        buffer = (buffer << 8) | u16::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            digest.push(char::from(ALPHABET[usize::from((buffer >> bits) & 31)]));
+        }
+    }
+    if bits > 0 {
+        digest.push(char::from(ALPHABET[usize::from((buffer << (5 - bits)) & 31)]));
+    }
+    // padded to a multiple of 8 characters
+    let padding = (8 - (digest.len() - "sha256:".len()) % 8) % 8;
+    digest.push_str(&"=".repeat(padding));
+    digest
+}
@@ -0,0 +1,162 @@
+use alloc::borrow::Cow;
+#[cfg(feature = "enterprise-wrappers")]
+use alloc::string::String;
+use core::str::FromStr;
+
+#[cfg(feature = "enterprise-wrappers")]
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+#[cfg(feature = "enterprise-wrappers")]
+use base64::{alphabet, Engine as _};
+#[cfg(feature = "enterprise-wrappers")]
+use percent_encoding::percent_decode_str;
+use url::{form_urlencoded, Url};
+
+use crate::hooks::{Action, Observer};
+use crate::inspect::{Change, Reason};
+use crate::MAX_NESTING_DEPTH;
+
+/// Which kinds of links that are wrapped around another URL are unwrapped, see
+/// [`UrlCleaner::unwrap_redirect_pages`][crate::UrlCleaner::unwrap_redirect_pages] and
+/// [`UrlCleaner::unwrap_enterprise_wrappers`][crate::UrlCleaner::unwrap_enterprise_wrappers].
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Unwrapping {
+    pub(crate) redirect_pages: bool,
+    #[cfg(feature = "enterprise-wrappers")]
+    pub(crate) enterprise: bool,
+}
+
+/// A link that is wrapped around the original URL.
+pub(crate) struct Wrapped<'a> {
+    /// The name of the wrapper, which is reported as the provider
+    pub(crate) name: &'static str,
+    /// What identifies the wrapper, which is reported as the redirection rule
+    pub(crate) rule: &'static str,
+    /// The target as it's written in the link
+    pub(crate) raw_target: &'a str,
+    /// The decoded target
+    pub(crate) target: Url,
+}
+
+impl Unwrapping {
+    /// Replace a wrapped link with its target, as often as it's wrapped.
+    pub(crate) fn unwrap_all<'a>(self, mut url: Cow<'a, Url>, observer: &Observer<'_>) -> Cow<'a, Url> {
+        for _ in 0..MAX_NESTING_DEPTH {
+            let Some(wrapped) = self.unwrap(&url) else {
+                break;
+            };
+            let change = || Change {
+                provider: wrapped.name.into(),
+                reason: Reason::Redirection { rule: wrapped.rule.into(), target: wrapped.raw_target.into() },
+            };
+            if !observer.allows(&url, Action::Redirection { target: wrapped.raw_target }, change) {
+                break;
+            }
+            #[cfg(feature = "tracing")]
+            tracing::debug!(target = %wrapped.target, "unwrapping {}", wrapped.name);
+            url = Cow::Owned(wrapped.target);
+        }
+        url
+    }
+
+    /// Decode the target of a wrapped link.
+    ///
+    /// Returns `None` if `url` isn't a wrapped link of an enabled kind, or if its target can't be decoded to a URL.
+    pub(crate) fn unwrap(self, url: &Url) -> Option<Wrapped<'_>> {
+        let host = url.host_str()?;
+        #[cfg(feature = "enterprise-wrappers")]
+        if self.enterprise {
+            if host.ends_with(".safelinks.protection.outlook.com")
+                || host.ends_with(".safelinks.protection.office365.us")
+            {
+                return unwrap_safelinks(url);
+            } else if matches!(host, "urldefense.com" | "urldefense.us") {
+                return unwrap_urldefense(url);
+            }
+        }
+        if !self.redirect_pages {
+            return None;
+        }
+        let host = host.strip_prefix("www.").unwrap_or(host);
+        match (host, url.path()) {
+            (_, "/url") if is_google(host) => {
+                // newer links have an empty `q` and the target in `url`
+                param(url, "url", "googleRedirect", "google.com/url?url=")
+                    .or_else(|| param(url, "q", "googleRedirect", "google.com/url?q="))
+            }
+            ("l.facebook.com" | "lm.facebook.com" | "m.facebook.com", "/l.php") => {
+                param(url, "u", "facebookRedirect", "l.facebook.com/l.php?u=")
+            }
+            ("youtube.com" | "m.youtube.com", "/redirect") => {
+                param(url, "q", "youtubeRedirect", "youtube.com/redirect?q=")
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Whether the host is a Google search domain, like `google.com`, `google.de` or `google.co.uk`.
+pub(crate) fn is_google(host: &str) -> bool {
+    let is_tld = |label: &str| label.len() >= 2 && label.bytes().all(|b| b.is_ascii_lowercase());
+    match host.strip_prefix("google.").map(|tld| tld.split_once('.')) {
+        Some(Some((second_level, tld))) => matches!(second_level, "co" | "com") && is_tld(tld),
+        Some(None) => is_tld(&host["google.".len()..]),
+        None => false,
+    }
+}
+
+/// The target in a query parameter of a redirect page, if it's an absolute `http` or `https` URL.
+///
+/// The value is decoded exactly once, so that targets that contain percent-encoded characters themselves stay intact.
+fn param<'a>(url: &'a Url, name: &str, wrapper: &'static str, rule: &'static str) -> Option<Wrapped<'a>> {
+    let pair = url.query()?.split('&').find(|p| p.strip_prefix(name).is_some_and(|v| v.starts_with('=')))?;
+    let (_, target) = form_urlencoded::parse(pair.as_bytes()).next()?;
+    let target = Url::from_str(&target).ok().filter(|t| matches!(t.scheme(), "http" | "https") && t.has_host())?;
+    Some(Wrapped { name: wrapper, rule, raw_target: &pair[name.len() + 1..], target })
+}
+
+/// Outlook `SafeLinks`, like `https://nam12.safelinks.protection.outlook.com/?url=https%3A%2F%2F...&data=...`.
+#[cfg(feature = "enterprise-wrappers")]
+fn unwrap_safelinks(url: &Url) -> Option<Wrapped<'_>> {
+    param(url, "url", "safeLinks", "safelinks.protection.outlook.com/?url=")
+}
+
+/// Proofpoint URL Defense version 3, like `https://urldefense.com/v3/__https://example.com/*__;Lw!!...$`.
+///
+/// Characters that would break the link are replaced with `*`, and the replaced characters are appended in base64,
+/// between `__;` and `!`. A run of several replaced characters is written as `**` and a letter for its length.
+#[cfg(feature = "enterprise-wrappers")]
+fn unwrap_urldefense(url: &Url) -> Option<Wrapped<'_>> {
+    const CONFIG: GeneralPurposeConfig =
+        GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+    const ENGINE: GeneralPurpose = GeneralPurpose::new(&alphabet::URL_SAFE, CONFIG);
+    const RUN_LENGTHS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+    // the wrapped URL may contain a query, so this can't go by the path,
+    // and the separators may have been percent-encoded when the query was serialized
+    let s = url.as_str();
+    let start = s.find("/v3/__")? + "/v3/__".len();
+    let decoded = percent_decode_str(&s[start..]).decode_utf8().ok()?;
+    let end = decoded.find("__;")?;
+    let replaced = ENGINE.decode(decoded[end + "__;".len()..].split('!').next()?).ok()?;
+    let replaced = String::from_utf8(replaced).ok()?;
+    let mut replaced = replaced.chars();
+
+    let mut target = String::with_capacity(end);
+    let mut chars = decoded[..end].chars();
+    while let Some(c) = chars.next() {
+        if c != '*' {
+            target.push(c);
+        } else if chars.as_str().starts_with('*') {
+            chars.next();
+            let run = chars.next()?;
+            let len = RUN_LENGTHS.iter().position(|&b| char::from(b) == run)? + 2;
+            for _ in 0..len {
+                target.push(replaced.next()?);
+            }
+        } else {
+            target.push(replaced.next()?);
+        }
+    }
+    let raw_target = s[start..].split("__").next()?;
+    Some(Wrapped { name: "urlDefense", rule: "urldefense.com/v3/__", raw_target, target: Url::from_str(&target).ok()? })
+}
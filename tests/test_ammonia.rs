@@ -0,0 +1,117 @@
+#[cfg(feature = "ammonia")]
+#[test]
+fn test_ammonia_filter() {
+    use std::sync::Arc;
+
+    use clearurls::UrlCleaner;
+
+    let cleaner = Arc::new(UrlCleaner::from_embedded_rules().unwrap());
+    let mut builder = ammonia::Builder::default();
+    builder.attribute_filter(UrlCleaner::ammonia_filter(Arc::clone(&cleaner))).link_rel(None);
+
+    let test = |input: &str, expected: &str| assert_eq!(builder.clean(input).to_string(), expected);
+    test(
+        r#"<a href="https://example.com/?utm_source=x&amp;page=2" title="https://example.com/?utm_source=x">a</a>"#,
+        r#"<a href="https://example.com/?page=2" title="https://example.com/?utm_source=x">a</a>"#,
+    );
+    test(r#"<img src="https://example.com/a.png?fbclid=1" alt="">"#, r#"<img src="https://example.com/a.png" alt="">"#);
+    test(r#"<blockquote cite="https://example.com/?utm_medium=x">q</blockquote>"#, r#"<blockquote cite="https://example.com/">q</blockquote>"#);
+    // relative and broken URLs are kept
+    test(r#"<a href="/page?utm_source=x">a</a>"#, r#"<a href="/page?utm_source=x">a</a>"#);
+    test(
+        r#"<a href="https://google.co.uk/url?foo=bar&amp;q=http%F0">a</a>"#,
+        r#"<a href="https://google.co.uk/url?foo=bar&amp;q=http%F0">a</a>"#,
+    );
+    // the sanitizer still removes what it doesn't allow
+    test(r#"<a href="javascript:alert(1)" onclick="x()">a</a>"#, "<a>a</a>");
+
+    assert_eq!(cleaner.clean_html_attribute("div", "title", "https://example.com/?utm_source=x"), "https://example.com/?utm_source=x");
+    assert_eq!(cleaner.clean_html_attribute("object", "data", "https://example.com/?utm_source=x"), "https://example.com/");
+}
+
+#[cfg(feature = "ammonia")]
+#[test]
+fn test_clean_srcset_and_style() {
+    use std::sync::Arc;
+
+    use clearurls::UrlCleaner;
+
+    let cleaner = Arc::new(UrlCleaner::from_embedded_rules().unwrap());
+    let srcset = |input: &str, expected: &str| assert_eq!(cleaner.clean_html_attribute("img", "srcset", input), expected);
+    srcset(
+        "https://a.com/s.png?utm_source=x 1x, https://a.com/l.png?utm_source=y 2x",
+        "https://a.com/s.png 1x, https://a.com/l.png 2x",
+    );
+    srcset("https://a.com/a.png?utm_source=x, https://a.com/b.png?fbclid=1", "https://a.com/a.png, https://a.com/b.png");
+    srcset("https://a.com/img?w=1,2&utm_source=x 100w", "https://a.com/img?w=1%2C2 100w");
+    srcset(
+        "  https://a.com/a.png?utm_source=x   1x (a, b) ,\n https://a.com/b.png?utm_source=x 2x ",
+        "  https://a.com/a.png   1x (a, b) ,\n https://a.com/b.png 2x ",
+    );
+    srcset("/a.png?utm_source=x 1x, https://a.com/b.png 2x", "/a.png?utm_source=x 1x, https://a.com/b.png 2x");
+    assert_eq!(cleaner.clean_html_attribute("a", "href", "//a.com/?utm_source=x"), "//a.com/?utm_source=x");
+    let relative = UrlCleaner::from_embedded_rules().unwrap().protocol_relative(true);
+    assert_eq!(relative.clean_html_attribute("a", "href", "//a.com/?utm_source=x"), "//a.com/");
+    assert_eq!(relative.clean_html_attribute("img", "srcset", "//a.com/a.png?fbclid=1 1x"), "//a.com/a.png 1x");
+
+    let style = |input: &str, expected: &str| assert_eq!(cleaner.clean_html_attribute("div", "style", input), expected);
+    style(
+        r#"background: url("https://a.com/bg.png?utm_source=x") no-repeat; border-image: URL( https://a.com/b.png?fbclid=1 )"#,
+        r#"background: url("https://a.com/bg.png") no-repeat; border-image: URL( https://a.com/b.png )"#,
+    );
+    style("background: url('https://a.com/bg.png?utm_source=x')", "background: url('https://a.com/bg.png')");
+    // escapes and unterminated values are left as they are
+    style(r"background: url(https://a.com/a\(b.png?utm_source=x)", r"background: url(https://a.com/a\(b.png?utm_source=x)");
+    style(r#"background: url("https://a.com/?utm_source=x"#, r#"background: url("https://a.com/?utm_source=x"#);
+    style("color: red", "color: red");
+
+    let mut builder = ammonia::Builder::default();
+    builder.attribute_filter(UrlCleaner::ammonia_filter(cleaner)).add_tag_attributes("img", ["srcset"]);
+    assert_eq!(
+        builder.clean(r#"<img srcset="https://a.com/s.png?utm_source=x 1x, https://a.com/l.png 2x">"#).to_string(),
+        r#"<img srcset="https://a.com/s.png 1x, https://a.com/l.png 2x">"#,
+    );
+}
+
+#[cfg(feature = "ammonia")]
+#[test]
+fn test_clean_meta_and_canonical() {
+    use std::sync::Arc;
+
+    use clearurls::UrlCleaner;
+
+    let cleaner = Arc::new(UrlCleaner::from_embedded_rules().unwrap());
+    let meta = |input: &str, expected: &str| assert_eq!(cleaner.clean_html_attribute("meta", "content", input), expected);
+    meta("0;url=https://example.com/?utm_source=x", "0;url=https://example.com/");
+    meta("5, URL = 'https://example.com/?utm_campaign=x' ", "5, URL = 'https://example.com/' ");
+    meta(r#"0; "https://example.com/?fbclid=1""#, r#"0; "https://example.com/""#);
+    meta("3; url=https://example.com/a b?utm_source=x ", "3; url=https://example.com/a%20b ");
+    meta(" https://example.com/post?utm_medium=social", " https://example.com/post");
+    // no URL, or a relative one
+    meta("5", "5");
+    meta("0;url=/next?utm_source=x", "0;url=/next?utm_source=x");
+    meta("width=device-width, initial-scale=1", "width=device-width, initial-scale=1");
+    assert_eq!(cleaner.clean_html_attribute("div", "content", "https://example.com/?utm_source=x"), "https://example.com/?utm_source=x");
+
+    let mut builder = ammonia::Builder::default();
+    builder
+        .attribute_filter(UrlCleaner::ammonia_filter(cleaner))
+        .add_tags(["meta", "link"])
+        .add_tag_attributes("meta", ["http-equiv", "property", "content"])
+        .add_tag_attributes("link", ["rel", "href"])
+        .link_rel(None);
+    assert_eq!(
+        builder
+            .clean(concat!(
+                r#"<meta http-equiv="refresh" content="0;url=https://example.com/?utm_source=x">"#,
+                r#"<meta property="og:url" content="https://example.com/?utm_source=x">"#,
+                r#"<link rel="canonical" href="https://example.com/?utm_source=x">"#,
+            ))
+            .to_string(),
+        concat!(
+            r#"<meta http-equiv="refresh" content="0;url=https://example.com/">"#,
+            r#"<meta property="og:url" content="https://example.com/">"#,
+            r#"<link rel="canonical" href="https://example.com/">"#,
+        ),
+    );
+}
@@ -0,0 +1,52 @@
+#![cfg(feature = "testing")]
+
+use arbitrary::{Arbitrary, Unstructured};
+use clearurls::{AdversarialUrl, ArbitraryRules, UrlCleaner};
+use url::Url;
+
+/// Deterministic bytes for `Unstructured`, so that failures can be reproduced.
+fn bytes(seed: u64, len: usize) -> Vec<u8> {
+    let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state.to_le_bytes()[0]
+        })
+        .collect()
+}
+
+#[test]
+fn test_arbitrary_rules_and_urls() {
+    let embedded = UrlCleaner::from_embedded_rules().unwrap();
+    for seed in 0..64 {
+        let data = bytes(seed, 4096);
+        let mut u = Unstructured::new(&data);
+        let rules = ArbitraryRules::arbitrary(&mut u).unwrap();
+        let json = rules.to_json();
+        let cleaner = UrlCleaner::from_rules_str(&json).unwrap_or_else(|e| panic!("invalid rules {json}: {e}"));
+        assert_eq!(cleaner.warnings(), []);
+        for _ in 0..8 {
+            let url = AdversarialUrl::arbitrary(&mut u).unwrap();
+            for cleaner in [&cleaner, &embedded] {
+                if let Ok(cleaned) = cleaner.clear_single_url_str(url.as_ref()) {
+                    assert!(Url::parse(&cleaned).is_ok(), "{url} was cleaned to the invalid URL {cleaned}");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_arbitrary_rules_json() {
+    let data = bytes(7, 1024);
+    let rules = ArbitraryRules::arbitrary(&mut Unstructured::new(&data)).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&rules.to_json()).unwrap();
+    let providers = json["providers"].as_object().unwrap();
+    assert_eq!(providers.len(), rules.providers.len());
+    for (name, provider) in &rules.providers {
+        assert_eq!(providers[name]["urlPattern"], provider.url_pattern.as_str());
+        assert_eq!(providers[name]["rules"].as_array().unwrap().len(), provider.rules.len());
+    }
+}
@@ -0,0 +1,103 @@
+use std::sync::{Arc, Mutex};
+
+use clearurls::{AuditSink, CleanReport, UrlCleaner};
+
+#[derive(Default)]
+struct Log(Mutex<Vec<(String, String, CleanReport)>>);
+
+impl AuditSink for Log {
+    fn record(&self, original: &str, cleaned: &str, details: &CleanReport) {
+        self.0.lock().unwrap().push((original.into(), cleaned.into(), details.clone()));
+    }
+}
+
+#[test]
+fn test_audit_sink() {
+    let log = Arc::new(Log::default());
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().audit_sink(Arc::clone(&log));
+    let url = "https://example.com/?utm_source=abc&page=2";
+    assert_eq!(cleaner.clear_single_url_str(url).unwrap(), "https://example.com/?page=2");
+    cleaner.clear_single_url_str("https://example.com/?page=2").unwrap();
+    // checks don't modify anything
+    assert!(!cleaner.is_clean(url).unwrap());
+    assert!(cleaner.same_after_cleaning(url, "https://example.com/?page=2").unwrap());
+    cleaner.clear_single_url_explained(url).unwrap();
+
+    let records = log.0.lock().unwrap();
+    assert_eq!(records.len(), 2);
+    for (original, cleaned, details) in records.iter() {
+        assert_eq!(original, url);
+        assert_eq!(cleaned, "https://example.com/?page=2");
+        assert_eq!(details.changes.len(), 1);
+        assert_eq!(details.changes[0].provider, "globalRules");
+    }
+}
+
+#[test]
+fn test_audit_sink_query() {
+    use std::borrow::Cow;
+
+    let log = Arc::new(Log::default());
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().audit_sink(Arc::clone(&log));
+    assert_eq!(cleaner.clean_query("example.com", "q=a+b&utm_source=x").unwrap(), "q=a+b");
+    assert_eq!(cleaner.clean_query("example.com", "q=a+b").unwrap(), "q=a+b");
+    let pairs = [("q", "a b"), ("fbclid", "1")].map(|(k, v)| (Cow::Borrowed(k), Cow::Borrowed(v)));
+    cleaner.clean_query_pairs("example.org", pairs).unwrap();
+
+    let records = log.0.lock().unwrap();
+    let urls: Vec<_> = records.iter().map(|(o, c, _)| (o.as_str(), c.as_str())).collect();
+    assert_eq!(
+        urls,
+        [
+            ("https://example.com/?q=a+b&utm_source=x", "https://example.com/?q=a+b"),
+            ("https://example.org/?q=a+b&fbclid=1", "https://example.org/?q=a+b"),
+        ]
+    );
+}
+
+#[test]
+#[cfg(feature = "linkify")]
+fn test_audit_sink_text() {
+    let log = Arc::new(Log::default());
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().audit_sink(Arc::clone(&log));
+    cleaner.clear_text("https://example.com/?utm_source=abc and https://example.org/?fbclid=1 and https://example.net/").unwrap();
+    let records = log.0.lock().unwrap();
+    let originals: Vec<_> = records.iter().map(|(o, ..)| o.as_str()).collect();
+    assert_eq!(originals, ["https://example.com/?utm_source=abc", "https://example.org/?fbclid=1"]);
+}
+
+#[test]
+#[cfg(all(feature = "std", feature = "serialize"))]
+fn test_jsonl_audit_log() {
+    use clearurls::JsonlAuditLog;
+
+    let log = Arc::new(JsonlAuditLog::new(Vec::new()));
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().audit_sink(Arc::clone(&log));
+    cleaner.clear_single_url_str("https://example.com/?utm_source=abc").unwrap();
+    cleaner.clear_single_url_str("https://example.com/?fbclid=1").unwrap();
+    drop(cleaner);
+    assert!(log.take_error().is_none());
+
+    let written = Arc::into_inner(log).unwrap().into_inner();
+    let lines: Vec<serde_json::Value> =
+        String::from_utf8(written).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0]["timestamp"].as_u64().unwrap() > 0);
+    assert_eq!(lines[0]["original"], "https://example.com/?utm_source=abc");
+    assert_eq!(lines[0]["cleaned"], "https://example.com/");
+    assert_eq!(lines[0]["changes"][0]["provider"], "globalRules");
+    assert_eq!(lines[0]["changes"][0]["kind"], "rule");
+    assert_eq!(lines[1]["changes"][0]["name"], "fbclid");
+}
+
+#[test]
+#[cfg(feature = "ammonia")]
+fn test_audit_sink_html_attribute() {
+    let log = Arc::new(Log::default());
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().audit_sink(Arc::clone(&log));
+    let url = "https://example.com/?utm_source=x";
+    assert_eq!(cleaner.clean_html_attribute("a", "title", url), url);
+    assert!(log.0.lock().unwrap().is_empty());
+    assert_eq!(cleaner.clean_html_attribute("a", "href", url), "https://example.com/");
+    assert_eq!(log.0.lock().unwrap().len(), 1);
+}
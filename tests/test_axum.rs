@@ -0,0 +1,50 @@
+// the tests need the runtime of the server
+#![cfg(all(feature = "axum", feature = "server"))]
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+
+use axum::http::Uri;
+use axum::routing::get;
+use axum::Router;
+use clearurls::UrlCleaner;
+
+/// Serve the app on a runtime of its own until the test ends and return its address.
+fn serve(app: Router) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    std::thread::spawn(move || {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            axum::serve(tokio::net::TcpListener::from_std(listener).unwrap(), app).await.unwrap();
+        });
+    });
+    address
+}
+
+/// Request the target with the given `Host` header and return the URI that the handler saw.
+fn seen_uri(address: &str, target: &str, host: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "GET {target} HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response.split_once("\r\n\r\n").unwrap().1.to_string()
+}
+
+#[test]
+fn test_strip_tracking_params() {
+    let cleaner = Arc::new(UrlCleaner::from_embedded_rules().unwrap());
+    let app = Router::new()
+        .route("/page", get(|uri: Uri| async move { uri.to_string() }))
+        .layer(axum::middleware::from_fn_with_state(cleaner, clearurls::strip_tracking_params));
+    let address = serve(app);
+
+    assert_eq!(seen_uri(&address, "/page?q=a+b&fbclid=1&gclid=2&p=%32", "example.com"), "/page?q=a+b&p=%32");
+    assert_eq!(seen_uri(&address, "/page?utm_source=x", "example.com"), "/page");
+    assert_eq!(seen_uri(&address, "/page?a=1&&b", "example.com"), "/page?a=1&&b");
+    assert_eq!(seen_uri(&address, "/page", "example.com"), "/page");
+    // the rules of the provider for the host apply as well
+    assert_eq!(seen_uri(&address, "/page?pf_rd_p=1&k=x", "www.amazon.com"), "/page?k=x");
+    assert_eq!(seen_uri(&address, "/page?pf_rd_p=1&k=x", "example.com"), "/page?pf_rd_p=1&k=x");
+}
@@ -0,0 +1,60 @@
+#![cfg(feature = "std")]
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use clearurls::{BulkCleaner, BulkProgress, UrlCleaner};
+
+#[test]
+fn test_bulk_cleaner() {
+    let cleaner = Arc::new(UrlCleaner::from_embedded_rules().unwrap());
+    let urls = (0..100).map(|id| {
+        let url = match id % 4 {
+            0 => format!("https://example.com/{id}?utm_source=x"),
+            1 => format!("https://example.com/{id}"),
+            2 => format!("https://example.com/{id}?gclid=1&q=a"),
+            _ => "not a url".to_string(),
+        };
+        (id, url)
+    });
+    let progress: Arc<Mutex<Vec<BulkProgress>>> = Arc::default();
+    let reported = Arc::clone(&progress);
+    let results = BulkCleaner::new(cleaner).workers(3).on_progress(move |p| reported.lock().unwrap().push(*p)).clean(urls);
+    let mut results: Vec<_> = results.collect();
+    results.sort_by_key(|r| r.id);
+
+    assert_eq!(results.len(), 100);
+    for r in &results {
+        match r.id % 4 {
+            0 => assert_eq!(r.cleaned.as_deref().unwrap(), format!("https://example.com/{}", r.id)),
+            1 => assert_eq!(r.cleaned.as_deref().unwrap(), format!("https://example.com/{}", r.id)),
+            2 => assert_eq!(r.cleaned.as_deref().unwrap(), format!("https://example.com/{}?q=a", r.id)),
+            _ => assert!(r.cleaned.is_err()),
+        }
+        assert_eq!(r.changed, matches!(r.id % 4, 0 | 2));
+    }
+
+    let progress = progress.lock().unwrap();
+    assert_eq!(progress.len(), 100);
+    let last = progress.last().unwrap();
+    assert_eq!((last.done, last.changed, last.errors, last.total), (100, 50, 25, Some(100)));
+}
+
+#[test]
+fn test_bulk_cleaner_rate_limit() {
+    let urls = (0..6).map(|id| (id, "https://example.com/?utm_source=x"));
+    let start = Instant::now();
+    let results = BulkCleaner::new(UrlCleaner::shared()).rate_limit(50).clean(urls);
+    assert_eq!(results.filter(|r| r.changed).count(), 6);
+    // the first URL is taken right away, and each of the others 20ms later
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
+
+#[test]
+fn test_bulk_cleaner_dropped_early() {
+    let urls = (0..).map(|id| (id, "https://example.com/"));
+    let mut results = BulkCleaner::new(UrlCleaner::shared()).workers(2).clean(urls);
+    assert!(results.by_ref().take(10).all(|r| !r.changed));
+    assert_eq!(results.progress().done, 10);
+    assert_eq!(results.progress().total, None);
+}
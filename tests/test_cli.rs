@@ -0,0 +1,308 @@
+#![cfg(feature = "cli")]
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn run(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_clearurls"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_cli_args() {
+    let out = run(&["https://example.com/?utm_source=x&a=1", "https://example.com/b?gclid=1"], "");
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "https://example.com/?a=1\nhttps://example.com/b\n");
+
+    let out = run(&["/relative", "https://example.com/?utm_source=x"], "");
+    assert_eq!(out.status.code(), Some(2));
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "https://example.com/\n");
+    assert!(String::from_utf8(out.stderr).unwrap().starts_with("error cleaning /relative: "));
+
+    let out = run(&["https://example.com/?a=1"], "");
+    assert_eq!(out.status.code(), Some(0));
+}
+
+#[test]
+fn test_cli_stdin() {
+    let out = run(&[], "see https://example.com/?utm_source=x\nand https://example.org/\n");
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "see https://example.com/\nand https://example.org/\n");
+}
+
+#[test]
+fn test_cli_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let write = |name: &str, contents: &str| {
+        let path = dir.path().join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    };
+    let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+    let markdown = "[a](https://example.com/?utm_source=x) `https://example.com/?utm_source=y`\n\
+        ```\nhttps://example.com/?utm_source=z\n```\n\
+        see https://example.com/?utm_source=y and <https://example.com/?utm_source=z>\n";
+    write("docs/a.md", markdown);
+    write("b.html", r#"<a href="https://example.com/?a=1&amp;utm_source=x">x</a> <code>https://example.com/?utm_source=y</code>"#);
+    write("c.rs", "// https://example.com/?utm_source=x\n");
+    write(".git/d.txt", "https://example.com/?utm_source=x\n");
+    let root = dir.path().to_str().unwrap();
+
+    let out = run(&["--files", "--dry-run", root], "");
+    assert_eq!(out.status.code(), Some(1));
+    let diff = String::from_utf8(out.stdout).unwrap();
+    assert!(diff.contains("-[a](https://example.com/?utm_source=x)"), "{diff}");
+    assert!(diff.contains("+[a](https://example.com/) `https://example.com/?utm_source=y`"), "{diff}");
+    assert_eq!(read("docs/a.md"), markdown);
+
+    // the diff isn't mixed into the JSON records
+    let out = run(&["--files", "--dry-run", "--format", "json", root], "");
+    assert_eq!(out.status.code(), Some(1));
+    for line in String::from_utf8(out.stdout).unwrap().lines() {
+        serde_json::from_str::<serde_json::Value>(line).unwrap();
+    }
+
+    let out = run(&["--files", "--glob", "**/*.md", root], "");
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(String::from_utf8(out.stdout).unwrap().lines().count(), 1);
+    assert_eq!(
+        read("docs/a.md"),
+        "[a](https://example.com/) `https://example.com/?utm_source=y`\n```\nhttps://example.com/?utm_source=z\n```\n\
+        see https://example.com/ and <https://example.com/>\n"
+    );
+    assert!(read("b.html").contains("utm_source=x"));
+
+    let out = run(&["--files", root], "");
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(
+        read("b.html"),
+        r#"<a href="https://example.com/?a=1">x</a> <code>https://example.com/?utm_source=y</code>"#
+    );
+    assert_eq!(read("c.rs"), "// https://example.com/?utm_source=x\n");
+    assert_eq!(read(".git/d.txt"), "https://example.com/?utm_source=x\n");
+
+    let out = run(&["--files", "--glob", "*.rs", root], "");
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(read("c.rs"), "// https://example.com/\n");
+
+    let out = run(&["--files", "--glob", "*.rs", root], "");
+    assert_eq!(out.status.code(), Some(0));
+
+    let out = run(&["--files", &format!("{root}/missing.md")], "");
+    assert_eq!(out.status.code(), Some(2));
+}
+
+#[test]
+fn test_cli_json() {
+    let out = run(&["--format", "json", "https://example.com/?utm_source=x&a=1&utm_source=y", "/relative"], "");
+    assert_eq!(out.status.code(), Some(2));
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let records: Vec<serde_json::Value> = stdout.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(
+        records[0],
+        serde_json::json!({
+            "original": "https://example.com/?utm_source=x&a=1&utm_source=y",
+            "cleaned": "https://example.com/?a=1",
+            "changed": true,
+            "removed_params": ["utm_source", "utm_source"],
+            "errors": [],
+        })
+    );
+    assert_eq!(records[1]["changed"], false);
+    assert!(records[1]["errors"][0].as_str().unwrap().starts_with("error cleaning /relative: "));
+    assert!(out.stderr.is_empty());
+
+    let out = run(&["--format", "json"], "see https://example.com/?gclid=1\n");
+    assert_eq!(out.status.code(), Some(1));
+    let record: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(record["cleaned"], "see https://example.com/\n");
+    assert_eq!(record["removed_params"], serde_json::json!(["gclid"]));
+}
+
+#[test]
+fn test_cli_lines() {
+    let out = run(&["--lines"], "https://example.com/?utm_source=x\n\n/relative\nhttps://example.org/\n");
+    assert_eq!(out.status.code(), Some(2));
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "https://example.com/\n\n/relative\nhttps://example.org/\n");
+    assert!(String::from_utf8(out.stderr).unwrap().starts_with("error cleaning /relative: "));
+
+    let out = run(&["--lines", "--text"], "see https://example.com/?gclid=1 and https://example.org/?gclid=2\nnothing\n");
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "see https://example.com/ and https://example.org/\nnothing\n");
+
+    let out = run(&["--lines", "--format", "json"], "https://example.com/?a=1\n");
+    assert_eq!(out.status.code(), Some(0));
+    let record: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(record["cleaned"], "https://example.com/?a=1");
+}
+
+#[test]
+fn test_cli_policy() {
+    let dir = tempfile::tempdir().unwrap();
+    let policy = dir.path().join("policy.json");
+    std::fs::write(&policy, r#"{"disabled_providers": ["globalRules"], "strip_fragment": true}"#).unwrap();
+    let out = run(&["--policy", policy.to_str().unwrap(), "https://example.com/?utm_source=x#top"], "");
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "https://example.com/?utm_source=x\n");
+
+    std::fs::write(&policy, r#"{"strict": "yes"}"#).unwrap();
+    let out = run(&["--policy", policy.to_str().unwrap(), "https://example.com/"], "");
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8(out.stderr).unwrap().starts_with("error loading the policy: "));
+}
+
+#[test]
+fn test_cli_env() {
+    let run_env = |vars: &[(&str, &str)], args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_clearurls")).envs(vars.iter().copied()).args(args).output().unwrap()
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let policy = dir.path().join("policy.json");
+    std::fs::write(&policy, r#"{"disabled_providers": ["globalRules"], "strip_fragment": true}"#).unwrap();
+    let policy = policy.to_str().unwrap();
+
+    let out = run_env(&[("CLEARURLS_POLICY", policy)], &["https://example.com/?utm_source=x#top"]);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "https://example.com/?utm_source=x\n");
+    // the environment takes precedence over the file
+    let vars = [("CLEARURLS_POLICY", policy), ("CLEARURLS_DISABLED_PROVIDERS", ""), ("CLEARURLS_STRIP_FRAGMENT", "off")];
+    let out = run_env(&vars, &["https://example.com/?utm_source=x#top"]);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "https://example.com/#top\n");
+    // and the argument over the environment
+    let out = run_env(&[("CLEARURLS_POLICY", "missing.json")], &["--policy", policy, "https://example.com/#a"]);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "https://example.com/\n");
+
+    let out = run_env(&[("CLEARURLS_STRIP_REFERRAL", "yes")], &["https://www.amazon.com/dp/B0?tag=x"]);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "https://www.amazon.com/dp/B0\n");
+    let out = run_env(&[("CLEARURLS_MAX_URL_LEN", "ten")], &["https://example.com/"]);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8(out.stderr).unwrap().contains("invalid value \"ten\" for CLEARURLS_MAX_URL_LEN"));
+}
+
+#[test]
+fn test_cli_lines_streaming() {
+    use std::io::{BufRead, BufReader};
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_clearurls"))
+        .arg("--lines")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    for i in 0..3 {
+        // the output for a line arrives while stdin is still open
+        writeln!(stdin, "https://example.com/{i}?utm_source=x").unwrap();
+        line.clear();
+        stdout.read_line(&mut line).unwrap();
+        assert_eq!(line, format!("https://example.com/{i}\n"));
+    }
+    drop(stdin);
+    assert_eq!(child.wait().unwrap().code(), Some(1));
+}
+
+/// Serve the given paths over HTTP until the test ends and return the base URL.
+#[cfg(feature = "crawl")]
+fn serve(pages: &'static [(&'static str, &'static str)]) -> String {
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let root = base.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = String::new();
+            reader.read_line(&mut request).unwrap();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let path = request.split(' ').nth(1).unwrap();
+            let response = match pages.iter().find(|(p, _)| *p == path) {
+                Some((_, body)) => {
+                    let body = body.replace("{base}", &root);
+                    format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}", body.len())
+                }
+                None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".into(),
+            };
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    base
+}
+
+#[cfg(feature = "crawl")]
+#[test]
+fn test_cli_crawl() {
+    let base = serve(&[
+        ("/sitemap_index.xml", "<sitemapindex><sitemap><loc>{base}/sitemap.xml</loc></sitemap></sitemapindex>"),
+        (
+            "/sitemap.xml",
+            "<urlset><url><loc>{base}/a</loc></url><url><loc><![CDATA[{base}/b]]></loc></url>\
+            <url><loc>{base}/missing</loc></url></urlset>",
+        ),
+        ("/a", "<a href=\"https://example.com/?utm_source=x\">x</a>\n<a href=\"https://example.org/\">y</a>\n"),
+        ("/b", "<p>https://example.com/b?a=1&amp;gclid=1</p>\n"),
+    ]);
+
+    let out = run(&["--crawl", "--concurrency", "2", &format!("{base}/sitemap_index.xml")], "");
+    assert_eq!(out.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        format!(
+            "{base}/a\n  https://example.com/?utm_source=x -> https://example.com/\n\
+            {base}/b\n  https://example.com/b?a=1&gclid=1 -> https://example.com/b?a=1\n"
+        )
+    );
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(stderr.starts_with(&format!("{base}/missing: error fetching: ")), "{stderr}");
+
+    let out = run(&["--crawl", "--patch", &format!("{base}/a")], "");
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        format!(
+            "--- {base}/a\n+++ {base}/a\n@@ line 1 @@\n-<a href=\"https://example.com/?utm_source=x\">x</a>\n\
+            +<a href=\"https://example.com/\">x</a>\n"
+        )
+    );
+    let out = run(&["--crawl", "--patch", "--format", "json", &format!("{base}/a")], "");
+    assert_eq!(out.status.code(), Some(1));
+    let record: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(
+        record["patch"],
+        format!(
+            "--- {base}/a\n+++ {base}/a\n@@ line 1 @@\n-<a href=\"https://example.com/?utm_source=x\">x</a>\n\
+            +<a href=\"https://example.com/\">x</a>\n"
+        )
+    );
+
+    let dir = tempfile::tempdir().unwrap();
+    let list = dir.path().join("pages.txt");
+    std::fs::write(&list, format!("# pages\n{base}/b\n\n{base}/b\n")).unwrap();
+    let out = run(&["--crawl", "--format", "json", list.to_str().unwrap()], "");
+    assert_eq!(out.status.code(), Some(1));
+    let record: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(
+        record,
+        serde_json::json!({
+            "page": format!("{base}/b"),
+            "changed": true,
+            "removed_params": ["gclid"],
+            "links": [{"original": "https://example.com/b?a=1&gclid=1", "cleaned": "https://example.com/b?a=1"}],
+            "errors": [],
+        })
+    );
+}
@@ -30,8 +30,204 @@ fn test_linkify() {
     );
 
     let err = cleaner.clear_text("This is a [markdown link](http://example.com/?&&&&), and another: https://google.co.uk/url?foo=bar&q=http%F0");
+    let err = err.unwrap_err();
     assert!(matches!(
-        err.unwrap_err()[..],
-        [Error::PercentDecodeUtf8Error(_)]
+        err[..],
+        [ref e] if matches!(e.error(), Error::PercentDecodeUtf8Error(_))
     ));
+    assert_eq!(err[0].url(), "https://google.co.uk/url?foo=bar&q=http%F0");
+    assert_eq!(err[0].to_string(), "error cleaning https://google.co.uk/url?foo=bar&q=http%F0: percent decoding resulted in non-UTF-8 bytes: incomplete utf-8 byte sequence from index 4");
+
+    let (text, err) = cleaner.clear_text_partial("This is a [markdown link](http://example.com/?&&&&), and another: https://google.co.uk/url?foo=bar&q=http%F0");
+    assert_eq!(text, "This is a [markdown link](http://example.com/), and another: https://google.co.uk/url?foo=bar&q=http%F0");
+    assert!(matches!(
+        err[..],
+        [ref e] if matches!(e.error(), Error::PercentDecodeUtf8Error(_))
+    ));
+
+    let (text, err) = cleaner.clear_text_partial("This is a markdown text.");
+    assert!(matches!(text, std::borrow::Cow::Borrowed("This is a markdown text.")));
+    assert!(err.is_empty());
+}
+
+#[cfg(feature = "linkify")]
+#[test]
+fn test_linkify_callback() {
+    use clearurls::UrlCleaner;
+
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let text = "a https://example.com/?utm_source=x b https://example.com/ c https://google.co.uk/url?foo=bar&q=http%F0";
+    let mut rebuilt = String::new();
+    let mut last = 0;
+    let errors = cleaner
+        .clear_text_with(text, |range, new| {
+            rebuilt.push_str(&text[last..range.start]);
+            rebuilt.push_str(new);
+            last = range.end;
+        })
+        .unwrap_err();
+    rebuilt.push_str(&text[last..]);
+    assert_eq!(rebuilt, "a https://example.com/ b https://example.com/ c https://google.co.uk/url?foo=bar&q=http%F0");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].url(), "https://google.co.uk/url?foo=bar&q=http%F0");
+    assert_eq!(cleaner.clear_text_partial(text).0, rebuilt);
+
+    let mut called = false;
+    cleaner.clear_text_with("nothing https://example.com/ here", |_, _| called = true).unwrap();
+    assert!(!called);
+}
+
+#[cfg(feature = "linkify")]
+#[test]
+fn test_lint_text() {
+    use clearurls::UrlCleaner;
+
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let text = "a https://example.com/?utm_source=x b https://EXAMPLE.com c https://google.co.uk/url?foo=bar&q=http%F0";
+    let (findings, errors) = cleaner.lint_text(text);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(&text[findings[0].span.clone()], "https://example.com/?utm_source=x");
+    assert_eq!(findings[0].url, "https://example.com/?utm_source=x");
+    assert_eq!(findings[0].suggestion, "https://example.com/");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].url(), "https://google.co.uk/url?foo=bar&q=http%F0");
+}
+
+#[cfg(feature = "linkify")]
+#[test]
+fn test_ansi_escapes() {
+    use clearurls::UrlCleaner;
+
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().ansi_escapes(true);
+    let test = |input: &str, expected: &str| assert_eq!(cleaner.clear_text(input).unwrap(), expected, "{input:?}");
+
+    test("\x1b[32mhttps://example.com/?utm_source=x\x1b[0m done", "\x1b[32mhttps://example.com/\x1b[0m done");
+    test(
+        "\x1b]8;id=1;https://example.com/?fbclid=1\x07https://example.org/?utm_source=x\x1b]8;;\x07",
+        "\x1b]8;id=1;https://example.com/\x07https://example.org/\x1b]8;;\x07",
+    );
+    test("\x1b]8;;https://example.com/a?utm_source=x\x1b\\link\x1b]8;;\x1b\\", "\x1b]8;;https://example.com/a\x1b\\link\x1b]8;;\x1b\\");
+    // other control strings aren't links, and unterminated ones extend to the end
+    test("\x1b]0;https://example.com/?utm_source=x\x07", "\x1b]0;https://example.com/?utm_source=x\x07");
+    test("\x1b]8;;https://example.com/?utm_source=x", "\x1b]8;;https://example.com/");
+    test("\x1b(B\x1b[1;31mhttps://example.com/?utm_source=x", "\x1b(B\x1b[1;31mhttps://example.com/");
+
+    let input = "\x1b[1mhi\x1b[0m \x1b]8;;https://google.co.uk/url?foo=bar&q=http%F0\x07x\x1b]8;;\x07";
+    let err = cleaner.clear_text(input).unwrap_err();
+    assert_eq!(err[0].url(), "https://google.co.uk/url?foo=bar&q=http%F0");
+
+    let mut edits = Vec::new();
+    cleaner.clear_text_with("\x1b[1mhttps://example.com/?utm_source=x", |range, new| edits.push((range, new.to_string()))).unwrap();
+    assert_eq!(edits, [(4..37, "https://example.com/".to_string())]);
+}
+
+#[cfg(feature = "linkify")]
+#[test]
+fn test_repair_urls() {
+    use clearurls::UrlCleaner;
+
+    let text = "at https://example.com:99999/?utm_source=x, https://example.com/?utm_source=y";
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().ansi_escapes(true);
+    let err = cleaner.clear_text(text).unwrap_err();
+    assert_eq!(err[0].url(), "https://example.com:99999/?utm_source=x");
+
+    let cleaner = cleaner.repair_urls(true);
+    let test = |input: &str, expected: &str| assert_eq!(cleaner.clear_text(input).unwrap(), expected, "{input:?}");
+    test(text, "at https://example.com:99999/?utm_source=x, https://example.com/");
+    // the target of a hyperlink is taken as it is, including punctuation
+    test("\x1b]8;;https://example.com:8080).\x07x\x1b]8;;\x07", "\x1b]8;;https://example.com:8080).\x07x\x1b]8;;\x07");
+    test("\x1b]8;;https://[::1]:8080/x)\x07x\x1b]8;;\x07", "\x1b]8;;https://[::1]:8080/x)\x07x\x1b]8;;\x07");
+    test("\x1b]8;;https://exa mple.com/\x07x\x1b]8;;\x07", "\x1b]8;;https://exa mple.com/\x07x\x1b]8;;\x07");
+}
+
+#[cfg(feature = "linkify")]
+#[test]
+fn test_trailing_punctuation() {
+    use clearurls::{TrailingPunctuation, UrlCleaner};
+
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().trailing_punctuation(TrailingPunctuation::Trim("_~".into()));
+    let test = |input: &str, expected: &str| assert_eq!(cleaner.clear_text(input).unwrap(), expected, "{input:?}");
+    test("_https://example.com/?utm_source=x_ and ~~https://example.org/?fbclid=1~~.", "_https://example.com/_ and ~~https://example.org/~~.");
+    test("(https://en.wikipedia.org/wiki/Rust_(language)?utm_source=x).", "(https://en.wikipedia.org/wiki/Rust_(language)).");
+    test("https://example.com/a_b?utm_source=x", "https://example.com/a_b");
+
+    let mut edits = Vec::new();
+    cleaner.clear_text_with("*https://example.com/?utm_source=x~*", |range, new| edits.push((range, new.to_string()))).unwrap();
+    assert_eq!(edits, [(1..34, "https://example.com/".to_string())]);
+}
+
+#[cfg(feature = "linkify")]
+#[test]
+fn test_protocol_relative_text() {
+    use clearurls::UrlCleaner;
+
+    let text = "see //cdn.example.com/x?utm_source=a and https://example.com/-//cdn.example.com/y";
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    assert_eq!(cleaner.clear_text(text).unwrap(), text);
+
+    let cleaner = cleaner.protocol_relative(true);
+    let test = |input: &str, expected: &str| assert_eq!(cleaner.clear_text(input).unwrap(), expected, "{input:?}");
+    test(text, "see //cdn.example.com/x and https://example.com/-//cdn.example.com/y");
+    test("(//a.example/?fbclid=1) https://b.example/?utm_source=x", "(//a.example/) https://b.example/");
+    // paths and comments aren't links
+    test("/usr//lib.so and a///b.example/?utm_source=x", "/usr//lib.so and a///b.example/?utm_source=x");
+}
+
+#[cfg(feature = "linkify")]
+#[test]
+fn test_clean_icalendar() {
+    use std::borrow::Cow;
+
+    use clearurls::UrlCleaner;
+
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+
+    let ics = "BEGIN:VEVENT\nSUMMARY:Launch https://example.com/?utm_source=x\nEND:VEVENT\n";
+    let (cleaned, errors) = cleaner.clean_icalendar(ics);
+    assert!(matches!(cleaned, Cow::Borrowed(_)));
+    assert!(errors.is_empty());
+
+    // the quoted parameter contains a colon, and the long line is folded again without splitting characters
+    let description = format!("Ünïcödé {} https://example.com/a?utm_source=x\\; more", "ä".repeat(40));
+    let ics = format!(
+        "BEGIN:VEVENT\n\
+        ATTACH;FMTTYPE=text/plain;ENCODING=BASE64;VALUE=BINARY:aHR0cHM6Ly9leGFtcGxlLmNvbS8/dXRtX3NvdXJjZT14\n\
+        ATTACH:/relative?utm_source=x\n\
+        COMMENT;ALTREP=\"https://example.com/?a=1\":see https://example.com/\n \\,x?utm_source=y\n\
+        DESCRIPTION:{description}\n\
+        END:VEVENT\n"
+    );
+    let (cleaned, errors) = cleaner.clean_icalendar(&ics);
+    let lines: Vec<&str> = cleaned.lines().collect();
+    assert_eq!(lines[1], "ATTACH;FMTTYPE=text/plain;ENCODING=BASE64;VALUE=BINARY:aHR0cHM6Ly9leGFtcGxlLmNvbS8/dXRtX3NvdXJjZT14");
+    assert_eq!(lines[2], "ATTACH:/relative?utm_source=x");
+    assert_eq!(lines[3], "COMMENT;ALTREP=\"https://example.com/?a=1\":see https://example.com/\\,x");
+    assert!(lines[4..].iter().all(|l| l.len() <= 75), "{lines:?}");
+    let description = lines[4..lines.len() - 1].iter().map(|l| l.strip_prefix(' ').unwrap_or(l)).collect::<String>();
+    assert_eq!(description, format!("DESCRIPTION:Ünïcödé {} https://example.com/a\\; more", "ä".repeat(40)));
+    assert_eq!(lines.last(), Some(&"END:VEVENT"));
+    assert!(cleaned.ends_with("END:VEVENT\n") && !cleaned.contains('\r'));
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].url(), "/relative?utm_source=x");
+}
+
+#[cfg(feature = "linkify")]
+#[test]
+fn test_clean_vcard() {
+    use clearurls::UrlCleaner;
+
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let vcf = "BEGIN:VCARD\r\nVERSION:3.0\r\nN:Doe;Jane;;;\r\nNOTE:https://example.com/?utm_source=x\r\n\
+        item1.URL:https://example.com/jane?fbclid=\r\n 123\r\nitem1.X-ABLabel:_$!<HomePage>!$_\r\n\
+        URL;VALUE=uri:mailto:jane@example.com\r\nURL:profile?utm_source=x\r\nEND:VCARD\r\n";
+    let (cleaned, errors) = cleaner.clean_vcard(vcf);
+    assert_eq!(
+        cleaned,
+        "BEGIN:VCARD\r\nVERSION:3.0\r\nN:Doe;Jane;;;\r\nNOTE:https://example.com/?utm_source=x\r\n\
+        item1.URL:https://example.com/jane\r\nitem1.X-ABLabel:_$!<HomePage>!$_\r\n\
+        URL;VALUE=uri:mailto:jane@example.com\r\nURL:profile?utm_source=x\r\nEND:VCARD\r\n"
+    );
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].url(), "profile?utm_source=x");
+    assert!(matches!(errors.into_iter().next().unwrap().into_error(), clearurls::Error::UrlSyntax(_)));
 }
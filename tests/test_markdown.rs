@@ -14,10 +14,15 @@ fn test_markdown() {
 
     let test = |msg: &str, input: String, expected: String| {
         let mut node = parser.parse(&input);
-        cleaner
+        let replacements = cleaner
             .clear_markdown(&mut node)
             .unwrap_or_else(|e| panic!("error in test {msg}: {e:?}"));
         let result = node.xrender();
+        assert_eq!(
+            replacements.is_empty(),
+            expected.contains(SINGLE_BLACK_PIXEL),
+            "Testing {msg}, replacements: {replacements:?}"
+        );
 
         assert_eq!(
             result, expected,
@@ -62,5 +67,134 @@ fn test_markdown() {
     );
 
     let err = cleaner.clear_markdown(&mut parser.parse("<ftp://example.%com>")).unwrap_err();
-    assert!(matches!(err[..], [Error::UrlSyntax(_)]));
+    assert!(matches!(err[..], [ref e] if matches!(e.error(), Error::UrlSyntax(_))));
+    assert_eq!(err[0].url(), "ftp://example.%25com");
+
+    let mut node = parser.parse("[a](https://example.com?utm_source=1) [b](https://example.com?utm_source=2) <https://example.com/>");
+    let replacements = cleaner.clear_markdown(&mut node).unwrap();
+    assert_eq!(replacements.len(), 2);
+    assert_eq!(replacements[0].old, "https://example.com?utm_source=1");
+    assert_eq!(replacements[0].new, "https://example.com/");
+    assert_eq!(replacements[1].old, "https://example.com?utm_source=2");
+}
+
+#[cfg(feature = "markdown-it")]
+#[test]
+fn test_markdown_str() {
+    use clearurls::UrlCleaner;
+
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let html = cleaner
+        .clear_markdown_str("# Title\n\n![img](https://example.com/a.png?utm_source=1) and http://example.com/?utm_source=2")
+        .unwrap();
+    assert_eq!(
+        html,
+        "<h1>Title</h1>\n<p><img src=\"https://example.com/a.png\" alt=\"img\"> and <a href=\"http://example.com/\">http://example.com/</a></p>\n"
+    );
+
+    let err = cleaner.clear_markdown_str("<ftp://example.%com>").unwrap_err();
+    assert_eq!(err.len(), 1);
+}
+
+#[cfg(feature = "markdown-it")]
+#[test]
+fn test_markdown_unchanged_link_text() {
+    use clearurls::UrlCleaner;
+    use markdown_it::MarkdownIt;
+
+    let mut parser = MarkdownIt::new();
+    markdown_it::plugins::cmark::add(&mut parser);
+    markdown_it::plugins::extra::linkify::add(&mut parser);
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let mut doc = parser.parse("<https://example.com/ä> and https://example.com/ö?utm_source=x");
+    let replacements = cleaner.clear_markdown(&mut doc).unwrap();
+    assert_eq!(replacements.len(), 1);
+    assert_eq!(replacements[0].old, "https://example.com/%C3%B6?utm_source=x");
+    assert_eq!(replacements[0].new, "https://example.com/%C3%B6");
+    assert_eq!(
+        doc.render(),
+        "<p><a href=\"https://example.com/%C3%A4\">https://example.com/ä</a> and \
+        <a href=\"https://example.com/%C3%B6\">https://example.com/%C3%B6</a></p>\n"
+    );
+}
+
+#[cfg(feature = "markdown-it")]
+#[test]
+fn test_markdown_protocol_relative() {
+    use clearurls::UrlCleaner;
+
+    let markdown = "[a](//cdn.example.com/x?utm_source=a) ![b](//cdn.example.com/b.png?fbclid=1)";
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let err = cleaner.clear_markdown_str(markdown).unwrap_err();
+    assert_eq!(err.len(), 2);
+
+    let html = cleaner.protocol_relative(true).clear_markdown_str(markdown).unwrap();
+    assert_eq!(html, "<p><a href=\"//cdn.example.com/x\">a</a> <img src=\"//cdn.example.com/b.png\" alt=\"b\"></p>\n");
+}
+
+#[cfg(feature = "markdown-it")]
+#[test]
+fn test_markdown_skip_code() {
+    use clearurls::UrlCleaner;
+    use markdown_it::MarkdownIt;
+
+    let mut parser = MarkdownIt::new();
+    markdown_it::plugins::cmark::add(&mut parser);
+    markdown_it::plugins::html::add(&mut parser);
+    markdown_it::plugins::extra::linkify::add(&mut parser);
+    let markdown = "`http://example.com/?utm_source=1`\n\n\
+        ```\nhttp://example.com/?utm_source=2\n```\n\n\
+        <CODE>http://example.com/?utm_source=3</CODE> and <pre><code>http://example.com/?utm_source=4</code> \
+        <https://example.com/?utm_source=5></pre>\n\n\
+        <code>unclosed\n\n\
+        http://example.com/?utm_source=6";
+
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let replacements = cleaner.clear_markdown(&mut parser.parse(markdown)).unwrap();
+    assert_eq!(replacements.len(), 4);
+
+    let cleaner = cleaner.skip_code(true);
+    let mut doc = parser.parse(markdown);
+    let replacements = cleaner.clear_markdown(&mut doc).unwrap();
+    assert_eq!(replacements.len(), 1);
+    assert_eq!(replacements[0].old, "http://example.com/?utm_source=6");
+    assert_eq!(
+        doc.render(),
+        "<p><code>http://example.com/?utm_source=1</code></p>\n\
+        <pre><code>http://example.com/?utm_source=2\n</code></pre>\n\
+        <p><CODE><a href=\"http://example.com/?utm_source=3\">http://example.com/?utm_source=3</a></CODE> and \
+        <pre><code><a href=\"http://example.com/?utm_source=4\">http://example.com/?utm_source=4</a></code> \
+        <a href=\"https://example.com/?utm_source=5\">https://example.com/?utm_source=5</a></pre></p>\n\
+        <p><code>unclosed</p>\n\
+        <p><a href=\"http://example.com/\">http://example.com/</a></p>\n"
+    );
+}
+
+#[cfg(feature = "markdown-it")]
+#[test]
+fn test_lint_markdown() {
+    use clearurls::UrlCleaner;
+
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let markdown = "[https://example.com/?utm_source=1](https://example.com/?utm_source=1) \
+        ![img](https://example.com/a.png?utm_source=2 \"title\")\n\n\
+        <https://example.com/?utm_source=3> `https://example.com/?utm_source=4` https://example.com/?utm_source=5 \
+        [clean](https://example.com/) [escaped](https://example.com/?a\\&utm_source=6)";
+    let (findings, errors) = cleaner.lint_markdown(markdown);
+    assert!(errors.is_empty());
+    let spans: Vec<_> = findings.iter().map(|f| &markdown[f.span.clone()]).collect();
+    assert_eq!(
+        spans,
+        [
+            "https://example.com/?utm_source=1",
+            "https://example.com/a.png?utm_source=2",
+            "https://example.com/?utm_source=3",
+            "https://example.com/?utm_source=5",
+            "[escaped](https://example.com/?a\\&utm_source=6)",
+        ]
+    );
+    assert_eq!(findings[0].span.start, 36);
+    assert_eq!(findings[1].suggestion, "https://example.com/a.png");
+    assert_eq!(findings[4].url, "https://example.com/?a&utm_source=6");
+    assert_eq!(findings[4].suggestion, "https://example.com/?a");
 }
@@ -0,0 +1,71 @@
+#![cfg(feature = "native-host")]
+
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+
+fn frame(message: &str) -> Vec<u8> {
+    let mut framed = u32::try_from(message.len()).unwrap().to_ne_bytes().to_vec();
+    framed.extend_from_slice(message.as_bytes());
+    framed
+}
+
+#[test]
+fn test_native_host() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_clearurls-native-host"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    for message in [
+        r#"{"action":"clean","url":"https://example.com/?utm_source=x&a=1","id":1}"#,
+        r#"{"action":"explain","url":"https://example.com/?utm_source=x"}"#,
+        r#"{"action":"clean","url":"/relative"}"#,
+        r#"{"action":"unknown"}"#,
+    ] {
+        stdin.write_all(&frame(message)).unwrap();
+    }
+    drop(stdin);
+
+    let mut output = Vec::new();
+    child.stdout.take().unwrap().read_to_end(&mut output).unwrap();
+    assert!(child.wait().unwrap().success());
+
+    let mut responses = Vec::new();
+    let mut rest = &output[..];
+    while !rest.is_empty() {
+        let (len, tail) = rest.split_at(4);
+        let (message, tail) = tail.split_at(u32::from_ne_bytes(len.try_into().unwrap()) as usize);
+        responses.push(String::from_utf8(message.to_vec()).unwrap());
+        rest = tail;
+    }
+    assert_eq!(responses.len(), 4);
+    assert_eq!(responses[0], r#"{"id":1,"url":"https://example.com/?a=1"}"#);
+    assert_eq!(
+        responses[1],
+        r#"{"rules":[{"kind":"remove_param","name":"utm_source","referral_marketing":false,"rule":"(?:%3F)?utm(?:_[a-z_]*)?"}]}"#
+    );
+    assert!(responses[2].starts_with(r#"{"error":{"code":"url_syntax","#), "{}", responses[2]);
+    assert!(responses[3].starts_with(r#"{"error":{"code":"invalid_message","#), "{}", responses[3]);
+}
+
+#[test]
+fn test_native_host_too_long() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_clearurls-native-host"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(&u32::MAX.to_ne_bytes()).unwrap();
+    drop(stdin);
+
+    let mut output = Vec::new();
+    child.stdout.take().unwrap().read_to_end(&mut output).unwrap();
+    assert!(!child.wait().unwrap().success());
+    let (len, message) = output.split_at(4);
+    assert_eq!(u32::from_ne_bytes(len.try_into().unwrap()) as usize, message.len());
+    let message = String::from_utf8(message.to_vec()).unwrap();
+    assert!(message.starts_with(r#"{"error":{"code":"message_too_long","#), "{message}");
+}
@@ -0,0 +1,57 @@
+#[cfg(feature = "std")]
+#[test]
+fn test_policy_file() {
+    use clearurls::{CleanError, LoadError, PolicyFile, UrlCleaner};
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("rules")).unwrap();
+    std::fs::write(
+        dir.path().join("rules/custom.json"),
+        r#"{"providers":{"a":{"urlPattern":".*","rules":["foo"]},"b":{"urlPattern":".*","rules":["bar"]}}}"#,
+    )
+    .unwrap();
+    let policy = r#"{
+        "rules": "rules",
+        "disabled_providers": ["b"],
+        "skip_schemes": ["myapp"],
+        "strip_fragment": true,
+        "limits": {"max_url_len": 40}
+    }"#;
+    std::fs::write(dir.path().join("policy.json"), policy).unwrap();
+
+    let cleaner = UrlCleaner::from_policy_file(&dir.path().join("policy.json")).unwrap();
+    let res = cleaner.clear_single_url_str("https://example.com/?foo=1&bar=2#top").unwrap();
+    assert_eq!(res, "https://example.com/?bar=2");
+    let res = cleaner.clear_single_url_str("myapp://open?foo=1").unwrap();
+    assert_eq!(res, "myapp://open?foo=1");
+    let res = cleaner.clear_single_url_str("https://example.com/?foo=1&bar=2&long=1234567890");
+    assert!(matches!(res, Err(CleanError::UrlTooLong(48))));
+
+    // without any settings, it's the same as the embedded rules with the defaults
+    let cleaner = PolicyFile::from_json("{}").unwrap().build().unwrap();
+    assert_eq!(cleaner.rules_version(), UrlCleaner::from_embedded_rules().unwrap().rules_version());
+
+    let err = PolicyFile::from_json(r#"{"strip_referal_marketing": true}"#).unwrap_err();
+    assert!(matches!(err, LoadError::InvalidPolicy(_)));
+    let err = PolicyFile::from_json(r#"{"limits": {"param_overflow": "drop"}}"#).unwrap_err();
+    assert!(matches!(err, LoadError::InvalidPolicy(_)));
+    let err = UrlCleaner::from_policy_file(&dir.path().join("missing.json")).unwrap_err();
+    assert!(matches!(err, LoadError::FileRead(_)));
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn test_policy_file_toml() {
+    use clearurls::UrlCleaner;
+
+    let dir = tempfile::tempdir().unwrap();
+    let policy = "strip_referral_marketing = true\nfollow_redirects = false\n\n\
+        [limits]\nmax_params = 1\nparam_overflow = \"truncate\"\n";
+    std::fs::write(dir.path().join("policy.toml"), policy).unwrap();
+
+    let cleaner = UrlCleaner::from_policy_file(&dir.path().join("policy.toml")).unwrap();
+    let res = cleaner.clear_single_url_str("https://www.amazon.com/dp/B0?tag=x&a=1").unwrap();
+    assert_eq!(res, "https://www.amazon.com/dp/B0");
+    let res = cleaner.clear_single_url_str("https://www.google.com/url?q=https%3A%2F%2Fexample.com%2F").unwrap();
+    assert_eq!(res, "https://www.google.com/url?q=https%3A%2F%2Fexample.com%2F");
+}
@@ -0,0 +1,96 @@
+#![cfg(feature = "server")]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Child, Command, Stdio};
+
+/// Kills the server when the test ends, even if it panics.
+struct Server {
+    child: Child,
+    address: String,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+fn start() -> Server {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_clearurls-server"))
+        .arg("127.0.0.1:0")
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().unwrap()).read_line(&mut line).unwrap();
+    let address = line.trim().strip_prefix("listening on http://").unwrap().to_string();
+    Server { child, address }
+}
+
+/// Send a request and return the status code and body.
+fn request(server: &Server, method: &str, path: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(&server.address).unwrap();
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    (head[9..12].parse().unwrap(), body.to_string())
+}
+
+#[test]
+fn test_server() {
+    let server = start();
+    assert_eq!(request(&server, "GET", "/health", ""), (200, "ok".into()));
+
+    let (status, body) = request(&server, "GET", "/rules-version", "");
+    assert_eq!(status, 200);
+    assert_eq!(body, format!(r#"{{"version":"{}"}}"#, clearurls::RulesVersion::EMBEDDED));
+
+    let res = request(&server, "POST", "/clean", r#"{"url":"https://example.com/?utm_source=x&a=1"}"#);
+    assert_eq!(res, (200, r#"{"url":"https://example.com/?a=1"}"#.into()));
+    let (status, body) = request(&server, "POST", "/clean", r#"{"url":"not a url"}"#);
+    assert_eq!(status, 422);
+    assert!(body.starts_with(r#"{"error":{"code":"url_syntax","message":"#), "{body}");
+
+    let res = request(&server, "POST", "/clean-text", r#"{"text":"see https://example.com/?utm_source=x"}"#);
+    assert_eq!(res, (200, r#"{"text":"see https://example.com/"}"#.into()));
+
+    let res = request(&server, "POST", "/clean-batch", r#"{"urls":["https://example.com/?utm_source=x","/relative"]}"#);
+    assert_eq!(res.0, 200);
+    assert!(
+        res.1.starts_with(r#"{"results":[{"url":"https://example.com/"},{"error":{"code":"url_syntax","#),
+        "{}",
+        res.1
+    );
+
+    let (status, _) = request(&server, "POST", "/clean", "{}");
+    assert_eq!(status, 422);
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn test_metrics() {
+    let server = start();
+    request(&server, "POST", "/clean", r#"{"url":"https://example.com/?utm_source=x"}"#);
+    request(&server, "POST", "/clean-batch", r#"{"urls":["https://example.com/","/relative"]}"#);
+
+    let (status, body) = request(&server, "GET", "/metrics", "");
+    assert_eq!(status, 200);
+    for line in [
+        r#"clearurls_requests_total{endpoint="clean"} 1"#,
+        r#"clearurls_requests_total{endpoint="clean-text"} 0"#,
+        r#"clearurls_requests_total{endpoint="clean-batch"} 1"#,
+        r#"clearurls_urls_total{result="unchanged"} 1"#,
+        r#"clearurls_urls_total{result="changed"} 1"#,
+        r#"clearurls_urls_total{result="error"} 1"#,
+        &format!(r#"clearurls_rules_info{{version="{}"}} 1"#, clearurls::RulesVersion::EMBEDDED),
+    ] {
+        assert!(body.lines().any(|l| l == line), "{line} not in {body}");
+    }
+}
@@ -1,6 +1,7 @@
+use std::borrow::Cow;
 use std::str::FromStr;
 use url::{ParseError, Url};
-use clearurls::{Error, UrlCleaner};
+use clearurls::{CleanError, ErrorCode, HostForm, LengthOverflow, Normalization, ParamOverflow, Policy, PolicyMap, TrailingSlash, UrlCleaner};
 
 #[test]
 fn test_single_url() {
@@ -26,8 +27,8 @@ fn test_single_url() {
     );
 
     // url encoded parameter that's not a url
-    assert!(matches!(cleaner.clear_single_url_str("https://www.google.com/url?q=http%3A%2F%2F%5B%3A%3A%3A1%5D").unwrap_err(), Error::UrlSyntax(ParseError::InvalidIpv6Address)));
-    assert!(matches!(cleaner.clear_single_url(&Url::from_str("https://www.google.com/url?q=http%3A%2F%2F%5B%3A%3A%3A1%5D").unwrap()).unwrap_err(), Error::UrlSyntax(ParseError::InvalidIpv6Address)));
+    assert!(matches!(cleaner.clear_single_url_str("https://www.google.com/url?q=http%3A%2F%2F%5B%3A%3A%3A1%5D").unwrap_err(), CleanError::UrlSyntax(ParseError::InvalidIpv6Address)));
+    assert!(matches!(cleaner.clear_single_url(&Url::from_str("https://www.google.com/url?q=http%3A%2F%2F%5B%3A%3A%3A1%5D").unwrap()).unwrap_err(), CleanError::UrlSyntax(ParseError::InvalidIpv6Address)));
 
     // double url encoded parameter
     test(
@@ -82,3 +83,647 @@ fn test_single_url() {
         "data:text/plain;base64,SGVsbG8sIFdvcmxkIQ==",
     );
 }
+
+#[test]
+fn test_nested_urls() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let nested = "https://example.com/login?next=https%3A%2F%2Fshop.example%2Fcart%3Futm_source%3Dx%26item%3D1";
+    assert_eq!(cleaner.clear_single_url_str(nested).unwrap(), nested);
+
+    let cleaner = cleaner.clean_nested_urls(true);
+    let test = |original: &str, expected: &str| {
+        let result = cleaner.clear_single_url_str(original).unwrap();
+        assert_eq!(result, expected);
+        let url = Url::from_str(original).unwrap();
+        let result = cleaner.clear_single_url(&url).unwrap();
+        assert_eq!(result.as_str(), expected);
+    };
+
+    test(
+        nested,
+        "https://example.com/login?next=https%3A%2F%2Fshop.example%2Fcart%3Fitem%3D1",
+    );
+    // nested values that don't change are kept verbatim
+    test(
+        "https://example.com/login?next=https%3A%2F%2Fshop.example&utm_source=x",
+        "https://example.com/login?next=https%3A%2F%2Fshop.example",
+    );
+    // only URLs are treated as such
+    test(
+        "https://example.com/login?next=%2Fcart%3Futm_source%3Dx",
+        "https://example.com/login?next=%2Fcart%3Futm_source%3Dx",
+    );
+    // twice nested
+    test(
+        "https://example.com/?a=https%3A%2F%2Fexample.org%2F%3Fb%3Dhttps%253A%252F%252Fexample.net%252F%253Futm_source%253Dx",
+        "https://example.com/?a=https%3A%2F%2Fexample.org%2F%3Fb%3Dhttps%253A%252F%252Fexample.net%252F",
+    );
+}
+
+#[test]
+fn test_nested_urls_keep_other_params() {
+    let rules = r#"{"providers":{"shop":{"urlPattern":"^https://shop\\.example","rules":["utm_source"]}}}"#;
+    let cleaner = UrlCleaner::from_rules_str(rules).unwrap().clean_nested_urls(true);
+    let test = |original: &str, expected: &str| assert_eq!(cleaner.clear_single_url_str(original).unwrap(), expected);
+
+    // only the changed value is encoded again
+    test(
+        "https://example.com/login?q=a%20b&next=https%3A%2F%2Fshop.example%2F%3Futm_source%3Dx&r=c+d",
+        "https://example.com/login?q=a%20b&next=https%3A%2F%2Fshop.example%2F&r=c+d",
+    );
+    // URLs in the fragment are cleaned as well
+    test(
+        "https://example.com/#q=a%20b&next=https%3A%2F%2Fshop.example%2F%3Futm_source%3Dx",
+        "https://example.com/#q=a%20b&next=https%3A%2F%2Fshop.example%2F",
+    );
+}
+
+#[test]
+fn test_url_with_base() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let base = Url::from_str("https://www.google.com/search/index.html?q=1").unwrap();
+
+    let test = |original: &str, expected: &str| {
+        let result = cleaner.clear_url_with_base(original, &base).unwrap();
+        assert_eq!(result, expected, "original: {original}");
+    };
+
+    test("/test?utm_source=abc", "/test");
+    test("other.html?utm_source=abc&a=1", "other.html?a=1");
+    test("../up.html?utm_source=abc", "../up.html");
+    test("//example.com/test?utm_source=abc", "//example.com/test");
+    test("https://example.com/test?utm_source=abc", "https://example.com/test");
+    // redirections leave the origin of the base
+    test("/url?q=https%3A%2F%2Fpypi.org%2Fproject%2FUnalix", "https://pypi.org/project/Unalix");
+    // unchanged URLs keep their exact form
+    test("./a/../test", "./a/../test");
+    assert!(matches!(cleaner.clear_url_with_base("/test", &base).unwrap(), Cow::Borrowed(_)));
+
+    assert!(matches!(cleaner.clear_url_with_base("//[::1", &base).unwrap_err(), CleanError::UrlSyntax(ParseError::InvalidIpv6Address)));
+}
+
+#[test]
+fn test_clean_and_dedup() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let urls = cleaner
+        .clean_and_dedup([
+            "https://example.com/a?utm_source=mail",
+            "https://example.com/b",
+            "https://example.com/a?utm_source=feed",
+            "https://example.com/b",
+            "https://example.com/a",
+        ])
+        .unwrap();
+    assert_eq!(urls, ["https://example.com/a", "https://example.com/b"]);
+
+    let errors = cleaner
+        .clean_and_dedup(["https://example.com/a", "//example.com", "https://example.com/a", "/b"])
+        .unwrap_err();
+    let urls: Vec<_> = errors.iter().map(clearurls::LinkError::url).collect();
+    assert_eq!(urls, ["//example.com", "/b"]);
+}
+
+#[test]
+fn test_trivially_clean() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let url = Url::from_str("https://example.com/a/b").unwrap();
+    assert!(matches!(cleaner.clear_single_url(&url).unwrap(), Cow::Borrowed(_)));
+    assert!(matches!(cleaner.clear_single_url_str("https://example.com/a/b").unwrap(), Cow::Borrowed(_)));
+    // not in the normalized form
+    assert!(matches!(cleaner.clear_single_url_str("https://Example.com/a").unwrap(), Cow::Owned(_)));
+
+    // raw rules and redirections apply to URLs without a query
+    assert_eq!(
+        cleaner.clear_single_url_str("https://www.amazon.com/dp/exampleProduct/ref=sxin_0_pb").unwrap(),
+        "https://www.amazon.com/dp/exampleProduct"
+    );
+    let cleaner = cleaner.strip_session_ids(true);
+    assert_eq!(cleaner.clear_single_url_str("https://example.com/a;jsessionid=1").unwrap(), "https://example.com/a");
+}
+
+#[test]
+fn test_same_after_cleaning() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    assert!(cleaner
+        .same_after_cleaning("https://example.com/a?utm_source=mail", "https://Example.com/b/../a?utm_medium=x")
+        .unwrap());
+    assert!(!cleaner
+        .same_after_cleaning("https://example.com/a?utm_source=mail", "https://example.com/a?id=1")
+        .unwrap());
+    assert_eq!(
+        cleaner.clean_key("HTTP://Example.COM:80/./a?utm_source=mail&id=1").unwrap(),
+        "http://example.com/a?id=1"
+    );
+    assert_eq!(cleaner.clean_key("data:text/plain,hi").unwrap(), "data:text/plain,hi");
+    assert!(matches!(
+        cleaner.same_after_cleaning("https://example.com", "//example.com"),
+        Err(CleanError::UrlSyntax(ParseError::RelativeUrlWithoutBase))
+    ));
+}
+
+#[test]
+fn test_canonical_key() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let key = |url: &str| cleaner.canonical_key(url).unwrap();
+    assert_eq!(key("https://Example.com/a?utm_source=x&b=2&a=1#top"), "https://example.com/a?a=1&b=2");
+    assert_eq!(key("https://example.com/a?b=2&fbclid=1&a=1"), key("https://example.com/a?a=1&b=2#other"));
+    assert_eq!(key("https://example.com/a?utm_source=x"), "https://example.com/a");
+    assert_eq!(key("myapp://Example.COM/a#x"), "myapp://example.com/a");
+    assert_ne!(key("https://example.com/a?id=1"), key("https://example.com/a?id=2"));
+
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().host_form(HostForm::Unicode);
+    assert_eq!(cleaner.canonical_key("https://bücher.example/#x").unwrap(), "https://xn--bcher-kva.example/");
+    assert!(matches!(cleaner.canonical_key("/a"), Err(CleanError::UrlSyntax(ParseError::RelativeUrlWithoutBase))));
+}
+
+#[test]
+fn test_normalization() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let test = |normalization: Normalization, original: &str, expected: &str| {
+        let cleaner = UrlCleaner::from_embedded_rules().unwrap().normalize(normalization);
+        assert_eq!(cleaner.clear_single_url_str(original).unwrap(), expected);
+    };
+    let n = Normalization::new();
+    test(n, "https://EXAMPLE.com:443/a/./b/../c/?utm_source=x", "https://example.com/a/c/");
+    test(n, "myapp://Example.COM/a", "myapp://example.com/a");
+    test(n.trailing_slash(TrailingSlash::Remove), "https://example.com/a/", "https://example.com/a");
+    test(n.trailing_slash(TrailingSlash::Remove), "https://example.com/", "https://example.com/");
+    test(n.trailing_slash(TrailingSlash::Add), "https://example.com/a?b=1", "https://example.com/a/?b=1");
+    test(n.trailing_slash(TrailingSlash::Add), "mailto:a@example.com", "mailto:a@example.com");
+    test(n.sort_query(true), "https://example.com/?c=3&a=1&b=2&a=0&d", "https://example.com/?a=1&a=0&b=2&c=3&d=");
+    test(n.sort_query(true), "https://example.com/?q=a+b%20c&p", "https://example.com/?p=&q=a+b+c");
+
+    let cleaner = cleaner.normalize(n.trailing_slash(TrailingSlash::Remove));
+    assert!(cleaner.same_after_cleaning("https://example.com/a/", "https://example.com/a").unwrap());
+}
+
+#[test]
+fn test_host_form() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let res = cleaner.clear_single_url_str("https://bücher.example/?utm_source=x").unwrap();
+    assert_eq!(res, "https://xn--bcher-kva.example/");
+
+    let cleaner = cleaner.host_form(HostForm::Unicode);
+    let res = cleaner.clear_single_url_str("https://xn--bcher-kva.example/?utm_source=x").unwrap();
+    assert_eq!(res, "https://bücher.example/");
+    let res = cleaner.clear_single_url_str("https://user@bücher.example:8080/a?utm_source=x#b").unwrap();
+    assert_eq!(res, "https://user@bücher.example:8080/a#b");
+    let res = cleaner.clear_single_url_str("https://example.com/?utm_source=x").unwrap();
+    assert_eq!(res, "https://example.com/");
+
+    let base = Url::from_str("https://example.com/").unwrap();
+    let res = cleaner.clear_url_with_base("//bücher.example/?utm_source=x", &base).unwrap();
+    assert_eq!(res, "//bücher.example/");
+    let url = Url::from_str("https://bücher.example/?utm_source=x").unwrap();
+    assert_eq!(cleaner.clear_single_url(&url).unwrap().as_str(), "https://xn--bcher-kva.example/");
+}
+
+#[test]
+fn test_strip_session_ids() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let url = "https://example.com/shop;jsessionid=A1B2C3?item=5";
+    assert_eq!(cleaner.clear_single_url_str(url).unwrap(), url);
+
+    let cleaner = cleaner.strip_session_ids(true);
+    let test = |original: &str, expected: &str| assert_eq!(cleaner.clear_single_url_str(original).unwrap(), expected);
+    test(url, "https://example.com/shop?item=5");
+    test("https://example.com/a;JSESSIONID=x;v=1/b", "https://example.com/a;v=1/b");
+    test("https://example.com/a;phpsessid=abc", "https://example.com/a");
+    test("https://example.com/a/sid/0123456789ABCDEF0123/b?utm_source=x", "https://example.com/a/b");
+    test("https://example.com/a/sid/0123456789abcdef", "https://example.com/a");
+    test("https://example.com/a/sid/short/b", "https://example.com/a/sid/short/b");
+    test("https://example.com/a/sid/0123456789abcdefxyz", "https://example.com/a/sid/0123456789abcdefxyz");
+    // only the path is affected
+    test("https://example.com/?q=a;sid=1&page=2", "https://example.com/?q=a%3Bsid%3D1&page=2");
+    test("https://example.com/a;sid=1?q=a;sid=1", "https://example.com/a?q=a%3Bsid%3D1");
+}
+
+#[test]
+fn test_clean_x_links() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let url = "https://fxtwitter.com/rustlang/status/1234?s=46&t=AbCdEf";
+    assert_eq!(cleaner.clear_single_url_str(url).unwrap(), url);
+
+    let cleaner = cleaner.clean_x_links(true);
+    let test = |original: &str, expected: &str| assert_eq!(cleaner.clear_single_url_str(original).unwrap(), expected);
+    test(url, "https://fxtwitter.com/rustlang/status/1234");
+    test("https://x.com/rustlang/status/1234?s=46&t=AbCdEf", "https://x.com/rustlang/status/1234");
+    test("https://twitter.com/rustlang/status/1234?s=20&t=AbCdEf", "https://twitter.com/rustlang/status/1234");
+    test("https://mobile.twitter.com/rustlang/status/1234?s=21", "https://mobile.twitter.com/rustlang/status/1234");
+    test("https://fixupx.com/rustlang/status/1234?s=20", "https://fixupx.com/rustlang/status/1234");
+    test("https://vxtwitter.com/rustlang/status/1234?t=x", "https://vxtwitter.com/rustlang/status/1234");
+    test(
+        "https://twitter.com/rustlang?ref_src=twsrc%5Etfw%7Ctwcamp%5Eembeddedtimeline&ref_url=https%3A%2F%2Fexample.com",
+        "https://twitter.com/rustlang",
+    );
+    test("https://t.co/AbC123xyz?amp=1", "https://t.co/AbC123xyz");
+    test("https://t.co/AbC123xyz", "https://t.co/AbC123xyz");
+    test("https://x.com/i/redirect?url=https%3A%2F%2Fexample.com%2F%3Futm_source%3Dx", "https://example.com/");
+    test(
+        "https://twitter-com.cdn.ampproject.org/c/s/twitter.com/rustlang/status/1234?s=20",
+        "https://twitter.com/rustlang/status/1234",
+    );
+    // other parameters and other sites are left alone
+    test("https://x.com/search?q=rust&src=typed_query&f=live", "https://x.com/search?q=rust&f=live");
+    test("https://example.com/?s=20&t=1", "https://example.com/?s=20&t=1");
+    test("https://fix.com/?s=20", "https://fix.com/?s=20");
+    test("https://notfxtwitter.com/?s=20", "https://notfxtwitter.com/?s=20");
+}
+
+#[test]
+fn test_follow_redirect_rules() {
+    let google = "https://www.google.com/url?q=https%3A%2F%2Fexample.com%2F%3Futm_source%3Dx&usg=AOvVaw1";
+    let facebook = "https://l.facebook.com/l.php?u=https%3A%2F%2Fexample.com%2F&h=AT0xYz";
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    assert_eq!(cleaner.clear_single_url_str(google).unwrap(), "https://example.com/");
+
+    let cleaner = cleaner.follow_redirect_rules(false);
+    // the parameters of the redirecting site are still removed
+    assert_eq!(
+        cleaner.clear_single_url_str(google).unwrap(),
+        "https://www.google.com/url?q=https%3A%2F%2Fexample.com%2F%3Futm_source%3Dx"
+    );
+    assert_eq!(cleaner.clear_single_url_str(facebook).unwrap(), facebook);
+
+    let cleaner = cleaner.follow_redirect_rules_for("google", true);
+    assert_eq!(cleaner.clear_single_url_str(google).unwrap(), "https://example.com/");
+    assert_eq!(cleaner.clear_single_url_str(facebook).unwrap(), facebook);
+
+    let cleaner = cleaner.follow_redirect_rules(true).follow_redirect_rules_for("facebook", false);
+    assert_eq!(cleaner.clear_single_url_str(google).unwrap(), "https://example.com/");
+    assert_eq!(cleaner.clear_single_url_str(facebook).unwrap(), facebook);
+    let cleaner = cleaner.follow_redirect_rules_for("facebook", true);
+    assert_eq!(cleaner.clear_single_url_str(facebook).unwrap(), "https://example.com/");
+
+    // raw rules that change the host are redirections too
+    let amp = "https://twitter-com.cdn.ampproject.org/c/s/twitter.com/rustlang/status/1234?s=20";
+    let cleaner = cleaner.clean_x_links(true);
+    assert_eq!(cleaner.clear_single_url_str(amp).unwrap(), "https://twitter.com/rustlang/status/1234");
+    let cleaner = cleaner.follow_redirect_rules_for("x", false);
+    assert_eq!(
+        cleaner.clear_single_url_str(amp).unwrap(),
+        "https://twitter-com.cdn.ampproject.org/c/s/twitter.com/rustlang/status/1234"
+    );
+}
+
+#[test]
+fn test_unwrap_redirect_pages() {
+    let google = "https://www.google.com/url?sa=t&q=&url=https%3A%2F%2Fexample.com%2Fa%252Fb%3Futm_source%3Dx&usg=AOvVaw1";
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    // the generic redirection decodes the target too often
+    assert_eq!(cleaner.clear_single_url_str(google).unwrap(), "https://example.com/a/b");
+
+    let cleaner = cleaner.unwrap_redirect_pages(true);
+    let test = |original: &str, expected: &str| assert_eq!(cleaner.clear_single_url_str(original).unwrap(), expected);
+    test(google, "https://example.com/a%2Fb");
+    test(
+        "https://www.google.co.uk/url?q=https://example.com/page&sa=D&source=editors&ust=1700000000&usg=AOvVaw2",
+        "https://example.com/page",
+    );
+    test("https://l.facebook.com/l.php?u=https%3A%2F%2Fexample.com%2F%3Ffbclid%3Dabc&h=AT0xYz", "https://example.com/");
+    test(
+        "https://www.youtube.com/redirect?event=video_description&redir_token=QUFF&q=https%3A%2F%2Fexample.com%2F&v=abc",
+        "https://example.com/",
+    );
+
+    // redirect pages that link to each other
+    let nested = "https://www.google.com/url?q=https%3A%2F%2Flm.facebook.com%2Fl.php%3Fu%3Dhttps%253A%252F%252Fexample.com%252F";
+    test(nested, "https://example.com/");
+    let (_, changes) = cleaner.clear_single_url_explained(nested).unwrap();
+    let providers: Vec<_> = changes.iter().map(|c| c.provider.as_str()).collect();
+    assert_eq!(providers, ["googleRedirect", "facebookRedirect"]);
+
+    // targets that aren't web pages, and lookalike domains
+    for url in [
+        "https://www.google.com/url?q=javascript%3Aalert%281%29",
+        "https://notyoutube.com/redirect?q=https%3A%2F%2Fexample.com%2F",
+    ] {
+        test(url, url);
+    }
+}
+
+#[test]
+#[cfg(feature = "enterprise-wrappers")]
+fn test_unwrap_enterprise_wrappers() {
+    let safelinks = "https://eur01.safelinks.protection.outlook.com/?url=https%3A%2F%2Fexample.com%2Fpage%3Futm_source%3Dmail\
+        %26id%3D5&data=05%7C02%7Cuser%40example.com&sdata=Zm9v&reserved=0";
+    let urldefense = "https://urldefense.com/v3/__https://google.com:443/search?q=a*test&gs=ps__;Kw!\
+        !-612Flbf0JvQ3kNJkRi5Jg!Ue6tQudNKaShHg93trcdjqDP8se2ySE65jyCIe2K1D_uNjZ1Lnf6YLQERujngZv9UWf66ujQIQ$";
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let url = "https://urldefense.com/v3/__https://example.com/__;!!abc!def$";
+    assert_eq!(cleaner.clear_single_url_str(url).unwrap(), url);
+
+    let cleaner = cleaner.unwrap_enterprise_wrappers(true);
+    let test = |original: &str, expected: &str| assert_eq!(cleaner.clear_single_url_str(original).unwrap(), expected);
+    test(safelinks, "https://example.com/page?id=5");
+    test(urldefense, "https://google.com/search?q=a+test&gs=ps");
+    // a run of two replaced characters
+    test("https://urldefense.com/v3/__https://example.com/a**Ab__;w6nDqQ!!abc$", "https://example.com/a%C3%A9%C3%A9b");
+    test(url, "https://example.com/");
+    // wrapped twice
+    let twice = ["https://nam12.safelinks.protection.outlook.com/?url=", "&reserved=0"]
+        .join(&url::form_urlencoded::byte_serialize(urldefense.as_bytes()).collect::<String>());
+    test(&twice, "https://google.com/search?q=a+test&gs=ps");
+    let (_, changes) = cleaner.clear_single_url_explained(&twice).unwrap();
+    let providers: Vec<_> = changes.iter().map(|c| c.provider.as_str()).collect();
+    assert_eq!(providers, ["safeLinks", "urlDefense"]);
+
+    // not enough replaced characters, or no target
+    for url in
+        ["https://urldefense.com/v3/__https://example.com/**B__;Lw!!abc$", "https://nam12.safelinks.protection.outlook.com/?data=abc"]
+    {
+        test(url, url);
+    }
+}
+
+#[test]
+fn test_policies() {
+    let policies = PolicyMap::new()
+        .insert("example.com", Policy::Skip)
+        .insert("News.Example.com", Policy::CleanDefault)
+        .insert(".amazon.com", Policy::CleanAggressive)
+        .insert("example.org", Policy::AllowlistParams(vec!["utm_source".into()]));
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().policies(policies);
+    let test = |original: &str, expected: &str| assert_eq!(cleaner.clear_single_url_str(original).unwrap(), expected);
+    test("https://example.com/?utm_source=x", "https://example.com/?utm_source=x");
+    test("https://www.example.com/?utm_source=x", "https://www.example.com/?utm_source=x");
+    test("https://notexample.com/?utm_source=x", "https://notexample.com/");
+    test("https://a.news.example.com/?utm_source=x", "https://a.news.example.com/");
+    test("https://www.amazon.com/dp/B00?tag=abc-20", "https://www.amazon.com/dp/B00");
+    test(
+        "https://www.amazon.com/?u=https%3A%2F%2Fexample.net%2F%3Futm_source%3Dx",
+        "https://www.amazon.com/?u=https%3A%2F%2Fexample.net%2F",
+    );
+    test("https://example.org/?utm_source=x&utm_medium=y", "https://example.org/?utm_source=x");
+
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    assert_eq!(
+        cleaner.clear_single_url_str("https://www.amazon.com/dp/B00?tag=abc-20").unwrap(),
+        "https://www.amazon.com/dp/B00?tag=abc-20"
+    );
+}
+
+#[test]
+fn test_strict() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().strict(true).strip_session_ids(true);
+    let test = |original: &str, expected: &str| assert_eq!(cleaner.clear_single_url_str(original).unwrap(), expected);
+    test("https://example.com/a?utm_source=x", "https://example.com/a");
+    test("https://www.google.com/url?q=https%3A%2F%2Fexample.com%2F", "https://example.com/");
+    test("https://example.com/a;jsessionid=1", "https://example.com/a");
+
+    let err = cleaner.clear_single_url_str("https://www.amazon.com/dp/B00/ref=sr_1_1").unwrap_err();
+    assert!(matches!(err, CleanError::UnexpectedRewrite { .. }));
+}
+
+#[test]
+fn test_matching_providers() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    assert!(cleaner.matches_any("https://example.com/"));
+    assert_eq!(cleaner.matching_providers("https://example.com/"), ["globalRules"]);
+    let providers = cleaner.matching_providers("HTTPS://WWW.AMAZON.COM/dp/B00");
+    assert!(providers.contains(&"amazon"), "{providers:?}");
+    assert!(providers.contains(&"globalRules"), "{providers:?}");
+
+    let cleaner = UrlCleaner::from_rules_str(r#"{"providers":{"a":{"urlPattern":"^https://a\\.com/"}}}"#).unwrap();
+    assert!(!cleaner.matches_any("https://b.com/"));
+    assert!(cleaner.matching_providers("not a url").is_empty());
+}
+
+#[test]
+fn test_clear_url_bytes() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let res = cleaner.clear_url_bytes(b"https://example.com/?utm_source=x&a=1").unwrap();
+    assert_eq!(&*res, b"https://example.com/?a=1");
+    let res = cleaner.clear_url_bytes("https://example.com/café?utm_source=x".as_bytes()).unwrap();
+    assert_eq!(&*res, b"https://example.com/caf%C3%A9");
+    let res = cleaner.clear_url_bytes(b"https://example.com/caf\xe9?a=\xff&utm_source=x").unwrap();
+    assert_eq!(&*res, b"https://example.com/caf%E9?a=%EF%BF%BD");
+    let res = cleaner.clear_url_bytes(b"javascript:alert(1)").unwrap();
+    assert!(matches!(res, Cow::Borrowed(b"javascript:alert(1)")));
+    assert!(matches!(cleaner.clear_url_bytes(b"\xff"), Err(CleanError::UrlSyntax(_))));
+}
+
+#[test]
+fn test_url_clean_ext() {
+    use clearurls::UrlCleanExt;
+
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    assert_eq!("https://example.com/?utm_source=x".cleaned_with(&cleaner).unwrap(), "https://example.com/");
+
+    let mut s = String::from("https://example.com/?utm_source=x");
+    s.clean_with(&cleaner).unwrap();
+    assert_eq!(s, "https://example.com/");
+    let mut s = String::from("//example.com/?utm_source=x");
+    assert!(s.clean_with(&cleaner).is_err());
+    assert_eq!(s, "//example.com/?utm_source=x");
+
+    let mut url = Url::from_str("https://example.com/?utm_source=x").unwrap();
+    assert_eq!(url.cleaned_with(&cleaner).unwrap().as_str(), "https://example.com/");
+    url.clean_with(&cleaner).unwrap();
+    assert_eq!(url.as_str(), "https://example.com/");
+}
+
+#[test]
+fn test_max_params() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let url = "https://example.com/?utm_source=x&a=1#b=2&utm_medium=y";
+
+    let pass = cleaner.max_params(3, ParamOverflow::PassThrough);
+    assert_eq!(pass.clear_single_url_str(url).unwrap(), url);
+    assert_eq!(pass.clear_single_url_str("https://example.com/?utm_source=x&a=1#b=2").unwrap(), "https://example.com/?a=1#b=2");
+
+    let truncate = pass.max_params(3, ParamOverflow::Truncate);
+    assert_eq!(truncate.clear_single_url_str(url).unwrap(), "https://example.com/?a=1#b=2");
+    let truncate = truncate.max_params(1, ParamOverflow::Truncate);
+    assert_eq!(truncate.clear_single_url_str(url).unwrap(), "https://example.com/");
+    let truncate = truncate.max_params(0, ParamOverflow::Truncate);
+    assert_eq!(truncate.clear_single_url_str("https://example.com/?a=1").unwrap(), "https://example.com/");
+}
+
+#[test]
+fn test_max_url_len() {
+    let long = format!("https://example.com/?utm_source=x&a={}", "b".repeat(100));
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().max_url_len(100, LengthOverflow::PassThrough);
+    assert_eq!(cleaner.clear_single_url_str(&long).unwrap(), long);
+    assert_eq!(cleaner.clear_url_bytes(long.as_bytes()).unwrap(), long.as_bytes());
+    assert_eq!(cleaner.clear_single_url_str("https://example.com/?utm_source=x").unwrap(), "https://example.com/");
+
+    let cleaner = cleaner.max_url_len(100, LengthOverflow::Reject);
+    let err = cleaner.clear_single_url_str(&long).unwrap_err();
+    assert!(matches!(err, CleanError::UrlTooLong(136)));
+    assert_eq!(err.code(), ErrorCode::UrlTooLong);
+    let url = Url::from_str(&long).unwrap();
+    assert!(matches!(cleaner.clear_single_url(&url), Err(CleanError::UrlTooLong(136))));
+    #[cfg(feature = "linkify")]
+    {
+        let text = format!("see {long} and https://example.com/?utm_source=x");
+        let (cleaned, errors) = cleaner.clear_text_partial(&text);
+        assert_eq!(cleaned, format!("see {long} and https://example.com/"));
+        assert_eq!(errors.len(), 1);
+    }
+}
+
+#[test]
+fn test_explained() {
+    use clearurls::Reason;
+
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().strip_referral_marketing(true);
+    let (url, changes) = cleaner.clear_single_url_explained("https://www.amazon.com/dp/exampleProduct/ref=sxin_0_pb?tag=x&utm_source=y").unwrap();
+    assert_eq!(url, "https://www.amazon.com/dp/exampleProduct");
+    assert!(changes.iter().all(|c| c.provider == "amazon" || c.provider == "globalRules"), "{changes:?}");
+    assert!(changes.iter().any(|c| matches!(&c.reason, Reason::RawRule { .. })));
+    assert!(changes.iter().any(|c| matches!(&c.reason, Reason::ReferralMarketing { name, .. } if name == "tag")));
+    assert!(changes.iter().any(|c| matches!(&c.reason, Reason::Rule { name, .. } if name == "utm_source")));
+
+    let (url, changes) = cleaner
+        .clear_single_url_explained("https://www.google.com/url?q=https%3A%2F%2Fexample.com%2F%3Futm_source%3Dx")
+        .unwrap();
+    assert_eq!(url, "https://example.com/");
+    assert!(matches!(&changes[0].reason, Reason::Redirection { target, .. } if target == "https%3A%2F%2Fexample.com%2F%3Futm_source%3Dx"));
+    assert_eq!(changes[0].provider, "google");
+
+    let (url, changes) = cleaner.clear_single_url_explained("https://example.com/?a=1").unwrap();
+    assert!(matches!(url, Cow::Borrowed(_)));
+    assert!(changes.is_empty());
+
+    #[cfg(feature = "serialize")]
+    {
+        let (_, changes) = cleaner.clear_single_url_explained("https://example.com/?utm_source=x").unwrap();
+        assert_eq!(
+            serde_json::to_string(&changes[0]).unwrap(),
+            r#"{"provider":"globalRules","kind":"rule","name":"utm_source","rule":"(?:%3F)?utm(?:_[a-z_]*)?"}"#
+        );
+    }
+}
+
+#[test]
+fn test_is_clean() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    assert!(cleaner.is_clean("https://example.com/").unwrap());
+    assert!(cleaner.is_clean("https://example.com/?q=a%20b#top").unwrap());
+    assert!(cleaner.is_clean("HTTPS://EXAMPLE.com").unwrap());
+    assert!(!cleaner.is_clean("https://example.com/?utm_source=x").unwrap());
+    assert!(!cleaner.is_clean("https://www.google.com/url?q=https%3A%2F%2Fexample.com%2F").unwrap());
+    assert!(!cleaner.is_clean("https://www.amazon.com/dp/exampleProduct/ref=sxin_0_pb").unwrap());
+    assert!(matches!(cleaner.is_clean("not a url"), Err(CleanError::UrlSyntax(_))));
+
+    let cleaner = cleaner.on_param_removal(|_| clearurls::Decision::Veto);
+    assert!(cleaner.is_clean("https://example.com/?utm_source=x").unwrap());
+
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().normalize(Normalization::new().sort_query(true));
+    assert!(cleaner.is_clean("https://example.com/?a=1&b=2").unwrap());
+    assert!(!cleaner.is_clean("https://example.com/?b=2&a=1").unwrap());
+
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().max_params(1, ParamOverflow::Truncate);
+    assert!(!cleaner.is_clean("https://example.com/?a=1&b=2").unwrap());
+}
+
+#[test]
+fn test_clean_query_pairs() {
+    let pairs = |pairs: &'static [(&'static str, &'static str)]| pairs.iter().map(|&(k, v)| (Cow::Borrowed(k), Cow::Borrowed(v)));
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let cleaned = cleaner.clean_query_pairs("www.amazon.com", pairs(&[("pf_rd_p", "1"), ("k", "a b"), ("utm_source", "x")]));
+    assert_eq!(cleaned.unwrap(), [(Cow::Borrowed("k"), Cow::Borrowed("a b"))]);
+    let cleaned = cleaner.clean_query_pairs("example.com", pairs(&[("pf_rd_p", "1"), ("fbclid", "2")]));
+    assert_eq!(cleaned.unwrap(), [(Cow::Borrowed("pf_rd_p"), Cow::Borrowed("1"))]);
+    assert!(cleaner.clean_query_pairs("example.com", pairs(&[])).unwrap().is_empty());
+    assert!(matches!(cleaner.clean_query_pairs("exa mple.com", pairs(&[])), Err(CleanError::UrlSyntax(_))));
+    assert!(matches!(cleaner.clean_query_pairs("example.com/path", pairs(&[])), Err(CleanError::UrlSyntax(_))));
+
+    let policies = PolicyMap::new().insert("example.com", Policy::Skip);
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().policies(policies);
+    let cleaned = cleaner.clean_query_pairs("example.com", pairs(&[("utm_source", "x")])).unwrap();
+    assert_eq!(cleaned, [(Cow::Borrowed("utm_source"), Cow::Borrowed("x"))]);
+
+    // the limits apply as for `https://{host}/?{query}`
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().max_url_len(40, LengthOverflow::PassThrough);
+    assert_eq!(cleaner.clean_query("example.com", "a=1&utm_source=x").unwrap(), "a=1");
+    assert_eq!(cleaner.clean_query("example.com", "a=1&utm_source=xxxxxxxxxx").unwrap(), "a=1&utm_source=xxxxxxxxxx");
+    let cleaned = cleaner.clean_query_pairs("example.com", pairs(&[("utm_source", "xxxxxxxxxxxxxxxxxxxx")])).unwrap();
+    assert_eq!(cleaned.len(), 1);
+    let cleaner = cleaner.max_url_len(40, LengthOverflow::Reject);
+    let res = cleaner.clean_query("example.com", "a=1&utm_source=xxxxxxxxxx");
+    assert!(matches!(res, Err(CleanError::UrlTooLong(46))));
+    let res = cleaner.clean_query_pairs("example.com", pairs(&[("utm_source", "xxxxxxxxxxxxxxxxxxxx")]));
+    assert!(matches!(res, Err(CleanError::UrlTooLong(_))));
+
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().max_params(2, ParamOverflow::PassThrough);
+    assert_eq!(cleaner.clean_query("example.com", "utm_source=x&a=1").unwrap(), "a=1");
+    assert_eq!(cleaner.clean_query("example.com", "utm_source=x&a=1&b=2").unwrap(), "utm_source=x&a=1&b=2");
+    let cleaner = cleaner.max_params(2, ParamOverflow::Truncate);
+    assert_eq!(cleaner.clean_query("example.com", "a=1&utm_source=x&b=2").unwrap(), "a=1");
+    let cleaned = cleaner.clean_query_pairs("example.com", pairs(&[("a", "1"), ("b", "2"), ("c", "3")])).unwrap();
+    assert_eq!(cleaned, [(Cow::Borrowed("a"), Cow::Borrowed("1")), (Cow::Borrowed("b"), Cow::Borrowed("2"))]);
+}
+
+#[test]
+fn test_clean_referrer() {
+    use clearurls::ReferrerPolicy;
+
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let referrer = " https://user:pw@example.com:8443/a?fbclid=1&b=2#top ";
+    assert_eq!(cleaner.clean_referrer(referrer, ReferrerPolicy::Clean).as_deref(), Some("https://example.com:8443/a?b=2"));
+    assert_eq!(cleaner.clean_referrer(referrer, ReferrerPolicy::Origin).as_deref(), Some("https://example.com:8443/"));
+    assert_eq!(cleaner.clean_referrer(referrer, ReferrerPolicy::NoReferrer), None);
+    assert_eq!(cleaner.clean_referrer("/relative", ReferrerPolicy::Origin), None);
+    assert_eq!(cleaner.clean_referrer("about:blank", ReferrerPolicy::Clean), None);
+    // a redirection is attributed to the redirecting site, not to its target
+    let referrer = "https://www.google.com/url?q=https%3A%2F%2Fexample.com%2F&sa=D";
+    assert_eq!(cleaner.clean_referrer(referrer, ReferrerPolicy::Clean).as_deref(), Some("https://www.google.com/"));
+
+    // if cleaning fails, only the origin is kept
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap().max_url_len(20, LengthOverflow::Reject);
+    let res = cleaner.clean_referrer("https://example.com/search?q=secret", ReferrerPolicy::Clean);
+    assert_eq!(res.as_deref(), Some("https://example.com/"));
+}
+
+#[test]
+fn test_multi_url() {
+    use clearurls::MultiUrl;
+
+    let rules = r#"{"providers":{
+        "redirect":{"urlPattern":"^https://r\\.example","redirections":["^https://r\\.example/\\?dest=([^&]+)"]},
+        "all":{"urlPattern":".*","rules":["utm_source"]}
+    }}"#;
+    let cleaner = UrlCleaner::from_rules_str(rules).unwrap();
+    let url = "https://r.example/?dest=https%3A%2F%2Fa.example%2F%3Fid%3D1%2Chttps%3A%2F%2Fb.example%2F";
+    assert_eq!(cleaner.clear_single_url_str(url).unwrap(), "https://a.example/?id=1%2Chttps%3A%2F%2Fb.example%2F");
+    for handling in [MultiUrl::First, MultiUrl::Each] {
+        let cleaner = UrlCleaner::from_rules_str(rules).unwrap().multi_url(handling);
+        assert_eq!(cleaner.clear_single_url_str(url).unwrap(), "https://a.example/?id=1");
+    }
+
+    let cleaner = |handling| UrlCleaner::from_rules_str(rules).unwrap().clean_nested_urls(true).multi_url(handling);
+    let url = "https://example.com/?next=https://a.example/?id=1%26utm_source=1|HTTPS://b.example/?utm_source=2,x&k=v";
+    let res = cleaner(MultiUrl::Single).clear_single_url_str(url).unwrap();
+    assert_eq!(res, "https://example.com/?next=https%3A%2F%2Fa.example%2F%3Fid%3D1&k=v");
+    let res = cleaner(MultiUrl::First).clear_single_url_str(url).unwrap();
+    assert_eq!(res, "https://example.com/?next=https%3A%2F%2Fa.example%2F%3Fid%3D1&k=v");
+    let res = cleaner(MultiUrl::Each).clear_single_url_str(url).unwrap();
+    assert_eq!(res, "https://example.com/?next=https%3A%2F%2Fa.example%2F%3Fid%3D1%7Chttps%3A%2F%2Fb.example%2F&k=v");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_clear_url_list() {
+    use clearurls::Error;
+
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let list = "# exported\r\nhttps://example.com/?utm_source=a\n\n  https://example.org/?fbclid=1  \r\n#https://example.com/?utm_source=b\nnot a url\nhttps://example.net/";
+    let lines: Vec<_> = cleaner.clear_url_list(list.as_bytes()).collect();
+    assert_eq!(lines.len(), 7);
+    assert_eq!(lines[0].as_ref().unwrap(), "# exported\r\n");
+    assert_eq!(lines[1].as_ref().unwrap(), "https://example.com/\n");
+    assert_eq!(lines[2].as_ref().unwrap(), "\n");
+    assert_eq!(lines[3].as_ref().unwrap(), "  https://example.org/  \r\n");
+    assert_eq!(lines[4].as_ref().unwrap(), "#https://example.com/?utm_source=b\n");
+    let err = lines[5].as_ref().unwrap_err();
+    assert!(matches!(err.error(), Error::UrlSyntax(_)));
+    assert_eq!(err.url(), "not a url\n");
+    assert_eq!(lines[6].as_ref().unwrap(), "https://example.net/");
+
+    // writing back the errors unchanged gives the original file, apart from the cleaned URLs
+    let cleaned: String = cleaner.clear_url_list(list.as_bytes()).map(|l| l.unwrap_or_else(|e| e.url().into())).collect();
+    let expected = "# exported\r\nhttps://example.com/\n\n  https://example.org/  \r\n#https://example.com/?utm_source=b\nnot a url\nhttps://example.net/";
+    assert_eq!(cleaned, expected);
+}
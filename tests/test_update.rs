@@ -0,0 +1,48 @@
+#![cfg(feature = "network")]
+
+use clearurls::{Error, RulesVersion, UpdateStatus, UrlCleaner};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+
+/// Serve a single HTTP response with the given body and return its URL.
+fn serve_once(body: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/rules.minify.hash", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 2 {
+            line.clear();
+        }
+        write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}", body.len()).unwrap();
+    });
+    url
+}
+
+#[test]
+fn test_update_current() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let hash: &'static str = cleaner.rules_version().to_string().leak();
+    let status = cleaner.check_for_rules_update_at(&serve_once(hash)).unwrap();
+    assert_eq!(status, UpdateStatus::Current);
+}
+
+#[test]
+fn test_update_available() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let hash = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855\n";
+    let status = cleaner.check_for_rules_update_at(&serve_once(hash)).unwrap();
+    assert_eq!(status, UpdateStatus::Available { version: RulesVersion::of(b"") });
+}
+
+#[test]
+fn test_update_errors() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let err = cleaner.check_for_rules_update_at(&serve_once("<html>")).unwrap_err();
+    assert!(matches!(err, Error::RulesVersionSyntax(_)));
+
+    let err = cleaner.check_for_rules_update_at("http://127.0.0.1:0/").unwrap_err();
+    assert!(matches!(err, Error::Network(_)));
+    assert!(err.to_string().starts_with("network error: "));
+}
@@ -0,0 +1,66 @@
+#![cfg(feature = "warc")]
+
+use clearurls::{ErrorCode, UrlCleaner};
+
+fn record(headers: &str, block: &str) -> String {
+    format!("WARC/1.1\r\n{headers}Content-Length: {}\r\n\r\n{block}\r\n\r\n", block.len())
+}
+
+fn http(headers: &str, body: &str) -> String {
+    format!("HTTP/1.1 200 OK\r\n{headers}Content-Length: {}\r\n\r\n{body}", body.len())
+}
+
+#[test]
+fn test_clean_warc() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let html = "<a href=\"https://example.com/?a=1&amp;utm_source=x&amp;b=2\">x</a>\n";
+    let warcinfo = record("WARC-Type: warcinfo\r\nContent-Type: application/warc-fields\r\n", "software: test\r\n");
+    let compressed = record(
+        "WARC-Type: response\r\nWARC-Target-URI: https://example.com/gz\r\nContent-Type: application/http; msgtype=response\r\n",
+        &http("Content-Type: text/html\r\nContent-Encoding: gzip\r\n", "https://example.com/?utm_source=x"),
+    );
+    let input = [
+        warcinfo.clone(),
+        record("WARC-Type: request\r\nWARC-Target-URI: <https://example.com/page?utm_source=x>\r\n", ""),
+        record(
+            "WARC-Type: response\r\nWARC-Target-URI: https://example.com/page?utm_source=x\r\n\
+            Content-Type: application/http; msgtype=response\r\n\
+            WARC-Payload-Digest: sha1:AAAA\r\nWARC-Block-Digest: sha1:AAAA\r\n",
+            &http("Content-Type: text/html; charset=utf-8\r\n", html),
+        ),
+        compressed.clone(),
+    ]
+    .concat();
+
+    let mut output = Vec::new();
+    let summary = cleaner.clean_warc(input.as_bytes(), &mut output).unwrap();
+    assert_eq!((summary.records, summary.changed), (4, 2));
+    assert!(summary.errors.is_empty());
+    let body = "<a href=\"https://example.com/?a=1&amp;b=2\">x</a>\n";
+    let expected = [
+        warcinfo,
+        record("WARC-Type: request\r\nWARC-Target-URI: <https://example.com/page>\r\n", ""),
+        record(
+            "WARC-Type: response\r\nWARC-Target-URI: https://example.com/page\r\n\
+            Content-Type: application/http; msgtype=response\r\n\
+            WARC-Payload-Digest: sha256:H5KKFHTFZWPGVYOY7XGNGX2WA5JH6FJBO5MJYZDYWMAVWZVOX6AQ====\r\n\
+            WARC-Block-Digest: sha256:HR5H4OHR7YEWFGHOL4L3ZSBJL5PHCH5ZH4HTNCBFKCYYEMRVR7PA====\r\n",
+            &http("Content-Type: text/html; charset=utf-8\r\n", body),
+        ),
+        compressed,
+    ]
+    .concat();
+    assert_eq!(String::from_utf8(output).unwrap(), expected);
+}
+
+#[test]
+fn test_clean_warc_errors() {
+    let cleaner = UrlCleaner::from_embedded_rules().unwrap();
+    let err = cleaner.clean_warc("<html></html>".as_bytes(), Vec::new()).unwrap_err();
+    assert_eq!(err.code(), ErrorCode::Warc);
+    assert!(err.to_string().starts_with("error processing WARC: expected a WARC record"), "{err}");
+
+    let truncated = "WARC/1.1\r\nWARC-Type: resource\r\nContent-Length: 100\r\n\r\nshort";
+    let err = cleaner.clean_warc(truncated.as_bytes(), Vec::new()).unwrap_err();
+    assert_eq!(err.to_string(), "error processing WARC: a record is shorter than its Content-Length");
+}